@@ -0,0 +1,119 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::JourneyLocation;
+
+macro_rules! activities {
+    ($($variant:ident = $code:literal, $doc:literal;)*) => {
+        /// Something a train does at a location, from the activity codes of a location record.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        #[cfg_attr(
+            feature = "rkyv",
+            derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+            archive(check_bytes, compare(PartialEq)),
+            archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+        )]
+        pub enum Activity {
+            $(
+                #[doc = $doc]
+                $variant,
+            )*
+        }
+
+        impl Activity {
+            /// The activity with a code, such as `TB`, if it is known. Surrounding spaces are
+            /// ignored.
+            pub fn from_code<S: AsRef<str>>(code: S) -> Option<Self> {
+                match code.as_ref().trim() {
+                    $($code => Some(Activity::$variant),)*
+                    _ => None,
+                }
+            }
+
+            /// The code of this activity, as it appears in a location record.
+            pub fn code(&self) -> &'static str {
+                match self {
+                    $(Activity::$variant => $code,)*
+                }
+            }
+        }
+    };
+}
+
+activities! {
+    StopsForOtherTrainsToPass = "A", "Stops or shunts for other trains to pass.";
+    AttachDetachAssistingLocomotive = "AE", "Attaches or detaches an assisting locomotive.";
+    ShowsAsXOnArrival = "AX", "Shows as 'X' on arrival.";
+    StopsForBankingLocomotive = "BL", "Stops for a banking locomotive.";
+    StopsToChangeTrainCrew = "C", "Stops to change train crew.";
+    SetsDown = "D", "Stops to set down passengers.";
+    StopsToDetachVehicles = "-D", "Stops to detach vehicles.";
+    StopsForExamination = "E", "Stops for examination.";
+    GBTTDataToAdd = "G", "National Rail Timetable data to add.";
+    NotionalActivity = "H", "A notional activity to prevent timing columns merging.";
+    NotionalActivityThirdColumn = "HH", "As `H`, where a third column is involved.";
+    PassengerCountPoint = "K", "Passenger count point.";
+    TicketCollectionAndExamination = "KC", "Ticket collection and examination point.";
+    TicketExamination = "KE", "Ticket examination point.";
+    TicketExaminationFirstClass = "KF", "Ticket examination point, first class only.";
+    SelectiveTicketExamination = "KS", "Selective ticket examination point.";
+    StopsToChangeLocomotives = "L", "Stops to change locomotives.";
+    StopNotAdvertised = "N", "Stop not advertised.";
+    StopsForOtherOperatingReasons = "OP", "Stops for other operating reasons.";
+    LocomotiveOnRear = "OR", "Train locomotive on the rear.";
+    Propelling = "PR", "Propelling between the points shown.";
+    StopsWhenRequired = "R", "Stops when required.";
+    ReversingMovement = "RM", "Reversing movement, or the driver changes ends.";
+    StopsForLocomotiveToRunRound = "RR", "Stops for the locomotive to run round the train.";
+    StopsForRailwayPersonnelOnly = "S", "Stops for railway personnel only.";
+    TakesUpAndSetsDown = "T", "Stops to take up and set down passengers.";
+    StopsToAttachAndDetachVehicles = "-T", "Stops to attach and detach vehicles.";
+    TrainBegins = "TB", "The train begins here.";
+    TrainFinishes = "TF", "The train finishes here.";
+    DetailConsistForTOPS = "TS", "Detail consist for TOPS Direct requested.";
+    StopsForTabletStaffOrToken = "TW", "Stops, or passes, for a tablet, staff or token.";
+    TakesUp = "U", "Stops to take up passengers.";
+    StopsToAttachVehicles = "-U", "Stops to attach vehicles.";
+    StopsForWatering = "W", "Stops for watering of coaches.";
+    PassesAtCrossingPoint = "X", "Passes another train at a crossing point on a single line.";
+}
+
+/// Parse the activity field of a location record, which holds up to six two character codes.
+/// Codes which aren't known are left out, but remain in the raw activity field.
+pub(crate) fn parse_activities(activity: &str) -> Vec<Activity> {
+    activity
+        .as_bytes()
+        .chunks(2)
+        .filter_map(|code| std::str::from_utf8(code).ok())
+        .filter_map(Activity::from_code)
+        .collect()
+}
+
+impl JourneyLocation {
+    /// Does the train pick up passengers at this location?
+    pub fn picks_up(&self) -> bool {
+        self.activities().iter().any(|activity| {
+            matches!(
+                activity,
+                Activity::TakesUpAndSetsDown
+                    | Activity::TrainBegins
+                    | Activity::TakesUp
+                    | Activity::StopsWhenRequired
+            )
+        })
+    }
+
+    /// Does the train set down passengers at this location?
+    pub fn sets_down(&self) -> bool {
+        self.activities().iter().any(|activity| {
+            matches!(
+                activity,
+                Activity::TakesUpAndSetsDown
+                    | Activity::TrainFinishes
+                    | Activity::SetsDown
+                    | Activity::StopsWhenRequired
+            )
+        })
+    }
+}
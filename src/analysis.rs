@@ -0,0 +1,492 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Duration, DurationRound, NaiveDate, NaiveDateTime};
+use getset::{CopyGetters, Getters};
+
+use crate::schedule::{
+    JourneyLocation, JourneyTime, PowerType, STPIndicator, Schedule, ScheduleDatabase,
+    TrainCategory,
+};
+
+/// A date on which a significant number of permanent schedules start or stop running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+pub struct TimetableChangeDate {
+    /// The first day of the new timetable.
+    #[getset(get_copy = "pub")]
+    date: NaiveDate,
+    /// The number of permanent schedules which start running on this date.
+    #[getset(get_copy = "pub")]
+    starting: usize,
+    /// The number of permanent schedules which ran for the last time the day before.
+    #[getset(get_copy = "pub")]
+    ending: usize,
+}
+
+/// A comparison of the working and public times at a location where a train calls for
+/// passengers.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct PublicTimeComparison {
+    /// The index of the location in the schedule's journey.
+    #[getset(get_copy = "pub")]
+    index: usize,
+    /// The TIPLOC of the location.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The public arrival time minus the working arrival time, if both are present.
+    #[getset(get_copy = "pub")]
+    arrival_difference: Option<Duration>,
+    /// The public departure time minus the working departure time, if both are present.
+    #[getset(get_copy = "pub")]
+    departure_difference: Option<Duration>,
+    /// Passengers are set down here, but there is no public arrival time.
+    #[getset(get_copy = "pub")]
+    missing_public_arrival: bool,
+    /// Passengers are picked up here, but there is no public departure time.
+    #[getset(get_copy = "pub")]
+    missing_public_departure: bool,
+}
+
+/// A summary of the routes an operator runs on a date.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+pub struct OperatorRoutes {
+    /// The distinct pairs of origin and destination TIPLOCs served.
+    #[getset(get = "pub")]
+    origin_destinations: BTreeSet<(String, String)>,
+    /// The trains run, grouped by the locations they call at, ordered by calling pattern.
+    #[getset(get = "pub")]
+    calling_patterns: Vec<CallingPattern>,
+}
+
+/// A group of trains which call at the same locations in the same order.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct CallingPattern {
+    /// The TIPLOCs called at, from origin to destination.
+    #[getset(get = "pub")]
+    calls: Vec<String>,
+    /// The UIDs of the trains with this calling pattern, in order.
+    #[getset(get = "pub")]
+    train_uids: Vec<String>,
+}
+
+/// The highest speed, in miles per hour, considered plausible for a train whose schedule
+/// doesn't give a maximum speed.
+const MAXIMUM_PLAUSIBLE_SPEED: f64 = 186.0;
+
+/// A leg between two consecutive locations in a schedule with an implausible timing.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct TimingAnomaly {
+    /// The index in the schedule's journey of the start of the leg.
+    #[getset(get_copy = "pub")]
+    index: usize,
+    /// The TIPLOC at the start of the leg.
+    #[getset(get = "pub")]
+    from: String,
+    /// The TIPLOC at the end of the leg.
+    #[getset(get = "pub")]
+    to: String,
+    /// What is implausible about the leg.
+    #[getset(get_copy = "pub")]
+    kind: TimingAnomalyKind,
+}
+
+/// The ways in which the timing of a leg can be implausible.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimingAnomalyKind {
+    /// No time is allowed to travel between two different locations.
+    ZeroTime,
+    /// The average speed over the leg, in miles per hour, is above the maximum speed of the
+    /// train, or above any speed possible on the network if no maximum is given.
+    ExcessiveSpeed(f64),
+}
+
+/// The running times between two adjacent locations of the trains of one category and power
+/// type.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct SectionalRunningTimes {
+    /// The category of the trains.
+    #[getset(get = "pub")]
+    train_category: TrainCategory,
+    /// The type of power of the trains.
+    #[getset(get = "pub")]
+    power_type: PowerType,
+    /// The number of schedules running between the locations.
+    #[getset(get_copy = "pub")]
+    samples: usize,
+    /// The shortest running time.
+    #[getset(get_copy = "pub")]
+    minimum: Duration,
+    /// The median running time.
+    #[getset(get_copy = "pub")]
+    median: Duration,
+    /// The longest running time.
+    #[getset(get_copy = "pub")]
+    maximum: Duration,
+}
+
+/// How often direct services run between two locations on a date.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters, CopyGetters)]
+pub struct ServiceFrequency {
+    /// The number of services.
+    #[getset(get_copy = "pub")]
+    services: usize,
+    /// The number of services departing in each hour, keyed by the start of the hour. Hours
+    /// without a departure are left out.
+    #[getset(get = "pub")]
+    per_hour: BTreeMap<NaiveDateTime, usize>,
+    /// The shortest gap between one departure and the next, if there are two or more.
+    #[getset(get_copy = "pub")]
+    minimum_headway: Option<Duration>,
+    /// The mean gap between one departure and the next, if there are two or more.
+    #[getset(get_copy = "pub")]
+    mean_headway: Option<Duration>,
+    /// The longest gap between one departure and the next, if there are two or more.
+    #[getset(get_copy = "pub")]
+    maximum_headway: Option<Duration>,
+}
+
+impl PublicTimeComparison {
+    /// Is either public time earlier than the working time by a minute or more, beyond what
+    /// rounding half minutes would explain?
+    pub fn public_earlier_than_working(&self) -> bool {
+        [self.arrival_difference, self.departure_difference]
+            .into_iter()
+            .flatten()
+            .any(|difference| difference <= Duration::minutes(-1))
+    }
+
+    /// Is there any discrepancy between the working and public times at this location?
+    pub fn has_discrepancy(&self) -> bool {
+        self.missing_public_arrival
+            || self.missing_public_departure
+            || [self.arrival_difference, self.departure_difference]
+                .into_iter()
+                .flatten()
+                .any(|difference| difference.num_seconds().abs() >= 60)
+    }
+}
+
+impl Schedule {
+    /// The working time from departing the origin to arriving at the destination, including
+    /// any half minutes and the days passed overnight.
+    pub fn duration(&self) -> Option<Duration> {
+        let origin = self.origin()?;
+        let destination = self.destination()?;
+        let departure = (*origin.departure_time())?.to_seconds() as i64;
+        let arrival = (*destination.arrival_time())?.to_seconds() as i64;
+        Some(
+            Duration::days(*destination.day_offset() as i64 - *origin.day_offset() as i64)
+                + Duration::seconds(arrival - departure),
+        )
+    }
+
+    /// The public time from departing the origin to arriving at the destination, as with
+    /// [`Schedule::duration`].
+    pub fn public_duration(&self) -> Option<Duration> {
+        let origin = self.origin()?;
+        let destination = self.destination()?;
+        Some(
+            self.duration()?
+                + difference(destination.arrival_time(), destination.public_arrival())?
+                - difference(origin.departure_time(), origin.public_departure())?,
+        )
+    }
+
+    /// Compare the working and public times at each location where this train calls for
+    /// passengers.
+    pub fn compare_public_times(&self) -> Vec<PublicTimeComparison> {
+        let last = self.journey().len().saturating_sub(1);
+        self.journey()
+            .iter()
+            .enumerate()
+            .filter(|(_, loc)| loc.passing_time().is_none() && (loc.picks_up() || loc.sets_down()))
+            .map(|(index, loc)| PublicTimeComparison {
+                index,
+                tiploc: loc.tiploc().clone(),
+                arrival_difference: difference(loc.arrival_time(), loc.public_arrival()),
+                departure_difference: difference(loc.departure_time(), loc.public_departure()),
+                missing_public_arrival: index != 0
+                    && loc.sets_down()
+                    && loc.public_arrival().is_none(),
+                missing_public_departure: index != last
+                    && loc.picks_up()
+                    && loc.public_departure().is_none(),
+            })
+            .collect()
+    }
+
+    /// Find the legs of this journey with implausible timings. Legs between two different
+    /// TIPLOCs with no time allowed are always reported. Where `distance` gives the distance
+    /// in miles between two TIPLOCs, legs with an average speed higher than this train could
+    /// achieve are also reported. Pass `|_, _| None` if no distances are available.
+    pub fn timing_anomalies<F>(&self, distance: F) -> Vec<TimingAnomaly>
+    where
+        F: Fn(&str, &str) -> Option<f64>,
+    {
+        let maximum_speed = if *self.speed() > 0 {
+            *self.speed() as f64
+        } else {
+            MAXIMUM_PLAUSIBLE_SPEED
+        };
+
+        self.journey()
+            .windows(2)
+            .enumerate()
+            .filter(|(_, leg)| leg[0].tiploc() != leg[1].tiploc())
+            .filter_map(|(index, leg)| {
+                let leaves = leg[0].departure_time().or(*leg[0].passing_time())?;
+                let reaches = leg[1]
+                    .arrival_time()
+                    .or(*leg[1].passing_time())
+                    .or(*leg[1].departure_time())?;
+                let mut elapsed = reaches.to_seconds() as i64 - leaves.to_seconds() as i64;
+                if elapsed < 0 {
+                    elapsed += 24 * 60 * 60;
+                }
+
+                let kind = if elapsed == 0 {
+                    TimingAnomalyKind::ZeroTime
+                } else {
+                    let miles = distance(leg[0].tiploc(), leg[1].tiploc())?;
+                    let speed = miles / (elapsed as f64 / 3600.0);
+                    if speed <= maximum_speed {
+                        return None;
+                    }
+                    TimingAnomalyKind::ExcessiveSpeed(speed)
+                };
+                Some(TimingAnomaly {
+                    index,
+                    from: leg[0].tiploc().clone(),
+                    to: leg[1].tiploc().clone(),
+                    kind,
+                })
+            })
+            .collect()
+    }
+}
+
+impl JourneyLocation {
+    /// The working time the train waits at this location, between arriving and departing.
+    /// Departures after midnight are handled.
+    pub fn dwell_time(&self) -> Option<Duration> {
+        Some((*self.arrival_time())?.duration_until(&(*self.departure_time())?))
+    }
+
+    /// The public time the train waits at this location, as with
+    /// [`JourneyLocation::dwell_time`].
+    pub fn public_dwell_time(&self) -> Option<Duration> {
+        Some((*self.public_arrival())?.duration_until(&(*self.public_departure())?))
+    }
+}
+
+/// The difference between two times, such as a working time and the public time for it, being
+/// the second minus the first. The times are assumed to be within twelve hours of each other.
+pub(crate) fn difference(from: &Option<JourneyTime>, to: &Option<JourneyTime>) -> Option<Duration> {
+    const DAY: i64 = 24 * 60 * 60;
+    let difference = (*to)?.to_seconds() as i64 - (*from)?.to_seconds() as i64;
+    let difference = (difference + DAY / 2).rem_euclid(DAY) - DAY / 2;
+    Some(Duration::seconds(difference))
+}
+
+impl ScheduleDatabase {
+    /// Find the principal timetable change dates covered by this database. These are dates on
+    /// which at least `min_proportion` (between 0 and 1) of the permanent schedules either
+    /// start running, or stopped running the day before. A proportion of around `0.05` picks
+    /// out the national timetable changes in a full extract.
+    ///
+    /// The results are ordered by date.
+    pub fn timetable_change_dates(&self, min_proportion: f64) -> Vec<TimetableChangeDate> {
+        let mut counts: BTreeMap<NaiveDate, (usize, usize)> = BTreeMap::new();
+        let mut total = 0;
+        for schedule in self
+            .schedules()
+            .values()
+            .flatten()
+            .filter(|s| *s.stp_indicator() == STPIndicator::PermanentAssociation)
+        {
+            total += 1;
+            counts.entry(*schedule.runs_from()).or_default().0 += 1;
+            counts
+                .entry(*schedule.runs_to() + Duration::days(1))
+                .or_default()
+                .1 += 1;
+        }
+
+        let threshold = (total as f64 * min_proportion).max(1.0);
+        counts
+            .into_iter()
+            .filter(|(_, (starting, ending))| (starting + ending) as f64 >= threshold)
+            .map(|(date, (starting, ending))| TimetableChangeDate {
+                date,
+                starting,
+                ending,
+            })
+            .collect()
+    }
+
+    /// Summarise the routes run by each operator on a date, after applying short term planning
+    /// overlays and cancellations. Locations the trains only pass are not part of a calling
+    /// pattern.
+    ///
+    /// The results are keyed by ATOC code.
+    pub fn operator_routes(&self, date: NaiveDate) -> BTreeMap<String, OperatorRoutes> {
+        let mut patterns: BTreeMap<&str, BTreeMap<Vec<String>, Vec<String>>> = BTreeMap::new();
+        for schedule in self.schedules_running_on(date) {
+            let calls: Vec<String> = schedule
+                .journey()
+                .iter()
+                .filter(|loc| loc.passing_time().is_none())
+                .map(|loc| loc.tiploc().clone())
+                .collect();
+            if calls.len() < 2 {
+                continue;
+            }
+            patterns
+                .entry(schedule.atoc_code())
+                .or_default()
+                .entry(calls)
+                .or_default()
+                .push(schedule.train_uid().clone());
+        }
+
+        patterns
+            .into_iter()
+            .map(|(atoc_code, patterns)| {
+                let mut routes = OperatorRoutes::default();
+                for (calls, mut train_uids) in patterns {
+                    routes
+                        .origin_destinations
+                        .insert((calls[0].clone(), calls[calls.len() - 1].clone()));
+                    train_uids.sort();
+                    routes
+                        .calling_patterns
+                        .push(CallingPattern { calls, train_uids });
+                }
+                (atoc_code.to_string(), routes)
+            })
+            .collect()
+    }
+
+    /// Gather the working running times of every schedule from one TIPLOC directly to
+    /// another, whether passing or stopping at either, grouped by train category and power
+    /// type. Running times include any allowances, and journeys over midnight are handled.
+    ///
+    /// The groups are ordered by the number of schedules in them, largest first.
+    pub fn sectional_running_times<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+    ) -> Vec<SectionalRunningTimes> {
+        let (from, to) = (from.as_ref().trim(), to.as_ref().trim());
+        let mut groups: Vec<(&TrainCategory, &PowerType, Vec<Duration>)> = vec![];
+        for schedule in self
+            .uids_at_locations([from])
+            .into_iter()
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+        {
+            for leg in schedule.journey().windows(2) {
+                if leg[0].tiploc() != from || leg[1].tiploc() != to {
+                    continue;
+                }
+                let Some(leaves) = leg[0].departure_time().or(*leg[0].passing_time()) else {
+                    continue;
+                };
+                let Some(reaches) = leg[1]
+                    .arrival_time()
+                    .or(*leg[1].passing_time())
+                    .or(*leg[1].departure_time())
+                else {
+                    continue;
+                };
+                let time = leaves.duration_until(&reaches);
+                let (category, power) = (schedule.train_category(), schedule.power_type());
+                match groups
+                    .iter_mut()
+                    .find(|(c, p, _)| *c == category && *p == power)
+                {
+                    Some((_, _, times)) => times.push(time),
+                    None => groups.push((category, power, vec![time])),
+                }
+            }
+        }
+
+        let mut results: Vec<SectionalRunningTimes> = groups
+            .into_iter()
+            .map(|(train_category, power_type, mut times)| {
+                times.sort();
+                let middle = times.len() / 2;
+                let median = if times.len() % 2 == 0 {
+                    (times[middle - 1] + times[middle]) / 2
+                } else {
+                    times[middle]
+                };
+                SectionalRunningTimes {
+                    train_category: train_category.clone(),
+                    power_type: power_type.clone(),
+                    samples: times.len(),
+                    minimum: times[0],
+                    median,
+                    maximum: times[times.len() - 1],
+                }
+            })
+            .collect();
+        results.sort_by_key(|times| std::cmp::Reverse(times.samples));
+        results
+    }
+
+    /// Measure how often direct services run from one location to another on a date, by
+    /// their departure times from `from`. Locations can be given as either a TIPLOC or a CRS
+    /// code, and the services are those found by [`ScheduleDatabase::direct_services`], with
+    /// short term planning overlays and cancellations applied.
+    pub fn service_frequency<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+        date: NaiveDate,
+    ) -> ServiceFrequency {
+        let mut departures: Vec<NaiveDateTime> = self
+            .dated_services(from.as_ref(), to.as_ref(), date)
+            .map(|service| service.departure())
+            .collect();
+        departures.sort();
+
+        let mut per_hour = BTreeMap::new();
+        for departure in &departures {
+            let hour = departure
+                .duration_trunc(Duration::hours(1))
+                .unwrap_or(*departure);
+            *per_hour.entry(hour).or_default() += 1;
+        }
+        let headways: Vec<Duration> = departures.windows(2).map(|d| d[1] - d[0]).collect();
+        ServiceFrequency {
+            services: departures.len(),
+            per_hour,
+            minimum_headway: headways.iter().min().copied(),
+            mean_headway: (!headways.is_empty())
+                .then(|| headways.iter().sum::<Duration>() / headways.len() as i32),
+            maximum_headway: headways.iter().max().copied(),
+        }
+    }
+
+    /// Find every schedule with implausible timings, as described in
+    /// [`Schedule::timing_anomalies`]. The results are ordered by train UID, then by the date
+    /// the schedule runs from.
+    pub fn timing_anomalies<F>(&self, distance: F) -> Vec<(&Schedule, Vec<TimingAnomaly>)>
+    where
+        F: Fn(&str, &str) -> Option<f64>,
+    {
+        let mut results: Vec<(&Schedule, Vec<TimingAnomaly>)> = self
+            .schedules()
+            .values()
+            .flatten()
+            .map(|schedule| (schedule, schedule.timing_anomalies(&distance)))
+            .filter(|(_, anomalies)| !anomalies.is_empty())
+            .collect();
+        results.sort_by(|a, b| {
+            a.0.train_uid()
+                .cmp(b.0.train_uid())
+                .then(a.0.runs_from().cmp(b.0.runs_from()))
+        });
+        results
+    }
+}
@@ -0,0 +1,102 @@
+use std::io::{self, Read};
+
+use log::debug;
+use thiserror::Error;
+
+use crate::{
+    parser::{parse_cif, CIFParseError},
+    query::matches_wildcard,
+    types::CIFFile,
+};
+
+/// An error reading CIF files from an archive.
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("failed to read archive")]
+    Read(#[from] io::Error),
+    #[cfg(feature = "zip")]
+    #[error("failed to read zip archive")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("failed to parse archive member {0}")]
+    Parse(String, #[source] CIFParseError),
+    #[error("no archive member matches {0}")]
+    NoMatchingMember(String),
+}
+
+/// Parse every member of a zip archive whose name matches `pattern`, where `*` matches any
+/// number of characters and `?` matches exactly one. For example, `*.MCA` picks out the full
+/// timetable from the ATOC data bundle.
+///
+/// The files are returned with their member names, ordered by name.
+#[cfg(feature = "zip")]
+pub fn parse_cif_zip<R: Read + io::Seek>(
+    reader: R,
+    pattern: &str,
+) -> Result<Vec<(String, CIFFile)>, ArchiveError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|name| matches_wildcard(pattern.as_bytes(), name.as_bytes()))
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    if names.is_empty() {
+        return Err(ArchiveError::NoMatchingMember(pattern.to_string()));
+    }
+
+    let mut files = vec![];
+    for name in names {
+        debug!("Parsing {name} from zip archive");
+        let member = archive.by_name(&name)?;
+        let file = parse_cif(member).map_err(|e| ArchiveError::Parse(name.clone(), e))?;
+        files.push((name, file));
+    }
+    Ok(files)
+}
+
+/// Parse every member of a tar archive whose path matches `pattern`, where `*` matches any
+/// number of characters and `?` matches exactly one. If the archive is gzip compressed, it is
+/// decompressed as it is read.
+///
+/// The files are returned with their member paths, in the order they appear in the archive.
+#[cfg(feature = "tar")]
+pub fn parse_cif_tar<R: Read>(
+    reader: R,
+    pattern: &str,
+) -> Result<Vec<(String, CIFFile)>, ArchiveError> {
+    let mut reader = io::BufReader::new(reader);
+    let compressed = io::BufRead::fill_buf(&mut reader)?.starts_with(&[0x1f, 0x8b]);
+    if compressed {
+        read_tar(
+            tar::Archive::new(flate2::read::GzDecoder::new(reader)),
+            pattern,
+        )
+    } else {
+        read_tar(tar::Archive::new(reader), pattern)
+    }
+}
+
+#[cfg(feature = "tar")]
+fn read_tar<R: Read>(
+    mut archive: tar::Archive<R>,
+    pattern: &str,
+) -> Result<Vec<(String, CIFFile)>, ArchiveError> {
+    let mut files = vec![];
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().to_string();
+        if !matches_wildcard(pattern.as_bytes(), name.as_bytes()) {
+            continue;
+        }
+        debug!("Parsing {name} from tar archive");
+        let file = parse_cif(entry).map_err(|e| ArchiveError::Parse(name.clone(), e))?;
+        files.push((name, file));
+    }
+    if files.is_empty() {
+        return Err(ArchiveError::NoMatchingMember(pattern.to_string()));
+    }
+    Ok(files)
+}
@@ -0,0 +1,106 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use log::debug;
+use memmap2::Mmap;
+use rkyv::Deserialize;
+use thiserror::Error;
+
+use crate::schedule::{ArchivedScheduleDatabase, ScheduleDatabase};
+
+/// The bytes every archive starts with.
+const MAGIC: &[u8; 8] = b"NRCIFRKV";
+
+/// The version of the archive layout written by this version of the crate. This must be
+/// increased whenever the layout of [`ScheduleDatabase`] or any type within it changes, as
+/// archives are accessed in place and can't be migrated.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// The length of the header before the archived data, which keeps the data aligned.
+const HEADER_LEN: usize = 16;
+
+/// An error saving or loading an archive.
+#[derive(Debug, Error)]
+pub enum ArchiveSnapshotError {
+    #[error("failed to read or write archive")]
+    Io(#[from] io::Error),
+    #[error("the data is not a schedule database archive")]
+    NotAnArchive,
+    #[error("archive version {0} is not supported, this version of the crate reads version {ARCHIVE_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("failed to encode archive")]
+    Encoding(String),
+    #[error("the archive is corrupt: {0}")]
+    Invalid(String),
+}
+
+impl ScheduleDatabase {
+    /// Write a zero-copy archive of this database, which can be queried in place with
+    /// [`access_archive`] or [`MappedScheduleDatabase`], without deserializing it first.
+    pub fn write_archive<W: Write>(&self, mut writer: W) -> Result<(), ArchiveSnapshotError> {
+        let bytes = rkyv::to_bytes::<_, 4096>(self)
+            .map_err(|e| ArchiveSnapshotError::Encoding(e.to_string()))?;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&ARCHIVE_VERSION.to_le_bytes())?;
+        writer.write_all(&[0; HEADER_LEN - 12])?;
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+/// Check an archive written by [`ScheduleDatabase::write_archive`], and access the database
+/// within it. The bytes must be aligned to 16 bytes, as they are when mapped from a file.
+pub fn access_archive(bytes: &[u8]) -> Result<&ArchivedScheduleDatabase, ArchiveSnapshotError> {
+    if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+        return Err(ArchiveSnapshotError::NotAnArchive);
+    }
+    let version = u32::from_le_bytes(bytes[8..12].try_into().expect("slice is four bytes"));
+    if version != ARCHIVE_VERSION {
+        return Err(ArchiveSnapshotError::UnsupportedVersion(version));
+    }
+    rkyv::check_archived_root::<ScheduleDatabase>(&bytes[HEADER_LEN..])
+        .map_err(|e| ArchiveSnapshotError::Invalid(e.to_string()))
+}
+
+/// A schedule database archive mapped into memory, which can be queried without reading or
+/// deserializing the whole file.
+pub struct MappedScheduleDatabase {
+    mmap: Mmap,
+}
+
+impl MappedScheduleDatabase {
+    /// Map an archive written by [`ScheduleDatabase::write_archive`] into memory, checking
+    /// that it is valid.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other process, while it is
+    /// mapped. The archive is only checked here, so a change afterwards could make the
+    /// database returned by [`MappedScheduleDatabase::database`] invalid.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self, ArchiveSnapshotError> {
+        let file = File::open(path)?;
+        // SAFETY: the caller guarantees the file isn't modified while it is mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        debug!("Checking mapped archive...");
+        access_archive(&mmap)?;
+        Ok(Self { mmap })
+    }
+
+    /// The archived database.
+    pub fn database(&self) -> &ArchivedScheduleDatabase {
+        // SAFETY: the archive was checked when it was opened, and the caller of `open`
+        // guarantees it hasn't changed since.
+        unsafe { rkyv::archived_root::<ScheduleDatabase>(&self.mmap[HEADER_LEN..]) }
+    }
+
+    /// Deserialize the whole database, for example to apply updates to it.
+    pub fn to_database(&self) -> ScheduleDatabase {
+        match self.database().deserialize(&mut rkyv::Infallible) {
+            Ok(database) => database,
+            Err(never) => match never {},
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    board::{BoardOrder, BoardSortKey},
+    departures::CallingPoint,
+    platform::Platform,
+    schedule::{PowerType, Schedule, ScheduleDatabase, SeatingClass, TimingLoad},
+};
+
+/// A train arriving at a station, as shown on an arrivals board.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct Arrival {
+    /// The public arrival time.
+    #[getset(get_copy = "pub")]
+    time: NaiveDateTime,
+    /// The working arrival time.
+    #[getset(get_copy = "pub")]
+    working_time: NaiveDateTime,
+    /// The date the train started its journey, which may be the day before it arrives if it
+    /// runs overnight.
+    #[getset(get_copy = "pub")]
+    running_date: NaiveDate,
+    /// The TIPLOC the train arrives at, which may be one of several at the station.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The platform, which is empty if it isn't known.
+    #[getset(get = "pub")]
+    platform: Platform,
+    /// The TIPLOC of the origin.
+    #[getset(get = "pub")]
+    origin: String,
+    /// The name of the origin.
+    #[getset(get = "pub")]
+    origin_name: String,
+    /// The ATOC code of the operator.
+    #[getset(get = "pub")]
+    atoc_code: String,
+    /// The headcode of the train, being its train identity.
+    #[getset(get = "pub")]
+    headcode: String,
+    /// The train UID.
+    #[getset(get = "pub")]
+    train_uid: String,
+    /// The earlier locations where the train picked up passengers, in order, starting with
+    /// the origin.
+    #[getset(get = "pub")]
+    calling_points: Vec<CallingPoint>,
+    /// The expected formation of the train.
+    #[getset(get = "pub")]
+    formation: Formation,
+}
+
+/// The rolling stock a train is planned to be formed of, as far as the schedule gives it.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct Formation {
+    /// The type of power the train uses.
+    #[getset(get = "pub")]
+    power_type: PowerType,
+    /// The timing load, which may name the class of unit.
+    #[getset(get = "pub")]
+    timing_load: TimingLoad,
+    /// The class of the traction, if the operator has given it.
+    #[getset(get = "pub")]
+    traction_class: Option<String>,
+    /// The classes of seating available.
+    #[getset(get = "pub")]
+    seating_class: SeatingClass,
+}
+
+impl From<&Schedule> for Formation {
+    fn from(schedule: &Schedule) -> Self {
+        Self {
+            power_type: schedule.power_type().clone(),
+            timing_load: schedule.timing_load().clone(),
+            traction_class: schedule.traction_class().clone(),
+            seating_class: *schedule.seating_class(),
+        }
+    }
+}
+
+impl ScheduleDatabase {
+    /// The public arrivals at a location (a TIPLOC or CRS code) in a window of time starting
+    /// at `from`. Short term planning overlays and cancellations are applied for the date
+    /// each train runs, and trains which started their journey the day before are included.
+    /// Arrivals are ordered by public time, then by train UID.
+    pub fn arrivals<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+    ) -> Vec<Arrival> {
+        self.arrivals_ordered(location, from, window, BoardOrder::default())
+    }
+
+    /// The public arrivals at a location, as with [`ScheduleDatabase::arrivals`], listed in
+    /// the given order.
+    pub fn arrivals_ordered<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+        order: BoardOrder,
+    ) -> Vec<Arrival> {
+        let tiplocs = self.resolve_location(location.as_ref());
+        let until = from + window;
+
+        let mut arrivals = vec![];
+        for (running_date, schedule) in self.trains_at(&tiplocs, from, until) {
+            let calls = schedule.materialise(running_date);
+            for (idx, call) in calls.iter().enumerate().skip(1) {
+                let location = call.location();
+                if !tiplocs.contains(location.tiploc()) || !location.sets_down() {
+                    continue;
+                }
+                let (Some(time), Some(working_time)) = (call.public_arrival(), call.arrival())
+                else {
+                    continue;
+                };
+                if time < from || time > until {
+                    continue;
+                }
+                let origin = calls[0].location().tiploc();
+                arrivals.push(Arrival {
+                    time,
+                    working_time,
+                    running_date,
+                    tiploc: location.tiploc().clone(),
+                    platform: location.platform().clone(),
+                    origin: origin.clone(),
+                    origin_name: self.location_name(origin),
+                    atoc_code: schedule.atoc_code().clone(),
+                    headcode: schedule.train_identity().clone(),
+                    train_uid: schedule.train_uid().clone(),
+                    calling_points: calls[..idx]
+                        .iter()
+                        .filter(|call| call.location().picks_up())
+                        .filter_map(|call| {
+                            let time = call.public_departure().or(call.public_arrival())?;
+                            Some(self.calling_point(call, time))
+                        })
+                        .collect(),
+                    formation: schedule.into(),
+                });
+            }
+        }
+        arrivals.sort_by(|a, b| order.compare(&a.sort_key(), &b.sort_key()));
+        arrivals
+    }
+}
+
+impl Arrival {
+    fn sort_key(&self) -> BoardSortKey<'_, NaiveDateTime> {
+        BoardSortKey {
+            public: Some(self.time),
+            working: self.working_time,
+            platform: self.platform.as_str(),
+            headcode: &self.headcode,
+            train_uid: &self.train_uid,
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{Datelike, NaiveDate};
+use getset::Getters;
+use log::{debug, warn};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    observer::ScheduleObserver,
+    schedule::{
+        parse_stp_indicator, ApplyWarningCategory, DaysRun, STPIndicator, ScheduleApplyError,
+        ScheduleDatabase,
+    },
+    sink::ScheduleSink,
+    types::CIFRecord,
+};
+
+/// A link between two trains, such as one dividing into two portions, or the same unit
+/// forming the next working.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
+pub struct Association {
+    /// The train UID of the main train.
+    #[getset(get = "pub")]
+    main_train_uid: String,
+    /// The train UID of the train associated with the main train.
+    #[getset(get = "pub")]
+    associated_train_uid: String,
+    /// When does this association start.
+    #[getset(get = "pub")]
+    start_date: NaiveDate,
+    /// When does this association end.
+    #[getset(get = "pub")]
+    end_date: NaiveDate,
+    /// Days on which this association applies, as dates of the main train.
+    #[getset(get = "pub")]
+    days: DaysRun,
+    #[getset(get = "pub")]
+    category: AssociationCategory,
+    #[getset(get = "pub")]
+    date_indicator: AssociationDateIndicator,
+    /// The TIPLOC of the location where the association happens.
+    #[getset(get = "pub")]
+    location: String,
+    /// The suffix of the main train's visit to the location, if it visits more than once.
+    #[getset(get = "pub")]
+    base_location_suffix: Option<char>,
+    /// The suffix of the associated train's visit to the location, if it visits more than
+    /// once.
+    #[getset(get = "pub")]
+    association_location_suffix: Option<char>,
+    #[getset(get = "pub")]
+    association_type: AssociationType,
+    #[getset(get = "pub")]
+    stp_indicator: STPIndicator,
+}
+
+impl Association {
+    /// Does this association apply to the main train starting on the given date?
+    pub fn runs_on(&self, date: NaiveDate) -> bool {
+        date >= self.start_date
+            && date <= self.end_date
+            && self.days.contains_weekday(date.weekday())
+    }
+
+    /// Is this association identified by the given trains, start date, location and STP
+    /// indicator?
+    pub(crate) fn has_key(
+        &self,
+        main_train_uid: &str,
+        associated_train_uid: &str,
+        start_date: NaiveDate,
+        location: &str,
+        stp_indicator: STPIndicator,
+    ) -> bool {
+        self.main_train_uid == main_train_uid
+            && self.associated_train_uid == associated_train_uid
+            && self.start_date == start_date
+            && self.location == location
+            && self.stp_indicator == stp_indicator
+    }
+
+    /// Is this association identified by the same key as another, so replaced by it?
+    pub(crate) fn same_key(&self, other: &Association) -> bool {
+        self.has_key(
+            &other.main_train_uid,
+            &other.associated_train_uid,
+            other.start_date,
+            &other.location,
+            other.stp_indicator,
+        )
+    }
+}
+
+/// How two associated trains are linked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+pub enum AssociationCategory {
+    /// The associated train joins the main train.
+    Join,
+    /// The associated train splits from the main train.
+    Divide,
+    /// The associated train is the next working of the main train's stock.
+    Next,
+    NotSpecified,
+}
+
+/// When the associated train is at the location, relative to the main train.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+pub enum AssociationDateIndicator {
+    /// The same day.
+    Standard,
+    /// The day after.
+    OverNextMidnight,
+    /// The day before.
+    OverPreviousMidnight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+pub enum AssociationType {
+    Passenger,
+    Operating,
+    NotSpecified,
+}
+
+impl ScheduleDatabase {
+    /// The associations where the given train is the main train.
+    pub fn associations_by_main_uid<S: AsRef<str>>(&self, train_uid: S) -> &[Association] {
+        self.associations()
+            .get(train_uid.as_ref().trim())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Rebuild the index of associated train UIDs from the associations.
+    pub(crate) fn rebuild_associated_index(&mut self) {
+        let mut index: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for (main, associations) in self.associations() {
+            for association in associations {
+                index
+                    .entry(association.associated_train_uid.clone())
+                    .or_default()
+                    .insert(main.clone());
+            }
+        }
+        *self.associated_index_mut() = index;
+    }
+
+    /// The associations where the given train is the associated train.
+    pub fn associations_by_associated_uid<S: AsRef<str>>(&self, train_uid: S) -> Vec<&Association> {
+        let train_uid = train_uid.as_ref().trim();
+        self.associated_index()
+            .get(train_uid)
+            .into_iter()
+            .flatten()
+            .flat_map(|main| self.associations_by_main_uid(main))
+            .filter(|association| association.associated_train_uid == train_uid)
+            .collect()
+    }
+}
+
+/// Apply an association record to a store. New and revised associations replace any
+/// association with the same trains, start date, location and STP indicator.
+pub(crate) fn apply_association_record<S: ScheduleSink + ?Sized>(
+    sink: &mut S,
+    record: &CIFRecord,
+    warnings: &mut Vec<(ApplyWarningCategory, String)>,
+    observer: &mut dyn ScheduleObserver,
+) -> Result<(), ScheduleApplyError> {
+    let CIFRecord::Association {
+        transaction_type,
+        main_train_uid,
+        associated_train_uid,
+        association_start_date,
+        association_end_date,
+        association_days,
+        association_category,
+        association_date_indicator,
+        association_location,
+        base_location_suffix,
+        association_location_suffix,
+        association_type,
+        stp_indicator: stp,
+        ..
+    } = record
+    else {
+        return Ok(());
+    };
+
+    let start_date = association_date(association_start_date)?;
+    let location = association_location.trim();
+    let stp_indicator = parse_stp_indicator(stp)?;
+    if *transaction_type == 'D' {
+        match sink.delete_association(
+            main_train_uid,
+            associated_train_uid,
+            start_date,
+            location,
+            stp_indicator,
+        ) {
+            Some(previous) => observer.on_association_changed(Some(&previous), None),
+            None => {
+                warn!("A record is trying to delete an association of {main_train_uid}, but it doesn't exist in the database.");
+                warnings.push((
+                    ApplyWarningCategory::DeletedUnknownAssociation,
+                    main_train_uid.clone(),
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    let suffix = |suffix: &str| suffix.chars().next().filter(|c| !c.is_whitespace());
+    let association = Association {
+        main_train_uid: main_train_uid.clone(),
+        associated_train_uid: associated_train_uid.clone(),
+        start_date,
+        end_date: association_date(association_end_date)?,
+        days: DaysRun::from_bits(u8::from_str_radix(association_days, 2).map_err(|_| {
+            ScheduleApplyError::InvalidAssociationDays(association_days.to_string())
+        })?)
+        .ok_or(ScheduleApplyError::InvalidAssociationDays(
+            association_days.to_string(),
+        ))?,
+        category: match association_category.as_str() {
+            "JJ" => AssociationCategory::Join,
+            "VV" => AssociationCategory::Divide,
+            "NP" => AssociationCategory::Next,
+            "  " => AssociationCategory::NotSpecified,
+            _ => {
+                return Err(ScheduleApplyError::InvalidAssociationCategory(
+                    association_category.clone(),
+                ))
+            }
+        },
+        date_indicator: match association_date_indicator {
+            'S' | ' ' => AssociationDateIndicator::Standard,
+            'N' => AssociationDateIndicator::OverNextMidnight,
+            'P' => AssociationDateIndicator::OverPreviousMidnight,
+            c => return Err(ScheduleApplyError::InvalidAssociationDateIndicator(*c)),
+        },
+        location: location.to_string(),
+        base_location_suffix: suffix(base_location_suffix),
+        association_location_suffix: suffix(association_location_suffix),
+        association_type: match association_type {
+            'P' => AssociationType::Passenger,
+            'O' => AssociationType::Operating,
+            ' ' => AssociationType::NotSpecified,
+            c => return Err(ScheduleApplyError::InvalidAssociationType(*c)),
+        },
+        stp_indicator,
+    };
+    if !sink.keeps_association(&association) {
+        debug!(
+            "Association of {} with {} is left out",
+            association.main_train_uid, association.associated_train_uid
+        );
+        let previous = sink.delete_association(
+            &association.main_train_uid,
+            &association.associated_train_uid,
+            association.start_date,
+            &association.location,
+            association.stp_indicator,
+        );
+        if let Some(previous) = previous {
+            observer.on_association_changed(Some(&previous), None);
+        }
+        return Ok(());
+    }
+    let previous = sink.upsert_association(association.clone());
+    observer.on_association_changed(previous.as_ref(), Some(&association));
+    Ok(())
+}
+
+fn association_date(date: &str) -> Result<NaiveDate, ScheduleApplyError> {
+    NaiveDate::parse_from_str(date, "%y%m%d")
+        .map_err(|_| ScheduleApplyError::InvalidAssociationDate(date.to_string()))
+}
@@ -0,0 +1,95 @@
+use futures_core::Stream;
+use log::debug;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+use crate::{
+    parser::{CIFParseError, LineParser, ParseOptions},
+    types::*,
+};
+
+/// Parse a CIF file from an asynchronous reader into a programmatic [`CIFFile`], for example
+/// while it is being downloaded.
+pub async fn parse_cif_async<R: AsyncRead + Unpin>(
+    content_reader: R,
+) -> Result<CIFFile, CIFParseError> {
+    parse_cif_async_with_options(content_reader, ParseOptions::strict()).await
+}
+
+/// Parse a CIF file from an asynchronous reader into a programmatic [`CIFFile`], with the
+/// given options.
+pub async fn parse_cif_async_with_options<R: AsyncRead + Unpin>(
+    content_reader: R,
+    options: ParseOptions,
+) -> Result<CIFFile, CIFParseError> {
+    debug!("Parsing CIF data...");
+    let mut file = CIFFile::new();
+    let mut reader = AsyncCIFRecordReader::with_options(content_reader, options);
+    while let Some(record) = reader.next_record().await {
+        file.records_mut().push(record?);
+    }
+    *file.warnings_mut() = reader.take_warnings();
+    debug!("Parsed {} records.", file.records().len());
+    Ok(file)
+}
+
+/// Reads records one at a time from an asynchronous reader, stopping after the trailer
+/// record. This is the asynchronous equivalent of [`crate::prelude::CIFRecordReader`].
+pub struct AsyncCIFRecordReader<R: AsyncRead + Unpin> {
+    content_reader: BufReader<R>,
+    buf: Vec<u8>,
+    lines: LineParser,
+    finished: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncCIFRecordReader<R> {
+    /// Read records from a CIF file.
+    pub fn new(content_reader: R) -> Self {
+        Self::with_options(content_reader, ParseOptions::default())
+    }
+
+    /// Read records from a CIF file, with the given options.
+    pub fn with_options(content_reader: R, options: ParseOptions) -> Self {
+        Self {
+            content_reader: BufReader::new(content_reader),
+            buf: Vec::with_capacity(82),
+            lines: LineParser::new(options),
+            finished: false,
+        }
+    }
+
+    /// Take the warnings for lines skipped so far.
+    pub fn take_warnings(&mut self) -> Vec<CIFParseWarning> {
+        self.lines.take_warnings()
+    }
+
+    /// Read the next record, or [`None`] once the trailer record or an error has been
+    /// returned.
+    pub async fn next_record(&mut self) -> Option<Result<CIFRecord, CIFParseError>> {
+        if self.finished {
+            return None;
+        }
+        let record = self.read_record().await;
+        self.finished = matches!(record, Ok(CIFRecord::Trailer) | Err(_));
+        Some(record)
+    }
+
+    /// Turn this reader into a [`Stream`] of records.
+    pub fn into_stream(self) -> impl Stream<Item = Result<CIFRecord, CIFParseError>> {
+        futures_util::stream::unfold(self, |mut reader| async move {
+            let record = reader.next_record().await?;
+            Some((record, reader))
+        })
+    }
+
+    async fn read_record(&mut self) -> Result<CIFRecord, CIFParseError> {
+        loop {
+            self.buf.clear();
+            if self.content_reader.read_until(b'\n', &mut self.buf).await? == 0 {
+                return Err(self.lines.missing_trailer());
+            }
+            if let Some(record) = self.lines.parse(&self.buf)? {
+                return Ok(record);
+            }
+        }
+    }
+}
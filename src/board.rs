@@ -0,0 +1,87 @@
+use std::cmp::Ordering;
+
+use getset::CopyGetters;
+
+/// Which time a board is ordered by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BoardTime {
+    /// The public timetable time, falling back to the working time where there isn't one.
+    #[default]
+    Public,
+    /// The working timetable time.
+    Working,
+}
+
+/// How trains at the same time on a board are ordered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BoardTieBreak {
+    /// Order by train UID.
+    #[default]
+    TrainUid,
+    /// Order by platform, with numbered platforms in numerical order.
+    Platform,
+    /// Order by headcode.
+    Headcode,
+}
+
+/// The order in which trains are listed on a board.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, CopyGetters)]
+pub struct BoardOrder {
+    /// The time trains are ordered by.
+    #[getset(get_copy = "pub")]
+    time: BoardTime,
+    /// How trains at the same time are ordered.
+    #[getset(get_copy = "pub")]
+    tie_break: BoardTieBreak,
+}
+
+/// The fields of a board entry used to order it.
+pub(crate) struct BoardSortKey<'a, T> {
+    pub(crate) public: Option<T>,
+    pub(crate) working: T,
+    pub(crate) platform: &'a str,
+    pub(crate) headcode: &'a str,
+    pub(crate) train_uid: &'a str,
+}
+
+impl BoardOrder {
+    /// Create a new [`BoardOrder`].
+    pub fn new(time: BoardTime, tie_break: BoardTieBreak) -> Self {
+        Self { time, tie_break }
+    }
+
+    /// Compare two board entries. Entries which are otherwise equal are ordered by train UID.
+    pub(crate) fn compare<T: Ord + Copy>(
+        &self,
+        a: &BoardSortKey<'_, T>,
+        b: &BoardSortKey<'_, T>,
+    ) -> Ordering {
+        let time = |key: &BoardSortKey<'_, T>| match self.time {
+            BoardTime::Public => key.public.unwrap_or(key.working),
+            BoardTime::Working => key.working,
+        };
+        let tie_break = match self.tie_break {
+            BoardTieBreak::TrainUid => Ordering::Equal,
+            BoardTieBreak::Platform => platform_key(a.platform).cmp(&platform_key(b.platform)),
+            BoardTieBreak::Headcode => a.headcode.cmp(b.headcode),
+        };
+        time(a)
+            .cmp(&time(b))
+            .then(tie_break)
+            .then(a.train_uid.cmp(b.train_uid))
+    }
+}
+
+/// Split a platform into its number and any suffix, so that platform 5 sorts before platform
+/// 10 and platform 1A sorts after platform 1. Unknown platforms sort last.
+pub(crate) fn platform_key(platform: &str) -> (bool, u32, &str) {
+    let platform = platform.trim();
+    let digits = platform
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(platform.len());
+    (
+        platform.is_empty(),
+        platform[..digits].parse().unwrap_or(u32::MAX),
+        &platform[digits..],
+    )
+}
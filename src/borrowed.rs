@@ -0,0 +1,114 @@
+use log::debug;
+
+use crate::{parser::CIFParseError, types::cif_record_layout};
+
+/// Reads fixed width fields from a line one after another.
+struct Fields<'a> {
+    line: &'a str,
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line: &'a str) -> Self {
+        Self { line, pos: 2 }
+    }
+
+    fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    /// Take the next field. Fields beyond the end of a short line are cut short, or empty. The
+    /// line must be ASCII, so that every byte offset is a character boundary.
+    fn take(&mut self, len: usize) -> &'a str {
+        let start = self.pos.min(self.line.len());
+        self.pos += len;
+        &self.line[start..self.pos.min(self.line.len())]
+    }
+}
+
+macro_rules! borrowed_records {
+    (
+        $(
+            $variant:ident = $record_type:tt {
+                $($field:ident $(@ $start:tt)? : $ty:tt = $len:tt,)*
+            },
+        )*
+        ;
+        $($unit:ident = $unit_type:tt,)*
+    ) => {
+        /// A record from a CIF file, borrowing each field from the line it was parsed from.
+        /// Fields are laid out as in [`crate::prelude::CIFRecord`], but are never converted
+        /// from text, so they are kept exactly as they appear in the file.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum CIFRecordRef<'a> {
+            $($variant { $($field: &'a str),* },)*
+            $($unit {},)*
+        }
+
+        impl<'a> CIFRecordRef<'a> {
+            /// Parse a record from a line, without the line ending. Returns [`None`] if the
+            /// record type isn't recognised, or the line isn't ASCII, as CIF files are.
+            pub fn from_line(line: &'a str) -> Option<Self> {
+                if !line.is_ascii() {
+                    return None;
+                }
+                $(
+                    if line.starts_with($record_type) {
+                        let mut fields = Fields::new(line);
+                        $(
+                            $(fields.seek($start);)?
+                            let $field = fields.take($len);
+                        )*
+                        return Some(Self::$variant { $($field),* });
+                    }
+                )*
+                $(
+                    if line.starts_with($unit_type) {
+                        return Some(Self::$unit {});
+                    }
+                )*
+                None
+            }
+        }
+    };
+}
+
+cif_record_layout!(borrowed_records);
+
+/// Parse a CIF file held in memory into records which borrow from it, avoiding an
+/// allocation for every field. Lines may end with LF or CRLF, and parsing stops after the
+/// trailer record.
+pub fn parse_cif_bytes(content: &[u8]) -> Result<Vec<CIFRecordRef<'_>>, CIFParseError> {
+    debug!("Parsing CIF data in place...");
+    let mut records = vec![];
+    let mut bytes_read = 0;
+    for (idx, line) in content.split_inclusive(|b| *b == b'\n').enumerate() {
+        let line_number = idx + 1;
+        bytes_read += line.len() as u64;
+        let unterminated = !line.ends_with(b"\n");
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if unterminated && line.len() < 80 && !line.starts_with(b"ZZ") {
+            return Err(CIFParseError::UnexpectedEof {
+                line: line_number,
+                bytes_read,
+            });
+        }
+        // fields are sliced by byte, so only ASCII lines can be split into them
+        let line = std::str::from_utf8(line)
+            .ok()
+            .filter(|line| line.is_ascii())
+            .ok_or(CIFParseError::InvalidText(line_number))?;
+        let record = CIFRecordRef::from_line(line).ok_or_else(|| {
+            CIFParseError::UnknownRecordType(line_number, line.chars().take(2).collect())
+        })?;
+        records.push(record);
+        if matches!(record, CIFRecordRef::Trailer {}) {
+            debug!("Parsed {} records.", records.len());
+            return Ok(records);
+        }
+    }
+    Err(CIFParseError::MissingTrailer {
+        lines_read: records.len(),
+    })
+}
@@ -0,0 +1,179 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use chrono::NaiveDate;
+
+use crate::{
+    calendar::BankHolidayCalendar, filter::ScheduleFilter, schedule::ScheduleDatabase,
+    types::CIFRecord,
+};
+
+/// What to do when a schedule has a code not known to this library, such as a new train
+/// category.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+pub enum UnknownCodePolicy {
+    /// Fail to apply the schedule.
+    Error,
+    /// Keep the code as an `Other` or `Unknown` value, and raise a warning.
+    Warn,
+    /// Keep the code as an `Other` or `Unknown` value.
+    #[default]
+    Fallback,
+}
+
+/// A kind of record which can be left out when applying records to a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+pub enum RecordKind {
+    /// TIPLOC insert, amend and delete records.
+    Tiploc,
+    /// Association records.
+    Association,
+    /// Schedules, with all of their records.
+    Schedule,
+    /// Train and location notes on schedules.
+    Note,
+}
+
+impl RecordKind {
+    /// The kind of a record, if it is a kind which can be left out.
+    pub fn of(record: &CIFRecord) -> Option<Self> {
+        match record {
+            CIFRecord::Header { .. } | CIFRecord::Trailer => None,
+            CIFRecord::TIPLOCInsert { .. }
+            | CIFRecord::TIPLOCAmend { .. }
+            | CIFRecord::TIPLOCDelete { .. } => Some(RecordKind::Tiploc),
+            CIFRecord::Association { .. } => Some(RecordKind::Association),
+            CIFRecord::TrainNote { .. } | CIFRecord::LocationNote { .. } => Some(RecordKind::Note),
+            _ => Some(RecordKind::Schedule),
+        }
+    }
+}
+
+/// Builds a [`ScheduleDatabase`], choosing how strictly records are decoded when they are
+/// applied to it.
+#[derive(Debug, Clone)]
+pub struct ScheduleDatabaseBuilder {
+    database: ScheduleDatabase,
+}
+
+impl Default for ScheduleDatabaseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScheduleDatabaseBuilder {
+    /// Start building a database with the default options.
+    pub fn new() -> Self {
+        Self {
+            database: ScheduleDatabase::new(),
+        }
+    }
+
+    /// What to do when a schedule has a code not known to this library. By default, the
+    /// code is kept as a fallback value.
+    pub fn on_unknown_code(mut self, policy: UnknownCodePolicy) -> Self {
+        self.database.set_unknown_code_policy(policy);
+        self
+    }
+
+    /// Leave out records of the given kinds when applying records.
+    pub fn skip_record_kinds<I: IntoIterator<Item = RecordKind>>(mut self, kinds: I) -> Self {
+        let mut skipped = self.database.skip_record_kinds().clone();
+        for kind in kinds {
+            if !skipped.contains(&kind) {
+                skipped.push(kind);
+            }
+        }
+        self.database.set_skip_record_kinds(skipped);
+        self
+    }
+
+    /// Panic on the first error when applying records, which is useful when testing. By
+    /// default this is only done if the `panic-on-first-error` feature is enabled.
+    pub fn panic_on_first_error(mut self, panic: bool) -> Self {
+        self.database.set_panic_on_first_error(panic);
+        self
+    }
+
+    /// Whether public times of "0000" are treated as not advertised, unless the train is
+    /// actually timed at midnight. Enabled by default.
+    pub fn ignore_placeholder_public_times(mut self, ignore: bool) -> Self {
+        self.database.set_ignore_placeholder_public_times(ignore);
+        self
+    }
+
+    /// Only keep the schedules a filter keeps when applying records.
+    pub fn filter_schedules(mut self, filter: ScheduleFilter) -> Self {
+        self.database.set_schedule_filter(filter);
+        self
+    }
+
+    /// Only keep passenger trains when applying records.
+    pub fn passenger_only(self) -> Self {
+        self.map_filter(ScheduleFilter::only_passenger)
+    }
+
+    /// Only keep trains run by the operators with these ATOC codes when applying records.
+    pub fn only_operators<I, S>(self, atoc_codes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.map_filter(|filter| filter.only_operators(atoc_codes))
+    }
+
+    /// Only keep schedules which run at some point between two dates, inclusive, when
+    /// applying records.
+    pub fn only_between(self, start: NaiveDate, end: NaiveDate) -> Self {
+        self.map_filter(|filter| filter.only_between(start, end))
+    }
+
+    /// Only keep trains which call at one of the stations with these CRS codes when applying
+    /// records.
+    pub fn only_calling_at_crs<I, S>(self, crs_codes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.map_filter(|filter| filter.only_calling_at_crs(crs_codes))
+    }
+
+    /// Only keep the schedules and associations which run at some point between two dates,
+    /// inclusive. See [`ScheduleDatabase::retain_window`].
+    pub fn retain_window(mut self, start: NaiveDate, end: NaiveDate) -> Self {
+        self.database.retain_window(start, end);
+        self
+    }
+
+    fn map_filter<F: FnOnce(ScheduleFilter) -> ScheduleFilter>(mut self, f: F) -> Self {
+        let filter = f(self.database.schedule_filter().clone());
+        self.database.set_schedule_filter(filter);
+        self
+    }
+
+    /// The bank holidays used when working out which trains run on a date.
+    pub fn bank_holidays(mut self, calendar: BankHolidayCalendar) -> Self {
+        self.database.set_bank_holidays(calendar);
+        self
+    }
+
+    /// Build the empty database.
+    pub fn build(self) -> ScheduleDatabase {
+        self.database
+    }
+}
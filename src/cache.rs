@@ -0,0 +1,184 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{Duration, DurationRound, NaiveDate, NaiveDateTime};
+use getset::{Getters, Setters};
+use lru::LruCache;
+
+use crate::{
+    arrivals::Arrival,
+    board::BoardOrder,
+    departures::Departure,
+    schedule::{ApplyReport, ScheduleApplyError, ScheduleDatabase},
+    timetable::StationTimetable,
+    types::{CIFFile, CIFRecord},
+};
+
+/// A location, start time bucket, window and order of a board query.
+type BoardKey = (String, NaiveDateTime, Duration, BoardOrder);
+
+/// A [`ScheduleDatabase`] which caches the results of board queries, so that repeated requests
+/// for the same board don't have to be recomputed. The cache is cleared whenever the database
+/// is changed.
+///
+/// The start times of departure and arrival boards are truncated to a bucket, one minute by
+/// default, so that requests made close together share a cached board. The board returned is
+/// the one from the start of the bucket.
+///
+/// Queries take `&self`, so a single cache can be shared between threads.
+#[derive(Debug, Getters, Setters)]
+pub struct BoardCache {
+    /// The database queries are answered from.
+    #[getset(get = "pub")]
+    database: ScheduleDatabase,
+    /// The length of time which the start times of boards are truncated to.
+    #[getset(get = "pub", set = "pub")]
+    bucket: Duration,
+    timetables: Mutex<LruCache<(String, NaiveDate, BoardOrder), Arc<StationTimetable>>>,
+    departures: Mutex<LruCache<BoardKey, Arc<[Departure]>>>,
+    arrivals: Mutex<LruCache<BoardKey, Arc<[Arrival]>>>,
+}
+
+impl BoardCache {
+    /// Wrap a database, keeping the results of up to `capacity` queries of each kind.
+    pub fn new(database: ScheduleDatabase, capacity: NonZeroUsize) -> Self {
+        Self {
+            database,
+            bucket: Duration::minutes(1),
+            timetables: Mutex::new(LruCache::new(capacity)),
+            departures: Mutex::new(LruCache::new(capacity)),
+            arrivals: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Get mutable access to the database. The cache is cleared, as the database may be
+    /// changed.
+    pub fn database_mut(&mut self) -> &mut ScheduleDatabase {
+        self.clear();
+        &mut self.database
+    }
+
+    /// Take the database back out of the cache.
+    pub fn into_database(self) -> ScheduleDatabase {
+        self.database
+    }
+
+    /// Apply a file onto the database, clearing the cache.
+    pub fn apply_file(&mut self, file: &CIFFile) -> Vec<(usize, ScheduleApplyError)> {
+        self.database_mut().apply_file(file)
+    }
+
+    /// Apply a list of records onto the database, clearing the cache.
+    pub fn apply_records(&mut self, records: &[CIFRecord]) -> ApplyReport {
+        self.database_mut()
+            .apply_records_with_progress(records, |_| ())
+    }
+
+    /// Clear every cached result.
+    pub fn clear(&self) {
+        lock(&self.timetables).clear();
+        lock(&self.departures).clear();
+        lock(&self.arrivals).clear();
+    }
+
+    /// A cached [`ScheduleDatabase::station_timetable`].
+    pub fn station_timetable<S: AsRef<str>>(
+        &self,
+        location: S,
+        date: NaiveDate,
+    ) -> Arc<StationTimetable> {
+        self.station_timetable_ordered(location, date, BoardOrder::default())
+    }
+
+    /// A cached [`ScheduleDatabase::station_timetable_ordered`].
+    pub fn station_timetable_ordered<S: AsRef<str>>(
+        &self,
+        location: S,
+        date: NaiveDate,
+        order: BoardOrder,
+    ) -> Arc<StationTimetable> {
+        let key = (location.as_ref().trim().to_string(), date, order);
+        if let Some(timetable) = lock(&self.timetables).get(&key) {
+            return timetable.clone();
+        }
+        let timetable = Arc::new(self.database.station_timetable_ordered(&key.0, date, order));
+        lock(&self.timetables).put(key, timetable.clone());
+        timetable
+    }
+
+    /// The start of the bucket a board start time falls in.
+    fn bucket_start(&self, from: NaiveDateTime) -> NaiveDateTime {
+        from.duration_trunc(self.bucket).unwrap_or(from)
+    }
+
+    /// A cached [`ScheduleDatabase::departures`].
+    pub fn departures<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+    ) -> Arc<[Departure]> {
+        self.departures_ordered(location, from, window, BoardOrder::default())
+    }
+
+    /// A cached [`ScheduleDatabase::departures_ordered`].
+    pub fn departures_ordered<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+        order: BoardOrder,
+    ) -> Arc<[Departure]> {
+        let from = self.bucket_start(from);
+        let key = (location.as_ref().trim().to_string(), from, window, order);
+        if let Some(departures) = lock(&self.departures).get(&key) {
+            return departures.clone();
+        }
+        let departures: Arc<[Departure]> = self
+            .database
+            .departures_ordered(&key.0, from, window, order)
+            .into();
+        lock(&self.departures).put(key, departures.clone());
+        departures
+    }
+
+    /// A cached [`ScheduleDatabase::arrivals`].
+    pub fn arrivals<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+    ) -> Arc<[Arrival]> {
+        self.arrivals_ordered(location, from, window, BoardOrder::default())
+    }
+
+    /// A cached [`ScheduleDatabase::arrivals_ordered`].
+    pub fn arrivals_ordered<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+        order: BoardOrder,
+    ) -> Arc<[Arrival]> {
+        let from = self.bucket_start(from);
+        let key = (location.as_ref().trim().to_string(), from, window, order);
+        if let Some(arrivals) = lock(&self.arrivals).get(&key) {
+            return arrivals.clone();
+        }
+        let arrivals: Arc<[Arrival]> = self
+            .database
+            .arrivals_ordered(&key.0, from, window, order)
+            .into();
+        lock(&self.arrivals).put(key, arrivals.clone());
+        arrivals
+    }
+}
+
+/// Lock a cache, recovering it if another thread panicked while holding it.
+fn lock<K: std::hash::Hash + Eq, V>(
+    cache: &Mutex<LruCache<K, V>>,
+) -> std::sync::MutexGuard<'_, LruCache<K, V>> {
+    cache.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,401 @@
+use std::{collections::BTreeSet, ops::RangeInclusive};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{
+    BankHolidayRunning, JourneyLocation, JourneyTime, STPIndicator, Schedule, ScheduleDatabase,
+};
+
+/// The bank holidays which affect whether a train runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
+pub struct BankHolidayCalendar {
+    /// The bank holiday Mondays on which trains marked as not running on specific bank
+    /// holiday Mondays do not run.
+    #[getset(get = "pub")]
+    bank_holiday_mondays: BTreeSet<NaiveDate>,
+    /// The Glasgow bank holidays on which trains marked as not running on Glasgow bank
+    /// holidays do not run.
+    #[getset(get = "pub")]
+    glasgow_bank_holidays: BTreeSet<NaiveDate>,
+}
+
+impl BankHolidayCalendar {
+    /// Create a new, empty, [`BankHolidayCalendar`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a calendar with the England and Wales bank holiday Mondays, and the Glasgow
+    /// bank holidays, for each year in a range.
+    pub fn great_britain(years: RangeInclusive<i32>) -> Self {
+        let mut calendar = Self::new();
+        for year in years {
+            calendar.add_england_and_wales(year);
+            calendar.add_scotland(year);
+        }
+        calendar
+    }
+
+    /// Add the bank holidays in England and Wales which fall on a Monday in a year: Easter
+    /// Monday, the early May, spring and summer bank holidays, and New Year's Day, Christmas
+    /// Day and Boxing Day, or their substitutes, when they are on a Monday.
+    ///
+    /// Holidays moved by proclamation, such as for a coronation, aren't included, and should
+    /// be added with [`BankHolidayCalendar::add_bank_holiday_monday`].
+    pub fn add_england_and_wales(&mut self, year: i32) {
+        let easter_monday = easter_sunday(year).and_then(|easter| easter.succ_opt());
+        let movable = [
+            easter_monday,
+            NaiveDate::from_weekday_of_month_opt(year, 5, Weekday::Mon, 1),
+            last_monday(year, 5),
+            last_monday(year, 8),
+        ];
+        // a fixed holiday at the weekend is replaced by the following Monday, so any of
+        // these which is a Monday is a bank holiday
+        let fixed = [
+            (1, 1),
+            (1, 2),
+            (1, 3),
+            (12, 25),
+            (12, 26),
+            (12, 27),
+            (12, 28),
+        ]
+        .map(|(month, day)| {
+            NaiveDate::from_ymd_opt(year, month, day).filter(|date| date.weekday() == Weekday::Mon)
+        });
+        self.bank_holiday_mondays
+            .extend(movable.into_iter().chain(fixed).flatten());
+    }
+
+    /// Add the Glasgow bank holidays in a year: Glasgow Fair Monday, the third Monday in
+    /// July, and the September weekend holiday, the last Monday in September.
+    pub fn add_scotland(&mut self, year: i32) {
+        let holidays = [
+            NaiveDate::from_weekday_of_month_opt(year, 7, Weekday::Mon, 3),
+            last_monday(year, 9),
+        ];
+        self.glasgow_bank_holidays
+            .extend(holidays.into_iter().flatten());
+    }
+
+    /// Add a bank holiday Monday to the calendar.
+    pub fn add_bank_holiday_monday(&mut self, date: NaiveDate) {
+        self.bank_holiday_mondays.insert(date);
+    }
+
+    /// Add a Glasgow bank holiday to the calendar.
+    pub fn add_glasgow_bank_holiday(&mut self, date: NaiveDate) {
+        self.glasgow_bank_holidays.insert(date);
+    }
+
+    /// Is a train with the given bank holiday running prevented from running on a date?
+    pub fn excludes(&self, bank_holiday_running: BankHolidayRunning, date: NaiveDate) -> bool {
+        match bank_holiday_running {
+            BankHolidayRunning::RunsNormally => false,
+            BankHolidayRunning::NotOnSpecificBankHolidayMondays => {
+                self.bank_holiday_mondays.contains(&date)
+            }
+            BankHolidayRunning::NotOnGlasgowBankHolidays => {
+                self.glasgow_bank_holidays.contains(&date)
+            }
+        }
+    }
+}
+
+/// A train running on a specific date, with the absolute date and time of each location.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct DatedTrip<'a> {
+    /// The date this train starts its journey.
+    #[getset(get_copy = "pub")]
+    date: NaiveDate,
+    /// The schedule in force for this train on this date.
+    #[getset(get_copy = "pub")]
+    schedule: &'a Schedule,
+    /// Each location in the journey, with absolute times.
+    #[getset(get = "pub")]
+    calls: Vec<DatedCall<'a>>,
+}
+
+/// A location in a journey, with the times given as absolute dates and times for a specific
+/// running date. Times after midnight fall on the following day.
+#[derive(Debug, Clone, Copy, CopyGetters)]
+pub struct DatedCall<'a> {
+    /// The location in the schedule.
+    #[getset(get_copy = "pub")]
+    location: &'a JourneyLocation,
+    /// The working arrival time.
+    #[getset(get_copy = "pub")]
+    arrival: Option<NaiveDateTime>,
+    /// The working departure time.
+    #[getset(get_copy = "pub")]
+    departure: Option<NaiveDateTime>,
+    /// The working passing time.
+    #[getset(get_copy = "pub")]
+    pass: Option<NaiveDateTime>,
+    /// The public arrival time.
+    #[getset(get_copy = "pub")]
+    public_arrival: Option<NaiveDateTime>,
+    /// The public departure time.
+    #[getset(get_copy = "pub")]
+    public_departure: Option<NaiveDateTime>,
+}
+
+/// A train running on a specific date, to a specific schedule.
+#[derive(Debug, Clone, Copy, CopyGetters)]
+pub struct TrainRun<'a> {
+    /// The date this train starts its journey.
+    #[getset(get_copy = "pub")]
+    date: NaiveDate,
+    /// The schedule this train runs to on this date.
+    #[getset(get_copy = "pub")]
+    schedule: &'a Schedule,
+}
+
+impl Schedule {
+    /// Each date in a range on which this schedule is valid, in order. As with
+    /// [`Schedule::runs_on`], other schedules for this service which may overlay or cancel
+    /// this one aren't considered; use [`ScheduleDatabase::instances`] to take them into
+    /// account.
+    pub fn instances(&self, dates: RangeInclusive<NaiveDate>) -> Vec<TrainRun<'_>> {
+        dates
+            .start()
+            .iter_days()
+            .take_while(|date| date <= dates.end())
+            .filter(|date| self.runs_on(*date))
+            .map(|date| TrainRun {
+                date,
+                schedule: self,
+            })
+            .collect()
+    }
+
+    /// Is this schedule valid on the given date, taking into account whether it runs on the
+    /// bank holidays in `calendar`? As with [`Schedule::runs_on`], other schedules for this
+    /// service which may overlay or cancel this one aren't considered.
+    pub fn runs_on_with_calendar(&self, date: NaiveDate, calendar: &BankHolidayCalendar) -> bool {
+        self.runs_on(date) && !calendar.excludes(*self.bank_holiday_running(), date)
+    }
+
+    /// The locations in this schedule when run on the given date, with absolute dates and
+    /// times. Half minutes are kept as thirty seconds. Working times are moved onto the
+    /// following day each time they pass midnight, and public times are placed on the day
+    /// closest to the matching working time.
+    pub fn materialise(&self, date: NaiveDate) -> Vec<DatedCall<'_>> {
+        let mut day = date;
+        let mut last_seconds = 0;
+        let mut working = |time: &Option<JourneyTime>| {
+            time.map(|time| {
+                if time.to_seconds() < last_seconds {
+                    day = day.succ_opt().unwrap_or(day);
+                }
+                last_seconds = time.to_seconds();
+                day.and_time(time.to_naive_time())
+            })
+        };
+
+        self.journey()
+            .iter()
+            .map(|location| {
+                let arrival = working(location.arrival_time());
+                let pass = working(location.passing_time());
+                let departure = working(location.departure_time());
+                let reference = arrival.or(departure).or(pass);
+                DatedCall {
+                    location,
+                    arrival,
+                    departure,
+                    pass,
+                    public_arrival: public(location.public_arrival(), reference),
+                    public_departure: public(location.public_departure(), reference),
+                }
+            })
+            .collect()
+    }
+}
+
+impl ScheduleDatabase {
+    /// Expand the database into the trains which actually run on each date in a range. Short
+    /// term planning overlays, cancellations and the database's bank holiday calendar are
+    /// applied.
+    ///
+    /// The trips are ordered by date, then by the time they start, then by train UID.
+    pub fn dated_trips(&self, dates: RangeInclusive<NaiveDate>) -> Vec<DatedTrip<'_>> {
+        self.dated_trips_with_calendar(dates, self.bank_holidays())
+    }
+
+    /// As [`ScheduleDatabase::dated_trips`], but trains which don't run on the bank holidays
+    /// in `calendar` are omitted instead of those in the database's own calendar.
+    pub fn dated_trips_with_calendar(
+        &self,
+        dates: RangeInclusive<NaiveDate>,
+        calendar: &BankHolidayCalendar,
+    ) -> Vec<DatedTrip<'_>> {
+        let mut trips = vec![];
+        let mut date = *dates.start();
+        while date <= *dates.end() {
+            let mut day: Vec<DatedTrip> = self
+                .schedules()
+                .values()
+                .filter_map(|schedules| resolve_stp(schedules, date, calendar))
+                .map(|schedule| DatedTrip {
+                    date,
+                    schedule,
+                    calls: schedule.materialise(date),
+                })
+                .collect();
+            day.sort_by(|a, b| {
+                let start = |trip: &DatedTrip| trip.calls.first().and_then(|c| c.departure);
+                start(a)
+                    .cmp(&start(b))
+                    .then(a.schedule.train_uid().cmp(b.schedule.train_uid()))
+            });
+            trips.append(&mut day);
+            date += Duration::days(1);
+        }
+        trips
+    }
+
+    /// The schedule actually in force for a train on a date, after applying short term
+    /// planning overlays, cancellations and the database's bank holiday calendar. Returns
+    /// [`None`] if the train doesn't run, or is cancelled, on that date.
+    pub fn effective_schedule<S: AsRef<str>>(
+        &self,
+        train_uid: S,
+        date: NaiveDate,
+    ) -> Option<&Schedule> {
+        resolve_stp(
+            self.schedules().get(train_uid.as_ref().trim())?,
+            date,
+            self.bank_holidays(),
+        )
+    }
+
+    /// Each date in a range on which a train runs, with the schedule actually in force on
+    /// that date, as given by [`ScheduleDatabase::effective_schedule`]. Dates on which the
+    /// train doesn't run, or is cancelled, are left out.
+    pub fn instances<S: AsRef<str>>(
+        &self,
+        train_uid: S,
+        dates: RangeInclusive<NaiveDate>,
+    ) -> Vec<TrainRun<'_>> {
+        let Some(schedules) = self.schedules().get(train_uid.as_ref().trim()) else {
+            return vec![];
+        };
+        dates
+            .start()
+            .iter_days()
+            .take_while(|date| date <= dates.end())
+            .filter_map(|date| {
+                resolve_stp(schedules, date, self.bank_holidays())
+                    .map(|schedule| TrainRun { date, schedule })
+            })
+            .collect()
+    }
+
+    /// All schedules in effect on a date, after applying short term planning overlays,
+    /// cancellations and the database's bank holiday calendar.
+    pub(crate) fn schedules_running_on(&self, date: NaiveDate) -> impl Iterator<Item = &Schedule> {
+        self.schedules()
+            .values()
+            .filter_map(move |schedules| resolve_stp(schedules, date, self.bank_holidays()))
+    }
+}
+
+/// Pick the schedule in force on a date from all the schedules for a single train UID. A
+/// cancellation takes priority over everything, then a new or overlay schedule, and finally
+/// the permanent schedule. If the schedule picked doesn't run on a bank holiday in
+/// `calendar`, the train doesn't run, even if the schedule it replaces would have.
+fn resolve_stp<'a>(
+    schedules: &'a [Schedule],
+    date: NaiveDate,
+    calendar: &BankHolidayCalendar,
+) -> Option<&'a Schedule> {
+    let mut permanent = None;
+    let mut short_term = None;
+    for schedule in schedules.iter().filter(|s| s.runs_on(date)) {
+        match schedule.stp_indicator() {
+            STPIndicator::STPCancellationOfPermanentAssociation => return None,
+            STPIndicator::NewSTPAssociation | STPIndicator::STPOverlayOfPermanentAssociation => {
+                short_term = Some(schedule)
+            }
+            STPIndicator::PermanentAssociation => permanent = Some(schedule),
+        }
+    }
+    short_term
+        .or(permanent)
+        .filter(|schedule| !calendar.excludes(*schedule.bank_holiday_running(), date))
+}
+
+/// If a train UID is cancelled on a date, find the schedule which would have run had it not
+/// been cancelled.
+pub(crate) fn cancelled_schedule(schedules: &[Schedule], date: NaiveDate) -> Option<&Schedule> {
+    let running: Vec<&Schedule> = schedules.iter().filter(|s| s.runs_on(date)).collect();
+    if !running
+        .iter()
+        .any(|s| *s.stp_indicator() == STPIndicator::STPCancellationOfPermanentAssociation)
+    {
+        return None;
+    }
+    let layer = |stp: &[STPIndicator]| {
+        running
+            .iter()
+            .rev()
+            .find(|s| stp.contains(s.stp_indicator()))
+            .copied()
+    };
+    layer(&[
+        STPIndicator::NewSTPAssociation,
+        STPIndicator::STPOverlayOfPermanentAssociation,
+    ])
+    .or_else(|| layer(&[STPIndicator::PermanentAssociation]))
+}
+
+/// Easter Sunday in a year, by the anonymous Gregorian algorithm.
+fn easter_sunday(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+/// The last Monday in a month.
+fn last_monday(year: i32, month: u32) -> Option<NaiveDate> {
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Mon, 5)
+        .or_else(|| NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Mon, 4))
+}
+
+/// Place a public time on the day nearest to a working date and time.
+fn public(time: &Option<JourneyTime>, reference: Option<NaiveDateTime>) -> Option<NaiveDateTime> {
+    let time = (*time)?;
+    let reference = reference?;
+    let candidate = reference.date().and_time(time.to_naive_time());
+    if candidate - reference > Duration::hours(12) {
+        Some(candidate - Duration::days(1))
+    } else if reference - candidate > Duration::hours(12) {
+        Some(candidate + Duration::days(1))
+    } else {
+        Some(candidate)
+    }
+}
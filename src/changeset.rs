@@ -0,0 +1,166 @@
+use chrono::NaiveDate;
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    association::Association,
+    observer::ScheduleObserver,
+    schedule::{ApplyReport, STPIndicator, Schedule, ScheduleDatabase, TIPLOC},
+    types::CIFFile,
+};
+
+/// What happened to an item in a [`ChangeSet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ChangeKind {
+    Inserted,
+    Amended,
+    Deleted,
+}
+
+/// A schedule that was changed, identified by its train UID, start date and STP indicator.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScheduleChange {
+    #[getset(get_copy = "pub")]
+    kind: ChangeKind,
+    #[getset(get = "pub")]
+    train_uid: String,
+    #[getset(get_copy = "pub")]
+    runs_from: NaiveDate,
+    #[getset(get_copy = "pub")]
+    stp_indicator: STPIndicator,
+}
+
+/// A TIPLOC that was changed.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TIPLOCChange {
+    #[getset(get_copy = "pub")]
+    kind: ChangeKind,
+    #[getset(get = "pub")]
+    tiploc: String,
+}
+
+/// An association that was changed, identified by its trains, start date and location.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AssociationChange {
+    #[getset(get_copy = "pub")]
+    kind: ChangeKind,
+    #[getset(get = "pub")]
+    main_train_uid: String,
+    #[getset(get = "pub")]
+    associated_train_uid: String,
+    #[getset(get_copy = "pub")]
+    start_date: NaiveDate,
+    #[getset(get = "pub")]
+    location: String,
+}
+
+/// Every change made to a [`ScheduleDatabase`] while records were applied, in the order they
+/// were made. Useful for auditing updates, or replicating them into another store.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChangeSet {
+    /// Was the database cleared by a full extract before the changes were made?
+    #[getset(get_copy = "pub")]
+    full_update: bool,
+    #[getset(get = "pub")]
+    schedules: Vec<ScheduleChange>,
+    #[getset(get = "pub")]
+    tiplocs: Vec<TIPLOCChange>,
+    #[getset(get = "pub")]
+    associations: Vec<AssociationChange>,
+}
+
+impl ChangeSet {
+    /// Create an empty change set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Were no changes made?
+    pub fn is_empty(&self) -> bool {
+        !self.full_update
+            && self.schedules.is_empty()
+            && self.tiplocs.is_empty()
+            && self.associations.is_empty()
+    }
+
+    fn push_schedule(&mut self, kind: ChangeKind, schedule: &Schedule) {
+        self.schedules.push(ScheduleChange {
+            kind,
+            train_uid: schedule.train_uid().clone(),
+            runs_from: *schedule.runs_from(),
+            stp_indicator: *schedule.stp_indicator(),
+        });
+    }
+}
+
+fn change_kind<T>(previous: Option<&T>, current: Option<&T>) -> ChangeKind {
+    match (previous, current) {
+        (None, _) => ChangeKind::Inserted,
+        (Some(_), Some(_)) => ChangeKind::Amended,
+        (Some(_), None) => ChangeKind::Deleted,
+    }
+}
+
+impl ScheduleObserver for ChangeSet {
+    fn on_full_update(&mut self) {
+        self.full_update = true;
+    }
+
+    fn on_schedule_inserted(&mut self, schedule: &Schedule) {
+        self.push_schedule(ChangeKind::Inserted, schedule);
+    }
+
+    fn on_schedule_revised(&mut self, _previous: &Schedule, schedule: &Schedule) {
+        self.push_schedule(ChangeKind::Amended, schedule);
+    }
+
+    fn on_schedule_deleted(&mut self, schedule: &Schedule) {
+        self.push_schedule(ChangeKind::Deleted, schedule);
+    }
+
+    fn on_tiploc_changed(
+        &mut self,
+        tiploc: &str,
+        previous: Option<&TIPLOC>,
+        current: Option<&TIPLOC>,
+    ) {
+        self.tiplocs.push(TIPLOCChange {
+            kind: change_kind(previous, current),
+            tiploc: tiploc.to_string(),
+        });
+    }
+
+    fn on_association_changed(
+        &mut self,
+        previous: Option<&Association>,
+        current: Option<&Association>,
+    ) {
+        let Some(association) = current.or(previous) else {
+            return;
+        };
+        self.associations.push(AssociationChange {
+            kind: change_kind(previous, current),
+            main_train_uid: association.main_train_uid().clone(),
+            associated_train_uid: association.associated_train_uid().clone(),
+            start_date: *association.start_date(),
+            location: association.location().clone(),
+        });
+    }
+}
+
+impl ScheduleDatabase {
+    /// Apply a file onto this schedule database, returning every change made as well as the
+    /// report of errors and warnings.
+    /// This can reset the database if this includes a full update.
+    pub fn apply_file_with_changes(&mut self, file: &CIFFile) -> (ApplyReport, ChangeSet) {
+        let mut changes = ChangeSet::new();
+        let report = self.apply_records_observed(file.records(), &mut changes);
+        (report, changes)
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    board::{BoardOrder, BoardSortKey},
+    calendar::DatedCall,
+    platform::Platform,
+    query::Page,
+    schedule::{JourneyLocation, Schedule, ScheduleDatabase},
+};
+
+/// A train departing a station, as shown on a departure board.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct Departure {
+    /// The public departure time.
+    #[getset(get_copy = "pub")]
+    time: NaiveDateTime,
+    /// The working departure time.
+    #[getset(get_copy = "pub")]
+    working_time: NaiveDateTime,
+    /// The date the train starts its journey, which may be the day before it departs if it
+    /// runs overnight.
+    #[getset(get_copy = "pub")]
+    running_date: NaiveDate,
+    /// The TIPLOC the train departs from, which may be one of several at the station.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The platform, which is empty if it isn't known.
+    #[getset(get = "pub")]
+    platform: Platform,
+    /// The TIPLOC of the destination.
+    #[getset(get = "pub")]
+    destination: String,
+    /// The name of the destination.
+    #[getset(get = "pub")]
+    destination_name: String,
+    /// The ATOC code of the operator.
+    #[getset(get = "pub")]
+    atoc_code: String,
+    /// The headcode of the train, being its train identity.
+    #[getset(get = "pub")]
+    headcode: String,
+    /// The train UID.
+    #[getset(get = "pub")]
+    train_uid: String,
+    /// The later locations where the train sets down passengers, in order, ending with the
+    /// destination.
+    #[getset(get = "pub")]
+    calling_points: Vec<CallingPoint>,
+}
+
+/// A location where a train calls for passengers, as listed on a board.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct CallingPoint {
+    /// The TIPLOC of the location.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The name of the location.
+    #[getset(get = "pub")]
+    name: String,
+    /// The public time of the train at the location. This is the arrival time for the
+    /// calling points after a departure, and the departure time for those before an arrival.
+    #[getset(get_copy = "pub")]
+    time: NaiveDateTime,
+}
+
+impl ScheduleDatabase {
+    /// The public departures from a location (a TIPLOC or CRS code) in a window of time
+    /// starting at `from`. Short term planning overlays and cancellations are applied for
+    /// the date each train runs, and trains which started their journey the day before are
+    /// included. Departures are ordered by public time, then by train UID.
+    pub fn departures<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+    ) -> Vec<Departure> {
+        self.departures_ordered(location, from, window, BoardOrder::default())
+    }
+
+    /// The public departures from a location, as with [`ScheduleDatabase::departures`],
+    /// listed in the given order.
+    pub fn departures_ordered<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+        order: BoardOrder,
+    ) -> Vec<Departure> {
+        self.departure_candidates(location.as_ref(), from, window, order)
+            .iter()
+            .map(|candidate| self.departure(candidate))
+            .collect()
+    }
+
+    /// One page of the public departures from a location, listed in the given order as with
+    /// [`ScheduleDatabase::departures_ordered`]. Every departure in the window must still be
+    /// found to sort them, but only those on the page are built, with their calling points.
+    pub fn departures_page<S: AsRef<str>>(
+        &self,
+        location: S,
+        from: NaiveDateTime,
+        window: Duration,
+        order: BoardOrder,
+        offset: usize,
+        limit: usize,
+    ) -> Page<Departure> {
+        let candidates = self.departure_candidates(location.as_ref(), from, window, order);
+        let items = candidates
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|candidate| self.departure(candidate))
+            .collect();
+        Page::new(items, offset, candidates.len())
+    }
+
+    /// The public departures from a location in a window of time, in the given order.
+    fn departure_candidates(
+        &self,
+        location: &str,
+        from: NaiveDateTime,
+        window: Duration,
+        order: BoardOrder,
+    ) -> Vec<DepartureCandidate<'_>> {
+        let tiplocs = self.resolve_location(location);
+        let until = from + window;
+
+        let mut candidates = vec![];
+        for (running_date, schedule) in self.trains_at(&tiplocs, from, until) {
+            let calls = schedule.materialise(running_date);
+            for (idx, call) in calls.iter().enumerate().take(calls.len() - 1) {
+                let location = call.location();
+                if !tiplocs.contains(location.tiploc()) || !location.picks_up() {
+                    continue;
+                }
+                let (Some(time), Some(working_time)) = (call.public_departure(), call.departure())
+                else {
+                    continue;
+                };
+                if time < from || time > until {
+                    continue;
+                }
+                candidates.push(DepartureCandidate {
+                    running_date,
+                    schedule,
+                    idx,
+                    location,
+                    time,
+                    working_time,
+                });
+            }
+        }
+        candidates.sort_by(|a, b| order.compare(&a.sort_key(), &b.sort_key()));
+        candidates
+    }
+
+    /// Build a departure in full, with its destination and calling points.
+    fn departure(&self, candidate: &DepartureCandidate<'_>) -> Departure {
+        let schedule = candidate.schedule;
+        let calls = schedule.materialise(candidate.running_date);
+        let destination = calls[calls.len() - 1].location().tiploc();
+        Departure {
+            time: candidate.time,
+            working_time: candidate.working_time,
+            running_date: candidate.running_date,
+            tiploc: candidate.location.tiploc().clone(),
+            platform: candidate.location.platform().clone(),
+            destination: destination.clone(),
+            destination_name: self.location_name(destination),
+            atoc_code: schedule.atoc_code().clone(),
+            headcode: schedule.train_identity().clone(),
+            train_uid: schedule.train_uid().clone(),
+            calling_points: self.calling_points(&calls[candidate.idx + 1..]),
+        }
+    }
+
+    /// The trains calling at any of the given TIPLOCs which may be there between two times,
+    /// with the date each starts its journey. The times of each train at the TIPLOCs must
+    /// still be checked.
+    pub(crate) fn trains_at<'a>(
+        &'a self,
+        tiplocs: &HashSet<String>,
+        from: NaiveDateTime,
+        until: NaiveDateTime,
+    ) -> impl Iterator<Item = (NaiveDate, &'a Schedule)> {
+        let uids = self.uids_at_locations(tiplocs.iter().map(String::as_str));
+        let first = from.date() - Duration::days(1);
+        first
+            .iter_days()
+            .take_while(move |date| *date <= until.date())
+            .flat_map(move |date| {
+                uids.iter()
+                    .filter_map(move |uid| self.effective_schedule(uid, date))
+                    .filter(|schedule| !schedule.journey().is_empty())
+                    .map(move |schedule| (date, schedule))
+                    .collect::<Vec<_>>()
+            })
+    }
+
+    /// The locations among `calls` where passengers are set down, with their public times.
+    pub(crate) fn calling_points(&self, calls: &[DatedCall<'_>]) -> Vec<CallingPoint> {
+        calls
+            .iter()
+            .filter(|call| call.location().sets_down())
+            .filter_map(|call| {
+                let time = call.public_arrival().or(call.public_departure())?;
+                Some(self.calling_point(call, time))
+            })
+            .collect()
+    }
+
+    /// A location a train calls at, at a public time.
+    pub(crate) fn calling_point(&self, call: &DatedCall<'_>, time: NaiveDateTime) -> CallingPoint {
+        let tiploc = call.location().tiploc();
+        CallingPoint {
+            tiploc: tiploc.clone(),
+            name: self.location_name(tiploc),
+            time,
+        }
+    }
+
+    /// The name of a TIPLOC, or the TIPLOC itself if it isn't known.
+    pub(crate) fn location_name(&self, tiploc: &str) -> String {
+        self.tiplocs()
+            .get(tiploc)
+            .map(|t| t.description().clone())
+            .unwrap_or_else(|| tiploc.to_string())
+    }
+}
+
+/// A departure found for a board, before it is built in full.
+struct DepartureCandidate<'a> {
+    running_date: NaiveDate,
+    schedule: &'a Schedule,
+    /// The position of the departure in the schedule's journey.
+    idx: usize,
+    location: &'a JourneyLocation,
+    time: NaiveDateTime,
+    working_time: NaiveDateTime,
+}
+
+impl DepartureCandidate<'_> {
+    fn sort_key(&self) -> BoardSortKey<'_, NaiveDateTime> {
+        BoardSortKey {
+            public: Some(self.time),
+            working: self.working_time,
+            platform: self.location.platform().as_str(),
+            headcode: self.schedule.train_identity(),
+            train_uid: self.schedule.train_uid(),
+        }
+    }
+}
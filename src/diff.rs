@@ -0,0 +1,256 @@
+use chrono::Duration;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    activity::Activity,
+    analysis::difference,
+    platform::Platform,
+    schedule::{JourneyLocation, JourneyTime, Schedule},
+};
+
+/// Which of the times at a location changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LocationTime {
+    Arrival,
+    Departure,
+    Pass,
+    PublicArrival,
+    PublicDeparture,
+}
+
+/// A difference between two versions of a schedule, found by [`Schedule::diff`].
+///
+/// Locations are given by their index in the journey of the new version, except for those
+/// removed, which are given by their index in the journey of the old version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ScheduleFieldChange {
+    /// An attribute of the schedule itself changed, such as `atoc_code` or `power_type`. The
+    /// values are given in their debug form.
+    Attribute {
+        field: String,
+        before: String,
+        after: String,
+    },
+    /// The train now visits a location it didn't before.
+    LocationAdded { position: usize, tiploc: String },
+    /// The train no longer visits a location.
+    LocationRemoved { position: usize, tiploc: String },
+    /// A time at a location was added, removed or changed.
+    TimeChanged {
+        position: usize,
+        tiploc: String,
+        time: LocationTime,
+        before: Option<JourneyTime>,
+        after: Option<JourneyTime>,
+    },
+    /// The platform at a location changed.
+    PlatformChanged {
+        position: usize,
+        tiploc: String,
+        before: Platform,
+        after: Platform,
+    },
+    /// What the train does at a location changed, such as now stopping for passengers.
+    ActivitiesChanged {
+        position: usize,
+        tiploc: String,
+        before: Vec<Activity>,
+        after: Vec<Activity>,
+    },
+}
+
+impl ScheduleFieldChange {
+    /// How much later a time now is, which is negative if it is now earlier. This is only
+    /// given for times which were changed, rather than added or removed.
+    pub fn time_difference(&self) -> Option<Duration> {
+        match self {
+            Self::TimeChanged { before, after, .. } => difference(before, after),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! compare_attributes {
+    ($changes:ident, $before:ident, $after:ident, $($field:ident),* $(,)?) => {
+        $(
+            if $before.$field() != $after.$field() {
+                $changes.push(ScheduleFieldChange::Attribute {
+                    field: stringify!($field).to_string(),
+                    before: format!("{:?}", $before.$field()),
+                    after: format!("{:?}", $after.$field()),
+                });
+            }
+        )*
+    };
+}
+
+impl Schedule {
+    /// Describe how `other`, a later version of this schedule, differs from it. Changes to
+    /// the attributes of the schedule are given first, then changes to the journey in order
+    /// along it: the locations visited, and the times, platform and activities at each.
+    ///
+    /// Locations are matched up by TIPLOC and suffix, keeping as many as possible in order,
+    /// so a location added part way along the journey doesn't change those after it.
+    pub fn diff(&self, other: &Schedule) -> Vec<ScheduleFieldChange> {
+        let mut changes = vec![];
+        compare_attributes!(
+            changes,
+            self,
+            other,
+            train_uid,
+            runs_from,
+            runs_to,
+            days_run,
+            bank_holiday_running,
+            atoc_code,
+            subject_to_performance_monitoring,
+            uic_code,
+            traction_class,
+            train_status,
+            train_category,
+            train_identity,
+            nrs_headcode,
+            course_indicator,
+            train_service_code,
+            portion_id,
+            power_type,
+            timing_load,
+            speed,
+            operating_characteristics,
+            seating_class,
+            sleepers,
+            reservations,
+            catering,
+            connection_indicator,
+            service_branding,
+            stp_indicator,
+            notes,
+        );
+
+        let (before, after) = (self.journey(), other.journey());
+        for step in align(before, after) {
+            match step {
+                (Some(old), None) => changes.push(ScheduleFieldChange::LocationRemoved {
+                    position: old,
+                    tiploc: before[old].tiploc().clone(),
+                }),
+                (None, Some(new)) => changes.push(ScheduleFieldChange::LocationAdded {
+                    position: new,
+                    tiploc: after[new].tiploc().clone(),
+                }),
+                (Some(old), Some(new)) => {
+                    diff_location(&mut changes, new, &before[old], &after[new])
+                }
+                (None, None) => {}
+            }
+        }
+        changes
+    }
+}
+
+/// The changes to a location visited by both versions of a schedule.
+fn diff_location(
+    changes: &mut Vec<ScheduleFieldChange>,
+    position: usize,
+    before: &JourneyLocation,
+    after: &JourneyLocation,
+) {
+    let tiploc = after.tiploc();
+    for (time, old, new) in [
+        (
+            LocationTime::Arrival,
+            before.arrival_time(),
+            after.arrival_time(),
+        ),
+        (
+            LocationTime::Departure,
+            before.departure_time(),
+            after.departure_time(),
+        ),
+        (
+            LocationTime::Pass,
+            before.passing_time(),
+            after.passing_time(),
+        ),
+        (
+            LocationTime::PublicArrival,
+            before.public_arrival(),
+            after.public_arrival(),
+        ),
+        (
+            LocationTime::PublicDeparture,
+            before.public_departure(),
+            after.public_departure(),
+        ),
+    ] {
+        if old != new {
+            changes.push(ScheduleFieldChange::TimeChanged {
+                position,
+                tiploc: tiploc.clone(),
+                time,
+                before: *old,
+                after: *new,
+            });
+        }
+    }
+    if before.platform() != after.platform() {
+        changes.push(ScheduleFieldChange::PlatformChanged {
+            position,
+            tiploc: tiploc.clone(),
+            before: before.platform().clone(),
+            after: after.platform().clone(),
+        });
+    }
+    if before.activities() != after.activities() {
+        changes.push(ScheduleFieldChange::ActivitiesChanged {
+            position,
+            tiploc: tiploc.clone(),
+            before: before.activities().clone(),
+            after: after.activities().clone(),
+        });
+    }
+}
+
+/// Match up the locations of two journeys by TIPLOC and suffix, using the longest common
+/// subsequence. Each step gives the index of a location in the old journey, the new journey,
+/// or both if it is in both.
+fn align(
+    before: &[JourneyLocation],
+    after: &[JourneyLocation],
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let same = |a: &JourneyLocation, b: &JourneyLocation| {
+        a.tiploc() == b.tiploc() && a.suffix() == b.suffix()
+    };
+    // lengths[i][j] is the length of the longest common subsequence of before[i..] and
+    // after[j..]
+    let mut lengths = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            lengths[i][j] = if same(&before[i], &after[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut steps = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && same(&before[i], &after[j]) {
+            steps.push((Some(i), Some(j)));
+            i += 1;
+            j += 1;
+        } else if j == after.len() || (i < before.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            steps.push((Some(i), None));
+            i += 1;
+        } else {
+            steps.push((None, Some(j)));
+            j += 1;
+        }
+    }
+    steps
+}
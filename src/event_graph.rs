@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use getset::{CopyGetters, Getters};
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use crate::{
+    calendar::BankHolidayCalendar, planner::MINIMUM_CONNECTION, schedule::ScheduleDatabase,
+};
+
+/// A time-expanded graph of a day's timetable. Each node is an arrival or departure of a
+/// train at a location, and each edge is a train running, dwelling or a passenger
+/// transferring between trains.
+pub type EventGraph = DiGraph<TimetableEvent, TimetableEdge>;
+
+/// An arrival or departure of a train at a location.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct TimetableEvent {
+    /// Whether this is an arrival or a departure.
+    #[getset(get_copy = "pub")]
+    kind: TimetableEventKind,
+    /// The train UID of the train.
+    #[getset(get = "pub")]
+    train_uid: String,
+    /// The TIPLOC of the location.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The working time of the event.
+    #[getset(get_copy = "pub")]
+    time: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimetableEventKind {
+    Arrival,
+    Departure,
+}
+
+/// A connection between two events.
+#[derive(Debug, Clone, Copy, CopyGetters)]
+pub struct TimetableEdge {
+    /// What this edge represents.
+    #[getset(get_copy = "pub")]
+    kind: TimetableEdgeKind,
+    /// The time between the two events.
+    #[getset(get_copy = "pub")]
+    duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimetableEdgeKind {
+    /// A train running from its departure at one location to its arrival at the next.
+    Run,
+    /// A train waiting at a location between arriving and departing.
+    Dwell,
+    /// A passenger changing from an arriving train to a departing one at the same station.
+    Transfer,
+}
+
+/// Build an [`EventGraph`] for the trains running on a date.
+pub struct EventGraphBuilder<'a> {
+    database: &'a ScheduleDatabase,
+    date: NaiveDate,
+    calendar: Option<&'a BankHolidayCalendar>,
+    minimum_transfer: Duration,
+    maximum_transfer_wait: Duration,
+}
+
+impl<'a> EventGraphBuilder<'a> {
+    /// Create a new builder for the trains in `database` which run on `date`. By default, a
+    /// transfer needs at least five minutes and waits no longer than an hour.
+    pub fn new(database: &'a ScheduleDatabase, date: NaiveDate) -> Self {
+        Self {
+            database,
+            date,
+            calendar: None,
+            minimum_transfer: MINIMUM_CONNECTION,
+            maximum_transfer_wait: Duration::hours(1),
+        }
+    }
+
+    /// Omit trains which don't run on the bank holidays in this calendar, instead of those in
+    /// the database's own calendar.
+    pub fn calendar(mut self, calendar: &'a BankHolidayCalendar) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
+    /// The minimum time needed to transfer between trains.
+    pub fn minimum_transfer(mut self, minimum_transfer: Duration) -> Self {
+        self.minimum_transfer = minimum_transfer;
+        self
+    }
+
+    /// The longest time a passenger will wait when transferring between trains.
+    pub fn maximum_transfer_wait(mut self, maximum_transfer_wait: Duration) -> Self {
+        self.maximum_transfer_wait = maximum_transfer_wait;
+        self
+    }
+
+    /// Build the graph.
+    pub fn build(self) -> EventGraph {
+        let calendar = self
+            .calendar
+            .unwrap_or_else(|| self.database.bank_holidays());
+        let trips = self
+            .database
+            .dated_trips_with_calendar(self.date..=self.date, calendar);
+
+        let mut graph = EventGraph::new();
+        // passenger arrivals and departures at each station, for building transfers
+        let mut arrivals: HashMap<String, Vec<(NaiveDateTime, NodeIndex)>> = HashMap::new();
+        let mut departures: HashMap<String, Vec<(NaiveDateTime, NodeIndex)>> = HashMap::new();
+
+        for trip in &trips {
+            let train_uid = trip.schedule().train_uid();
+            let mut last_departure: Option<(NaiveDateTime, NodeIndex)> = None;
+            for call in trip.calls() {
+                let location = call.location();
+                let station = self.station(location.tiploc());
+                let mut add = |kind, time| {
+                    graph.add_node(TimetableEvent {
+                        kind,
+                        train_uid: train_uid.clone(),
+                        tiploc: location.tiploc().clone(),
+                        time,
+                    })
+                };
+
+                let arrival = call
+                    .arrival()
+                    .map(|time| (time, add(TimetableEventKind::Arrival, time)));
+                let departure = call
+                    .departure()
+                    .map(|time| (time, add(TimetableEventKind::Departure, time)));
+
+                if let (Some((from_time, from)), Some((to_time, to))) = (last_departure, arrival) {
+                    graph.add_edge(from, to, edge(TimetableEdgeKind::Run, from_time, to_time));
+                }
+                if let (Some((from_time, from)), Some((to_time, to))) = (arrival, departure) {
+                    graph.add_edge(from, to, edge(TimetableEdgeKind::Dwell, from_time, to_time));
+                }
+
+                if let Some(arrival) = arrival.filter(|_| location.sets_down()) {
+                    arrivals.entry(station.clone()).or_default().push(arrival);
+                }
+                if let Some(departure) = departure.filter(|_| location.picks_up()) {
+                    departures.entry(station).or_default().push(departure);
+                }
+                if departure.is_some() {
+                    last_departure = departure;
+                }
+            }
+        }
+
+        for (station, arrivals) in &arrivals {
+            let Some(departures) = departures.get_mut(station) else {
+                continue;
+            };
+            departures.sort();
+            for (arrival_time, arrival) in arrivals {
+                let earliest = *arrival_time + self.minimum_transfer;
+                let latest = *arrival_time + self.maximum_transfer_wait;
+                let start = departures.partition_point(|(time, _)| *time < earliest);
+                for (departure_time, departure) in &departures[start..] {
+                    if *departure_time > latest {
+                        break;
+                    }
+                    if graph[*departure].train_uid == graph[*arrival].train_uid {
+                        continue;
+                    }
+                    graph.add_edge(
+                        *arrival,
+                        *departure,
+                        edge(TimetableEdgeKind::Transfer, *arrival_time, *departure_time),
+                    );
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// The station a TIPLOC belongs to, identified by STANOX where it is known.
+    fn station(&self, tiploc: &str) -> String {
+        match self.database.tiplocs().get(tiploc) {
+            Some(t) if *t.stanox() != 0 => t.stanox().to_string(),
+            _ => tiploc.to_string(),
+        }
+    }
+}
+
+fn edge(kind: TimetableEdgeKind, from: NaiveDateTime, to: NaiveDateTime) -> TimetableEdge {
+    TimetableEdge {
+        kind,
+        duration: to - from,
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{self, Write};
+
+use rayon::prelude::*;
+
+use crate::schedule::{Schedule, ScheduleDatabase};
+
+/// The number of schedules serialised by each task.
+const CHUNK_SIZE: usize = 256;
+
+/// The number of schedules serialised before the output is written, limiting how much is held
+/// in memory at once.
+const BATCH_SIZE: usize = CHUNK_SIZE * 64;
+
+impl ScheduleDatabase {
+    /// Serialise every schedule in parallel, writing the output of `serialise` for each one to
+    /// `writer`. The output is written in the same order as it would be if the schedules were
+    /// serialised one at a time: by train UID, then in the order the schedules for each UID are
+    /// held in the database.
+    ///
+    /// This is intended for exporters writing the whole database, where serialising each
+    /// schedule is the expensive part.
+    pub fn write_schedules_parallel<W, F, E>(&self, writer: &mut W, serialise: F) -> Result<(), E>
+    where
+        W: Write,
+        F: Fn(&Schedule, &mut Vec<u8>) -> Result<(), E> + Sync,
+        E: Send + From<io::Error>,
+    {
+        let schedules: Vec<&Schedule> = self
+            .uid_index()
+            .iter()
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+            .collect();
+
+        for batch in schedules.chunks(BATCH_SIZE) {
+            let buffers = batch
+                .par_chunks(CHUNK_SIZE)
+                .map(|chunk| {
+                    let mut buffer = vec![];
+                    for schedule in chunk {
+                        serialise(schedule, &mut buffer)?;
+                    }
+                    Ok(buffer)
+                })
+                .collect::<Result<Vec<Vec<u8>>, E>>()?;
+            for buffer in buffers {
+                writer.write_all(&buffer)?;
+            }
+        }
+        Ok(())
+    }
+}
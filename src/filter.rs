@@ -0,0 +1,136 @@
+use chrono::NaiveDate;
+use getset::Getters;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{STPIndicator, Schedule, ScheduleDatabase, TrainStatus};
+
+impl TrainStatus {
+    /// Is this the status of a passenger and parcels train, either permanent or short term
+    /// planned?
+    pub fn is_passenger(&self) -> bool {
+        matches!(
+            self,
+            TrainStatus::PassengerAndParcels | TrainStatus::STPPassengerAndParcels
+        )
+    }
+}
+
+impl Schedule {
+    /// Is this schedule for a passenger and parcels train?
+    pub fn is_passenger(&self) -> bool {
+        self.train_status().is_passenger()
+    }
+}
+
+/// Which schedules to keep when applying records to a [`ScheduleDatabase`], so a database
+/// only holds the trains of interest. Every schedule is kept by default.
+///
+/// Short term planning overlays and cancellations of a train which has already been kept are
+/// always kept, as long as they fall within the date window, so they still apply to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
+pub struct ScheduleFilter {
+    /// Only keep passenger trains.
+    #[getset(get = "pub")]
+    passenger_only: bool,
+    /// Only keep trains run by these operators, given by ATOC code, if any are given.
+    #[getset(get = "pub")]
+    atoc_codes: Vec<String>,
+    /// Only keep schedules which run at some point between these dates, inclusive.
+    #[getset(get = "pub")]
+    date_window: Option<(NaiveDate, NaiveDate)>,
+    /// Only keep trains which call at one of these stations, given by CRS code, if any are
+    /// given.
+    #[getset(get = "pub")]
+    crs_codes: Vec<String>,
+}
+
+impl ScheduleFilter {
+    /// A filter which keeps every schedule.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only keep passenger trains.
+    pub fn only_passenger(mut self) -> Self {
+        self.passenger_only = true;
+        self
+    }
+
+    /// Only keep trains run by the operators with these ATOC codes, such as `SW`.
+    pub fn only_operators<I, S>(mut self, atoc_codes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.atoc_codes.extend(
+            atoc_codes
+                .into_iter()
+                .map(|c| c.as_ref().trim().to_string()),
+        );
+        self
+    }
+
+    /// Only keep schedules which run at some point between two dates, inclusive.
+    pub fn only_between(mut self, start: NaiveDate, end: NaiveDate) -> Self {
+        self.date_window = Some((start, end));
+        self
+    }
+
+    /// Only keep trains which call at one of the stations with these CRS codes, such as
+    /// `WAT`. The TIPLOCs for the stations must be applied before the schedules, as they are
+    /// in a CIF file.
+    pub fn only_calling_at_crs<I, S>(mut self, crs_codes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.crs_codes
+            .extend(crs_codes.into_iter().map(|c| c.as_ref().trim().to_string()));
+        self
+    }
+
+    /// Does this filter keep every schedule?
+    pub fn keeps_everything(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Does a schedule run at some point within the date window?
+    pub(crate) fn in_window(&self, schedule: &Schedule) -> bool {
+        self.date_window
+            .is_none_or(|(start, end)| *schedule.runs_from() <= end && *schedule.runs_to() >= start)
+    }
+
+    /// Should a schedule being applied to a database be kept?
+    pub(crate) fn keeps(&self, schedule: &Schedule, database: &ScheduleDatabase) -> bool {
+        if !self.in_window(schedule) {
+            return false;
+        }
+        if *schedule.stp_indicator() != STPIndicator::PermanentAssociation
+            && database.schedules().contains_key(schedule.train_uid())
+        {
+            return true;
+        }
+        if self.passenger_only && !schedule.is_passenger() {
+            return false;
+        }
+        if !self.atoc_codes.is_empty() && !self.atoc_codes.contains(schedule.atoc_code()) {
+            return false;
+        }
+        self.crs_codes.is_empty()
+            || self.crs_codes.iter().any(|crs| {
+                let tiplocs = database.get_tiplocs_for_crs(crs);
+                schedule.journey().iter().any(|location| {
+                    location.passing_time().is_none()
+                        && tiplocs.iter().any(|t| t.tiploc() == location.tiploc())
+                })
+            })
+    }
+}
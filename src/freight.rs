@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::schedule::{
+    OperatingCharacteristic, Schedule, ScheduleDatabase, TimingLoad, TrainStatus,
+};
+
+impl TrainStatus {
+    /// Is this the status of a freight train, either permanent or short term planned?
+    pub fn is_freight(&self) -> bool {
+        matches!(self, TrainStatus::Freight | TrainStatus::STPFreight)
+    }
+}
+
+impl Schedule {
+    /// Is this schedule for a freight train?
+    pub fn is_freight(&self) -> bool {
+        self.train_status().is_freight()
+    }
+
+    /// The trailing load of the train in tonnes, if the timing load is given as a weight.
+    pub fn trailing_load(&self) -> Option<u16> {
+        match self.timing_load() {
+            TimingLoad::LoadInTonnes(tonnes) => Some(*tonnes),
+            _ => None,
+        }
+    }
+
+    /// The class of the train, taken from the first character of its identity, for example
+    /// `'6'` for a freight train timed at up to 60 mph.
+    pub fn train_class(&self) -> Option<char> {
+        self.train_identity().chars().next()
+    }
+
+    /// Does this train only run when required, either throughout or to terminals?
+    pub fn runs_as_required(&self) -> bool {
+        self.operating_characteristics().iter().any(|c| {
+            matches!(
+                c,
+                OperatingCharacteristic::RunsAsRequired
+                    | OperatingCharacteristic::RunsToTerminalsAsRequired
+            )
+        })
+    }
+}
+
+impl ScheduleDatabase {
+    /// All freight trains planned to run on a date, after applying short term planning
+    /// overlays and cancellations. Trains which only run as required are included, and can be
+    /// identified with [`Schedule::runs_as_required`].
+    pub fn freight_services(&self, date: NaiveDate) -> impl Iterator<Item = &Schedule> {
+        self.schedules_running_on(date)
+            .filter(|schedule| schedule.is_freight())
+    }
+
+    /// All freight trains planned to run on a date, grouped by their train service code.
+    pub fn freight_services_by_service_code(
+        &self,
+        date: NaiveDate,
+    ) -> BTreeMap<&str, Vec<&Schedule>> {
+        let mut groups: BTreeMap<&str, Vec<&Schedule>> = BTreeMap::new();
+        for schedule in self.freight_services(date) {
+            groups
+                .entry(schedule.train_service_code().as_str())
+                .or_default()
+                .push(schedule);
+        }
+        for schedules in groups.values_mut() {
+            schedules.sort_by(|a, b| a.train_uid().cmp(b.train_uid()));
+        }
+        groups
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chrono::NaiveDate;
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{JourneyLocation, Schedule, ScheduleDatabase};
+
+/// An index from a key, such as a TIPLOC, to the train UIDs of the schedules with that key.
+/// The number of schedules for each train UID is kept, so the index can be kept up to date
+/// as schedules are removed.
+pub(crate) type ScheduleIndex = HashMap<String, BTreeMap<String, usize>>;
+
+/// An index from a TIPLOC to every visit a schedule makes to it.
+pub(crate) type CallIndex = HashMap<String, Vec<ScheduleCall>>;
+
+/// A visit by a schedule to a location, whether calling or passing, from
+/// [`ScheduleDatabase::calls_at`].
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
+pub struct ScheduleCall {
+    /// The train UID of the schedule.
+    #[getset(get = "pub")]
+    train_uid: String,
+    /// The index of the schedule among those for its train UID.
+    #[getset(get_copy = "pub")]
+    schedule_index: usize,
+    /// The index of the location in the schedule's journey.
+    #[getset(get_copy = "pub")]
+    position: usize,
+}
+
+fn index_insert(index: &mut ScheduleIndex, key: &str, train_uid: &str) {
+    *index
+        .entry(key.to_string())
+        .or_default()
+        .entry(train_uid.to_string())
+        .or_default() += 1;
+}
+
+fn index_remove(index: &mut ScheduleIndex, key: &str, train_uid: &str) {
+    let Some(uids) = index.get_mut(key) else {
+        return;
+    };
+    if let Some(count) = uids.get_mut(train_uid) {
+        *count -= 1;
+        if *count == 0 {
+            uids.remove(train_uid);
+        }
+    }
+    if uids.is_empty() {
+        index.remove(key);
+    }
+}
+
+fn insert_calls(index: &mut CallIndex, schedule_index: usize, schedule: &Schedule) {
+    for (position, location) in schedule.journey().iter().enumerate() {
+        index
+            .entry(location.tiploc().clone())
+            .or_default()
+            .push(ScheduleCall {
+                train_uid: schedule.train_uid().clone(),
+                schedule_index,
+                position,
+            });
+    }
+}
+
+/// The distinct TIPLOCs a schedule visits, whether calling or passing.
+fn schedule_locations(schedule: &Schedule) -> BTreeSet<&str> {
+    schedule
+        .journey()
+        .iter()
+        .map(|location| location.tiploc().as_str())
+        .collect()
+}
+
+impl ScheduleDatabase {
+    /// Add a schedule to the indexes of schedules.
+    pub(crate) fn index_schedule(&mut self, schedule: &Schedule) {
+        let uid = schedule.train_uid();
+        for tiploc in schedule_locations(schedule) {
+            index_insert(self.location_index_mut(), tiploc, uid);
+        }
+        if !schedule.train_identity().is_empty() {
+            index_insert(self.headcode_index_mut(), schedule.train_identity(), uid);
+        }
+        if !schedule.atoc_code().is_empty() {
+            index_insert(self.operator_index_mut(), schedule.atoc_code(), uid);
+        }
+        if !schedule.train_service_code().is_empty() {
+            index_insert(
+                self.service_code_index_mut(),
+                schedule.train_service_code(),
+                uid,
+            );
+        }
+    }
+
+    /// Remove a schedule from the indexes of schedules.
+    pub(crate) fn unindex_schedule(&mut self, schedule: &Schedule) {
+        let uid = schedule.train_uid();
+        for tiploc in schedule_locations(schedule) {
+            index_remove(self.location_index_mut(), tiploc, uid);
+        }
+        index_remove(self.headcode_index_mut(), schedule.train_identity(), uid);
+        index_remove(self.operator_index_mut(), schedule.atoc_code(), uid);
+        index_remove(
+            self.service_code_index_mut(),
+            schedule.train_service_code(),
+            uid,
+        );
+    }
+
+    /// Rebuild the indexes of schedules, including the train UIDs, from the schedules.
+    pub(crate) fn rebuild_schedule_indexes(&mut self) {
+        let schedules = std::mem::take(self.schedules_mut());
+        self.location_index_mut().clear();
+        self.headcode_index_mut().clear();
+        self.operator_index_mut().clear();
+        self.service_code_index_mut().clear();
+        for schedule in schedules.values().flatten() {
+            self.index_schedule(schedule);
+        }
+        *self.uid_index_mut() = schedules.keys().cloned().collect();
+        *self.schedules_mut() = schedules;
+        self.rebuild_call_index();
+    }
+
+    /// Add the visits of the schedule at an index among those for a train UID to the call
+    /// index.
+    pub(crate) fn index_calls(&mut self, train_uid: &str, schedule_index: usize) {
+        let mut index = std::mem::take(self.call_index_mut());
+        if let Some(schedule) = self
+            .schedules()
+            .get(train_uid)
+            .and_then(|s| s.get(schedule_index))
+        {
+            insert_calls(&mut index, schedule_index, schedule);
+        }
+        *self.call_index_mut() = index;
+    }
+
+    /// Remove the visits of the schedule at an index among those for its train UID from the
+    /// call index. If `removed` is set, the schedule has been taken out of the list, so the
+    /// visits of the schedules after it are moved down.
+    pub(crate) fn unindex_calls(
+        &mut self,
+        schedule_index: usize,
+        schedule: &Schedule,
+        removed: bool,
+    ) {
+        let uid = schedule.train_uid();
+        let mut tiplocs: BTreeSet<String> = schedule_locations(schedule)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        if removed {
+            if let Some(later) = self.schedules().get(uid) {
+                tiplocs.extend(
+                    later
+                        .iter()
+                        .skip(schedule_index)
+                        .flat_map(|s| s.journey().iter().map(|l| l.tiploc().clone())),
+                );
+            }
+        }
+        for tiploc in tiplocs {
+            let Some(calls) = self.call_index_mut().get_mut(&tiploc) else {
+                continue;
+            };
+            calls.retain(|call| call.train_uid != *uid || call.schedule_index != schedule_index);
+            if removed {
+                for call in calls.iter_mut() {
+                    if call.train_uid == *uid && call.schedule_index > schedule_index {
+                        call.schedule_index -= 1;
+                    }
+                }
+            }
+            if calls.is_empty() {
+                self.call_index_mut().remove(&tiploc);
+            }
+        }
+    }
+
+    /// Rebuild the call index from the schedules.
+    pub(crate) fn rebuild_call_index(&mut self) {
+        let mut index = CallIndex::new();
+        for schedules in self.schedules().values() {
+            for (idx, schedule) in schedules.iter().enumerate() {
+                insert_calls(&mut index, idx, schedule);
+            }
+        }
+        *self.call_index_mut() = index;
+    }
+
+    /// Every visit by any schedule to a TIPLOC, whether calling or passing, on any date. The
+    /// results are ordered by train UID, then by schedule, then by position in the journey.
+    pub fn calls_at<S: AsRef<str>>(&self, tiploc: S) -> Vec<&ScheduleCall> {
+        let mut calls: Vec<&ScheduleCall> = self
+            .call_index()
+            .get(tiploc.as_ref().trim())
+            .into_iter()
+            .flatten()
+            .collect();
+        calls.sort_by(|a, b| {
+            (&a.train_uid, a.schedule_index, a.position).cmp(&(
+                &b.train_uid,
+                b.schedule_index,
+                b.position,
+            ))
+        });
+        calls
+    }
+
+    /// The schedule and location of a visit found with [`ScheduleDatabase::calls_at`].
+    pub fn resolve_call(&self, call: &ScheduleCall) -> Option<(&Schedule, &JourneyLocation)> {
+        let schedule = self
+            .schedules()
+            .get(&call.train_uid)?
+            .get(call.schedule_index)?;
+        Some((schedule, schedule.journey().get(call.position)?))
+    }
+
+    /// The train UIDs with a schedule visiting any of the given TIPLOCs, in order.
+    pub(crate) fn uids_at_locations<'a, I>(&self, tiplocs: I) -> BTreeSet<&String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        tiplocs
+            .into_iter()
+            .filter_map(|tiploc| self.location_index().get(tiploc))
+            .flat_map(BTreeMap::keys)
+            .collect()
+    }
+
+    /// All schedules with the given headcode (train identity), such as `1A23`, on any date.
+    /// The results are ordered by train UID.
+    pub fn schedules_by_headcode<S: AsRef<str>>(&self, headcode: S) -> Vec<&Schedule> {
+        let headcode = headcode.as_ref().trim();
+        self.headcode_index()
+            .get(headcode)
+            .into_iter()
+            .flat_map(BTreeMap::keys)
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+            .filter(|schedule| schedule.train_identity() == headcode)
+            .collect()
+    }
+
+    /// All schedules with the given train service code, such as `24671005`, on any date.
+    /// The results are ordered by train UID.
+    pub fn schedules_by_service_code<S: AsRef<str>>(
+        &self,
+        train_service_code: S,
+    ) -> Vec<&Schedule> {
+        let train_service_code = train_service_code.as_ref().trim();
+        self.service_code_index()
+            .get(train_service_code)
+            .into_iter()
+            .flat_map(BTreeMap::keys)
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+            .filter(|schedule| schedule.train_service_code() == train_service_code)
+            .collect()
+    }
+
+    /// The schedules in effect on a date with the given headcode (train identity), such as
+    /// `1A23`, after applying short term planning overlays and cancellations. The results are
+    /// ordered by train UID.
+    pub fn find_by_headcode<S: AsRef<str>>(&self, headcode: S, date: NaiveDate) -> Vec<&Schedule> {
+        let headcode = headcode.as_ref().trim();
+        self.headcode_index()
+            .get(headcode)
+            .into_iter()
+            .flat_map(BTreeMap::keys)
+            .filter_map(|uid| self.effective_schedule(uid, date))
+            .filter(|schedule| schedule.train_identity() == headcode)
+            .collect()
+    }
+
+    /// As [`Self::find_by_headcode`], but only the schedules which pass through a TRUST
+    /// area, given by the first two digits of the STANOX of its locations. Headcodes are only
+    /// unique within an area, so this narrows a headcode seen in one place to its train.
+    pub fn find_by_headcode_in_area<S: AsRef<str>>(
+        &self,
+        headcode: S,
+        date: NaiveDate,
+        area: u32,
+    ) -> Vec<&Schedule> {
+        self.find_by_headcode(headcode, date)
+            .into_iter()
+            .filter(|schedule| {
+                schedule.journey().iter().any(|location| {
+                    self.tiplocs().get(location.tiploc()).is_some_and(|tiploc| {
+                        *tiploc.stanox() != 0 && tiploc.stanox() / 1000 == area
+                    })
+                })
+            })
+            .collect()
+    }
+}
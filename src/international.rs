@@ -0,0 +1,62 @@
+use chrono::NaiveDate;
+
+use crate::schedule::{Schedule, ScheduleDatabase, TrainCategory};
+
+impl TrainCategory {
+    /// Is this the category of a train running through the Channel Tunnel, either a
+    /// passenger service or a European freight service?
+    pub fn is_channel_tunnel(&self) -> bool {
+        matches!(
+            self,
+            TrainCategory::ChannelTunnel
+                | TrainCategory::RfDEuropeanChannelTunnelMixed
+                | TrainCategory::RfDEuropeanChannelTunnelIntermodal
+                | TrainCategory::RfDEuropeanChannelTunnelAutomotive
+                | TrainCategory::RfDEuropeanChannelTunnelContractServices
+                | TrainCategory::RfDEuropeanChannelTunnelHaulmark
+                | TrainCategory::RfDEuropeanChannelTunnelJointVenture
+        )
+    }
+
+    /// Is this the category of an international train?
+    pub fn is_international(&self) -> bool {
+        *self == TrainCategory::International || self.is_channel_tunnel()
+    }
+}
+
+impl Schedule {
+    /// Is this schedule for a train running through the Channel Tunnel?
+    pub fn is_channel_tunnel(&self) -> bool {
+        self.train_category().is_channel_tunnel()
+    }
+
+    /// Is this schedule for an international train? This is the case if it has an
+    /// international category, or if it has been given a UIC code.
+    pub fn is_international(&self) -> bool {
+        self.train_category().is_international() || self.uic_code().is_some()
+    }
+}
+
+impl ScheduleDatabase {
+    /// All international trains planned to run on a date, after applying short term planning
+    /// overlays and cancellations, ordered by train UID.
+    pub fn international_services(&self, date: NaiveDate) -> Vec<&Schedule> {
+        let mut services: Vec<&Schedule> = self
+            .schedules_running_on(date)
+            .filter(|schedule| schedule.is_international())
+            .collect();
+        services.sort_by(|a, b| a.train_uid().cmp(b.train_uid()));
+        services
+    }
+
+    /// All trains planned to run through the Channel Tunnel on a date, after applying short
+    /// term planning overlays and cancellations, ordered by train UID.
+    pub fn channel_tunnel_services(&self, date: NaiveDate) -> Vec<&Schedule> {
+        let mut services: Vec<&Schedule> = self
+            .schedules_running_on(date)
+            .filter(|schedule| schedule.is_channel_tunnel())
+            .collect();
+        services.sort_by(|a, b| a.train_uid().cmp(b.train_uid()));
+        services
+    }
+}
@@ -1,11 +1,114 @@
 #![doc = include_str!("../README.md")]
 
+mod activity;
+mod analysis;
+#[cfg(any(feature = "zip", feature = "tar"))]
+mod archive;
+#[cfg(feature = "rkyv")]
+mod archived;
+mod arrivals;
+mod association;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod board;
+mod borrowed;
+mod builder;
+#[cfg(feature = "lru")]
+mod cache;
+mod calendar;
+mod changeset;
+mod departures;
+mod diff;
+#[cfg(feature = "petgraph")]
+mod event_graph;
+#[cfg(feature = "rayon")]
+mod export;
+mod filter;
+mod freight;
+mod index;
+mod international;
+mod location;
+mod merge;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod observer;
+mod occupancy;
+mod operator;
+pub mod pagination;
 mod parser;
+mod planner;
+mod platform;
+mod positioning;
+mod progress;
+mod query;
+mod reference;
+mod retention;
 mod schedule;
+mod sink;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+mod statistics;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod timetable;
+#[cfg(feature = "chrono-tz")]
+mod timezone;
 mod types;
+mod update;
+mod validation;
+#[cfg(feature = "watch")]
+mod watch;
+mod writer;
 
 pub mod prelude {
+    pub use crate::activity::*;
+    pub use crate::analysis::*;
+    #[cfg(any(feature = "zip", feature = "tar"))]
+    pub use crate::archive::*;
+    #[cfg(feature = "rkyv")]
+    pub use crate::archived::*;
+    pub use crate::arrivals::*;
+    pub use crate::association::*;
+    #[cfg(feature = "async")]
+    pub use crate::asynchronous::*;
+    pub use crate::board::*;
+    pub use crate::borrowed::*;
+    pub use crate::builder::*;
+    #[cfg(feature = "lru")]
+    pub use crate::cache::*;
+    pub use crate::calendar::*;
+    pub use crate::changeset::*;
+    pub use crate::departures::*;
+    pub use crate::diff::*;
+    #[cfg(feature = "petgraph")]
+    pub use crate::event_graph::*;
+    pub use crate::filter::*;
+    pub use crate::index::ScheduleCall;
+    pub use crate::location::*;
+    pub use crate::merge::*;
+    #[cfg(feature = "mmap")]
+    pub use crate::mmap::*;
+    pub use crate::observer::*;
+    pub use crate::occupancy::*;
+    pub use crate::operator::*;
     pub use crate::parser::*;
+    pub use crate::planner::*;
+    pub use crate::platform::*;
+    pub use crate::progress::*;
+    pub use crate::query::*;
+    pub use crate::reference::*;
+    pub use crate::retention::*;
     pub use crate::schedule::*;
+    pub use crate::sink::*;
+    #[cfg(feature = "snapshot")]
+    pub use crate::snapshot::*;
+    pub use crate::statistics::*;
+    pub use crate::timetable::*;
+    #[cfg(feature = "chrono-tz")]
+    pub use crate::timezone::*;
     pub use crate::types::*;
+    pub use crate::validation::*;
+    #[cfg(feature = "watch")]
+    pub use crate::watch::*;
+    pub use crate::writer::*;
 }
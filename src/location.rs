@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use getset::{CopyGetters, Getters};
+
+use crate::schedule::{Schedule, ScheduleDatabase, TIPLOC};
+
+/// A group of TIPLOCs which share a STANOX, and so make up a single station.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct StationGroup {
+    /// The STANOX shared by the TIPLOCs in this group.
+    #[getset(get_copy = "pub")]
+    stanox: u32,
+    /// The TIPLOCs in this group, in alphabetical order.
+    #[getset(get = "pub")]
+    tiplocs: Vec<String>,
+    /// A CRS code for the station, if any TIPLOC in the group has one.
+    #[getset(get = "pub")]
+    crs: Option<String>,
+    /// A description of the station, taken from the TIPLOC with the CRS code if there is one.
+    #[getset(get = "pub")]
+    description: String,
+}
+
+/// A known location which closely matches a location which couldn't be found.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct LocationSuggestion {
+    /// The TIPLOC of the suggested location.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The CRS code of the suggested location, if it has one.
+    #[getset(get = "pub")]
+    crs: Option<String>,
+    /// The description of the suggested location.
+    #[getset(get = "pub")]
+    description: String,
+    /// The edit distance between the query and the closest of the TIPLOC, CRS code and
+    /// description.
+    #[getset(get_copy = "pub")]
+    distance: usize,
+}
+
+impl ScheduleDatabase {
+    /// The TIPLOCs with the given STANOX, in alphabetical order, for joining feeds which
+    /// identify locations by STANOX, such as TRUST and TD, against the timetable.
+    pub fn tiplocs_for_stanox(&self, stanox: u32) -> Vec<&TIPLOC> {
+        self.stanox_index()
+            .get(&stanox)
+            .into_iter()
+            .flatten()
+            .filter_map(|tiploc| self.tiplocs().get(tiploc))
+            .collect()
+    }
+
+    /// The TIPLOCs for a CRS code, in alphabetical order. TIPLOCs without their own CRS code
+    /// are included if they share a STANOX with a TIPLOC that has the CRS code.
+    pub fn get_tiplocs_for_crs<S: AsRef<str>>(&self, crs: S) -> Vec<&TIPLOC> {
+        let Some(with_crs) = self.crs_index().get(crs.as_ref().trim()) else {
+            return vec![];
+        };
+        let mut tiplocs: BTreeSet<&String> = with_crs.iter().collect();
+        for tiploc in with_crs.iter().filter_map(|t| self.tiplocs().get(t)) {
+            tiplocs.extend(
+                self.stanox_index()
+                    .get(tiploc.stanox())
+                    .into_iter()
+                    .flatten()
+                    .filter(|t| self.tiplocs()[*t].three_alpha_code().is_empty()),
+            );
+        }
+        tiplocs
+            .into_iter()
+            .filter_map(|t| self.tiplocs().get(t))
+            .collect()
+    }
+
+    /// The schedules which call at a station, given by its CRS code, at any of its TIPLOCs.
+    /// Locations the train only passes are not considered calls.
+    ///
+    /// The results are ordered by train UID. The schedules for each UID are kept in the order
+    /// they are held in the database.
+    pub fn schedules_calling_at_crs<S: AsRef<str>>(&self, crs: S) -> Vec<&Schedule> {
+        let tiplocs: HashSet<&str> = self
+            .get_tiplocs_for_crs(crs)
+            .into_iter()
+            .map(|t| t.tiploc().as_str())
+            .collect();
+        self.uids_at_locations(tiplocs.iter().copied())
+            .into_iter()
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+            .filter(|schedule| {
+                schedule.journey().iter().any(|location| {
+                    location.passing_time().is_none()
+                        && tiplocs.contains(location.tiploc().as_str())
+                })
+            })
+            .collect()
+    }
+
+    /// The schedules which visit any TIPLOC with the given STANOX, whether calling or
+    /// passing, such as to match train movements to their schedules.
+    ///
+    /// The results are ordered by train UID. The schedules for each UID are kept in the order
+    /// they are held in the database.
+    pub fn schedules_at_stanox(&self, stanox: u32) -> Vec<&Schedule> {
+        let Some(tiplocs) = self.stanox_index().get(&stanox) else {
+            return vec![];
+        };
+        self.uids_at_locations(tiplocs.iter().map(String::as_str))
+            .into_iter()
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+            .filter(|schedule| {
+                schedule
+                    .journey()
+                    .iter()
+                    .any(|location| tiplocs.contains(location.tiploc()))
+            })
+            .collect()
+    }
+
+    /// Add a TIPLOC to the indexes of TIPLOCs.
+    pub(crate) fn index_tiploc(&mut self, tiploc: &TIPLOC) {
+        if *tiploc.stanox() != 0 {
+            self.stanox_index_mut()
+                .entry(*tiploc.stanox())
+                .or_default()
+                .insert(tiploc.tiploc().clone());
+        }
+        if !tiploc.three_alpha_code().is_empty() {
+            self.crs_index_mut()
+                .entry(tiploc.three_alpha_code().clone())
+                .or_default()
+                .insert(tiploc.tiploc().clone());
+        }
+    }
+
+    /// Remove a TIPLOC from the indexes of TIPLOCs.
+    pub(crate) fn unindex_tiploc(&mut self, tiploc: &TIPLOC) {
+        if let Some(tiplocs) = self.stanox_index_mut().get_mut(tiploc.stanox()) {
+            tiplocs.remove(tiploc.tiploc());
+            if tiplocs.is_empty() {
+                self.stanox_index_mut().remove(tiploc.stanox());
+            }
+        }
+        if let Some(tiplocs) = self.crs_index_mut().get_mut(tiploc.three_alpha_code()) {
+            tiplocs.remove(tiploc.tiploc());
+            if tiplocs.is_empty() {
+                self.crs_index_mut().remove(tiploc.three_alpha_code());
+            }
+        }
+    }
+
+    /// Rebuild the indexes of TIPLOCs from the TIPLOCs.
+    pub(crate) fn rebuild_tiploc_indexes(&mut self) {
+        self.stanox_index_mut().clear();
+        self.crs_index_mut().clear();
+        let tiplocs = std::mem::take(self.tiplocs_mut());
+        for tiploc in tiplocs.values() {
+            self.index_tiploc(tiploc);
+        }
+        *self.tiplocs_mut() = tiplocs;
+    }
+
+    /// Group the TIPLOCs in the database by their STANOX, so that timing points which make up
+    /// the same station can be treated together. TIPLOCs without a STANOX are not included.
+    ///
+    /// The groups are ordered by STANOX.
+    pub fn station_groups(&self) -> Vec<StationGroup> {
+        let groups: BTreeMap<u32, Vec<String>> = self
+            .stanox_index()
+            .iter()
+            .map(|(stanox, tiplocs)| (*stanox, tiplocs.iter().cloned().collect()))
+            .collect();
+
+        groups
+            .into_iter()
+            .map(|(stanox, tiplocs)| {
+                let members: Vec<_> = tiplocs.iter().map(|t| &self.tiplocs()[t]).collect();
+                let representative = members
+                    .iter()
+                    .find(|t| !t.three_alpha_code().is_empty())
+                    .unwrap_or(&members[0]);
+                StationGroup {
+                    stanox,
+                    crs: Some(representative.three_alpha_code().clone()).filter(|c| !c.is_empty()),
+                    description: representative.description().clone(),
+                    tiplocs,
+                }
+            })
+            .collect()
+    }
+
+    /// Resolve a location, given as either a TIPLOC or a CRS code, to the set of TIPLOCs it
+    /// covers. TIPLOCs without their own CRS are included if they share a STANOX with a
+    /// TIPLOC that has the CRS.
+    pub(crate) fn resolve_location(&self, location: &str) -> HashSet<String> {
+        let location = location.trim();
+        if self.tiplocs().contains_key(location) {
+            return HashSet::from([location.to_string()]);
+        }
+
+        self.get_tiplocs_for_crs(location)
+            .into_iter()
+            .map(|t| t.tiploc().clone())
+            .collect()
+    }
+
+    /// Suggest up to `limit` known locations which closely match a location, for example to
+    /// offer alternatives when a TIPLOC or CRS code is mistyped. The query is compared,
+    /// ignoring case, to each TIPLOC, CRS code and description.
+    ///
+    /// The suggestions are ordered by edit distance, then by TIPLOC.
+    pub fn suggest_locations<S: AsRef<str>>(
+        &self,
+        query: S,
+        limit: usize,
+    ) -> Vec<LocationSuggestion> {
+        let query = query.as_ref().trim().to_uppercase();
+        let mut suggestions: Vec<LocationSuggestion> = self
+            .tiplocs()
+            .values()
+            .map(|tiploc| {
+                let distance = [
+                    tiploc.tiploc(),
+                    tiploc.three_alpha_code(),
+                    tiploc.description(),
+                ]
+                .into_iter()
+                .filter(|candidate| !candidate.is_empty())
+                .map(|candidate| edit_distance(&query, &candidate.to_uppercase()))
+                .min()
+                .unwrap_or(usize::MAX);
+                LocationSuggestion {
+                    tiploc: tiploc.tiploc().clone(),
+                    crs: Some(tiploc.three_alpha_code().clone()).filter(|c| !c.is_empty()),
+                    description: tiploc.description().clone(),
+                    distance,
+                }
+            })
+            .collect();
+        suggestions.sort_by(|a, b| a.distance.cmp(&b.distance).then(a.tiploc.cmp(&b.tiploc)));
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,129 @@
+use std::collections::hash_map::Entry;
+
+use log::debug;
+use thiserror::Error;
+
+use crate::schedule::ScheduleDatabase;
+
+/// How to handle a train UID or TIPLOC which is present in both databases being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeConflictPolicy {
+    /// Keep the entry already in the database, and discard the other.
+    KeepExisting,
+    /// Replace the entry already in the database with the other.
+    PreferOther,
+    /// Keep both sets of schedules for a train UID, with the other database's schedules
+    /// placed after the existing ones so they take precedence. Associations are combined in
+    /// the same way, except that one with the same key as an existing association replaces
+    /// it. TIPLOCs are replaced, as with [`MergeConflictPolicy::PreferOther`].
+    Combine,
+    /// Don't merge anything, and return an error.
+    Error,
+}
+
+/// An error merging two databases, when the conflict policy is
+/// [`MergeConflictPolicy::Error`].
+#[derive(Error, Debug)]
+pub enum MergeError {
+    #[error("train UID {0} is present in both databases")]
+    ScheduleConflict(String),
+    #[error("TIPLOC {0} is present in both databases")]
+    TIPLOCConflict(String),
+}
+
+impl ScheduleDatabase {
+    /// Merge another database into this one, for example to add a locally maintained
+    /// supplement to the national timetable. Train UIDs and TIPLOCs present in both databases
+    /// are handled according to `conflict_policy`.
+    ///
+    /// The extract date and time of the merged database is the later of the two.
+    pub fn merge(
+        &mut self,
+        mut other: ScheduleDatabase,
+        conflict_policy: MergeConflictPolicy,
+    ) -> Result<(), MergeError> {
+        if conflict_policy == MergeConflictPolicy::Error {
+            if let Some(uid) = other
+                .schedules()
+                .keys()
+                .find(|uid| self.schedules().contains_key(*uid))
+            {
+                return Err(MergeError::ScheduleConflict(uid.clone()));
+            }
+            if let Some(tiploc) = other
+                .tiplocs()
+                .keys()
+                .find(|tiploc| self.tiplocs().contains_key(*tiploc))
+            {
+                return Err(MergeError::TIPLOCConflict(tiploc.clone()));
+            }
+        }
+
+        if other.extract_date_time() > self.extract_date_time() {
+            *self.extract_date_time_mut() = *other.extract_date_time();
+        }
+
+        for (code, tiploc) in std::mem::take(other.tiplocs_mut()) {
+            match self.tiplocs_mut().entry(code) {
+                Entry::Vacant(entry) => {
+                    entry.insert(tiploc);
+                }
+                Entry::Occupied(mut entry) => {
+                    debug!("TIPLOC {} is present in both databases", entry.key());
+                    if conflict_policy != MergeConflictPolicy::KeepExisting {
+                        entry.insert(tiploc);
+                    }
+                }
+            }
+        }
+        for (uid, mut other_schedules) in std::mem::take(other.schedules_mut()) {
+            self.uid_index_mut().insert(uid.clone());
+            match self.schedules_mut().entry(uid) {
+                Entry::Vacant(entry) => {
+                    entry.insert(other_schedules);
+                }
+                Entry::Occupied(mut entry) => {
+                    debug!("Train UID {} is present in both databases", entry.key());
+                    match conflict_policy {
+                        MergeConflictPolicy::KeepExisting | MergeConflictPolicy::Error => (),
+                        MergeConflictPolicy::PreferOther => {
+                            entry.insert(other_schedules);
+                        }
+                        MergeConflictPolicy::Combine => {
+                            entry.get_mut().append(&mut other_schedules)
+                        }
+                    }
+                }
+            }
+        }
+        for (uid, other_associations) in std::mem::take(other.associations_mut()) {
+            match self.associations_mut().entry(uid) {
+                Entry::Vacant(entry) => {
+                    entry.insert(other_associations);
+                }
+                Entry::Occupied(mut entry) => match conflict_policy {
+                    MergeConflictPolicy::KeepExisting | MergeConflictPolicy::Error => (),
+                    MergeConflictPolicy::PreferOther => {
+                        entry.insert(other_associations);
+                    }
+                    MergeConflictPolicy::Combine => {
+                        let associations = entry.get_mut();
+                        for association in other_associations {
+                            match associations
+                                .iter()
+                                .position(|existing| existing.same_key(&association))
+                            {
+                                Some(idx) => associations[idx] = association,
+                                None => associations.push(association),
+                            }
+                        }
+                    }
+                },
+            }
+        }
+        self.rebuild_associated_index();
+        self.rebuild_tiploc_indexes();
+        self.rebuild_schedule_indexes();
+        Ok(())
+    }
+}
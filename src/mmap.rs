@@ -0,0 +1,66 @@
+use std::{fs::File, path::Path};
+
+use log::debug;
+use memmap2::Mmap;
+
+use crate::{
+    borrowed::{parse_cif_bytes, CIFRecordRef},
+    parser::{CIFParseError, LineParser, ParseOptions},
+    types::{CIFFile, CIFRecord},
+};
+
+/// A CIF file mapped into memory, for parsing very large files in place.
+pub struct MappedCIFFile {
+    mmap: Mmap,
+}
+
+impl MappedCIFFile {
+    /// Map a CIF file into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or any other process, while it is
+    /// mapped, as the mapping would change underneath any records borrowed from it.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self, CIFParseError> {
+        let file = File::open(path)?;
+        // SAFETY: the caller guarantees the file isn't modified while it is mapped.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self { mmap })
+    }
+
+    /// The contents of the file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Parse the records in the file, borrowing each field from the mapping.
+    pub fn records(&self) -> Result<Vec<CIFRecordRef<'_>>, CIFParseError> {
+        parse_cif_bytes(&self.mmap)
+    }
+}
+
+/// Parse a CIF file into a programmatic [`CIFFile`] by mapping it into memory, which avoids
+/// copying it through a read buffer first. Each line is parsed straight from the mapping.
+///
+/// # Safety
+///
+/// The file must not be modified or truncated, by this or any other process, while it is
+/// being parsed.
+pub unsafe fn parse_cif_mmap<P: AsRef<Path>>(path: P) -> Result<CIFFile, CIFParseError> {
+    // SAFETY: the caller guarantees the file isn't modified while it is mapped.
+    let mapped = unsafe { MappedCIFFile::open(path)? };
+    debug!("Parsing mapped CIF data...");
+    let mut file = CIFFile::new();
+    let mut lines = LineParser::new(ParseOptions::strict());
+    for line in mapped.bytes().split_inclusive(|b| *b == b'\n') {
+        if let Some(record) = lines.parse(line)? {
+            let is_trailer = matches!(record, CIFRecord::Trailer);
+            file.records_mut().push(record);
+            if is_trailer {
+                debug!("Parsed {} records.", file.records().len());
+                return Ok(file);
+            }
+        }
+    }
+    Err(lines.missing_trailer())
+}
@@ -0,0 +1,62 @@
+use std::convert::Infallible;
+
+use crate::{
+    association::Association,
+    schedule::{ApplyReport, Schedule, ScheduleDatabase, TIPLOC},
+    types::CIFRecord,
+};
+
+/// Receives the changes made to a [`ScheduleDatabase`] while records are applied to it, for
+/// example to keep a search index or downstream store in step. Every method does nothing by
+/// default, so only those of interest need to be implemented.
+pub trait ScheduleObserver {
+    /// Called when a full extract clears the database, before its records are applied.
+    fn on_full_update(&mut self) {}
+
+    /// Called when a schedule is added.
+    fn on_schedule_inserted(&mut self, _schedule: &Schedule) {}
+
+    /// Called when a schedule is replaced by a revision of it.
+    fn on_schedule_revised(&mut self, _previous: &Schedule, _schedule: &Schedule) {}
+
+    /// Called when a schedule is deleted.
+    fn on_schedule_deleted(&mut self, _schedule: &Schedule) {}
+
+    /// Called when a TIPLOC is inserted, amended or deleted, with the location before and
+    /// after the change. Renaming a TIPLOC is reported as a change to both codes.
+    fn on_tiploc_changed(
+        &mut self,
+        _tiploc: &str,
+        _previous: Option<&TIPLOC>,
+        _current: Option<&TIPLOC>,
+    ) {
+    }
+
+    /// Called when an association is inserted, revised or deleted, with the association
+    /// before and after the change.
+    fn on_association_changed(
+        &mut self,
+        _previous: Option<&Association>,
+        _current: Option<&Association>,
+    ) {
+    }
+}
+
+impl ScheduleObserver for () {}
+
+impl ScheduleDatabase {
+    /// Apply records onto this schedule database, telling `observer` about each change made.
+    /// This can reset the database if this includes a full update.
+    pub fn apply_records_observed<O: ScheduleObserver>(
+        &mut self,
+        records: &[CIFRecord],
+        observer: &mut O,
+    ) -> ApplyReport {
+        let total_records = Some(records.len());
+        let records = records.iter().map(Ok::<_, Infallible>);
+        match self.apply_stream(records, total_records, |_| (), observer) {
+            Ok(report) => report,
+            Err(never) => match never {},
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    board::platform_key,
+    platform::Platform,
+    schedule::{STPIndicator, ScheduleDatabase},
+};
+
+/// The trains scheduled to use one platform at a station on a date.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+pub struct PlatformOccupancy {
+    /// The TIPLOC the platform is at, which may be one of several at the station.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The platform, which is empty for the trains whose platform isn't known.
+    #[getset(get = "pub")]
+    platform: Platform,
+    /// The trains using the platform, ordered by the time they arrive, then by train UID.
+    #[getset(get = "pub")]
+    occupations: Vec<PlatformOccupation>,
+}
+
+impl PlatformOccupancy {
+    /// Is more than one train scheduled to be on the platform at once?
+    pub fn has_conflicts(&self) -> bool {
+        self.occupations
+            .iter()
+            .any(|occupation| !occupation.conflicts.is_empty())
+    }
+}
+
+/// A train scheduled to use a platform, from the time it arrives until the time it departs.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct PlatformOccupation {
+    /// The train UID.
+    #[getset(get = "pub")]
+    train_uid: String,
+    /// The headcode of the train, being its train identity.
+    #[getset(get = "pub")]
+    headcode: String,
+    /// The date the train started its journey.
+    #[getset(get_copy = "pub")]
+    running_date: NaiveDate,
+    /// The STP indicator of the schedule the train runs to on this date, so trains moved by
+    /// a short term planning overlay can be picked out.
+    #[getset(get_copy = "pub")]
+    stp_indicator: STPIndicator,
+    /// The working time the train arrives, or departs if it starts here. For trains passing
+    /// through, this is the passing time.
+    #[getset(get_copy = "pub")]
+    from: NaiveDateTime,
+    /// The working time the train departs, or arrives if it terminates here. For trains
+    /// passing through, this is the passing time.
+    #[getset(get_copy = "pub")]
+    until: NaiveDateTime,
+    /// The train UIDs of the other trains scheduled to be on the platform at some point
+    /// during this time, in the order they arrive. A train arriving as another departs
+    /// doesn't conflict with it.
+    #[getset(get = "pub")]
+    conflicts: Vec<String>,
+}
+
+impl ScheduleDatabase {
+    /// The trains scheduled to use each platform at a location (a TIPLOC or CRS code) on a
+    /// date, with any that are on the same platform at the same time flagged. Short term
+    /// planning overlays and cancellations are applied for the date each train runs, and a
+    /// train is included if any part of its time at the location falls on the date.
+    ///
+    /// Trains whose platform isn't known are gathered together under an empty platform, and
+    /// are never flagged. The platforms are ordered by TIPLOC, then by platform number.
+    pub fn platform_occupancy<S: AsRef<str>>(
+        &self,
+        location: S,
+        date: NaiveDate,
+    ) -> Vec<PlatformOccupancy> {
+        let tiplocs = self.resolve_location(location.as_ref());
+        let start = date.and_time(NaiveTime::MIN);
+        let end = start + Duration::days(1);
+
+        let mut platforms: Vec<PlatformOccupancy> = vec![];
+        for (running_date, schedule) in self.trains_at(&tiplocs, start, end) {
+            for call in schedule.materialise(running_date) {
+                let location = call.location();
+                if !tiplocs.contains(location.tiploc()) {
+                    continue;
+                }
+                let (Some(from), Some(until)) = (
+                    call.arrival().or(call.pass()).or(call.departure()),
+                    call.departure().or(call.pass()).or(call.arrival()),
+                ) else {
+                    continue;
+                };
+                if until < start || from >= end {
+                    continue;
+                }
+                let occupation = PlatformOccupation {
+                    train_uid: schedule.train_uid().clone(),
+                    headcode: schedule.train_identity().clone(),
+                    running_date,
+                    stp_indicator: *schedule.stp_indicator(),
+                    from,
+                    until,
+                    conflicts: vec![],
+                };
+                match platforms.iter_mut().find(|platform| {
+                    platform.tiploc == *location.tiploc()
+                        && platform.platform == *location.platform()
+                }) {
+                    Some(platform) => platform.occupations.push(occupation),
+                    None => platforms.push(PlatformOccupancy {
+                        tiploc: location.tiploc().clone(),
+                        platform: location.platform().clone(),
+                        occupations: vec![occupation],
+                    }),
+                }
+            }
+        }
+
+        for platform in &mut platforms {
+            platform
+                .occupations
+                .sort_by(|a, b| a.from.cmp(&b.from).then(a.train_uid.cmp(&b.train_uid)));
+            if platform.platform.is_empty() {
+                continue;
+            }
+            let spans: Vec<(NaiveDateTime, NaiveDateTime, String)> = platform
+                .occupations
+                .iter()
+                .map(|o| (o.from, o.until, o.train_uid.clone()))
+                .collect();
+            for (idx, occupation) in platform.occupations.iter_mut().enumerate() {
+                occupation.conflicts = spans
+                    .iter()
+                    .enumerate()
+                    .filter(|(other, (from, until, _))| {
+                        *other != idx && *from < occupation.until && occupation.from < *until
+                    })
+                    .map(|(_, (_, _, train_uid))| train_uid.clone())
+                    .collect();
+            }
+        }
+        platforms.sort_by(|a, b| {
+            a.tiploc.cmp(&b.tiploc).then_with(|| {
+                platform_key(a.platform.as_str()).cmp(&platform_key(b.platform.as_str()))
+            })
+        });
+        platforms
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{Schedule, ScheduleDatabase};
+
+macro_rules! train_operators {
+    ($($variant:ident = $code:literal, $name:literal;)*) => {
+        /// A train operating company, as identified by its ATOC code.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub enum TrainOperator {
+            $(
+                #[doc = $name]
+                $variant,
+            )*
+        }
+
+        impl TrainOperator {
+            /// Every known operator.
+            pub const ALL: &'static [TrainOperator] = &[$(TrainOperator::$variant),*];
+
+            /// The operator with an ATOC code, such as `SW`, if it is known.
+            pub fn from_atoc_code<S: AsRef<str>>(atoc_code: S) -> Option<Self> {
+                match atoc_code.as_ref().trim() {
+                    $($code => Some(TrainOperator::$variant),)*
+                    _ => None,
+                }
+            }
+
+            /// The two letter ATOC code of this operator.
+            pub fn atoc_code(&self) -> &'static str {
+                match self {
+                    $(TrainOperator::$variant => $code,)*
+                }
+            }
+
+            /// The name this operator trades under.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(TrainOperator::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+train_operators! {
+    TransportForWales = "AW", "Transport for Wales";
+    C2C = "CC", "c2c";
+    ChilternRailways = "CH", "Chiltern Railways";
+    CaledonianSleeper = "CS", "Caledonian Sleeper";
+    EastMidlandsRailway = "EM", "East Midlands Railway";
+    Eurostar = "ES", "Eurostar";
+    GrandCentral = "GC", "Grand Central";
+    GreatNorthern = "GN", "Great Northern";
+    LondonNorthEasternRailway = "GR", "London North Eastern Railway";
+    GreatWesternRailway = "GW", "Great Western Railway";
+    GatwickExpress = "GX", "Gatwick Express";
+    HullTrains = "HT", "Hull Trains";
+    HeathrowExpress = "HX", "Heathrow Express";
+    IslandLine = "IL", "Island Line";
+    Lumo = "LD", "Lumo";
+    GreaterAnglia = "LE", "Greater Anglia";
+    WestMidlandsTrains = "LM", "West Midlands Trains";
+    LondonOverground = "LO", "London Overground";
+    LondonUnderground = "LT", "London Underground";
+    Merseyrail = "ME", "Merseyrail";
+    NorthYorkshireMoorsRailway = "NY", "North Yorkshire Moors Railway";
+    Northern = "NT", "Northern";
+    Southeastern = "SE", "Southeastern";
+    Southern = "SN", "Southern";
+    ScotRail = "SR", "ScotRail";
+    SouthWesternRailway = "SW", "South Western Railway";
+    Thameslink = "TL", "Thameslink";
+    TransPennineExpress = "TP", "TransPennine Express";
+    AvantiWestCoast = "VT", "Avanti West Coast";
+    WestCoastRailways = "WR", "West Coast Railways";
+    CrossCountry = "XC", "CrossCountry";
+    ElizabethLine = "XR", "Elizabeth line";
+    NetworkRail = "ZZ", "Network Rail and other operators";
+}
+
+impl Schedule {
+    /// The operator of this schedule, if its ATOC code is known.
+    pub fn operator(&self) -> Option<TrainOperator> {
+        TrainOperator::from_atoc_code(self.atoc_code())
+    }
+}
+
+impl ScheduleDatabase {
+    /// All schedules run by the operator with an ATOC code, such as `SW`, on any date. The
+    /// results are ordered by train UID.
+    pub fn schedules_by_operator<S: AsRef<str>>(&self, atoc_code: S) -> Vec<&Schedule> {
+        let atoc_code = atoc_code.as_ref().trim();
+        self.operator_index()
+            .get(atoc_code)
+            .into_iter()
+            .flat_map(BTreeMap::keys)
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+            .filter(|schedule| schedule.atoc_code() == atoc_code)
+            .collect()
+    }
+}
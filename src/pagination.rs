@@ -0,0 +1,30 @@
+//! Paging the results of queries which don't have a paged variant. Import [`Paginate`] to
+//! split any iterator into pages.
+
+use crate::query::{page_of, Page};
+
+/// Split the results of any query into pages. Only the results on the requested page are
+/// kept, although the remaining results are still counted to give the total.
+///
+/// Queries which return a [`Vec`] have already found every result, so prefer their paged
+/// variants, such as [`crate::prelude::ScheduleDatabase::schedules_matching_uid_page`],
+/// where there is one.
+///
+/// ```
+/// use nr_cif::{pagination::Paginate, prelude::*};
+///
+/// let db = ScheduleDatabase::new();
+/// let page = db
+///     .schedules_by_class(444, None, None)
+///     .into_iter()
+///     .paginate(20, 10);
+/// assert_eq!(page.total(), 0);
+/// ```
+pub trait Paginate: Iterator + Sized {
+    /// Skip `offset` results, then take at most `limit` results.
+    fn paginate(self, offset: usize, limit: usize) -> Page<Self::Item> {
+        page_of(self, offset, limit)
+    }
+}
+
+impl<I: Iterator> Paginate for I {}
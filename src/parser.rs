@@ -1,7 +1,8 @@
 use std::io::{self, prelude::*};
 
 use crate::types::*;
-use log::debug;
+use getset::{CopyGetters, Setters};
+use log::{debug, warn};
 use thiserror::Error;
 
 /// An error that occurred during parsing a CIF file.
@@ -11,38 +12,285 @@ pub enum CIFParseError {
     AtLine(usize, Box<CIFRecordParseError>),
     #[error("failed to read CIF file")]
     Read(#[from] io::Error),
+    #[error("unexpected end of file part way through line {line}, after {bytes_read} bytes")]
+    UnexpectedEof { line: usize, bytes_read: u64 },
+    #[error("file ended after {lines_read} lines without a trailer record")]
+    MissingTrailer { lines_read: usize },
+    #[error("line {0} is not valid text")]
+    InvalidText(usize),
+    #[error("unknown record type {1:?} at line {0}")]
+    UnknownRecordType(usize, String),
+}
+
+/// Options controlling how CIF files are parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters, Setters)]
+pub struct ParseOptions {
+    /// Skip lines which can't be parsed, recording a warning, rather than failing. Enabled by
+    /// [`ParseOptions::lenient`].
+    #[getset(get_copy = "pub", set = "pub")]
+    skip_invalid_lines: bool,
+}
+
+impl ParseOptions {
+    /// Fail on the first line which can't be parsed. This is the default.
+    pub fn strict() -> Self {
+        Self {
+            skip_invalid_lines: false,
+        }
+    }
+
+    /// Skip lines which can't be parsed, recording a warning for each in
+    /// [`CIFFile::warnings`].
+    pub fn lenient() -> Self {
+        Self {
+            skip_invalid_lines: true,
+        }
+    }
+}
+
+/// Parse a single line of a CIF file into a [`CIFRecord`], for example when records arrive
+/// one at a time rather than in a file. A line ending is ignored, and a line shorter than 80
+/// characters is padded with spaces.
+pub fn parse_record(line: &str) -> Result<CIFRecord, CIFRecordParseError> {
+    pad_line(line.as_bytes()).parse()
 }
 
 /// Parse a CIF file into a programmatic [`CIFFile`].
-pub fn parse_cif<R: Read>(mut content_reader: R) -> Result<crate::types::CIFFile, CIFParseError> {
+pub fn parse_cif<R: Read>(content_reader: R) -> Result<crate::types::CIFFile, CIFParseError> {
+    parse_cif_with_options(content_reader, ParseOptions::strict())
+}
+
+/// Parse a CIF file into a programmatic [`CIFFile`], with the given options.
+pub fn parse_cif_with_options<R: Read>(
+    content_reader: R,
+    options: ParseOptions,
+) -> Result<crate::types::CIFFile, CIFParseError> {
     debug!("Parsing CIF data...");
     let mut file = CIFFile::new();
-    let mut buf = [0u8; 81];
-    // human readable line of the input file
-    let mut line = 1;
+    let mut reader = CIFRecordReader::with_options(content_reader, options);
+    for record in &mut reader {
+        file.records_mut().push(record?);
+    }
+    *file.warnings_mut() = reader.take_warnings();
+    log::debug!("Parsed {} records.", file.records().len());
+    Ok(file)
+}
+
+/// Parse a stream of concatenated CIF files, such as several update extracts downloaded
+/// together, into a [`CIFFile`] for each header and trailer pair.
+pub fn parse_cif_multi<R: Read>(content_reader: R) -> Result<Vec<CIFFile>, CIFParseError> {
+    debug!("Parsing concatenated CIF data...");
+    let mut reader = CIFRecordReader::new(content_reader);
+    let mut files = vec![];
     loop {
-        if line % 10000 == 0 {
-            log::debug!("Parsed {line} CIF records...");
+        let mut file = CIFFile::new();
+        for record in &mut reader {
+            file.records_mut().push(record?);
         }
+        *file.warnings_mut() = reader.take_warnings();
+        files.push(file);
+        if !reader.next_extract()? {
+            break;
+        }
+    }
+    log::debug!("Parsed {} extracts.", files.len());
+    Ok(files)
+}
 
-        // read 80 character row + new line
-        // the file should only contain ASCII characters, so we don't need to worry
-        // about multi-byte characters
-        content_reader.read_exact(&mut buf)?;
+/// Reads records one at a time from a CIF file, stopping after the trailer record.
+pub struct CIFRecordReader<R: Read> {
+    content_reader: io::BufReader<R>,
+    buf: Vec<u8>,
+    lines: LineParser,
+    finished: bool,
+}
 
-        // process buffer
-        let record_raw = String::from_utf8_lossy(&buf[0..80]);
+impl<R: Read> CIFRecordReader<R> {
+    /// Read records from a CIF file.
+    pub fn new(content_reader: R) -> Self {
+        Self::with_options(content_reader, ParseOptions::default())
+    }
 
-        let record: CIFRecord = record_raw
-            .parse()
-            .map_err(|e| CIFParseError::AtLine(line, Box::new(e)))?;
-        file.records_mut().push(record.clone());
+    /// Read records from a CIF file, with the given options.
+    pub fn with_options(content_reader: R, options: ParseOptions) -> Self {
+        Self {
+            content_reader: io::BufReader::new(content_reader),
+            buf: Vec::with_capacity(82),
+            lines: LineParser::new(options),
+            finished: false,
+        }
+    }
 
-        if let CIFRecord::Trailer = record {
-            break;
+    /// Take the warnings for lines skipped so far.
+    pub fn take_warnings(&mut self) -> Vec<CIFParseWarning> {
+        self.lines.take_warnings()
+    }
+
+    /// After the trailer record, continue with the next extract if there is more data, as
+    /// when several files have been concatenated. Returns whether there was more data.
+    pub fn next_extract(&mut self) -> Result<bool, CIFParseError> {
+        if self.content_reader.fill_buf()?.is_empty() {
+            return Ok(false);
+        }
+        self.finished = false;
+        Ok(true)
+    }
+
+    fn read_record(&mut self) -> Result<CIFRecord, CIFParseError> {
+        loop {
+            self.buf.clear();
+            if self.content_reader.read_until(b'\n', &mut self.buf)? == 0 {
+                return Err(self.lines.missing_trailer());
+            }
+            if let Some(record) = self.lines.parse(&self.buf)? {
+                return Ok(record);
+            }
         }
-        line += 1;
     }
-    log::debug!("Parsed {line} records.");
+}
+
+impl<R: Read> Iterator for CIFRecordReader<R> {
+    type Item = Result<CIFRecord, CIFParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let record = self.read_record();
+        self.finished = matches!(record, Ok(CIFRecord::Trailer) | Err(_));
+        Some(record)
+    }
+}
+
+/// Parse only the reference data in a CIF file: the header, TIPLOC records and, if
+/// `include_associations` is set, association records. Schedule records are skipped without
+/// being parsed, so this is much faster than [`parse_cif`] for a full extract.
+pub fn parse_cif_reference<R: Read>(
+    content_reader: R,
+    include_associations: bool,
+) -> Result<crate::types::CIFFile, CIFParseError> {
+    debug!("Parsing CIF reference data...");
+    let mut file = CIFFile::new();
+    let mut content_reader = io::BufReader::new(content_reader);
+    let mut buf = Vec::with_capacity(82);
+    let mut lines = LineParser::new(ParseOptions::strict());
+    loop {
+        buf.clear();
+        if content_reader.read_until(b'\n', &mut buf)? == 0 {
+            return Err(lines.missing_trailer());
+        }
+
+        let wanted = match buf.get(0..2) {
+            Some(b"HD" | b"TI" | b"TA" | b"TD" | b"ZZ") => true,
+            Some(b"AA") => include_associations,
+            _ => false,
+        };
+        if !wanted {
+            lines.skip(&buf)?;
+            continue;
+        }
+        if let Some(record) = lines.parse(&buf)? {
+            let is_trailer = matches!(record, CIFRecord::Trailer);
+            file.records_mut().push(record);
+            if is_trailer {
+                break;
+            }
+        }
+    }
+    log::debug!(
+        "Read {} records, kept {}.",
+        lines.line - 1,
+        file.records().len()
+    );
     Ok(file)
 }
+
+/// Tracks line numbers and skipped lines while turning lines into records, for both the
+/// blocking and asynchronous readers.
+pub(crate) struct LineParser {
+    options: ParseOptions,
+    /// The human readable line number of the next record.
+    line: usize,
+    /// The number of bytes in the lines seen so far, including line endings.
+    bytes_read: u64,
+    warnings: Vec<CIFParseWarning>,
+}
+
+impl LineParser {
+    pub(crate) fn new(options: ParseOptions) -> Self {
+        Self {
+            options,
+            line: 1,
+            bytes_read: 0,
+            warnings: vec![],
+        }
+    }
+
+    pub(crate) fn take_warnings(&mut self) -> Vec<CIFParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Parse a line as read from the file, including any line ending. Returns [`None`] if
+    /// the line was skipped.
+    pub(crate) fn parse(&mut self, buf: &[u8]) -> Result<Option<CIFRecord>, CIFParseError> {
+        let line = self.skip(buf)?;
+        let record_raw = pad_line(buf);
+        match record_raw.parse() {
+            Ok(record) => Ok(Some(record)),
+            Err(e) if self.options.skip_invalid_lines => {
+                warn!("Skipping line {line}, which can't be parsed: {e}");
+                self.warnings
+                    .push(CIFParseWarning::new(line, record_raw, e.to_string()));
+                Ok(None)
+            }
+            Err(e) => Err(CIFParseError::AtLine(line, Box::new(e))),
+        }
+    }
+
+    /// Account for a line as read from the file, including any line ending, without parsing
+    /// it, returning its line number. A line cut short by the end of the file is an error,
+    /// unless it is the trailer record.
+    pub(crate) fn skip(&mut self, buf: &[u8]) -> Result<usize, CIFParseError> {
+        let line = self.line;
+        self.line += 1;
+        self.bytes_read += buf.len() as u64;
+        if line.is_multiple_of(10000) {
+            log::debug!("Parsed {line} CIF records...");
+        }
+
+        let unterminated = !buf.ends_with(b"\n");
+        let content = buf.strip_suffix(b"\r").unwrap_or(buf);
+        if unterminated && content.len() < 80 && !content.starts_with(b"ZZ") {
+            return Err(CIFParseError::UnexpectedEof {
+                line,
+                bytes_read: self.bytes_read,
+            });
+        }
+        Ok(line)
+    }
+
+    /// The error for data which ended cleanly before the trailer record.
+    pub(crate) fn missing_trailer(&self) -> CIFParseError {
+        CIFParseError::MissingTrailer {
+            lines_read: self.line - 1,
+        }
+    }
+}
+
+/// Strip the line ending from a line, and pad it with spaces, or truncate it, to the 80
+/// characters of a CIF record.
+fn pad_line(mut line: &[u8]) -> String {
+    while let Some(rest) = line
+        .strip_suffix(b"\n")
+        .or_else(|| line.strip_suffix(b"\r"))
+    {
+        line = rest;
+    }
+    let line = &line[..line.len().min(80)];
+    // the file should only contain ASCII characters, so we don't need to worry about
+    // multi-byte characters
+    let mut record = String::with_capacity(80);
+    record.push_str(&String::from_utf8_lossy(line));
+    record.extend(std::iter::repeat_n(' ', 80 - line.len()));
+    record
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    calendar::DatedCall,
+    schedule::{Schedule, ScheduleDatabase},
+};
+
+/// The minimum time allowed to change between trains.
+pub(crate) const MINIMUM_CONNECTION: Duration = Duration::minutes(5);
+
+/// A location which can be reached from an origin.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct ReachableLocation {
+    /// The TIPLOC of the reachable location.
+    #[getset(get = "pub")]
+    tiploc: String,
+    /// The earliest arrival at this location.
+    #[getset(get_copy = "pub")]
+    arrival: NaiveDateTime,
+    /// The number of changes of train needed to make the earliest arrival.
+    #[getset(get_copy = "pub")]
+    changes: u8,
+}
+
+/// An interchange between two trains at a location.
+#[derive(Debug, Clone, Copy, CopyGetters)]
+pub struct Connection {
+    /// When the first train arrives at the location.
+    #[getset(get_copy = "pub")]
+    arrival: NaiveDateTime,
+    /// When the second train departs the location.
+    #[getset(get_copy = "pub")]
+    departure: NaiveDateTime,
+    /// The time between the first train arriving and the second departing. This is negative
+    /// if the second train leaves first.
+    #[getset(get_copy = "pub")]
+    margin: Duration,
+    /// Is the margin at least the minimum connection time requested?
+    #[getset(get_copy = "pub")]
+    feasible: bool,
+}
+
+impl ScheduleDatabase {
+    /// Check whether a passenger can change from one train to another at a location (a
+    /// TIPLOC or CRS code), with both trains running on the given date. The first train must
+    /// set down passengers at the location, and the second must pick them up, although they
+    /// may use different TIPLOCs at the same station.
+    ///
+    /// Returns [`None`] if either train doesn't call at the location as needed.
+    pub fn connection_feasible<S: AsRef<str>>(
+        &self,
+        service_a: &Schedule,
+        service_b: &Schedule,
+        at_location: S,
+        date: NaiveDate,
+        min_connection: Duration,
+    ) -> Option<Connection> {
+        let tiplocs = self.resolve_location(at_location.as_ref());
+        let arrival = service_a
+            .materialise(date)
+            .iter()
+            .skip(1)
+            .find(|call| tiplocs.contains(call.location().tiploc()) && call.location().sets_down())
+            .and_then(|call| call.public_arrival().or(call.arrival()))?;
+        let calls = service_b.materialise(date);
+        let departure = calls[..calls.len().saturating_sub(1)]
+            .iter()
+            .find(|call| tiplocs.contains(call.location().tiploc()) && call.location().picks_up())
+            .and_then(|call| call.public_departure().or(call.departure()))?;
+        let margin = departure - arrival;
+        Some(Connection {
+            arrival,
+            departure,
+            margin,
+            feasible: margin >= min_connection,
+        })
+    }
+
+    /// Find every location which can be reached from `location` (a TIPLOC or CRS code),
+    /// departing no earlier than `depart_after` and arriving within `max_duration` of it.
+    ///
+    /// Only direct services are considered, unless `allow_change` is set, in which case a
+    /// single change of train is allowed, with at least five minutes to make the connection.
+    /// The results are ordered by arrival time.
+    pub fn reachable_from<S: AsRef<str>>(
+        &self,
+        location: S,
+        depart_after: NaiveDateTime,
+        max_duration: Duration,
+        allow_change: bool,
+    ) -> Vec<ReachableLocation> {
+        let deadline = depart_after + max_duration;
+        let origin = self.resolve_location(location.as_ref());
+
+        // trains which started the previous day may still be running
+        let mut runs: Vec<Vec<DatedCall>> = vec![];
+        let mut date = depart_after.date() - Duration::days(1);
+        while date <= deadline.date() {
+            for schedule in self.schedules_running_on(date) {
+                runs.push(schedule.materialise(date));
+            }
+            date += Duration::days(1);
+        }
+
+        let start: HashMap<String, NaiveDateTime> = origin
+            .iter()
+            .map(|tiploc| (tiploc.clone(), depart_after))
+            .collect();
+        let direct = earliest_arrivals(&runs, &start, Duration::zero(), deadline);
+
+        let mut reachable: HashMap<String, (NaiveDateTime, u8)> = direct
+            .iter()
+            .map(|(tiploc, arrival)| (tiploc.clone(), (*arrival, 0)))
+            .collect();
+        if allow_change {
+            for (tiploc, arrival) in earliest_arrivals(&runs, &direct, MINIMUM_CONNECTION, deadline)
+            {
+                if reachable
+                    .get(&tiploc)
+                    .is_none_or(|(best, _)| arrival < *best)
+                {
+                    reachable.insert(tiploc, (arrival, 1));
+                }
+            }
+        }
+
+        let mut results: Vec<ReachableLocation> = reachable
+            .into_iter()
+            .filter(|(tiploc, _)| !origin.contains(tiploc))
+            .map(|(tiploc, (arrival, changes))| ReachableLocation {
+                tiploc,
+                arrival,
+                changes,
+            })
+            .collect();
+        results.sort_by(|a, b| a.arrival.cmp(&b.arrival).then(a.tiploc.cmp(&b.tiploc)));
+        results
+    }
+}
+
+/// Given the times passengers are ready to board at a set of locations, find the earliest
+/// arrival at every location reachable by a single train, arriving no later than `deadline`.
+fn earliest_arrivals(
+    runs: &[Vec<DatedCall>],
+    ready_at: &HashMap<String, NaiveDateTime>,
+    connection: Duration,
+    deadline: NaiveDateTime,
+) -> HashMap<String, NaiveDateTime> {
+    let mut arrivals: HashMap<String, NaiveDateTime> = HashMap::new();
+    for calls in runs {
+        let mut boarded = false;
+        for call in calls {
+            let loc = call.location();
+            if boarded && loc.sets_down() {
+                if let Some(arrival) = call.arrival().filter(|arrival| *arrival <= deadline) {
+                    arrivals
+                        .entry(loc.tiploc().clone())
+                        .and_modify(|best| *best = (*best).min(arrival))
+                        .or_insert(arrival);
+                }
+            }
+            if !boarded && loc.picks_up() {
+                if let (Some(ready), Some(departure)) =
+                    (ready_at.get(loc.tiploc()), call.departure())
+                {
+                    boarded = departure >= *ready + connection && departure <= deadline;
+                }
+            }
+        }
+    }
+    arrivals
+}
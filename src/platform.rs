@@ -0,0 +1,104 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+macro_rules! location_codes {
+    ($($(#[$meta:meta])* $name:ident / $archived:ident;)*) => {
+        $(
+            $(#[$meta])*
+            ///
+            /// Whitespace is normalised, and codes compare without regard to case.
+            #[derive(Debug, Clone, Default, Eq)]
+            #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+            #[cfg_attr(
+                feature = "rkyv",
+                derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+                archive(check_bytes)
+            )]
+            pub struct $name(String);
+
+            impl $name {
+                /// Create from a field of a location record, trimming it and collapsing any
+                /// runs of whitespace inside it.
+                pub fn new<S: AsRef<str>>(code: S) -> Self {
+                    Self(code.as_ref().split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+
+                /// The code as text, which is empty if none is given.
+                pub fn as_str(&self) -> &str {
+                    &self.0
+                }
+
+                /// Is no code given?
+                pub fn is_empty(&self) -> bool {
+                    self.0.is_empty()
+                }
+            }
+
+            impl PartialEq for $name {
+                fn eq(&self, other: &Self) -> bool {
+                    self.0.eq_ignore_ascii_case(&other.0)
+                }
+            }
+
+            impl PartialEq<str> for $name {
+                fn eq(&self, other: &str) -> bool {
+                    self.0.eq_ignore_ascii_case(other.trim())
+                }
+            }
+
+            impl PartialEq<&str> for $name {
+                fn eq(&self, other: &&str) -> bool {
+                    self == *other
+                }
+            }
+
+            impl Hash for $name {
+                fn hash<H: Hasher>(&self, state: &mut H) {
+                    for b in self.0.bytes() {
+                        state.write_u8(b.to_ascii_uppercase());
+                    }
+                }
+            }
+
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str(&self.0)
+                }
+            }
+
+            impl From<&str> for $name {
+                fn from(code: &str) -> Self {
+                    Self::new(code)
+                }
+            }
+
+            impl AsRef<str> for $name {
+                fn as_ref(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            #[cfg(feature = "rkyv")]
+            impl $archived {
+                /// The code as text, which is empty if none is given.
+                pub fn as_str(&self) -> &str {
+                    self.0.as_str()
+                }
+            }
+        )*
+    };
+}
+
+location_codes! {
+    /// The platform a train uses at a location, such as `1` or `10A`.
+    Platform / ArchivedPlatform;
+    /// The line a train departs on from a location, such as `FL` for the fast line.
+    Line / ArchivedLine;
+    /// The path a train arrives on at a location.
+    Path / ArchivedPath;
+}
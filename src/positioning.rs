@@ -0,0 +1,76 @@
+use chrono::NaiveDate;
+
+use crate::schedule::{JourneyLocation, Schedule, ScheduleDatabase, TrainCategory};
+
+impl TrainCategory {
+    /// Is this the category of an empty coaching stock movement?
+    pub fn is_empty_coaching_stock(&self) -> bool {
+        matches!(
+            self,
+            TrainCategory::EmptyCoachingStock
+                | TrainCategory::ECSLondonUnderground
+                | TrainCategory::ECSAndStaff
+        )
+    }
+
+    /// Is this the category of a positioning move, either empty coaching stock or a light
+    /// locomotive?
+    pub fn is_positioning_move(&self) -> bool {
+        self.is_empty_coaching_stock() || *self == TrainCategory::LightLocomotive
+    }
+}
+
+impl Schedule {
+    /// Is this schedule for an empty coaching stock movement?
+    pub fn is_empty_coaching_stock(&self) -> bool {
+        self.train_category().is_empty_coaching_stock()
+    }
+
+    /// Is this schedule for a positioning move, either empty coaching stock or a light
+    /// locomotive?
+    pub fn is_positioning_move(&self) -> bool {
+        self.train_category().is_positioning_move()
+    }
+}
+
+impl ScheduleDatabase {
+    /// Find the positioning moves running on a date which travel from one location to
+    /// another. Locations can be given as either a TIPLOC or a CRS code, and the train may
+    /// call at or pass either location.
+    ///
+    /// The results are ordered by working time at `from`, then by train UID.
+    pub fn positioning_moves<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+        date: NaiveDate,
+    ) -> Vec<&Schedule> {
+        let from = self.resolve_location(from.as_ref());
+        let to = self.resolve_location(to.as_ref());
+
+        let mut moves: Vec<(u32, &Schedule)> = self
+            .schedules_running_on(date)
+            .filter(|schedule| schedule.is_positioning_move())
+            .filter_map(|schedule| {
+                let journey = schedule.journey();
+                let from_index = journey.iter().position(|loc| from.contains(loc.tiploc()))?;
+                journey
+                    .iter()
+                    .skip(from_index + 1)
+                    .find(|loc| to.contains(loc.tiploc()))?;
+                working_time(&journey[from_index]).map(|time| (time, schedule))
+            })
+            .collect();
+        moves.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.train_uid().cmp(b.1.train_uid())));
+        moves.into_iter().map(|(_, schedule)| schedule).collect()
+    }
+}
+
+/// The working time a train leaves or passes a location, in seconds after midnight.
+fn working_time(location: &JourneyLocation) -> Option<u32> {
+    location
+        .departure_time()
+        .or(*location.passing_time())
+        .or(*location.arrival_time())
+        .map(|time| time.to_seconds())
+}
@@ -0,0 +1,108 @@
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, Read},
+};
+
+use log::debug;
+
+use crate::{
+    parser::{CIFParseError, CIFRecordReader, ParseOptions},
+    schedule::{ApplyProgress, ApplyReport, ScheduleDatabase},
+    types::{CIFFile, CIFRecord},
+};
+
+/// Receives progress updates while a CIF file is parsed and applied, for example to drive a
+/// progress bar. Every method does nothing by default, so only those of interest need to be
+/// implemented.
+pub trait ProgressObserver {
+    /// Called after each record is read, with the total number of bytes taken from the
+    /// reader so far. Reads are buffered, so this may run slightly ahead of the records
+    /// parsed.
+    fn bytes_read(&mut self, _total: u64) {}
+
+    /// Called after each record is parsed, with the total number of records parsed so far.
+    fn records_parsed(&mut self, _total: usize) {}
+
+    /// Called after each record, or bundle of schedule records, is applied to a database.
+    fn records_applied(&mut self, _progress: &ApplyProgress) {}
+}
+
+/// Parse a CIF file into a programmatic [`CIFFile`], with the given options, reporting
+/// progress to `observer`.
+pub fn parse_cif_with_observer<R: Read, O: ProgressObserver>(
+    content_reader: R,
+    options: ParseOptions,
+    observer: &mut O,
+) -> Result<CIFFile, CIFParseError> {
+    debug!("Parsing CIF data...");
+    let bytes = Cell::new(0);
+    let mut file = CIFFile::new();
+    let mut reader =
+        CIFRecordReader::with_options(CountingReader::new(content_reader, &bytes), options);
+    for record in &mut reader {
+        file.records_mut().push(record?);
+        observer.bytes_read(bytes.get());
+        observer.records_parsed(file.records().len());
+    }
+    *file.warnings_mut() = reader.take_warnings();
+    debug!("Parsed {} records.", file.records().len());
+    Ok(file)
+}
+
+impl ScheduleDatabase {
+    /// Apply records onto this schedule database, reporting progress to `observer`.
+    pub fn apply_records_with_observer<O: ProgressObserver>(
+        &mut self,
+        records: &[CIFRecord],
+        observer: &mut O,
+    ) -> ApplyReport {
+        self.apply_records_with_progress(records, |progress| observer.records_applied(&progress))
+    }
+
+    /// Parse a CIF file and apply it onto this schedule database as it is read, reporting
+    /// the progress of both to `observer`.
+    pub fn apply_reader_with_observer<R: Read, O: ProgressObserver>(
+        &mut self,
+        reader: R,
+        observer: &mut O,
+    ) -> Result<ApplyReport, CIFParseError> {
+        let bytes = Cell::new(0);
+        let observer = RefCell::new(observer);
+        let records = CIFRecordReader::new(CountingReader::new(reader, &bytes))
+            .enumerate()
+            .map(|(idx, record)| {
+                let mut observer = observer.borrow_mut();
+                observer.bytes_read(bytes.get());
+                if record.is_ok() {
+                    observer.records_parsed(idx + 1);
+                }
+                record
+            });
+        self.apply_stream(
+            records,
+            None,
+            |progress| observer.borrow_mut().records_applied(&progress),
+            &mut (),
+        )
+    }
+}
+
+/// Counts the bytes taken from a reader.
+struct CountingReader<'a, R> {
+    inner: R,
+    count: &'a Cell<u64>,
+}
+
+impl<'a, R> CountingReader<'a, R> {
+    fn new(inner: R, count: &'a Cell<u64>) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
@@ -0,0 +1,552 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    ops::{Bound, RangeInclusive},
+};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    calendar::cancelled_schedule,
+    schedule::{
+        DaysRun, JourneyTime, PowerType, STPIndicator, Schedule, ScheduleDatabase, TrainCategory,
+    },
+};
+
+/// A service which calls at two locations, in order, without a change of train.
+#[derive(Debug, Clone, Copy, CopyGetters)]
+pub struct DirectService<'a> {
+    /// The schedule operating this service.
+    #[getset(get_copy = "pub")]
+    schedule: &'a Schedule,
+    /// The index in the schedule's journey of the boarding location.
+    #[getset(get_copy = "pub")]
+    from_index: usize,
+    /// The index in the schedule's journey of the alighting location.
+    #[getset(get_copy = "pub")]
+    to_index: usize,
+    /// The departure time from the boarding location.
+    #[getset(get_copy = "pub")]
+    departure: JourneyTime,
+    /// The arrival time at the alighting location.
+    #[getset(get_copy = "pub")]
+    arrival: JourneyTime,
+}
+
+impl DirectService<'_> {
+    /// The time taken between departing the boarding location and arriving at the alighting
+    /// location. Journeys which cross midnight are handled.
+    pub fn duration(&self) -> Duration {
+        self.departure.duration_until(&self.arrival)
+    }
+}
+
+/// A direct service departing at a specific date and time.
+#[derive(Debug, Clone, Copy, CopyGetters)]
+pub struct NextDeparture<'a> {
+    /// The service, with the times at each location as given in the schedule.
+    #[getset(get_copy = "pub")]
+    service: DirectService<'a>,
+    /// The date the train starts its journey, which may be the day before it departs if it
+    /// runs overnight.
+    #[getset(get_copy = "pub")]
+    running_date: NaiveDate,
+    /// The date and time the train departs the boarding location.
+    #[getset(get_copy = "pub")]
+    departure: NaiveDateTime,
+    /// The date and time the train arrives at the alighting location.
+    #[getset(get_copy = "pub")]
+    arrival: NaiveDateTime,
+}
+
+/// A single page of results from a query, along with the total number of results.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct Page<T> {
+    /// The results on this page.
+    #[getset(get = "pub")]
+    items: Vec<T>,
+    /// The number of results skipped before this page.
+    #[getset(get_copy = "pub")]
+    offset: usize,
+    /// The total number of results, across all pages.
+    #[getset(get_copy = "pub")]
+    total: usize,
+}
+
+impl<T> Page<T> {
+    pub(crate) fn new(items: Vec<T>, offset: usize, total: usize) -> Self {
+        Self {
+            items,
+            offset,
+            total,
+        }
+    }
+
+    /// Are there more results after this page?
+    pub fn has_more(&self) -> bool {
+        self.offset + self.items.len() < self.total
+    }
+
+    /// The offset of the next page, if there is one.
+    pub fn next_offset(&self) -> Option<usize> {
+        self.has_more().then_some(self.offset + self.items.len())
+    }
+
+    /// Take the results on this page.
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
+
+/// Skip `offset` items, then collect at most `limit` items, counting the rest to give the
+/// total.
+pub(crate) fn page_of<I: Iterator>(items: I, offset: usize, limit: usize) -> Page<I::Item> {
+    let mut page = vec![];
+    let mut total = 0;
+    for (index, item) in items.enumerate() {
+        if index >= offset && page.len() < limit {
+            page.push(item);
+        }
+        total += 1;
+    }
+    Page::new(page, offset, total)
+}
+
+/// A search for the schedules meeting a set of conditions, started with
+/// [`ScheduleDatabase::query`]. Giving the same kind of condition more than once matches
+/// schedules meeting any of them, except for [`ScheduleQuery::calling_at`], where the
+/// schedule must call at every location given. A query with no conditions matches every
+/// schedule.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use nr_cif::prelude::*;
+///
+/// let db = ScheduleDatabase::new();
+/// let start = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+/// let end = NaiveDate::from_ymd_opt(2024, 6, 7).unwrap();
+/// let schedules: Vec<&Schedule> = db
+///     .query()
+///     .operator("SW")
+///     .calling_at("WAT")
+///     .running_between(start, end)
+///     .execute()
+///     .collect();
+/// assert!(schedules.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScheduleQuery<'a> {
+    database: &'a ScheduleDatabase,
+    atoc_codes: Vec<String>,
+    categories: Vec<TrainCategory>,
+    power_types: Vec<PowerType>,
+    days_run: Option<DaysRun>,
+    date_window: Option<(NaiveDate, NaiveDate)>,
+    calling_at: Vec<HashSet<String>>,
+    stp_indicators: Vec<STPIndicator>,
+}
+
+impl<'a> ScheduleQuery<'a> {
+    fn new(database: &'a ScheduleDatabase) -> Self {
+        Self {
+            database,
+            atoc_codes: vec![],
+            categories: vec![],
+            power_types: vec![],
+            days_run: None,
+            date_window: None,
+            calling_at: vec![],
+            stp_indicators: vec![],
+        }
+    }
+
+    /// Only match trains run by the operator with this ATOC code, such as `SW`.
+    pub fn operator<S: AsRef<str>>(mut self, atoc_code: S) -> Self {
+        self.atoc_codes.push(atoc_code.as_ref().trim().to_string());
+        self
+    }
+
+    /// Only match trains of this category.
+    pub fn category(mut self, category: TrainCategory) -> Self {
+        self.categories.push(category);
+        self
+    }
+
+    /// Only match trains with this type of power.
+    pub fn power_type(mut self, power_type: PowerType) -> Self {
+        self.power_types.push(power_type);
+        self
+    }
+
+    /// Only match schedules which run on at least one of these days of the week.
+    pub fn days_run(mut self, days_run: DaysRun) -> Self {
+        self.days_run = Some(self.days_run.unwrap_or(DaysRun::empty()) | days_run);
+        self
+    }
+
+    /// Only match schedules which run on at least one date between two dates, inclusive.
+    /// This replaces any window given before.
+    pub fn running_between(mut self, start: NaiveDate, end: NaiveDate) -> Self {
+        self.date_window = Some((start, end));
+        self
+    }
+
+    /// Only match trains which call at a location, given as a TIPLOC or CRS code. Locations
+    /// the train only passes don't count.
+    pub fn calling_at<S: AsRef<str>>(mut self, location: S) -> Self {
+        self.calling_at
+            .push(self.database.resolve_location(location.as_ref()));
+        self
+    }
+
+    /// Only match schedules with this STP indicator, such as only permanent schedules.
+    pub fn stp_indicator(mut self, stp_indicator: STPIndicator) -> Self {
+        self.stp_indicators.push(stp_indicator);
+        self
+    }
+
+    /// Does a schedule meet every condition of this query?
+    pub fn matches(&self, schedule: &Schedule) -> bool {
+        (self.atoc_codes.is_empty() || self.atoc_codes.contains(schedule.atoc_code()))
+            && (self.categories.is_empty() || self.categories.contains(schedule.train_category()))
+            && (self.power_types.is_empty() || self.power_types.contains(schedule.power_type()))
+            && self
+                .days_run
+                .is_none_or(|days_run| schedule.days_run().intersects(days_run))
+            && self.date_window.is_none_or(|(start, end)| {
+                start
+                    .max(*schedule.runs_from())
+                    .iter_days()
+                    .take_while(|date| *date <= end.min(*schedule.runs_to()))
+                    .take(7)
+                    .any(|date| schedule.runs_on(date))
+            })
+            && self.calling_at.iter().all(|tiplocs| {
+                schedule.journey().iter().any(|location| {
+                    location.passing_time().is_none() && tiplocs.contains(location.tiploc())
+                })
+            })
+            && (self.stp_indicators.is_empty()
+                || self.stp_indicators.contains(schedule.stp_indicator()))
+    }
+
+    /// Run the query, giving the matching schedules ordered by train UID. The schedules for
+    /// each UID are kept in the order they are held in the database.
+    ///
+    /// Where the query names operators or locations, only the trains found for them in the
+    /// database's indexes are checked.
+    pub fn execute(self) -> impl Iterator<Item = &'a Schedule> {
+        let database = self.database;
+        let mut candidates: Option<BTreeSet<&'a String>> = None;
+        if !self.atoc_codes.is_empty() {
+            candidates = Some(
+                self.atoc_codes
+                    .iter()
+                    .filter_map(|code| database.operator_index().get(code))
+                    .flat_map(BTreeMap::keys)
+                    .collect(),
+            );
+        }
+        for tiplocs in &self.calling_at {
+            let uids = database.uids_at_locations(tiplocs.iter().map(String::as_str));
+            candidates = Some(match candidates {
+                Some(candidates) => candidates.intersection(&uids).copied().collect(),
+                None => uids,
+            });
+        }
+        let uids: Vec<&'a String> = match candidates {
+            Some(candidates) => candidates.into_iter().collect(),
+            None => database.uid_index().iter().collect(),
+        };
+        uids.into_iter()
+            .filter_map(move |uid| database.schedules().get(uid))
+            .flatten()
+            .filter(move |schedule| self.matches(schedule))
+    }
+
+    /// Run the query, giving one page of the results of [`ScheduleQuery::execute`]. Only the
+    /// schedules on the page are kept.
+    pub fn execute_page(self, offset: usize, limit: usize) -> Page<&'a Schedule> {
+        page_of(self.execute(), offset, limit)
+    }
+}
+
+impl ScheduleDatabase {
+    /// Start a search for the schedules meeting a set of conditions. See [`ScheduleQuery`].
+    pub fn query(&self) -> ScheduleQuery<'_> {
+        ScheduleQuery::new(self)
+    }
+
+    /// Find all schedules whose timing load indicates the given rolling stock class, for
+    /// example `800` or `158`. Optionally these can be limited to a single operator (by ATOC
+    /// code) and to schedules valid on a given date.
+    ///
+    /// The results are ordered by train UID, then by the date the schedule runs from.
+    pub fn schedules_by_class(
+        &self,
+        class: u16,
+        atoc_code: Option<&str>,
+        date: Option<NaiveDate>,
+    ) -> Vec<&Schedule> {
+        let mut results: Vec<&Schedule> = self
+            .schedules()
+            .values()
+            .flatten()
+            .filter(|s| s.timing_load().indicates_class(class))
+            .filter(|s| atoc_code.is_none_or(|code| s.atoc_code() == code))
+            .filter(|s| date.is_none_or(|date| s.runs_on(date)))
+            .collect();
+        results.sort_by(|a, b| {
+            a.train_uid()
+                .cmp(b.train_uid())
+                .then(a.runs_from().cmp(b.runs_from()))
+        });
+        results
+    }
+
+    /// Find the schedules whose train UID matches a pattern, where `*` matches any number of
+    /// characters and `?` matches exactly one, for example `C11*` or `?1234?`. A pattern
+    /// without wildcards must match the UID exactly.
+    ///
+    /// The results are ordered by train UID. The schedules for each UID are kept in the order
+    /// they are held in the database.
+    pub fn schedules_matching_uid<S: AsRef<str>>(&self, pattern: S) -> Vec<&Schedule> {
+        self.iter_schedules_matching_uid(pattern.as_ref()).collect()
+    }
+
+    /// Find one page of the schedules whose train UID matches a pattern, in the same order as
+    /// [`ScheduleDatabase::schedules_matching_uid`]. Only the schedules on the page are kept.
+    pub fn schedules_matching_uid_page<S: AsRef<str>>(
+        &self,
+        pattern: S,
+        offset: usize,
+        limit: usize,
+    ) -> Page<&Schedule> {
+        page_of(
+            self.iter_schedules_matching_uid(pattern.as_ref()),
+            offset,
+            limit,
+        )
+    }
+
+    fn iter_schedules_matching_uid<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> impl Iterator<Item = &'a Schedule> {
+        let prefix = &pattern[..pattern.find(['*', '?']).unwrap_or(pattern.len())];
+        self.uid_index()
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |uid| uid.starts_with(prefix))
+            .filter(move |uid| matches_wildcard(pattern.as_bytes(), uid.as_bytes()))
+            .filter_map(|uid| self.schedules().get(uid))
+            .flatten()
+    }
+
+    /// Find the direct service with the shortest journey time between two locations on a
+    /// date. Locations can be given as either a TIPLOC or a CRS code. If a departure window is
+    /// given, only services departing within it are considered.
+    pub fn fastest_service<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+        date: NaiveDate,
+        departure_window: Option<RangeInclusive<NaiveTime>>,
+    ) -> Option<DirectService<'_>> {
+        self.direct_services(from.as_ref(), to.as_ref(), date)
+            .into_iter()
+            .filter(|service| {
+                departure_window
+                    .as_ref()
+                    .is_none_or(|window| window.contains(&service.departure.to_naive_time()))
+            })
+            .min_by_key(|service| (service.duration(), service.departure))
+    }
+
+    /// Find the next `n` services departing a location at or after a date and time, which
+    /// later call at another location. Locations can be given as either a TIPLOC or a CRS
+    /// code. Short term planning overlays and cancellations are applied for the date each
+    /// train runs, and trains which started their journey the day before are included.
+    ///
+    /// Services are searched for up to a week ahead, and are ordered by departure time.
+    pub fn next_departures_to<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+        after: NaiveDateTime,
+        n: usize,
+    ) -> Vec<NextDeparture<'_>> {
+        if n == 0 {
+            return vec![];
+        }
+        let mut departures: Vec<NextDeparture> = vec![];
+        let last_date = after.date() + Duration::days(7);
+        let mut date = after.date() - Duration::days(1);
+        while date <= last_date {
+            if departures.len() >= n && departures[n - 1].departure < date.and_time(NaiveTime::MIN)
+            {
+                break;
+            }
+
+            departures.extend(
+                self.dated_services(from.as_ref(), to.as_ref(), date)
+                    .filter(|departure| departure.departure >= after),
+            );
+            departures.sort_by(|a, b| {
+                a.departure.cmp(&b.departure).then(
+                    a.service
+                        .schedule
+                        .train_uid()
+                        .cmp(b.service.schedule.train_uid()),
+                )
+            });
+            date += Duration::days(1);
+        }
+        departures.truncate(n);
+        departures
+    }
+
+    /// Find the first service on a date which picks up passengers at `from` and later sets
+    /// down passengers at `to`. Locations can be given as either a TIPLOC or a CRS code.
+    /// Short term planning overlays and cancellations are applied.
+    ///
+    /// Only trains starting their journey on the date are considered, so a train which
+    /// started the evening before and departs `from` after midnight is not the first.
+    pub fn first_departure<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+        date: NaiveDate,
+    ) -> Option<NextDeparture<'_>> {
+        self.dated_services(from.as_ref(), to.as_ref(), date)
+            .min_by_key(|departure| (departure.departure, departure.arrival))
+    }
+
+    /// Find the last service on a date which picks up passengers at `from` and later sets
+    /// down passengers at `to`, as with [`ScheduleDatabase::first_departure`]. A train which
+    /// starts its journey on the date but departs `from` after midnight is the last, as it
+    /// is the last train of that day.
+    pub fn last_departure<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+        date: NaiveDate,
+    ) -> Option<NextDeparture<'_>> {
+        self.dated_services(from.as_ref(), to.as_ref(), date)
+            .max_by_key(|departure| (departure.departure, departure.arrival))
+    }
+
+    /// The direct services starting their journey on a date, with the date and time they
+    /// depart `from` and arrive at `to`.
+    pub(crate) fn dated_services<'a>(
+        &'a self,
+        from: &str,
+        to: &str,
+        date: NaiveDate,
+    ) -> impl Iterator<Item = NextDeparture<'a>> {
+        self.direct_services(from, to, date)
+            .into_iter()
+            .filter_map(move |service| {
+                let calls = service.schedule.materialise(date);
+                let from_call = &calls[service.from_index];
+                let to_call = &calls[service.to_index];
+                Some(NextDeparture {
+                    service,
+                    running_date: date,
+                    departure: from_call.public_departure().or(from_call.departure())?,
+                    arrival: to_call.public_arrival().or(to_call.arrival())?,
+                })
+            })
+    }
+
+    /// Find the services which would normally call for passengers at a location (a TIPLOC or
+    /// CRS code) on a date, but have been cancelled on that date by a short term planning
+    /// cancellation. The schedules returned are those which would otherwise have run, ordered
+    /// by their public time at the location.
+    pub fn cancellations_at<S: AsRef<str>>(&self, location: S, date: NaiveDate) -> Vec<&Schedule> {
+        let tiplocs = self.resolve_location(location.as_ref());
+        let mut cancelled: Vec<(JourneyTime, &Schedule)> = self
+            .schedules()
+            .values()
+            .filter_map(|schedules| cancelled_schedule(schedules, date))
+            .filter_map(|schedule| {
+                schedule
+                    .journey()
+                    .iter()
+                    .filter(|loc| {
+                        tiplocs.contains(loc.tiploc()) && (loc.picks_up() || loc.sets_down())
+                    })
+                    .find_map(|loc| loc.public_departure().or(*loc.public_arrival()))
+                    .map(|time| (time, schedule))
+            })
+            .collect();
+        cancelled.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.train_uid().cmp(b.1.train_uid())));
+        cancelled
+            .into_iter()
+            .map(|(_, schedule)| schedule)
+            .collect()
+    }
+
+    /// Find every service running on a date which picks up passengers at `from` and later
+    /// sets down passengers at `to`, with its times at each. Locations can be given as either
+    /// a TIPLOC or a CRS code. Short term planning overlays and cancellations are applied.
+    ///
+    /// The services are ordered by departure time, then by arrival time.
+    pub fn direct_services<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from: S,
+        to: T,
+        date: NaiveDate,
+    ) -> Vec<DirectService<'_>> {
+        let from = self.resolve_location(from.as_ref());
+        let to = self.resolve_location(to.as_ref());
+
+        let mut services = vec![];
+        for schedule in self.schedules_running_on(date) {
+            let journey = schedule.journey();
+            let Some(from_index) = journey
+                .iter()
+                .position(|loc| from.contains(loc.tiploc()) && loc.picks_up())
+            else {
+                continue;
+            };
+            let Some(to_index) = journey
+                .iter()
+                .skip(from_index + 1)
+                .position(|loc| to.contains(loc.tiploc()) && loc.sets_down())
+                .map(|idx| idx + from_index + 1)
+            else {
+                continue;
+            };
+            let (Some(departure), Some(arrival)) = (
+                journey[from_index]
+                    .public_departure()
+                    .or(*journey[from_index].departure_time()),
+                journey[to_index]
+                    .public_arrival()
+                    .or(*journey[to_index].arrival_time()),
+            ) else {
+                continue;
+            };
+            services.push(DirectService {
+                schedule,
+                from_index,
+                to_index,
+                departure,
+                arrival,
+            });
+        }
+        services.sort_by_key(|service| (service.departure, service.arrival));
+        services
+    }
+}
+
+/// Does `text` match `pattern`, where `*` matches any number of characters and `?` matches
+/// exactly one?
+pub(crate) fn matches_wildcard(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|skip| matches_wildcard(rest, &text[skip..])),
+        Some((b'?', rest)) => !text.is_empty() && matches_wildcard(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && matches_wildcard(rest, &text[1..]),
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use getset::Getters;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    association::Association,
+    builder::RecordKind,
+    schedule::{STPIndicator, Schedule, ScheduleApplyError, TIPLOC},
+    sink::{apply_records_to_sink, ScheduleSink},
+    types::{CIFFile, CIFRecord},
+};
+
+/// A lightweight store of the reference data in CIF files: locations and, optionally,
+/// associations. Schedules are ignored entirely, making this suitable for gazetteer-type
+/// uses. Use with [`crate::parser::parse_cif_reference`] to avoid parsing schedules at all.
+#[derive(Debug, Clone, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReferenceDatabase {
+    #[getset(get = "pub")]
+    extract_date_time: NaiveDateTime,
+    /// A map of TIPLOC to [`TIPLOC`] objects.
+    #[getset(get = "pub")]
+    tiplocs: HashMap<String, TIPLOC>,
+    /// The associations currently in force, in the order they were inserted.
+    #[getset(get = "pub")]
+    associations: Vec<Association>,
+}
+
+impl Default for ReferenceDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReferenceDatabase {
+    /// Create a new [`ReferenceDatabase`].
+    pub fn new() -> Self {
+        Self {
+            extract_date_time: NaiveDateTime::MIN,
+            tiplocs: HashMap::new(),
+            associations: vec![],
+        }
+    }
+
+    /// Apply a file onto this database.
+    /// This can reset the database if this includes a full update.
+    pub fn apply_file(&mut self, file: &CIFFile) -> Vec<(usize, ScheduleApplyError)> {
+        self.apply_records(file.records())
+    }
+
+    /// Apply a list of records onto this database. Schedule records are ignored. If the header
+    /// record is rejected, nothing after it is applied.
+    /// Returns a list of errors and their respective record index.
+    pub fn apply_records(&mut self, records: &[CIFRecord]) -> Vec<(usize, ScheduleApplyError)> {
+        apply_records_to_sink(self, records).into_parts().0
+    }
+}
+
+/// Only locations and associations are kept, so schedules are discarded.
+impl ScheduleSink for ReferenceDatabase {
+    fn full_update(&mut self) {
+        self.tiplocs.clear();
+        self.associations.clear();
+    }
+
+    fn last_extract_date_time(&self) -> NaiveDateTime {
+        self.extract_date_time
+    }
+
+    fn set_extract(&mut self, extract_date_time: NaiveDateTime, _file_reference: String) {
+        self.extract_date_time = extract_date_time;
+    }
+
+    fn upsert_tiploc(&mut self, tiploc: TIPLOC) -> Option<TIPLOC> {
+        self.tiplocs.insert(tiploc.tiploc().clone(), tiploc)
+    }
+
+    fn delete_tiploc(&mut self, tiploc: &str) -> Option<TIPLOC> {
+        self.tiplocs.remove(tiploc)
+    }
+
+    fn upsert_association(&mut self, association: Association) -> Option<Association> {
+        match self
+            .associations
+            .iter()
+            .position(|other| other.same_key(&association))
+        {
+            Some(idx) => Some(std::mem::replace(&mut self.associations[idx], association)),
+            None => {
+                self.associations.push(association);
+                None
+            }
+        }
+    }
+
+    fn delete_association(
+        &mut self,
+        main_train_uid: &str,
+        associated_train_uid: &str,
+        start_date: NaiveDate,
+        location: &str,
+        stp_indicator: STPIndicator,
+    ) -> Option<Association> {
+        let idx = self.associations.iter().position(|association| {
+            association.has_key(
+                main_train_uid,
+                associated_train_uid,
+                start_date,
+                location,
+                stp_indicator,
+            )
+        })?;
+        Some(self.associations.remove(idx))
+    }
+
+    fn skips_record_kind(&self, kind: RecordKind) -> bool {
+        matches!(kind, RecordKind::Schedule | RecordKind::Note)
+    }
+
+    fn insert_schedule(&mut self, _schedule: Schedule) {}
+
+    fn revise_schedule(&mut self, _schedule: Schedule) -> Option<Schedule> {
+        None
+    }
+
+    fn delete_schedule(
+        &mut self,
+        _train_uid: &str,
+        _runs_from: NaiveDate,
+        _stp_indicator: STPIndicator,
+    ) -> Option<Schedule> {
+        None
+    }
+
+    fn has_schedules(&self, _train_uid: &str) -> bool {
+        false
+    }
+}
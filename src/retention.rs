@@ -0,0 +1,91 @@
+use chrono::NaiveDate;
+use getset::CopyGetters;
+use log::debug;
+
+use crate::schedule::ScheduleDatabase;
+
+/// How much was removed from a database by [`ScheduleDatabase::purge_before`] or
+/// [`ScheduleDatabase::retain_window`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+pub struct PurgeSummary {
+    /// The number of schedules removed.
+    #[getset(get_copy = "pub")]
+    schedules_removed: usize,
+    /// The number of associations removed.
+    #[getset(get_copy = "pub")]
+    associations_removed: usize,
+}
+
+impl ScheduleDatabase {
+    /// Remove the schedules and associations which stopped running before a date, to keep
+    /// a database which has daily updates applied to it for a long time from growing.
+    pub fn purge_before(&mut self, date: NaiveDate) -> PurgeSummary {
+        let summary = self.purge_where(|_, runs_to| runs_to < date);
+        debug!(
+            "Purged {} schedules and {} associations which ended before {date}",
+            summary.schedules_removed, summary.associations_removed
+        );
+        summary
+    }
+
+    /// Only keep the schedules and associations which run at some point between two dates,
+    /// inclusive. Those entirely outside the window are removed now, and are left out when
+    /// records are applied from then on.
+    ///
+    /// Call this again with a later window as time passes, to roll it forward and evict the
+    /// entries which have fallen out of it.
+    pub fn retain_window(&mut self, start: NaiveDate, end: NaiveDate) -> PurgeSummary {
+        *self.retention_window_mut() = Some((start, end));
+        let summary = self.purge_where(|runs_from, runs_to| runs_to < start || runs_from > end);
+        debug!(
+            "Purged {} schedules and {} associations outside {start} to {end}",
+            summary.schedules_removed, summary.associations_removed
+        );
+        summary
+    }
+
+    /// Does something running between two dates fall within the retention window, if there
+    /// is one?
+    pub(crate) fn in_retention_window(&self, runs_from: NaiveDate, runs_to: NaiveDate) -> bool {
+        self.retention_window()
+            .is_none_or(|(start, end)| runs_from <= end && runs_to >= start)
+    }
+
+    /// Remove the schedules and associations for which `expired` returns true, given the
+    /// dates they run between.
+    fn purge_where<F: Fn(NaiveDate, NaiveDate) -> bool>(&mut self, expired: F) -> PurgeSummary {
+        let mut summary = PurgeSummary::default();
+
+        let mut removed = vec![];
+        self.schedules_mut().retain(|_, schedules| {
+            let (keep, old) = std::mem::take(schedules)
+                .into_iter()
+                .partition(|schedule| !expired(*schedule.runs_from(), *schedule.runs_to()));
+            *schedules = keep;
+            removed.extend::<Vec<_>>(old);
+            !schedules.is_empty()
+        });
+        for schedule in &removed {
+            self.unindex_schedule(schedule);
+            if !self.schedules().contains_key(schedule.train_uid()) {
+                self.uid_index_mut().remove(schedule.train_uid());
+            }
+        }
+        summary.schedules_removed = removed.len();
+        if !removed.is_empty() {
+            self.rebuild_call_index();
+        }
+
+        self.associations_mut().retain(|_, associations| {
+            let before = associations.len();
+            associations
+                .retain(|association| !expired(*association.start_date(), *association.end_date()));
+            summary.associations_removed += before - associations.len();
+            !associations.is_empty()
+        });
+        if summary.associations_removed > 0 {
+            self.rebuild_associated_index();
+        }
+        summary
+    }
+}
@@ -1,16 +1,35 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeSet, HashMap},
+    convert::Infallible,
+    io::Read,
+    str::FromStr,
+};
 
 use bitflags::bitflags;
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use getset::Getters;
-use log::{info, trace, warn};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use getset::{CopyGetters, Getters, MutGetters, Setters};
+use log::{debug, info, trace, warn};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::types::{CIFFile, CIFRecord};
+use crate::{
+    activity::{parse_activities, Activity},
+    association::{apply_association_record, Association},
+    builder::{RecordKind, UnknownCodePolicy},
+    calendar::BankHolidayCalendar,
+    filter::ScheduleFilter,
+    index::{CallIndex, ScheduleIndex},
+    observer::ScheduleObserver,
+    parser::{CIFParseError, CIFRecordReader},
+    platform::{Line, Path, Platform},
+    sink::ScheduleSink,
+    types::{CIFFile, CIFRecord, UpdateIndicator},
+};
 
 #[derive(Error, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ScheduleApplyError {
     #[error("the data being applied is older than the data already loaded")]
     AttemptingToApplyOlderData,
@@ -42,22 +61,195 @@ pub enum ScheduleApplyError {
     InvalidSTPIndicator(char),
     #[error("invalid journey time in location record")]
     InvalidJourneyTime(String),
+    #[error("invalid allowance in location record")]
+    InvalidAllowance(String),
+    #[error("invalid date in association record")]
+    InvalidAssociationDate(String),
+    #[error("invalid days in association record")]
+    InvalidAssociationDays(String),
+    #[error("invalid category in association record")]
+    InvalidAssociationCategory(String),
+    #[error("invalid date indicator in association record")]
+    InvalidAssociationDateIndicator(char),
+    #[error("invalid association type in association record")]
+    InvalidAssociationType(char),
+}
+
+/// Why an update file can't be applied after the files already applied.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SequenceError {
+    #[error("the file has no header record")]
+    MissingHeader,
+    #[error("file {0} has already been applied")]
+    AlreadyApplied(String),
+    #[error("the file follows on from {found}, but the last file applied was {expected}")]
+    OutOfSequence { expected: String, found: String },
 }
 
-#[derive(Debug, Clone, Getters)]
+/// A kind of unexpected data which was applied anyway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct ScheduleDatabase {
+pub enum ApplyWarningCategory {
+    /// A schedule was revised, but didn't exist, so was inserted as new.
+    RevisedUnknownSchedule,
+    /// A schedule was deleted, but didn't exist.
+    DeletedUnknownSchedule,
+    /// A cancellation was received for a train UID with no schedules, so was discarded.
+    CancelledUnknownSchedule,
+    /// An association was deleted, but didn't exist.
+    DeletedUnknownAssociation,
+    /// A TIPLOC was amended, but didn't exist, so was inserted as new.
+    AmendedUnknownTIPLOC,
+    /// A TIPLOC was deleted, but didn't exist.
+    DeletedUnknownTIPLOC,
+    /// A schedule had a code not known to this library, which was kept as a fallback value.
+    UnknownCode,
+}
+
+/// Something unexpected in the records applied to a [`ScheduleDatabase`], which didn't stop
+/// them being applied.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ApplyWarning {
+    /// The index of the record the warning relates to.
+    #[getset(get_copy = "pub")]
+    record_index: usize,
+    /// What was unexpected.
+    #[getset(get_copy = "pub")]
+    category: ApplyWarningCategory,
+    /// The train UID, or TIPLOC for TIPLOC warnings, the warning relates to. For
+    /// associations, this is the main train UID.
     #[getset(get = "pub")]
+    identifier: String,
+}
+
+/// The result of applying a list of records to a [`ScheduleDatabase`].
+#[derive(Debug, Default, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ApplyReport {
+    /// The errors encountered, and their respective record index.
+    #[getset(get = "pub")]
+    errors: Vec<(usize, ScheduleApplyError)>,
+    /// The warnings raised.
+    #[getset(get = "pub")]
+    warnings: Vec<ApplyWarning>,
+}
+
+impl ApplyReport {
+    /// Split the report into its errors and warnings.
+    pub fn into_parts(self) -> (Vec<(usize, ScheduleApplyError)>, Vec<ApplyWarning>) {
+        (self.errors, self.warnings)
+    }
+}
+
+/// The kind of records being applied to a [`ScheduleDatabase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ApplyPhase {
+    Header,
+    Tiplocs,
+    Associations,
+    Schedules,
+    Trailer,
+}
+
+/// How far through applying a list of records a [`ScheduleDatabase`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ApplyProgress {
+    /// The index of the last record applied.
+    #[getset(get_copy = "pub")]
+    record_index: usize,
+    /// The total number of records being applied, if it is known in advance.
+    #[getset(get_copy = "pub")]
+    total_records: Option<usize>,
+    /// The number of bundles of records applied so far.
+    #[getset(get_copy = "pub")]
+    bundles_applied: usize,
+    /// The kind of records in the last bundle applied.
+    #[getset(get_copy = "pub")]
+    phase: ApplyPhase,
+}
+
+#[derive(Debug, Clone, Getters, MutGetters, Setters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
+pub struct ScheduleDatabase {
+    #[getset(get = "pub", get_mut = "pub(crate)")]
     extract_date_time: NaiveDateTime,
+    /// The reference of the last file applied, from its header.
+    #[getset(get = "pub", get_mut = "pub(crate)")]
+    file_reference: Option<String>,
     /// A map of TIPLOC to [`TIPLOC`] objects, with extra information like name and
     /// CRS (3 alpha code).
-    #[getset(get = "pub")]
+    #[getset(get = "pub", get_mut = "pub(crate)")]
     tiplocs: HashMap<String, TIPLOC>,
     /// A map of schedule UIDs to a list of applicable schedules. These should be filtered by the
     /// validity date for the period in question, then you should get the one at the latest index
     /// valid in your time period. This will be the schedule to take effect.
-    #[getset(get = "pub")]
+    #[getset(get = "pub", get_mut = "pub(crate)")]
     schedules: HashMap<String, Vec<Schedule>>,
+    /// A map of main train UIDs to the associations with other trains.
+    #[getset(get = "pub", get_mut = "pub(crate)")]
+    associations: HashMap<String, Vec<Association>>,
+    /// A map of associated train UIDs to the main train UIDs they are associated with.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    associated_index: HashMap<String, BTreeSet<String>>,
+    /// A map of STANOX to the TIPLOCs with that STANOX.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    stanox_index: HashMap<u32, BTreeSet<String>>,
+    /// A map of CRS codes to the TIPLOCs with that CRS code.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    crs_index: HashMap<String, BTreeSet<String>>,
+    /// A map of TIPLOCs to the train UIDs of the schedules visiting them.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    location_index: ScheduleIndex,
+    /// A map of TIPLOCs to every visit made to them by a schedule.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    call_index: CallIndex,
+    /// A map of train identities to the train UIDs of the schedules with that identity.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    headcode_index: ScheduleIndex,
+    /// A map of ATOC codes to the train UIDs of the schedules run by that operator.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    operator_index: ScheduleIndex,
+    /// A map of train service codes to the train UIDs of the schedules with that code.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    service_code_index: ScheduleIndex,
+    /// The train UIDs in `schedules`, in order, for prefix searches.
+    #[getset(get = "pub(crate)", get_mut = "pub(crate)")]
+    uid_index: BTreeSet<String>,
+    /// Whether public times of "0000" are treated as not advertised, unless the train is
+    /// actually timed at midnight. Enabled by default.
+    #[getset(get = "pub", set = "pub")]
+    ignore_placeholder_public_times: bool,
+    /// What to do when a schedule has a code not known to this library, such as a new train
+    /// category. By default, the code is kept as an `Other` or `Unknown` value.
+    #[getset(get = "pub", set = "pub")]
+    unknown_code_policy: UnknownCodePolicy,
+    /// The kinds of records left out when applying records. Empty by default.
+    #[getset(get = "pub", set = "pub")]
+    skip_record_kinds: Vec<RecordKind>,
+    /// Whether to panic on the first error when applying records. Disabled by default, unless
+    /// the `panic-on-first-error` feature is enabled.
+    #[getset(get = "pub", set = "pub")]
+    panic_on_first_error: bool,
+    /// Which schedules are kept when applying records. By default every schedule is kept.
+    #[getset(get = "pub", set = "pub")]
+    schedule_filter: ScheduleFilter,
+    /// The dates schedules and associations must run between to be kept, set with
+    /// [`ScheduleDatabase::retain_window`]. By default everything is kept.
+    #[getset(get = "pub", get_mut = "pub(crate)")]
+    retention_window: Option<(NaiveDate, NaiveDate)>,
+    /// The bank holidays used when working out which trains run on a date. Empty by default,
+    /// so bank holiday running is ignored.
+    #[getset(get = "pub", set = "pub")]
+    bank_holidays: BankHolidayCalendar,
 }
 
 impl Default for ScheduleDatabase {
@@ -71,8 +263,26 @@ impl ScheduleDatabase {
     pub fn new() -> Self {
         Self {
             extract_date_time: NaiveDateTime::MIN,
+            file_reference: None,
             tiplocs: HashMap::new(),
             schedules: HashMap::new(),
+            associations: HashMap::new(),
+            associated_index: HashMap::new(),
+            stanox_index: HashMap::new(),
+            crs_index: HashMap::new(),
+            location_index: HashMap::new(),
+            call_index: HashMap::new(),
+            headcode_index: HashMap::new(),
+            operator_index: HashMap::new(),
+            service_code_index: HashMap::new(),
+            uid_index: BTreeSet::new(),
+            ignore_placeholder_public_times: true,
+            unknown_code_policy: UnknownCodePolicy::default(),
+            skip_record_kinds: vec![],
+            panic_on_first_error: cfg!(feature = "panic-on-first-error"),
+            schedule_filter: ScheduleFilter::new(),
+            retention_window: None,
+            bank_holidays: BankHolidayCalendar::new(),
         }
     }
 
@@ -82,6 +292,104 @@ impl ScheduleDatabase {
         self.apply_records(file.records())
     }
 
+    /// Apply several files onto this schedule database in the right order, such as a week of
+    /// daily updates. If there are any full extracts, the latest is applied first and files
+    /// older than it are skipped. The remaining updates are applied in order of extract date
+    /// and time, then file reference.
+    ///
+    /// Returns the errors for each file, in the order the files were given.
+    pub fn apply_files(&mut self, files: Vec<CIFFile>) -> Vec<Vec<(usize, ScheduleApplyError)>> {
+        let mut order: Vec<usize> = (0..files.len()).collect();
+        order.sort_by(|a, b| {
+            let key = |file: &CIFFile| {
+                (
+                    file.extract_date_time(),
+                    file.current_file_reference().map(str::to_string),
+                )
+            };
+            key(&files[*a]).cmp(&key(&files[*b]))
+        });
+        if let Some(full) = order
+            .iter()
+            .rposition(|idx| files[*idx].update_indicator() == Some(UpdateIndicator::Full))
+        {
+            for idx in &order[..full] {
+                info!(
+                    "Skipping file {:?}, which is older than the latest full extract.",
+                    files[*idx].current_file_reference()
+                );
+            }
+            order.drain(..full);
+        }
+
+        let mut errors: Vec<Vec<(usize, ScheduleApplyError)>> =
+            files.iter().map(|_| vec![]).collect();
+        for idx in order {
+            info!("Applying file {:?}", files[idx].current_file_reference());
+            errors[idx] = self.apply_file(&files[idx]);
+        }
+        errors
+    }
+
+    /// Apply a file onto this schedule database, returning the warnings raised as well as the
+    /// errors.
+    /// This can reset the database if this includes a full update.
+    pub fn apply_file_with_report(&mut self, file: &CIFFile) -> ApplyReport {
+        self.apply_records_with_progress(file.records(), |_| ())
+    }
+
+    /// Apply a file onto this schedule database as a single transaction. If more than
+    /// `max_errors` records fail to apply, the database is restored to its state before the
+    /// file was applied and the report is returned as an error, rather than leaving a
+    /// partially applied update in place.
+    ///
+    /// The database is copied before the file is applied, so this needs enough memory for
+    /// two copies of it.
+    pub fn apply_file_transactional(
+        &mut self,
+        file: &CIFFile,
+        max_errors: usize,
+    ) -> Result<ApplyReport, ApplyReport> {
+        let previous = self.clone();
+        let report = self.apply_file_with_report(file);
+        if report.errors().len() > max_errors {
+            warn!(
+                "Applying the file raised {} errors, which is more than {max_errors}. Rolling back...",
+                report.errors().len()
+            );
+            *self = previous;
+            return Err(report);
+        }
+        Ok(report)
+    }
+
+    /// Apply a file onto this schedule database, only if it follows on from the last file
+    /// applied. A full extract can always be applied, as can any file onto a database which
+    /// hasn't had a file applied yet.
+    pub fn apply_file_checked(
+        &mut self,
+        file: &CIFFile,
+    ) -> Result<Vec<(usize, ScheduleApplyError)>, SequenceError> {
+        let current = file
+            .current_file_reference()
+            .ok_or(SequenceError::MissingHeader)?;
+        if let Some(applied) = &self.file_reference {
+            if file.update_indicator() != Some(UpdateIndicator::Full) {
+                if applied == current {
+                    return Err(SequenceError::AlreadyApplied(current.to_string()));
+                }
+                let last = file.last_file_reference().unwrap_or_default();
+                if applied != last {
+                    return Err(SequenceError::OutOfSequence {
+                        expected: applied.clone(),
+                        found: last.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(self.apply_file(file))
+    }
+
     /// Finds a CRS from a given TIPLOC. If the TIPLOC is not immediately
     /// associated with a CRS, this will check other TIPLOCs associated with
     /// the same STANOX.
@@ -91,9 +399,8 @@ impl ScheduleDatabase {
                 return vec![base_tiploc.three_alpha_code().clone()];
             }
 
-            let stanox = *base_tiploc.stanox();
             let mut crs = vec![];
-            for (_, tiploc) in self.tiplocs().iter().filter(|(_, t)| stanox == *t.stanox()) {
+            for tiploc in self.tiplocs_for_stanox(*base_tiploc.stanox()) {
                 if !tiploc.three_alpha_code().is_empty() {
                     crs.push(tiploc.three_alpha_code().clone());
                 }
@@ -104,151 +411,339 @@ impl ScheduleDatabase {
     }
 
     /// Apply a list of records onto this schedule database.
-    /// This can reset the database if this includes a full update.
+    /// This can reset the database if this includes a full update. If the header record is
+    /// rejected, such as for data older than that already loaded, nothing after it is applied.
     /// Returns a list of errors and their respective record index.
     pub fn apply_records(&mut self, records: &[CIFRecord]) -> Vec<(usize, ScheduleApplyError)> {
-        let mut bundle = vec![];
-        let mut errors = vec![];
-        for (record_idx, record) in records.iter().enumerate() {
-            if record_idx % 10000 == 0 {
+        let mut next_report = 0;
+        self.apply_records_with_progress(records, |progress| {
+            if progress.record_index() >= next_report {
                 info!(
                     "Processing record #{}. (shown every 10000 records)",
-                    record_idx + 1
+                    progress.record_index() + 1
                 );
+                next_report = progress.record_index() + 10000;
             }
-            bundle.push(record);
-
-            // Check type
-            let submit = match &record {
-                CIFRecord::Header { .. } => true,
-                CIFRecord::Trailer => true,
-                CIFRecord::Association { .. } => true,
-                CIFRecord::LocationTerminate { .. } => true,
-                CIFRecord::TIPLOCInsert { .. } => true,
-                CIFRecord::TIPLOCAmend { .. } => true,
-                CIFRecord::TIPLOCDelete { .. } => true,
-                CIFRecord::BasicSchedule { .. } => {
-                    if let CIFRecord::BasicSchedule {
-                        transaction_type,
-                        stp_indicator,
-                        ..
-                    } = record
-                    {
-                        // only submit a BS record alone if it's a delete, or a cancellation
-                        *transaction_type == 'D' || *stp_indicator == 'C'
-                    } else {
-                        false
-                    }
-                }
-                _ => false,
-            };
-            trace!("Record: {:?}, submitting: {submit}", record);
+        })
+        .errors
+    }
+
+    /// Apply a list of records onto this schedule database, calling `progress` after each
+    /// bundle of records is applied.
+    /// This can reset the database if this includes a full update.
+    /// Returns the errors encountered and any warnings raised.
+    pub fn apply_records_with_progress<F>(
+        &mut self,
+        records: &[CIFRecord],
+        progress: F,
+    ) -> ApplyReport
+    where
+        F: FnMut(ApplyProgress),
+    {
+        let total_records = Some(records.len());
+        let records = records.iter().map(Ok::<_, Infallible>);
+        match self.apply_stream(records, total_records, progress, &mut ()) {
+            Ok(report) => report,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Parse records from a reader and apply them onto this schedule database as they are
+    /// read, without holding the whole file in memory. This can reset the database if this
+    /// includes a full update.
+    ///
+    /// If the data can't be parsed, the records before the error will already have been
+    /// applied.
+    pub fn apply_reader<R: Read>(&mut self, reader: R) -> Result<ApplyReport, CIFParseError> {
+        self.apply_stream(CIFRecordReader::new(reader), None, |_| (), &mut ())
+    }
+
+    /// Apply records onto this schedule database, one bundle at a time, as they are produced
+    /// by an iterator, telling `observer` about each change made.
+    pub(crate) fn apply_stream<I, B, E, F>(
+        &mut self,
+        records: I,
+        total_records: Option<usize>,
+        progress: F,
+        observer: &mut dyn ScheduleObserver,
+    ) -> Result<ApplyReport, E>
+    where
+        I: Iterator<Item = Result<B, E>>,
+        B: Borrow<CIFRecord>,
+        F: FnMut(ApplyProgress),
+    {
+        apply_stream(self, records, total_records, progress, observer)
+    }
+}
+
+/// Decode records and apply them to a store, one bundle at a time, as they are produced by
+/// an iterator, telling `observer` about each change made.
+pub(crate) fn apply_stream<S, I, B, E, F>(
+    sink: &mut S,
+    records: I,
+    total_records: Option<usize>,
+    mut progress: F,
+    observer: &mut dyn ScheduleObserver,
+) -> Result<ApplyReport, E>
+where
+    S: ScheduleSink + ?Sized,
+    I: Iterator<Item = Result<B, E>>,
+    B: Borrow<CIFRecord>,
+    F: FnMut(ApplyProgress),
+{
+    let mut bundle: Vec<B> = vec![];
+    let mut report = ApplyReport::default();
+    let mut warnings = vec![];
+    let mut bundles_applied = 0;
+    let mut records = records.enumerate().peekable();
+    while let Some((record_idx, record)) = records.next() {
+        let record = record?;
+        if RecordKind::of(record.borrow()).is_some_and(|kind| sink.skips_record_kind(kind)) {
+            continue;
+        }
+        bundle.push(record);
+        let record = bundle[bundle.len() - 1].borrow();
 
-            if submit {
-                let r = if bundle.len() == 1 {
-                    self.apply_single_record(bundle[0])
+        // Check type
+        let submit = match &record {
+            CIFRecord::Header { .. } => true,
+            CIFRecord::Trailer => true,
+            CIFRecord::Association { .. } => true,
+            CIFRecord::LocationTerminate { .. } | CIFRecord::LocationNote { .. } => {
+                // notes on the terminating location follow it, unless they are left out
+                let terminated = bundle
+                    .iter()
+                    .any(|r| matches!(r.borrow(), CIFRecord::LocationTerminate { .. }));
+                let note_follows = matches!(
+                    records.peek(),
+                    Some((_, Ok(next))) if matches!(next.borrow(), CIFRecord::LocationNote { .. })
+                ) && !sink.skips_record_kind(RecordKind::Note);
+                terminated && !note_follows
+            }
+            CIFRecord::TIPLOCInsert { .. } => true,
+            CIFRecord::TIPLOCAmend { .. } => true,
+            CIFRecord::TIPLOCDelete { .. } => true,
+            CIFRecord::BasicSchedule { .. } => {
+                if let CIFRecord::BasicSchedule {
+                    transaction_type,
+                    stp_indicator,
+                    ..
+                } = record
+                {
+                    // only submit a BS record alone if it's a delete, or a cancellation
+                    *transaction_type == 'D' || *stp_indicator == 'C'
                 } else {
-                    self.apply_record_bundle(&bundle)
-                };
-                if let Err(e) = r {
-                    #[cfg(feature = "panic-on-first-error")]
-                    {
-                        log::error!("Error at record {record_idx}, line {}", record_idx + 1);
-                        log::error!("Error: {e:?}");
-                        log::error!("Records: {:?}", bundle);
-                    }
-                    errors.push((record_idx, e));
-                    #[cfg(feature = "panic-on-first-error")]
-                    panic!(
-                        "Came across an error and the `panic-on-first-error` feature is enabled."
-                    );
+                    false
                 }
-                bundle.clear();
             }
-        }
-        errors
-    }
+            _ => false,
+        };
+        trace!("Record: {:?}, submitting: {submit}", record);
 
-    /// Apply a single record. A bundle consists of either:
-    /// - A header record.
-    /// - A TIPLOC record.
-    /// - An association record.
-    /// - A lone BS record for a delete type.
-    /// - A trailer record.
-    fn apply_single_record(&mut self, record: &CIFRecord) -> Result<(), ScheduleApplyError> {
-        match record {
-            CIFRecord::Header {
-                date_of_extract,
-                time_of_extract,
-                update_indicator,
-                ..
-            } => {
-                if *update_indicator == 'F' {
-                    // full update, empty database
-                    info!("Received full update, clearing database.");
-                    self.tiplocs.clear();
+        if submit {
+            let records: Vec<&CIFRecord> = bundle.iter().map(Borrow::borrow).collect();
+            let r = if records.len() == 1 {
+                apply_single_record(sink, records[0], &mut warnings, observer)
+            } else {
+                apply_record_bundle(sink, &records, &mut warnings, observer)
+            };
+            report.warnings.extend(
+                warnings
+                    .drain(..)
+                    .map(|(category, identifier)| ApplyWarning {
+                        record_index: record_idx,
+                        category,
+                        identifier,
+                    }),
+            );
+            if let Err(e) = r {
+                if sink.panics_on_first_error() {
+                    log::error!("Error at record {record_idx}, line {}", record_idx + 1);
+                    log::error!("Error: {e:?}");
+                    log::error!("Records: {:?}", records);
+                    panic!("Came across an error and panicking on the first error is enabled.");
                 }
-                let date = NaiveDate::parse_from_str(date_of_extract, "%d%m%y").map_err(|_| {
-                    ScheduleApplyError::InvalidHeaderDateTime(date_of_extract.clone())
-                })?;
-                let time = NaiveTime::parse_from_str(time_of_extract, "%H%M").map_err(|_| {
-                    ScheduleApplyError::InvalidHeaderDateTime(time_of_extract.clone())
-                })?;
-                let date_and_time = date.and_time(time);
-                if self.extract_date_time > date_and_time {
-                    return Err(ScheduleApplyError::AttemptingToApplyOlderData);
+                report.errors.push((record_idx, e));
+                if matches!(records[0], CIFRecord::Header { .. }) {
+                    // the rest of a rejected file mustn't be applied
+                    warn!("The header record was rejected, so the rest of the file is skipped.");
+                    break;
                 }
-                self.extract_date_time = date_and_time;
             }
+            bundles_applied += 1;
+            progress(ApplyProgress {
+                record_index: record_idx,
+                total_records,
+                bundles_applied,
+                phase: match records[0] {
+                    CIFRecord::Header { .. } => ApplyPhase::Header,
+                    CIFRecord::TIPLOCInsert { .. }
+                    | CIFRecord::TIPLOCAmend { .. }
+                    | CIFRecord::TIPLOCDelete { .. } => ApplyPhase::Tiplocs,
+                    CIFRecord::Association { .. } => ApplyPhase::Associations,
+                    CIFRecord::Trailer => ApplyPhase::Trailer,
+                    _ => ApplyPhase::Schedules,
+                },
+            });
+            bundle.clear();
+        }
+    }
+    Ok(report)
+}
 
-            CIFRecord::TIPLOCInsert {
-                tiploc,
-                tps_description,
-                three_alpha_code,
-                stanox,
-                ..
-            } => {
-                info!("New TIPLOC: {}", tiploc.trim());
-                self.tiplocs.insert(
-                    tiploc.trim().to_string(),
-                    TIPLOC {
-                        tiploc: tiploc.trim().to_string(),
-                        three_alpha_code: three_alpha_code.trim().to_string(),
-                        description: tps_description.trim().to_string(),
-                        stanox: *stanox,
-                    },
-                );
+/// Apply a single record. A bundle consists of either:
+/// - A header record.
+/// - A TIPLOC record.
+/// - An association record.
+/// - A lone BS record for a delete type.
+/// - A trailer record.
+fn apply_single_record<S: ScheduleSink + ?Sized>(
+    sink: &mut S,
+    record: &CIFRecord,
+    warnings: &mut Vec<(ApplyWarningCategory, String)>,
+    observer: &mut dyn ScheduleObserver,
+) -> Result<(), ScheduleApplyError> {
+    match record {
+        CIFRecord::Header {
+            date_of_extract,
+            time_of_extract,
+            current_file_reference,
+            update_indicator,
+            ..
+        } => {
+            let date_and_time = extract_date_time(date_of_extract, time_of_extract)?;
+            if sink.last_extract_date_time() > date_and_time {
+                return Err(ScheduleApplyError::AttemptingToApplyOlderData);
             }
-            CIFRecord::TIPLOCAmend {
-                tiploc,
-                tps_description,
-                three_alpha_code,
-                new_tiploc,
-                stanox,
-                ..
-            } => {
-                info!("Amendment for TIPLOC {}", tiploc.trim());
-                let tiploc = if new_tiploc.trim().is_empty() {
-                    tiploc.trim().to_string()
-                } else {
-                    self.tiplocs.remove(&tiploc.trim().to_string());
-                    new_tiploc.trim().to_string()
-                };
-                self.tiplocs.insert(
-                    tiploc.clone(),
-                    TIPLOC {
-                        tiploc,
-                        three_alpha_code: three_alpha_code.trim().to_string(),
-                        description: tps_description.trim().to_string(),
-                        stanox: *stanox,
-                    },
-                );
+            if *update_indicator == 'F' {
+                // full update, empty database
+                info!("Received full update, clearing database.");
+                sink.full_update();
+                observer.on_full_update();
             }
-            CIFRecord::TIPLOCDelete { tiploc } => {
-                info!("Removed TIPLOC {}", tiploc.trim());
-                self.tiplocs.remove(tiploc.trim());
+            sink.set_extract(date_and_time, current_file_reference.trim().to_string());
+        }
+
+        CIFRecord::TIPLOCInsert { .. }
+        | CIFRecord::TIPLOCAmend { .. }
+        | CIFRecord::TIPLOCDelete { .. } => apply_tiploc_record(sink, record, warnings, observer),
+
+        CIFRecord::Association { .. } => {
+            apply_association_record(sink, record, warnings, observer)?
+        }
+
+        CIFRecord::BasicSchedule {
+            transaction_type,
+            train_uid,
+            date_runs_from,
+            date_runs_to,
+            days_run,
+            bank_holiday_running,
+            train_status,
+            train_category,
+            train_identity,
+            headcode,
+            course_indicator,
+            train_service_code,
+            portion_id,
+            power_type,
+            timing_load,
+            speed,
+            operating_characteristics,
+            seating_class,
+            sleepers,
+            reservations,
+            catering_code,
+            connection_indicator,
+            service_branding,
+            stp_indicator,
+            ..
+        } => {
+            assert!(
+                *transaction_type == 'D' || *stp_indicator == 'C',
+                "transaction type must be delete, or it must be a cancellation to be processed as a single record"
+            );
+            if *transaction_type == 'D' {
+                // only the schedule with the same start date and STP indicator is deleted
+                let runs_from =
+                    NaiveDate::parse_from_str(date_runs_from, "%y%m%d").map_err(|_| {
+                        ScheduleApplyError::InvalidScheduleDate(date_runs_from.to_string())
+                    })?;
+                let stp_indicator = parse_stp_indicator(stp_indicator)?;
+                if let Some(schedule) = sink.delete_schedule(train_uid, runs_from, stp_indicator) {
+                    observer.on_schedule_deleted(&schedule);
+                } else {
+                    warn!("A record is trying to delete schedule {train_uid}, but it doesn't exist in the database.");
+                    warnings.push((
+                        ApplyWarningCategory::DeletedUnknownSchedule,
+                        train_uid.clone(),
+                    ));
+                }
+            } else {
+                let mut sch = Schedule::new();
+                bs_record_to_schedule(
+                    &mut sch,
+                    train_uid,
+                    date_runs_from,
+                    date_runs_to,
+                    days_run,
+                    bank_holiday_running,
+                    train_status,
+                    train_category,
+                    train_identity,
+                    headcode,
+                    course_indicator,
+                    train_service_code,
+                    portion_id,
+                    power_type,
+                    timing_load,
+                    speed,
+                    operating_characteristics,
+                    seating_class,
+                    sleepers,
+                    reservations,
+                    catering_code,
+                    connection_indicator,
+                    service_branding,
+                    stp_indicator,
+                    sink.unknown_code_policy(),
+                    warnings,
+                )?;
+                if sink.has_schedules(train_uid) {
+                    add_schedule(sink, sch, *transaction_type == 'R', warnings, observer);
+                } else {
+                    warn!("A record is trying to cancel schedule {train_uid}, but it doesn't exist in the database. Discarding it...");
+                    warnings.push((
+                        ApplyWarningCategory::CancelledUnknownSchedule,
+                        train_uid.clone(),
+                    ));
+                }
             }
+        }
+
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Apply a bundle of records. A bundle consists of either:
+/// - A schedule, from the BS record to the LT record, for a new or revise types.
+fn apply_record_bundle<S: ScheduleSink + ?Sized>(
+    sink: &mut S,
+    record_bundle: &Vec<&CIFRecord>,
+    warnings: &mut Vec<(ApplyWarningCategory, String)>,
+    observer: &mut dyn ScheduleObserver,
+) -> Result<(), ScheduleApplyError> {
+    let mut schedule = Schedule::new();
+    let mut revise = false;
+    let ignore_placeholders = sink.ignores_placeholder_public_times();
+    let policy = sink.unknown_code_policy();
+    let public = |public: &str, working: Option<JourneyTime>| {
+        public_time(public, working, ignore_placeholders)
+    };
 
+    for record in record_bundle {
+        match record {
             CIFRecord::BasicSchedule {
                 transaction_type,
                 train_uid,
@@ -259,6 +754,9 @@ impl ScheduleDatabase {
                 train_status,
                 train_category,
                 train_identity,
+                headcode,
+                course_indicator,
+                train_service_code,
                 portion_id,
                 power_type,
                 timing_load,
@@ -268,62 +766,17 @@ impl ScheduleDatabase {
                 sleepers,
                 reservations,
                 catering_code,
+                connection_indicator,
+                service_branding,
                 stp_indicator,
                 ..
             } => {
-                assert!(
-                    *transaction_type == 'D' || *stp_indicator == 'C',
-                    "transaction type must be delete, or it must be a cancellation to be processed as a single record"
-                );
-                if *transaction_type == 'D' {
-                    self.schedules.remove(train_uid);
-                } else {
-                    let mut sch = Schedule::new();
-                    bs_record_to_schedule(
-                        &mut sch,
-                        train_uid,
-                        date_runs_from,
-                        date_runs_to,
-                        days_run,
-                        bank_holiday_running,
-                        train_status,
-                        train_category,
-                        train_identity,
-                        portion_id,
-                        power_type,
-                        timing_load,
-                        speed,
-                        operating_characteristics,
-                        seating_class,
-                        sleepers,
-                        reservations,
-                        catering_code,
-                        stp_indicator,
-                    )?;
-                    self.schedules
-                        .entry(train_uid.clone())
-                        .and_modify(|v| v.push(sch));
-                }
-            }
-
-            _ => (),
-        }
-        Ok(())
-    }
-
-    /// Apply a bundle of records. A bundle consists of either:
-    /// - A schedule, from the BS record to the LT record, for a new or revise types.
-    fn apply_record_bundle(
-        &mut self,
-        record_bundle: &Vec<&CIFRecord>,
-    ) -> Result<(), ScheduleApplyError> {
-        let mut schedule = Schedule::new();
+                let uid = train_uid.trim().to_string();
+                revise = *transaction_type == 'R';
 
-        for record in record_bundle {
-            match record {
-                CIFRecord::BasicSchedule {
-                    transaction_type,
-                    train_uid,
+                bs_record_to_schedule(
+                    &mut schedule,
+                    &uid,
                     date_runs_from,
                     date_runs_to,
                     days_run,
@@ -331,6 +784,9 @@ impl ScheduleDatabase {
                     train_status,
                     train_category,
                     train_identity,
+                    headcode,
+                    course_indicator,
+                    train_service_code,
                     portion_id,
                     power_type,
                     timing_load,
@@ -340,138 +796,262 @@ impl ScheduleDatabase {
                     sleepers,
                     reservations,
                     catering_code,
+                    connection_indicator,
+                    service_branding,
                     stp_indicator,
-                    ..
-                } => {
-                    let uid = train_uid.trim().to_string();
-                    if *transaction_type == 'R' && !self.schedules.contains_key(&uid) {
-                        warn!("A record is trying to revise schedule {uid}, but it doesn't exist in the database. Inserting it as new...");
-                    }
-
-                    bs_record_to_schedule(
-                        &mut schedule,
-                        &uid,
-                        date_runs_from,
-                        date_runs_to,
-                        days_run,
-                        bank_holiday_running,
-                        train_status,
-                        train_category,
-                        train_identity,
-                        portion_id,
-                        power_type,
-                        timing_load,
-                        speed,
-                        operating_characteristics,
-                        seating_class,
-                        sleepers,
-                        reservations,
-                        catering_code,
-                        stp_indicator,
-                    )?;
-                }
-                CIFRecord::BasicScheduleExtended {
-                    atoc_code,
-                    applicable_timetable_code,
-                    ..
-                } => {
-                    schedule.atoc_code = atoc_code.trim().to_string();
-                    schedule.subject_to_performance_monitoring = *applicable_timetable_code == 'Y';
-                }
-                CIFRecord::LocationOrigin {
-                    location,
-                    scheduled_departure_time,
-                    public_departure_time,
-                    platform,
-                    line,
-                    activity,
-                    ..
-                } => schedule.journey.push(JourneyLocation {
+                    policy,
+                    warnings,
+                )?;
+            }
+            CIFRecord::BasicScheduleExtended {
+                traction_class,
+                uic_code,
+                atoc_code,
+                applicable_timetable_code,
+                ..
+            } => {
+                schedule.uic_code =
+                    Some(uic_code.trim().to_string()).filter(|code| !code.is_empty());
+                schedule.traction_class =
+                    Some(traction_class.trim().to_string()).filter(|class| !class.is_empty());
+                schedule.atoc_code = atoc_code.trim().to_string();
+                schedule.subject_to_performance_monitoring = *applicable_timetable_code == 'Y';
+            }
+            CIFRecord::LocationOrigin {
+                location,
+                scheduled_departure_time,
+                public_departure_time,
+                platform,
+                line,
+                activity,
+                engineering_allowance,
+                pathing_allowance,
+                performance_allowance,
+                ..
+            } => {
+                let departure_time = Some(scheduled_departure_time.parse()?);
+                schedule.journey.push(JourneyLocation {
                     tiploc: location[0..7].trim().to_string(),
+                    suffix: location_suffix(location),
+                    day_offset: 0,
                     arrival_time: None,
-                    departure_time: Some(scheduled_departure_time.parse()?),
+                    departure_time,
                     passing_time: None,
                     public_arrival: None,
-                    public_departure: Some(public_departure_time.parse()?),
-                    platform: platform.trim().to_string(),
-                    line: line.trim().to_string(),
+                    public_departure: public(public_departure_time, departure_time)?,
+                    platform: Platform::new(platform),
+                    line: Line::new(line),
+                    path: Path::default(),
                     activity: activity.trim().to_string(),
-                }),
-                CIFRecord::LocationIntermediate {
-                    location,
-                    scheduled_arrival_time,
-                    scheduled_departure_time,
-                    scheduled_pass,
-                    public_arrival_time,
-                    public_departure_time,
-                    platform,
-                    line,
-                    activity,
-                    ..
-                } => schedule.journey.push(JourneyLocation {
+                    activities: parse_activities(activity),
+                    engineering_allowance: Allowance::parse_optional(engineering_allowance)?,
+                    pathing_allowance: Allowance::parse_optional(pathing_allowance)?,
+                    performance_allowance: Allowance::parse_optional(performance_allowance)?,
+                    notes: vec![],
+                })
+            }
+            CIFRecord::LocationIntermediate {
+                location,
+                scheduled_arrival_time,
+                scheduled_departure_time,
+                scheduled_pass,
+                public_arrival_time,
+                public_departure_time,
+                platform,
+                line,
+                path,
+                activity,
+                engineering_allowance,
+                pathing_allowance,
+                performance_allowance,
+                ..
+            } => {
+                let arrival_time = if scheduled_arrival_time.trim().is_empty() {
+                    None
+                } else {
+                    Some(scheduled_arrival_time.parse()?)
+                };
+                let departure_time = if scheduled_departure_time.trim().is_empty() {
+                    None
+                } else {
+                    Some(scheduled_departure_time.parse()?)
+                };
+                schedule.journey.push(JourneyLocation {
                     tiploc: location[0..7].trim().to_string(),
-                    arrival_time: if scheduled_arrival_time.trim().is_empty() {
-                        None
-                    } else {
-                        Some(scheduled_arrival_time.parse()?)
-                    },
-                    departure_time: if scheduled_departure_time.trim().is_empty() {
-                        None
-                    } else {
-                        Some(scheduled_departure_time.parse()?)
-                    },
+                    suffix: location_suffix(location),
+                    day_offset: 0,
+                    arrival_time,
+                    departure_time,
                     passing_time: if scheduled_pass.trim().is_empty() {
                         None
                     } else {
                         Some(scheduled_pass.parse()?)
                     },
-                    public_arrival: if public_arrival_time.trim().is_empty() {
-                        None
-                    } else {
-                        Some(public_arrival_time.parse()?)
-                    },
-                    public_departure: if public_departure_time.trim().is_empty() {
-                        None
-                    } else {
-                        Some(public_departure_time.parse()?)
-                    },
-                    platform: platform.trim().to_string(),
-                    line: line.trim().to_string(),
+                    public_arrival: public(public_arrival_time, arrival_time)?,
+                    public_departure: public(public_departure_time, departure_time)?,
+                    platform: Platform::new(platform),
+                    line: Line::new(line),
+                    path: Path::new(path),
                     activity: activity.trim().to_string(),
-                }),
-                CIFRecord::LocationTerminate {
-                    location,
-                    scheduled_arrival_time,
-                    public_arrival_time,
-                    platform,
-                    activity,
-                    ..
-                } => schedule.journey.push(JourneyLocation {
+                    activities: parse_activities(activity),
+                    engineering_allowance: Allowance::parse_optional(engineering_allowance)?,
+                    pathing_allowance: Allowance::parse_optional(pathing_allowance)?,
+                    performance_allowance: Allowance::parse_optional(performance_allowance)?,
+                    notes: vec![],
+                })
+            }
+            CIFRecord::LocationTerminate {
+                location,
+                scheduled_arrival_time,
+                public_arrival_time,
+                platform,
+                path,
+                activity,
+                ..
+            } => {
+                let arrival_time = Some(scheduled_arrival_time.parse()?);
+                schedule.journey.push(JourneyLocation {
                     tiploc: location[0..7].trim().to_string(),
-                    arrival_time: Some(scheduled_arrival_time.parse()?),
+                    suffix: location_suffix(location),
+                    day_offset: 0,
+                    arrival_time,
                     departure_time: None,
                     passing_time: None,
-                    public_arrival: Some(public_arrival_time.parse()?),
+                    public_arrival: public(public_arrival_time, arrival_time)?,
                     public_departure: None,
-                    platform: platform.trim().to_string(),
-                    line: String::new(),
+                    platform: Platform::new(platform),
+                    line: Line::default(),
+                    path: Path::new(path),
                     activity: activity.trim().to_string(),
-                }),
+                    activities: parse_activities(activity),
+                    engineering_allowance: None,
+                    pathing_allowance: None,
+                    performance_allowance: None,
+                    notes: vec![],
+                })
+            }
 
-                _ => (),
+            CIFRecord::TrainNote { note_type, note } => {
+                schedule.notes.push(Note::new(*note_type, note));
             }
+            CIFRecord::LocationNote { note_type, note } => match schedule.journey.last_mut() {
+                Some(location) => location.notes.push(Note::new(*note_type, note)),
+                None => warn!(
+                    "Schedule {} has a location note before any location, ignoring it.",
+                    schedule.train_uid
+                ),
+            },
+
+            _ => (),
         }
+    }
 
-        self.schedules
-            .entry(schedule.train_uid.clone())
-            .and_modify(|v| v.push(schedule.clone()))
-            .or_insert(vec![schedule]);
-        Ok(())
+    assign_day_offsets(&mut schedule.journey);
+    add_schedule(sink, schedule, revise, warnings, observer);
+    Ok(())
+}
+
+/// Work out the day offset of each location in a journey, moving onto the next day each time
+/// the working times go backwards.
+fn assign_day_offsets(journey: &mut [JourneyLocation]) {
+    let mut day_offset = 0u8;
+    let mut last = None;
+    for location in journey {
+        let times = [
+            location.arrival_time,
+            location.passing_time,
+            location.departure_time,
+        ];
+        for (idx, time) in times.into_iter().flatten().enumerate() {
+            if last.is_some_and(|last| time < last) {
+                day_offset = day_offset.saturating_add(1);
+            }
+            if idx == 0 {
+                location.day_offset = day_offset;
+            }
+            last = Some(time);
+        }
     }
 }
 
-#[derive(Debug, Clone, Getters)]
+/// A note on a schedule, or on one of its locations.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
+pub struct Note {
+    /// The kind of note, as given in the note record.
+    #[getset(get = "pub")]
+    note_type: char,
+    #[getset(get = "pub")]
+    text: String,
+}
+
+impl Note {
+    fn new(note_type: char, text: &str) -> Self {
+        Self {
+            note_type,
+            text: text.trim().to_string(),
+        }
+    }
+}
+
+/// The suffix of a location in a location record, which follows the 7 character TIPLOC.
+fn location_suffix(location: &str) -> Option<u8> {
+    location
+        .chars()
+        .nth(7)
+        .and_then(|c| c.to_digit(10))
+        .map(|digit| digit as u8)
+}
+
+/// Add a schedule to a store. A revision replaces the schedule it revises, which is the one
+/// with the same start date and STP indicator, as the end date and everything else may be
+/// revised. A new schedule is always added after the others.
+fn add_schedule<S: ScheduleSink + ?Sized>(
+    sink: &mut S,
+    schedule: Schedule,
+    revise: bool,
+    warnings: &mut Vec<(ApplyWarningCategory, String)>,
+    observer: &mut dyn ScheduleObserver,
+) {
+    if !sink.keeps_schedule(&schedule) {
+        let uid = &schedule.train_uid;
+        debug!("Schedule {uid} is left out by the schedule filter");
+        if revise {
+            if let Some(previous) =
+                sink.delete_schedule(uid, schedule.runs_from, schedule.stp_indicator)
+            {
+                observer.on_schedule_deleted(&previous);
+            }
+        }
+        return;
+    }
+    if revise {
+        if let Some(previous) = sink.revise_schedule(schedule.clone()) {
+            observer.on_schedule_revised(&previous, &schedule);
+            return;
+        }
+        let uid = &schedule.train_uid;
+        warn!("A record is trying to revise schedule {uid}, but it doesn't exist in the database. Inserting it as new...");
+        warnings.push((ApplyWarningCategory::RevisedUnknownSchedule, uid.clone()));
+    }
+    observer.on_schedule_inserted(&schedule);
+    sink.insert_schedule(schedule);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
 pub struct TIPLOC {
     /// The TIPLOC code of this location.
     #[getset(get = "pub")]
@@ -482,13 +1062,31 @@ pub struct TIPLOC {
     /// The description of this location.
     #[getset(get = "pub")]
     description: String,
-    /// The STANOX of this location.
+    /// The STANOX of this location, or 0 if it doesn't have one.
     #[getset(get = "pub")]
     stanox: u32,
+    /// The National Location Code of this location.
+    #[getset(get = "pub")]
+    nlc: u32,
+    /// The check character of the National Location Code.
+    #[getset(get = "pub")]
+    nlc_check_char: char,
+    /// The description of this location for the National Location Code.
+    #[getset(get = "pub")]
+    nlc_description: String,
+    /// The Post Office Mail Collection Point code of this location, or an empty string.
+    #[getset(get = "pub")]
+    po_mcp_code: String,
 }
 
-#[derive(Debug, Clone, Getters)]
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
 pub struct Schedule {
     /// The service identifier.
     #[getset(get = "pub")]
@@ -511,12 +1109,28 @@ pub struct Schedule {
     /// Is this train subject to performance monitoring.
     #[getset(get = "pub")]
     subject_to_performance_monitoring: bool,
+    /// The UIC code of a train running to or from the continent, if one is given.
+    #[getset(get = "pub")]
+    uic_code: Option<String>,
+    /// The traction class from the extended schedule record, if one is given.
+    #[getset(get = "pub")]
+    traction_class: Option<String>,
     #[getset(get = "pub")]
     train_status: TrainStatus,
     #[getset(get = "pub")]
     train_category: TrainCategory,
+    /// The train identity used in signalling, commonly called the headcode, such as `1A23`.
     #[getset(get = "pub")]
-    headcode: String,
+    train_identity: String,
+    /// The NRS headcode, used for reservations on some services, or an empty string.
+    #[getset(get = "pub")]
+    nrs_headcode: String,
+    /// The course indicator, which is always `'1'` in current data.
+    #[getset(get = "pub")]
+    course_indicator: char,
+    /// The train service code, used to group trains for revenue and operational purposes.
+    #[getset(get = "pub")]
+    train_service_code: String,
     #[getset(get = "pub")]
     portion_id: char,
     #[getset(get = "pub")]
@@ -536,9 +1150,17 @@ pub struct Schedule {
     #[getset(get = "pub")]
     catering: Vec<Catering>,
     #[getset(get = "pub")]
+    connection_indicator: ConnectionIndicator,
+    /// The service brand, such as `E` for Eurostar, or an empty string.
+    #[getset(get = "pub")]
+    service_branding: String,
+    #[getset(get = "pub")]
     stp_indicator: STPIndicator,
     #[getset(get = "pub")]
     journey: Vec<JourneyLocation>,
+    /// Notes about the train, from train note records.
+    #[getset(get = "pub")]
+    notes: Vec<Note>,
 }
 
 impl Schedule {
@@ -551,9 +1173,14 @@ impl Schedule {
             bank_holiday_running: BankHolidayRunning::RunsNormally,
             atoc_code: String::new(),
             subject_to_performance_monitoring: false,
+            uic_code: None,
+            traction_class: None,
             train_status: TrainStatus::PassengerAndParcels,
             train_category: TrainCategory::NotSpecified,
-            headcode: String::new(),
+            train_identity: String::new(),
+            nrs_headcode: String::new(),
+            course_indicator: ' ',
+            train_service_code: String::new(),
             portion_id: ' ',
             power_type: PowerType::Diesel,
             timing_load: TimingLoad::LoadInTonnes(0),
@@ -563,10 +1190,396 @@ impl Schedule {
             sleepers: Sleepers::NotSpecified,
             reservations: Reservations::Possible,
             catering: vec![],
+            connection_indicator: ConnectionIndicator::NotSpecified,
+            service_branding: String::new(),
             stp_indicator: STPIndicator::PermanentAssociation,
             journey: vec![],
+            notes: vec![],
         }
     }
+
+    /// The train identity, such as `1A23`. The NRS headcode is given by
+    /// [`Self::nrs_headcode`].
+    #[deprecated(note = "use `train_identity()`, or `nrs_headcode()` for the NRS headcode")]
+    pub fn headcode(&self) -> &String {
+        &self.train_identity
+    }
+
+    /// Is this schedule valid on the given date? This checks the date range and the days run,
+    /// but does not take into account any other schedules for this service which may overlay or
+    /// cancel this one.
+    pub fn runs_on(&self, date: NaiveDate) -> bool {
+        date >= self.runs_from
+            && date <= self.runs_to
+            && self.days_run.contains_weekday(date.weekday())
+    }
+
+    /// Get the part of the journey between two calling points, given by TIPLOC, including both
+    /// ends. Locations the train only passes are not considered calling points.
+    ///
+    /// Returns `None` if the train doesn't call at either location, or if it doesn't call at
+    /// `to_location` after `from_location`.
+    pub fn segment<S: AsRef<str>, T: AsRef<str>>(
+        &self,
+        from_location: S,
+        to_location: T,
+    ) -> Option<&[JourneyLocation]> {
+        let is_call = |loc: &JourneyLocation, tiploc: &str| {
+            loc.tiploc == tiploc && loc.passing_time.is_none()
+        };
+        let from = self
+            .journey
+            .iter()
+            .position(|loc| is_call(loc, from_location.as_ref().trim()))?;
+        let to = self
+            .journey
+            .iter()
+            .skip(from + 1)
+            .position(|loc| is_call(loc, to_location.as_ref().trim()))?
+            + from
+            + 1;
+        Some(&self.journey[from..=to])
+    }
+
+    /// The location this train starts from, if it has a journey.
+    pub fn origin(&self) -> Option<&JourneyLocation> {
+        self.journey.first()
+    }
+
+    /// The location this train finishes at, if it has a journey.
+    pub fn destination(&self) -> Option<&JourneyLocation> {
+        self.journey.last()
+    }
+
+    /// The locations where this train calls for passengers, being those with a public time
+    /// where passengers are picked up or set down, in order.
+    pub fn public_calling_points(&self) -> Vec<&JourneyLocation> {
+        self.journey
+            .iter()
+            .filter(|loc| loc.public_arrival.is_some() || loc.public_departure.is_some())
+            .filter(|loc| loc.picks_up() || loc.sets_down())
+            .collect()
+    }
+
+    /// The locations this train passes without stopping, in order.
+    pub fn passes(&self) -> Vec<&JourneyLocation> {
+        self.journey
+            .iter()
+            .filter(|loc| loc.passing_time.is_some())
+            .collect()
+    }
+}
+
+/// Builds a [`Schedule`] from scratch, such as for a timetable written by hand rather than
+/// read from a CIF file.
+///
+/// The journey is given in order, starting with [`ScheduleBuilder::origin`] and ending with
+/// [`ScheduleBuilder::terminus`]. Public times default to the working times, and activities
+/// to those of a train setting down and picking up passengers, so an advertised stopping
+/// train only needs its working times. Methods such as [`ScheduleBuilder::platform`] change
+/// the location added last. Use [`Schedule::validate`] to check the result makes sense.
+#[derive(Debug, Clone)]
+pub struct ScheduleBuilder {
+    schedule: Schedule,
+}
+
+impl ScheduleBuilder {
+    /// Start building a permanent schedule for a train which runs on the given days between
+    /// two dates, inclusive.
+    pub fn new<S: AsRef<str>>(
+        train_uid: S,
+        runs_from: NaiveDate,
+        runs_to: NaiveDate,
+        days_run: DaysRun,
+    ) -> Self {
+        let mut schedule = Schedule::new();
+        schedule.train_uid = train_uid.as_ref().trim().to_string();
+        schedule.runs_from = runs_from;
+        schedule.runs_to = runs_to;
+        schedule.days_run = days_run;
+        schedule.course_indicator = '1';
+        schedule.power_type = PowerType::NotSpecified;
+        schedule.timing_load = TimingLoad::NotSpecified;
+        schedule.seating_class = SeatingClass::FirstAndStandard;
+        schedule.reservations = Reservations::NotSpecified;
+        Self { schedule }
+    }
+
+    /// Whether the train runs on bank holidays. Runs normally by default.
+    pub fn bank_holiday_running(mut self, bank_holiday_running: BankHolidayRunning) -> Self {
+        self.schedule.bank_holiday_running = bank_holiday_running;
+        self
+    }
+
+    /// The ATOC code of the operator.
+    pub fn atoc_code<S: AsRef<str>>(mut self, atoc_code: S) -> Self {
+        self.schedule.atoc_code = atoc_code.as_ref().trim().to_string();
+        self
+    }
+
+    /// Whether the train is subject to performance monitoring. Disabled by default.
+    pub fn performance_monitoring(mut self, monitored: bool) -> Self {
+        self.schedule.subject_to_performance_monitoring = monitored;
+        self
+    }
+
+    /// The UIC code of a train running to or from the continent.
+    pub fn uic_code<S: AsRef<str>>(mut self, uic_code: S) -> Self {
+        self.schedule.uic_code = Some(uic_code.as_ref().trim().to_string());
+        self
+    }
+
+    /// The class of the traction, as given by the operator.
+    pub fn traction_class<S: AsRef<str>>(mut self, traction_class: S) -> Self {
+        self.schedule.traction_class = Some(traction_class.as_ref().trim().to_string());
+        self
+    }
+
+    /// The train status. Passenger and parcels by default.
+    pub fn train_status(mut self, train_status: TrainStatus) -> Self {
+        self.schedule.train_status = train_status;
+        self
+    }
+
+    /// The train category, such as ordinary or express passenger.
+    pub fn train_category(mut self, train_category: TrainCategory) -> Self {
+        self.schedule.train_category = train_category;
+        self
+    }
+
+    /// The train identity used in signalling, such as `1A23`.
+    pub fn train_identity<S: AsRef<str>>(mut self, train_identity: S) -> Self {
+        self.schedule.train_identity = train_identity.as_ref().trim().to_string();
+        self
+    }
+
+    /// The NRS headcode, used for reservations on some services.
+    pub fn nrs_headcode<S: AsRef<str>>(mut self, nrs_headcode: S) -> Self {
+        self.schedule.nrs_headcode = nrs_headcode.as_ref().trim().to_string();
+        self
+    }
+
+    /// The train service code, used to group trains for revenue and operational purposes.
+    pub fn train_service_code<S: AsRef<str>>(mut self, train_service_code: S) -> Self {
+        self.schedule.train_service_code = train_service_code.as_ref().trim().to_string();
+        self
+    }
+
+    /// The portion of a train which divides, if it does.
+    pub fn portion_id(mut self, portion_id: char) -> Self {
+        self.schedule.portion_id = portion_id;
+        self
+    }
+
+    /// The type of power the train uses. Not specified by default.
+    pub fn power_type(mut self, power_type: PowerType) -> Self {
+        self.schedule.power_type = power_type;
+        self
+    }
+
+    /// The timing load, which may name the class of unit. Not specified by default.
+    pub fn timing_load(mut self, timing_load: TimingLoad) -> Self {
+        self.schedule.timing_load = timing_load;
+        self
+    }
+
+    /// The speed the train is timed at, in miles per hour.
+    pub fn speed(mut self, speed: u32) -> Self {
+        self.schedule.speed = speed;
+        self
+    }
+
+    /// The operating characteristics of the train, such as being driver only operated.
+    pub fn operating_characteristics<I>(mut self, characteristics: I) -> Self
+    where
+        I: IntoIterator<Item = OperatingCharacteristic>,
+    {
+        self.schedule.operating_characteristics = characteristics.into_iter().collect();
+        self
+    }
+
+    /// The classes of seating available. First and standard by default.
+    pub fn seating_class(mut self, seating_class: SeatingClass) -> Self {
+        self.schedule.seating_class = seating_class;
+        self
+    }
+
+    /// The classes of sleeping accommodation available. None by default.
+    pub fn sleepers(mut self, sleepers: Sleepers) -> Self {
+        self.schedule.sleepers = sleepers;
+        self
+    }
+
+    /// Whether seats may or must be reserved. Not specified by default.
+    pub fn reservations(mut self, reservations: Reservations) -> Self {
+        self.schedule.reservations = reservations;
+        self
+    }
+
+    /// The catering available on the train.
+    pub fn catering<I: IntoIterator<Item = Catering>>(mut self, catering: I) -> Self {
+        self.schedule.catering = catering.into_iter().collect();
+        self
+    }
+
+    /// The service brand, such as `E` for Eurostar.
+    pub fn service_branding<S: AsRef<str>>(mut self, service_branding: S) -> Self {
+        self.schedule.service_branding = service_branding.as_ref().trim().to_string();
+        self
+    }
+
+    /// The STP indicator. Permanent by default.
+    pub fn stp_indicator(mut self, stp_indicator: STPIndicator) -> Self {
+        self.schedule.stp_indicator = stp_indicator;
+        self
+    }
+
+    /// Add a note about the train.
+    pub fn note<S: AsRef<str>>(mut self, note_type: char, text: S) -> Self {
+        self.schedule
+            .notes
+            .push(Note::new(note_type, text.as_ref()));
+        self
+    }
+
+    /// Add the location the train starts from.
+    pub fn origin<S: AsRef<str>>(self, tiploc: S, departure: JourneyTime) -> Self {
+        self.location(tiploc, None, Some(departure), None, "TB")
+    }
+
+    /// Add a location the train stops at.
+    pub fn call<S: AsRef<str>>(
+        self,
+        tiploc: S,
+        arrival: JourneyTime,
+        departure: JourneyTime,
+    ) -> Self {
+        self.location(tiploc, Some(arrival), Some(departure), None, "T")
+    }
+
+    /// Add a location the train passes without stopping.
+    pub fn pass<S: AsRef<str>>(self, tiploc: S, time: JourneyTime) -> Self {
+        self.location(tiploc, None, None, Some(time), "")
+    }
+
+    /// Add the location the train finishes at.
+    pub fn terminus<S: AsRef<str>>(self, tiploc: S, arrival: JourneyTime) -> Self {
+        self.location(tiploc, Some(arrival), None, None, "TF")
+    }
+
+    fn location<S: AsRef<str>>(
+        mut self,
+        tiploc: S,
+        arrival_time: Option<JourneyTime>,
+        departure_time: Option<JourneyTime>,
+        passing_time: Option<JourneyTime>,
+        activity: &str,
+    ) -> Self {
+        // public times are in whole minutes
+        let public = |time: Option<JourneyTime>| {
+            time.map(|time| JourneyTime {
+                half: false,
+                ..time
+            })
+        };
+        self.schedule.journey.push(JourneyLocation {
+            tiploc: tiploc.as_ref().trim().to_string(),
+            suffix: None,
+            day_offset: 0,
+            arrival_time,
+            departure_time,
+            passing_time,
+            public_arrival: public(arrival_time),
+            public_departure: public(departure_time),
+            platform: Platform::default(),
+            line: Line::default(),
+            path: Path::default(),
+            activity: activity.to_string(),
+            activities: parse_activities(activity),
+            engineering_allowance: None,
+            pathing_allowance: None,
+            performance_allowance: None,
+            notes: vec![],
+        });
+        self
+    }
+
+    /// Change the location added last, if there is one.
+    fn last_location<F: FnOnce(&mut JourneyLocation)>(mut self, f: F) -> Self {
+        if let Some(location) = self.schedule.journey.last_mut() {
+            f(location);
+        }
+        self
+    }
+
+    /// The suffix distinguishing the location added last from other visits to the same
+    /// TIPLOC.
+    pub fn suffix(self, suffix: u8) -> Self {
+        self.last_location(|location| location.suffix = Some(suffix))
+    }
+
+    /// The public times at the location added last, replacing those taken from the working
+    /// times. Giving neither leaves the location unadvertised.
+    pub fn public_times(
+        self,
+        arrival: Option<JourneyTime>,
+        departure: Option<JourneyTime>,
+    ) -> Self {
+        self.last_location(|location| {
+            location.public_arrival = arrival;
+            location.public_departure = departure;
+        })
+    }
+
+    /// The platform at the location added last.
+    pub fn platform<S: AsRef<str>>(self, platform: S) -> Self {
+        self.last_location(|location| location.platform = Platform::new(platform))
+    }
+
+    /// The line the train leaves the location added last by.
+    pub fn line<S: AsRef<str>>(self, line: S) -> Self {
+        self.last_location(|location| location.line = Line::new(line))
+    }
+
+    /// The path the train reaches the location added last by.
+    pub fn path<S: AsRef<str>>(self, path: S) -> Self {
+        self.last_location(|location| location.path = Path::new(path))
+    }
+
+    /// The activity codes at the location added last, such as `TB` or `U`, replacing the
+    /// default ones.
+    pub fn activity<S: AsRef<str>>(self, activity: S) -> Self {
+        self.last_location(|location| {
+            location.activity = activity.as_ref().trim().to_string();
+            location.activities = parse_activities(activity.as_ref());
+        })
+    }
+
+    /// Time allowed for engineering works before the location added last.
+    pub fn engineering_allowance(self, allowance: Allowance) -> Self {
+        self.last_location(|location| location.engineering_allowance = Some(allowance))
+    }
+
+    /// Time allowed before the location added last, to fit the train's path around others.
+    pub fn pathing_allowance(self, allowance: Allowance) -> Self {
+        self.last_location(|location| location.pathing_allowance = Some(allowance))
+    }
+
+    /// Time allowed before the location added last, to help the train recover from delays.
+    pub fn performance_allowance(self, allowance: Allowance) -> Self {
+        self.last_location(|location| location.performance_allowance = Some(allowance))
+    }
+
+    /// Add a note about the location added last.
+    pub fn location_note<S: AsRef<str>>(self, note_type: char, text: S) -> Self {
+        self.last_location(|location| location.notes.push(Note::new(note_type, text.as_ref())))
+    }
+
+    /// Build the schedule, working out which day each location is reached on.
+    pub fn build(mut self) -> Schedule {
+        assign_day_offsets(&mut self.schedule.journey);
+        self.schedule
+    }
 }
 
 bitflags! {
@@ -586,8 +1599,54 @@ bitflags! {
     }
 }
 
+/// Days run are archived as their bits.
+#[cfg(feature = "rkyv")]
+impl rkyv::Archive for DaysRun {
+    type Archived = u8;
+    type Resolver = ();
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        self.bits().resolve(pos, resolver, out)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<S: rkyv::Fallible + ?Sized> rkyv::Serialize<S> for DaysRun {
+    fn serialize(&self, _serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<D: rkyv::Fallible + ?Sized> rkyv::Deserialize<DaysRun, D> for u8 {
+    fn deserialize(&self, _deserializer: &mut D) -> Result<DaysRun, D::Error> {
+        Ok(DaysRun::from_bits_retain(*self))
+    }
+}
+
+impl DaysRun {
+    /// Does this set of days include the given weekday?
+    pub fn contains_weekday(&self, weekday: Weekday) -> bool {
+        self.contains(match weekday {
+            Weekday::Mon => Self::MONDAY,
+            Weekday::Tue => Self::TUESDAY,
+            Weekday::Wed => Self::WEDNESDAY,
+            Weekday::Thu => Self::THURSDAY,
+            Weekday::Fri => Self::FRIDAY,
+            Weekday::Sat => Self::SATURDAY,
+            Weekday::Sun => Self::SUNDAY,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
 pub enum BankHolidayRunning {
     RunsNormally,
     NotOnSpecificBankHolidayMondays,
@@ -596,6 +1655,13 @@ pub enum BankHolidayRunning {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum TrainStatus {
     Bus,
     Freight,
@@ -608,10 +1674,19 @@ pub enum TrainStatus {
     STPShip,
     STPBus,
     NotSpecified,
+    /// A train status not known to this library.
+    Unknown(char),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum TrainCategory {
     NotSpecified,
     LondonUnderground,
@@ -668,10 +1743,19 @@ pub enum TrainCategory {
     RfDEuropeanChannelTunnelContractServices,
     RfDEuropeanChannelTunnelHaulmark,
     RfDEuropeanChannelTunnelJointVenture,
+    /// A train category not known to this library.
+    Other(String),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum PowerType {
     Diesel,
     DieselElectricMultipleUnit,
@@ -682,10 +1766,19 @@ pub enum PowerType {
     ElectricMultipleUnit,
     HighSpeedTrain,
     NotSpecified,
+    /// A power type not known to this library.
+    Other(String),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum OperatingCharacteristic {
     VacuumBraked,
     TimedAt100MPH,
@@ -699,10 +1792,19 @@ pub enum OperatingCharacteristic {
     SteamHeated,
     RunsToTerminalsAsRequired,
     MayConveyTrafficToSB1CGauge,
+    /// An operating characteristic not known to this library.
+    Unknown(char),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum TimingLoad {
     /// Unspecifed
     NotSpecified,
@@ -742,37 +1844,121 @@ pub enum TimingLoad {
     SpecificClass(u16),
     /// Load in tonnes
     LoadInTonnes(u16),
+    /// A timing load not known to this library, as given in the schedule.
+    Other(String),
+}
+
+impl TimingLoad {
+    /// The rolling stock classes indicated by this timing load, if any. Loads given in tonnes,
+    /// and unknown loads, do not indicate a class.
+    pub fn classes(&self) -> Vec<u16> {
+        match self {
+            TimingLoad::NotSpecified => vec![],
+            TimingLoad::Class17201721Or1722 => vec![170, 172],
+            TimingLoad::Class141To144 => vec![141, 142, 143, 144],
+            TimingLoad::Class158168170Or175 => vec![158, 168, 170, 175],
+            TimingLoad::Class1650 => vec![165],
+            TimingLoad::Class150153155Or156 => vec![150, 153, 155, 156],
+            TimingLoad::Class1651Or166 => vec![165, 166],
+            TimingLoad::Class220Or221 => vec![220, 221],
+            TimingLoad::Class159 => vec![159],
+            TimingLoad::DMUPowerCarTrailer => vec![],
+            TimingLoad::DMU2PowerCarsTrailer => vec![],
+            TimingLoad::DMUPowerTwin => vec![],
+            TimingLoad::AcceleratedTimings => vec![],
+            TimingLoad::Class458 => vec![458],
+            TimingLoad::Class380 => vec![380],
+            TimingLoad::Class3501110MPH => vec![350],
+            TimingLoad::Class325ElectricParcelsUnit => vec![325],
+            TimingLoad::SpecificClass(class) => vec![*class],
+            TimingLoad::LoadInTonnes(_) | TimingLoad::Other(_) => vec![],
+        }
+    }
+
+    /// Does this timing load indicate the given rolling stock class?
+    pub fn indicates_class(&self, class: u16) -> bool {
+        self.classes().contains(&class)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum SeatingClass {
     FirstAndStandard,
     StandardOnly,
     NotSpecified,
+    /// A seating class not known to this library.
+    Unknown(char),
+}
+
+/// The connection indicator of a schedule. This field is reserved in the CIF specification, so
+/// any value given is kept as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+pub enum ConnectionIndicator {
+    Indicated(char),
+    NotSpecified,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum Sleepers {
     FirstAndStandard,
     FirstOnly,
     StandardOnly,
     NotSpecified,
+    /// A sleeper class not known to this library.
+    Unknown(char),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum Reservations {
     Compulsory,
     CompulsoryForBicycles,
     Recommended,
     Possible,
     NotSpecified,
+    /// A reservations code not known to this library.
+    Unknown(char),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
+#[non_exhaustive]
 pub enum Catering {
     NotSpecified,
     BuffetService,
@@ -782,10 +1968,18 @@ pub enum Catering {
     WheelchairReservations,
     Restaurant,
     TrolleyService,
+    /// A catering code not known to this library.
+    Unknown(char),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes, compare(PartialEq)),
+    archive_attr(derive(Debug, Clone, Copy, PartialEq, Eq))
+)]
 pub enum STPIndicator {
     NewSTPAssociation,
     STPCancellationOfPermanentAssociation,
@@ -795,9 +1989,24 @@ pub enum STPIndicator {
 
 #[derive(Debug, Clone, PartialEq, Eq, Getters)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
 pub struct JourneyLocation {
     #[getset(get = "pub")]
     tiploc: String,
+    /// The suffix distinguishing this visit to the TIPLOC from others by the same train, if
+    /// it visits more than once.
+    #[getset(get = "pub")]
+    suffix: Option<u8>,
+    /// The number of times the train has passed midnight since leaving its origin when it
+    /// reaches this location. A departure time earlier than the arrival time at the same
+    /// location is on the following day.
+    #[getset(get = "pub")]
+    day_offset: u8,
     #[getset(get = "pub")]
     arrival_time: Option<JourneyTime>,
     #[getset(get = "pub")]
@@ -809,15 +2018,39 @@ pub struct JourneyLocation {
     #[getset(get = "pub")]
     public_departure: Option<JourneyTime>,
     #[getset(get = "pub")]
-    platform: String,
+    platform: Platform,
+    #[getset(get = "pub")]
+    line: Line,
     #[getset(get = "pub")]
-    line: String,
+    path: Path,
+    /// The raw activity codes at this location.
     #[getset(get = "pub")]
     activity: String,
+    /// The activities at this location, parsed from the activity codes.
+    #[getset(get = "pub")]
+    activities: Vec<Activity>,
+    /// Time allowed for engineering works before this location.
+    #[getset(get = "pub")]
+    engineering_allowance: Option<Allowance>,
+    /// Time allowed to fit this train's path around others.
+    #[getset(get = "pub")]
+    pathing_allowance: Option<Allowance>,
+    /// Time allowed to help the train recover from delays.
+    #[getset(get = "pub")]
+    performance_allowance: Option<Allowance>,
+    /// Notes about this location, from location note records.
+    #[getset(get = "pub")]
+    notes: Vec<Note>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Getters)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
 pub struct JourneyTime {
     #[getset(get = "pub")]
     hour: u8,
@@ -827,6 +2060,101 @@ pub struct JourneyTime {
     half: bool,
 }
 
+/// Extra time allowed in a schedule, in half minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Getters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize),
+    archive(check_bytes),
+    archive_attr(derive(Getters), getset(get = "pub"))
+)]
+pub struct Allowance {
+    #[getset(get = "pub")]
+    minutes: u8,
+    #[getset(get = "pub")]
+    half: bool,
+}
+
+impl Allowance {
+    /// The length of this allowance in seconds.
+    pub fn seconds(&self) -> u32 {
+        self.minutes as u32 * 60 + if self.half { 30 } else { 0 }
+    }
+
+    /// Parse an allowance from a location record, which is blank if there is no allowance.
+    fn parse_optional(s: &str) -> Result<Option<Self>, ScheduleApplyError> {
+        if s.trim().is_empty() {
+            Ok(None)
+        } else {
+            s.parse().map(Some)
+        }
+    }
+}
+
+impl FromStr for Allowance {
+    type Err = ScheduleApplyError;
+
+    /// Parse an allowance such as `2`, `10`, `1H` or `H`, where `H` adds half a minute.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ScheduleApplyError::InvalidAllowance(s.to_string());
+        let trimmed = s.trim();
+        let (minutes, half) = match trimmed.strip_suffix('H') {
+            Some(minutes) => (minutes, true),
+            None => (trimmed, false),
+        };
+        let minutes = if minutes.is_empty() && half {
+            0
+        } else {
+            minutes.parse().map_err(|_| err())?
+        };
+        Ok(Self { minutes, half })
+    }
+}
+
+impl JourneyTime {
+    const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+    /// A time of day, with `half` adding half a minute.
+    pub fn new(hour: u8, minute: u8, half: bool) -> Self {
+        Self { hour, minute, half }
+    }
+
+    /// The number of seconds since midnight, with a half minute counting as thirty seconds.
+    pub fn to_seconds(&self) -> u32 {
+        self.hour as u32 * 60 * 60 + self.minute as u32 * 60 + if self.half { 30 } else { 0 }
+    }
+
+    /// The time of day, keeping a half minute as thirty seconds.
+    pub fn to_naive_time(&self) -> NaiveTime {
+        NaiveTime::from_num_seconds_from_midnight_opt(self.to_seconds() % Self::SECONDS_PER_DAY, 0)
+            .unwrap_or(NaiveTime::MIN)
+    }
+
+    /// The time from this time until a later one. If `other` is earlier in the day, it is
+    /// taken to be on the following day.
+    pub fn duration_until(&self, other: &JourneyTime) -> Duration {
+        let from = self.to_seconds();
+        let mut until = other.to_seconds();
+        if until < from {
+            until += Self::SECONDS_PER_DAY;
+        }
+        Duration::seconds((until - from) as i64)
+    }
+
+    /// This time moved on by a number of minutes, which may be negative, wrapping around
+    /// midnight. The half minute is kept.
+    pub fn add_minutes(&self, minutes: i32) -> JourneyTime {
+        let day = 24 * 60;
+        let total = (self.hour as i32 * 60 + self.minute as i32 + minutes).rem_euclid(day);
+        JourneyTime {
+            hour: (total / 60) as u8,
+            minute: (total % 60) as u8,
+            half: self.half,
+        }
+    }
+}
+
 impl FromStr for JourneyTime {
     type Err = ScheduleApplyError;
 
@@ -859,6 +2187,141 @@ impl FromStr for JourneyTime {
     }
 }
 
+/// Apply a TIPLOC insert, amend or delete record to a store. Other records are ignored.
+pub(crate) fn apply_tiploc_record<S: ScheduleSink + ?Sized>(
+    sink: &mut S,
+    record: &CIFRecord,
+    warnings: &mut Vec<(ApplyWarningCategory, String)>,
+    observer: &mut dyn ScheduleObserver,
+) {
+    match record {
+        CIFRecord::TIPLOCInsert {
+            tiploc,
+            nlc,
+            nlc_check_char,
+            tps_description,
+            stanox,
+            po_mcp_code,
+            three_alpha_code,
+            nlc_description,
+            ..
+        } => {
+            info!("New TIPLOC: {}", tiploc.trim());
+            let tiploc = TIPLOC {
+                tiploc: tiploc.trim().to_string(),
+                three_alpha_code: three_alpha_code.trim().to_string(),
+                description: tps_description.trim().to_string(),
+                stanox: *stanox,
+                nlc: *nlc,
+                nlc_check_char: *nlc_check_char,
+                nlc_description: nlc_description.trim().to_string(),
+                po_mcp_code: po_mcp_code.trim().to_string(),
+            };
+            let previous = sink.upsert_tiploc(tiploc.clone());
+            if previous.as_ref() != Some(&tiploc) {
+                observer.on_tiploc_changed(&tiploc.tiploc, previous.as_ref(), Some(&tiploc));
+            }
+        }
+        CIFRecord::TIPLOCAmend {
+            tiploc: code,
+            nlc,
+            nlc_check_char,
+            tps_description,
+            stanox,
+            po_mcp_code,
+            three_alpha_code,
+            nlc_description,
+            new_tiploc,
+            ..
+        } => {
+            let code = code.trim();
+            info!("Amendment for TIPLOC {code}");
+            let renamed = !new_tiploc.trim().is_empty();
+            let tiploc = TIPLOC {
+                tiploc: if renamed { new_tiploc.trim() } else { code }.to_string(),
+                three_alpha_code: three_alpha_code.trim().to_string(),
+                description: tps_description.trim().to_string(),
+                stanox: *stanox,
+                nlc: *nlc,
+                nlc_check_char: *nlc_check_char,
+                nlc_description: nlc_description.trim().to_string(),
+                po_mcp_code: po_mcp_code.trim().to_string(),
+            };
+            // a rename moves the TIPLOC to its new code
+            let removed = if renamed {
+                sink.delete_tiploc(code)
+            } else {
+                None
+            };
+            let mut previous = sink.upsert_tiploc(tiploc.clone());
+            let existed = removed.is_some() || (!renamed && previous.is_some());
+            if tiploc.tiploc == code {
+                previous = previous.or(removed);
+            } else if let Some(removed) = &removed {
+                observer.on_tiploc_changed(code, Some(removed), None);
+            }
+            if previous.as_ref() != Some(&tiploc) {
+                observer.on_tiploc_changed(&tiploc.tiploc, previous.as_ref(), Some(&tiploc));
+            }
+            if !existed {
+                warn!(
+                    "A record is trying to amend TIPLOC {code}, but it doesn't exist in the database. Inserting it as new..."
+                );
+                warnings.push((ApplyWarningCategory::AmendedUnknownTIPLOC, code.to_string()));
+            }
+        }
+        CIFRecord::TIPLOCDelete { tiploc } => {
+            info!("Removed TIPLOC {}", tiploc.trim());
+            match sink.delete_tiploc(tiploc.trim()) {
+                Some(previous) => observer.on_tiploc_changed(tiploc.trim(), Some(&previous), None),
+                None => {
+                    warn!(
+                        "A record is trying to delete TIPLOC {}, but it doesn't exist in the database.",
+                        tiploc.trim()
+                    );
+                    warnings.push((
+                        ApplyWarningCategory::DeletedUnknownTIPLOC,
+                        tiploc.trim().to_string(),
+                    ));
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Parse the extract date and time from a header record.
+pub(crate) fn extract_date_time(
+    date_of_extract: &str,
+    time_of_extract: &str,
+) -> Result<NaiveDateTime, ScheduleApplyError> {
+    let date = NaiveDate::parse_from_str(date_of_extract, "%d%m%y")
+        .map_err(|_| ScheduleApplyError::InvalidHeaderDateTime(date_of_extract.to_string()))?;
+    let time = NaiveTime::parse_from_str(time_of_extract, "%H%M")
+        .map_err(|_| ScheduleApplyError::InvalidHeaderDateTime(time_of_extract.to_string()))?;
+    Ok(date.and_time(time))
+}
+
+/// Parse a public time. A blank time is not advertised. If `ignore_placeholders` is set, a
+/// time of "0000" is also treated as not advertised, unless the working time shows the train
+/// really is there at midnight.
+fn public_time(
+    public: &str,
+    working: Option<JourneyTime>,
+    ignore_placeholders: bool,
+) -> Result<Option<JourneyTime>, ScheduleApplyError> {
+    if public.trim().is_empty() {
+        return Ok(None);
+    }
+    let time: JourneyTime = public.parse()?;
+    let at_midnight =
+        working.is_some_and(|w| (w.hour == 23 && w.minute == 59) || (w.hour == 0 && w.minute == 0));
+    if ignore_placeholders && public.trim() == "0000" && !at_midnight {
+        return Ok(None);
+    }
+    Ok(Some(time))
+}
+
 #[allow(clippy::too_many_arguments)]
 fn bs_record_to_schedule(
     schedule: &mut Schedule,
@@ -870,6 +2333,9 @@ fn bs_record_to_schedule(
     train_status: &char,
     train_category: &str,
     train_identity: &str,
+    headcode: &str,
+    course_indicator: &char,
+    train_service_code: &str,
     portion_id: &char,
     power_type: &str,
     timing_load: &str,
@@ -879,8 +2345,22 @@ fn bs_record_to_schedule(
     sleepers: &char,
     reservations: &char,
     catering_code: &str,
+    connection_indicator: &char,
+    service_branding: &str,
     stp_indicator: &char,
+    policy: UnknownCodePolicy,
+    warnings: &mut Vec<(ApplyWarningCategory, String)>,
 ) -> Result<(), ScheduleApplyError> {
+    // whether an unknown code can be kept as a fallback value
+    let mut fallback = |what: &str, code: &str| match policy {
+        UnknownCodePolicy::Error => false,
+        UnknownCodePolicy::Warn => {
+            warn!("Schedule {uid} has an unknown {what} {code:?}, keeping it anyway.");
+            warnings.push((ApplyWarningCategory::UnknownCode, uid.to_string()));
+            true
+        }
+        UnknownCodePolicy::Fallback => true,
+    };
     schedule.train_uid = uid.to_string();
     schedule.runs_from = NaiveDate::parse_from_str(date_runs_from, "%y%m%d")
         .map_err(|_| ScheduleApplyError::InvalidScheduleDate(date_runs_from.to_string()))?;
@@ -908,6 +2388,7 @@ fn bs_record_to_schedule(
         '3' => TrainStatus::STPTrip,
         '4' => TrainStatus::STPShip,
         '5' => TrainStatus::STPBus,
+        c if fallback("train status", &c.to_string()) => TrainStatus::Unknown(*c),
         _ => return Err(ScheduleApplyError::InvalidTrainStatus(*train_status)),
     };
     schedule.train_category = match train_category {
@@ -966,13 +2447,19 @@ fn bs_record_to_schedule(
         "H4" => TrainCategory::RfDEuropeanChannelTunnelContractServices,
         "H5" => TrainCategory::RfDEuropeanChannelTunnelHaulmark,
         "H6" => TrainCategory::RfDEuropeanChannelTunnelJointVenture,
+        other if fallback("train category", other) => {
+            TrainCategory::Other(other.trim().to_string())
+        }
         _ => {
             return Err(ScheduleApplyError::InvalidTrainCategory(
                 train_category.to_string(),
             ))
         }
     };
-    schedule.headcode = train_identity.trim().to_string();
+    schedule.train_identity = train_identity.trim().to_string();
+    schedule.nrs_headcode = headcode.trim().to_string();
+    schedule.course_indicator = *course_indicator;
+    schedule.train_service_code = train_service_code.trim().to_string();
     schedule.portion_id = *portion_id;
     schedule.power_type = match power_type.trim() {
         "" => PowerType::NotSpecified,
@@ -984,6 +2471,7 @@ fn bs_record_to_schedule(
         "EML" => PowerType::EMUPlusLocomotive,
         "EMU" => PowerType::ElectricMultipleUnit,
         "HST" => PowerType::HighSpeedTrain,
+        other if fallback("power type", other) => PowerType::Other(other.to_string()),
         _ => return Err(ScheduleApplyError::InvalidPowerType(power_type.to_string())),
     };
     schedule.timing_load = if schedule.power_type == PowerType::DieselMechanicalMultipleUnit
@@ -1005,6 +2493,8 @@ fn bs_record_to_schedule(
             _ => {
                 if let Ok(n) = timing_load.trim().parse::<u16>() {
                     TimingLoad::SpecificClass(n)
+                } else if fallback("timing load", timing_load.trim()) {
+                    TimingLoad::Other(timing_load.trim().to_string())
                 } else {
                     return Err(ScheduleApplyError::InvalidTimingLoad(
                         timing_load.to_string(),
@@ -1022,6 +2512,8 @@ fn bs_record_to_schedule(
             _ => {
                 if let Ok(n) = timing_load.trim().parse::<u16>() {
                     TimingLoad::SpecificClass(n)
+                } else if fallback("timing load", timing_load.trim()) {
+                    TimingLoad::Other(timing_load.trim().to_string())
                 } else {
                     return Err(ScheduleApplyError::InvalidTimingLoad(
                         timing_load.to_string(),
@@ -1039,11 +2531,15 @@ fn bs_record_to_schedule(
             TimingLoad::Class325ElectricParcelsUnit
         } else if let Ok(n) = timing_load.trim().parse::<u16>() {
             TimingLoad::LoadInTonnes(n)
+        } else if fallback("timing load", timing_load.trim()) {
+            TimingLoad::Other(timing_load.trim().to_string())
         } else {
             return Err(ScheduleApplyError::InvalidTimingLoad(
                 timing_load.to_string(),
             ));
         }
+    } else if matches!(schedule.power_type, PowerType::Other(_)) && !timing_load.trim().is_empty() {
+        TimingLoad::Other(timing_load.trim().to_string())
     } else {
         TimingLoad::NotSpecified
     };
@@ -1087,6 +2583,9 @@ fn bs_record_to_schedule(
                 .operating_characteristics
                 .push(OperatingCharacteristic::MayConveyTrafficToSB1CGauge),
             ' ' => (),
+            _ if fallback("operating characteristic", &c.to_string()) => schedule
+                .operating_characteristics
+                .push(OperatingCharacteristic::Unknown(c)),
             _ => return Err(ScheduleApplyError::InvalidOperatingCharacteristic(c)),
         };
     }
@@ -1094,6 +2593,7 @@ fn bs_record_to_schedule(
         ' ' => SeatingClass::FirstAndStandard,
         'B' => SeatingClass::FirstAndStandard,
         'S' => SeatingClass::StandardOnly,
+        c if fallback("seating class", &c.to_string()) => SeatingClass::Unknown(*c),
         _ => return Err(ScheduleApplyError::InvalidSeatingClass(*seating_class)),
     };
     schedule.sleepers = match sleepers {
@@ -1101,6 +2601,7 @@ fn bs_record_to_schedule(
         'F' => Sleepers::FirstOnly,
         'S' => Sleepers::StandardOnly,
         ' ' => Sleepers::NotSpecified,
+        c if fallback("sleepers code", &c.to_string()) => Sleepers::Unknown(*c),
         _ => return Err(ScheduleApplyError::InvalidSleepers(*sleepers)),
     };
     schedule.reservations = match reservations {
@@ -1109,6 +2610,7 @@ fn bs_record_to_schedule(
         'R' => Reservations::Recommended,
         'S' => Reservations::Possible,
         ' ' => Reservations::NotSpecified,
+        c if fallback("reservations code", &c.to_string()) => Reservations::Unknown(*c),
         _ => return Err(ScheduleApplyError::InvalidReservations(*reservations)),
     };
     for c in catering_code.chars() {
@@ -1121,15 +2623,29 @@ fn bs_record_to_schedule(
             'R' => schedule.catering.push(Catering::Restaurant),
             'T' => schedule.catering.push(Catering::TrolleyService),
             ' ' => (),
+            _ if fallback("catering code", &c.to_string()) => {
+                schedule.catering.push(Catering::Unknown(c))
+            }
             _ => return Err(ScheduleApplyError::InvalidCateringCode(c)),
         };
     }
-    schedule.stp_indicator = match stp_indicator {
-        'C' => STPIndicator::STPCancellationOfPermanentAssociation,
-        'N' => STPIndicator::NewSTPAssociation,
-        'O' => STPIndicator::STPOverlayOfPermanentAssociation,
-        'P' => STPIndicator::PermanentAssociation,
-        _ => return Err(ScheduleApplyError::InvalidSTPIndicator(*stp_indicator)),
+    schedule.connection_indicator = match connection_indicator {
+        ' ' => ConnectionIndicator::NotSpecified,
+        c => ConnectionIndicator::Indicated(*c),
     };
+    schedule.service_branding = service_branding.trim().to_string();
+    schedule.stp_indicator = parse_stp_indicator(stp_indicator)?;
     Ok(())
 }
+
+pub(crate) fn parse_stp_indicator(
+    stp_indicator: &char,
+) -> Result<STPIndicator, ScheduleApplyError> {
+    match stp_indicator {
+        'C' => Ok(STPIndicator::STPCancellationOfPermanentAssociation),
+        'N' => Ok(STPIndicator::NewSTPAssociation),
+        'O' => Ok(STPIndicator::STPOverlayOfPermanentAssociation),
+        'P' => Ok(STPIndicator::PermanentAssociation),
+        _ => Err(ScheduleApplyError::InvalidSTPIndicator(*stp_indicator)),
+    }
+}
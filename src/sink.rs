@@ -0,0 +1,276 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{
+    association::Association,
+    builder::{RecordKind, UnknownCodePolicy},
+    schedule::{apply_stream, ApplyReport, STPIndicator, Schedule, ScheduleDatabase, TIPLOC},
+    types::CIFRecord,
+};
+
+/// A store which decoded CIF records are applied to. [`ScheduleDatabase`] keeps everything
+/// in memory, but this can be implemented for other stores, such as a database server, to
+/// reuse the decoding of records with [`apply_records_to_sink`].
+pub trait ScheduleSink {
+    /// Called when a full extract is applied, before any of its records. Stores which are
+    /// replaced by full extracts should clear themselves here.
+    fn full_update(&mut self);
+
+    /// The extract date and time of the last file applied, used to reject older files.
+    fn last_extract_date_time(&self) -> NaiveDateTime;
+
+    /// Record the extract date and time, and reference, of the file being applied.
+    fn set_extract(&mut self, extract_date_time: NaiveDateTime, file_reference: String);
+
+    /// Insert or replace a TIPLOC, returning the TIPLOC it replaced.
+    fn upsert_tiploc(&mut self, tiploc: TIPLOC) -> Option<TIPLOC>;
+
+    /// Delete a TIPLOC, returning it if it existed.
+    fn delete_tiploc(&mut self, tiploc: &str) -> Option<TIPLOC>;
+
+    /// Insert an association, or replace the one with the same trains, start date, location
+    /// and STP indicator, returning the association it replaced.
+    fn upsert_association(&mut self, association: Association) -> Option<Association>;
+
+    /// Delete the association with the given trains, start date, location and STP indicator,
+    /// returning it if it existed.
+    fn delete_association(
+        &mut self,
+        main_train_uid: &str,
+        associated_train_uid: &str,
+        start_date: NaiveDate,
+        location: &str,
+        stp_indicator: STPIndicator,
+    ) -> Option<Association>;
+
+    /// Add a new schedule, after any others for the same train UID.
+    fn insert_schedule(&mut self, schedule: Schedule);
+
+    /// Replace the schedule with the same train UID, start date and STP indicator, returning
+    /// the schedule it replaced. If there is no such schedule, returns [`None`] without
+    /// storing the revision.
+    fn revise_schedule(&mut self, schedule: Schedule) -> Option<Schedule>;
+
+    /// Delete the schedule with the given train UID, start date and STP indicator, returning
+    /// it if it existed.
+    fn delete_schedule(
+        &mut self,
+        train_uid: &str,
+        runs_from: NaiveDate,
+        stp_indicator: STPIndicator,
+    ) -> Option<Schedule>;
+
+    /// Are there any schedules for the given train UID? Cancellations for trains with no
+    /// schedules are discarded.
+    fn has_schedules(&self, train_uid: &str) -> bool;
+
+    /// Whether public times of "0000" are treated as not advertised, unless the train is
+    /// actually timed at midnight.
+    fn ignores_placeholder_public_times(&self) -> bool {
+        true
+    }
+
+    /// What to do when a schedule has a code not known to this library, such as a new train
+    /// category.
+    fn unknown_code_policy(&self) -> UnknownCodePolicy {
+        UnknownCodePolicy::Fallback
+    }
+
+    /// Whether records of a kind are left out when applying records.
+    fn skips_record_kind(&self, _kind: RecordKind) -> bool {
+        false
+    }
+
+    /// Whether to panic on the first error when applying records.
+    fn panics_on_first_error(&self) -> bool {
+        cfg!(feature = "panic-on-first-error")
+    }
+
+    /// Whether a schedule being applied should be stored. Schedules which aren't kept are
+    /// dropped, and revise away the schedule they would have replaced.
+    fn keeps_schedule(&self, _schedule: &Schedule) -> bool {
+        true
+    }
+
+    /// Whether an association being applied should be stored. Associations which aren't
+    /// kept are dropped, and revise away the association they would have replaced.
+    fn keeps_association(&self, _association: &Association) -> bool {
+        true
+    }
+}
+
+/// Decode a list of records and apply them to a store.
+/// Returns the errors encountered and any warnings raised.
+pub fn apply_records_to_sink<S: ScheduleSink>(sink: &mut S, records: &[CIFRecord]) -> ApplyReport {
+    let total_records = Some(records.len());
+    let records = records.iter().map(Ok::<_, std::convert::Infallible>);
+    match apply_stream(sink, records, total_records, |_| (), &mut ()) {
+        Ok(report) => report,
+        Err(never) => match never {},
+    }
+}
+
+impl ScheduleSink for ScheduleDatabase {
+    fn full_update(&mut self) {
+        self.tiplocs_mut().clear();
+        self.stanox_index_mut().clear();
+        self.crs_index_mut().clear();
+        self.associations_mut().clear();
+        self.associated_index_mut().clear();
+        self.schedules_mut().clear();
+        self.uid_index_mut().clear();
+        self.rebuild_schedule_indexes();
+    }
+
+    fn last_extract_date_time(&self) -> NaiveDateTime {
+        *self.extract_date_time()
+    }
+
+    fn set_extract(&mut self, extract_date_time: NaiveDateTime, file_reference: String) {
+        *self.extract_date_time_mut() = extract_date_time;
+        *self.file_reference_mut() = Some(file_reference);
+    }
+
+    fn upsert_tiploc(&mut self, tiploc: TIPLOC) -> Option<TIPLOC> {
+        let previous = self.delete_tiploc(tiploc.tiploc());
+        self.index_tiploc(&tiploc);
+        self.tiplocs_mut().insert(tiploc.tiploc().clone(), tiploc);
+        previous
+    }
+
+    fn delete_tiploc(&mut self, tiploc: &str) -> Option<TIPLOC> {
+        let previous = self.tiplocs_mut().remove(tiploc)?;
+        self.unindex_tiploc(&previous);
+        Some(previous)
+    }
+
+    fn upsert_association(&mut self, association: Association) -> Option<Association> {
+        self.associated_index_mut()
+            .entry(association.associated_train_uid().clone())
+            .or_default()
+            .insert(association.main_train_uid().clone());
+        let associations = self
+            .associations_mut()
+            .entry(association.main_train_uid().clone())
+            .or_default();
+        match associations
+            .iter()
+            .position(|other| other.same_key(&association))
+        {
+            Some(idx) => Some(std::mem::replace(&mut associations[idx], association)),
+            None => {
+                associations.push(association);
+                None
+            }
+        }
+    }
+
+    fn delete_association(
+        &mut self,
+        main_train_uid: &str,
+        associated_train_uid: &str,
+        start_date: NaiveDate,
+        location: &str,
+        stp_indicator: STPIndicator,
+    ) -> Option<Association> {
+        let associations = self.associations_mut().get_mut(main_train_uid)?;
+        let idx = associations.iter().position(|association| {
+            association.has_key(
+                main_train_uid,
+                associated_train_uid,
+                start_date,
+                location,
+                stp_indicator,
+            )
+        })?;
+        let association = associations.remove(idx);
+        let still_associated = associations
+            .iter()
+            .any(|other| other.associated_train_uid() == associated_train_uid);
+        if associations.is_empty() {
+            self.associations_mut().remove(main_train_uid);
+        }
+        if !still_associated {
+            if let Some(mains) = self.associated_index_mut().get_mut(associated_train_uid) {
+                mains.remove(main_train_uid);
+                if mains.is_empty() {
+                    self.associated_index_mut().remove(associated_train_uid);
+                }
+            }
+        }
+        Some(association)
+    }
+
+    fn insert_schedule(&mut self, schedule: Schedule) {
+        self.index_schedule(&schedule);
+        let uid = schedule.train_uid().clone();
+        self.uid_index_mut().insert(uid.clone());
+        let schedules = self.schedules_mut().entry(uid.clone()).or_default();
+        schedules.push(schedule);
+        let idx = schedules.len() - 1;
+        self.index_calls(&uid, idx);
+    }
+
+    fn revise_schedule(&mut self, schedule: Schedule) -> Option<Schedule> {
+        let schedules = self.schedules().get(schedule.train_uid())?;
+        let idx = schedules.iter().position(|other| {
+            other.runs_from() == schedule.runs_from()
+                && other.stp_indicator() == schedule.stp_indicator()
+        })?;
+        self.index_schedule(&schedule);
+        let schedules = self.schedules_mut().get_mut(schedule.train_uid())?;
+        let previous = std::mem::replace(&mut schedules[idx], schedule);
+        self.unindex_schedule(&previous);
+        self.unindex_calls(idx, &previous, false);
+        self.index_calls(previous.train_uid(), idx);
+        Some(previous)
+    }
+
+    fn delete_schedule(
+        &mut self,
+        train_uid: &str,
+        runs_from: NaiveDate,
+        stp_indicator: STPIndicator,
+    ) -> Option<Schedule> {
+        let schedules = self.schedules_mut().get_mut(train_uid)?;
+        let idx = schedules.iter().position(|schedule| {
+            *schedule.runs_from() == runs_from && *schedule.stp_indicator() == stp_indicator
+        })?;
+        let schedule = schedules.remove(idx);
+        if schedules.is_empty() {
+            self.schedules_mut().remove(train_uid);
+            self.uid_index_mut().remove(train_uid);
+        }
+        self.unindex_schedule(&schedule);
+        self.unindex_calls(idx, &schedule, true);
+        Some(schedule)
+    }
+
+    fn has_schedules(&self, train_uid: &str) -> bool {
+        self.schedules().contains_key(train_uid)
+    }
+
+    fn ignores_placeholder_public_times(&self) -> bool {
+        *self.ignore_placeholder_public_times()
+    }
+
+    fn unknown_code_policy(&self) -> UnknownCodePolicy {
+        *self.unknown_code_policy()
+    }
+
+    fn skips_record_kind(&self, kind: RecordKind) -> bool {
+        self.skip_record_kinds().contains(&kind)
+    }
+
+    fn panics_on_first_error(&self) -> bool {
+        *self.panic_on_first_error()
+    }
+
+    fn keeps_schedule(&self, schedule: &Schedule) -> bool {
+        self.in_retention_window(*schedule.runs_from(), *schedule.runs_to())
+            && self.schedule_filter().keeps(schedule, self)
+    }
+
+    fn keeps_association(&self, association: &Association) -> bool {
+        self.in_retention_window(*association.start_date(), *association.end_date())
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use chrono::NaiveDateTime;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    association::Association,
+    schedule::{Schedule, ScheduleDatabase, TIPLOC},
+};
+
+/// The bytes every snapshot starts with.
+const MAGIC: &[u8; 8] = b"NRCIFDB\0";
+
+/// The version of the snapshot layout written by this version of the crate. This must be
+/// increased whenever the layout of [`Snapshot`] or any type within it changes.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// What is kept in a snapshot. Settings, such as the schedule filter, and the indexes are
+/// left out; the indexes are rebuilt when the snapshot is loaded.
+#[derive(Deserialize)]
+struct Snapshot {
+    extract_date_time: NaiveDateTime,
+    file_reference: Option<String>,
+    tiplocs: HashMap<String, TIPLOC>,
+    schedules: HashMap<String, Vec<Schedule>>,
+    associations: HashMap<String, Vec<Association>>,
+}
+
+/// A [`Snapshot`] borrowed from a database, for writing it without copying the data.
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    extract_date_time: &'a NaiveDateTime,
+    file_reference: &'a Option<String>,
+    tiplocs: &'a HashMap<String, TIPLOC>,
+    schedules: &'a HashMap<String, Vec<Schedule>>,
+    associations: &'a HashMap<String, Vec<Association>>,
+}
+
+/// An error saving or loading a snapshot.
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("failed to read or write snapshot")]
+    Io(#[from] io::Error),
+    #[error("the data is not a schedule database snapshot")]
+    NotASnapshot,
+    #[error("snapshot version {0} is not supported, this version of the crate reads version {SNAPSHOT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("failed to encode or decode snapshot")]
+    Encoding(#[from] bincode::Error),
+}
+
+impl ScheduleDatabase {
+    /// Write a binary snapshot of the data in this database, which can be loaded again with
+    /// [`ScheduleDatabase::read_snapshot`] much faster than applying the CIF files again.
+    /// Settings, such as the schedule filter and bank holiday calendar, aren't saved.
+    pub fn write_snapshot<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        let snapshot = SnapshotRef {
+            extract_date_time: self.extract_date_time(),
+            file_reference: self.file_reference(),
+            tiplocs: self.tiplocs(),
+            schedules: self.schedules(),
+            associations: self.associations(),
+        };
+        bincode::serialize_into(&mut writer, &snapshot)?;
+        Ok(())
+    }
+
+    /// Read a binary snapshot written by [`ScheduleDatabase::write_snapshot`]. The settings of
+    /// the database returned are the defaults. Snapshots written with a different layout
+    /// version return [`SnapshotError::UnsupportedVersion`], and should be rebuilt from the
+    /// CIF files.
+    pub fn read_snapshot<R: Read>(mut reader: R) -> Result<Self, SnapshotError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        debug!("Reading snapshot version {version}");
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let snapshot: Snapshot = bincode::deserialize_from(reader)?;
+        let mut database = ScheduleDatabase::new();
+        *database.extract_date_time_mut() = snapshot.extract_date_time;
+        *database.file_reference_mut() = snapshot.file_reference;
+        *database.tiplocs_mut() = snapshot.tiplocs;
+        *database.schedules_mut() = snapshot.schedules;
+        *database.associations_mut() = snapshot.associations;
+        database.rebuild_associated_index();
+        database.rebuild_tiploc_indexes();
+        database.rebuild_schedule_indexes();
+        Ok(database)
+    }
+}
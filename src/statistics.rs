@@ -0,0 +1,105 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use getset::{CopyGetters, Getters};
+
+use crate::types::{CIFFile, CIFRecord};
+
+/// A summary of the contents of a CIF file, for checking a download looks sensible.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters, CopyGetters)]
+pub struct CIFStatistics {
+    /// The number of records of each kind, keyed by record type, such as "BS".
+    #[getset(get = "pub")]
+    record_counts: BTreeMap<&'static str, usize>,
+    /// The total number of records.
+    #[getset(get_copy = "pub")]
+    total_records: usize,
+    /// The mainframe identity from the header, if there is one.
+    #[getset(get = "pub")]
+    file_mainframe_identity: Option<String>,
+    /// The date and time of the extract from the header, if it is present and valid.
+    #[getset(get_copy = "pub")]
+    extract_date_time: Option<NaiveDateTime>,
+    /// The current file reference from the header, if there is one.
+    #[getset(get = "pub")]
+    current_file_reference: Option<String>,
+    /// The last file reference from the header, if there is one.
+    #[getset(get = "pub")]
+    last_file_reference: Option<String>,
+    /// The earliest date any schedule runs from.
+    #[getset(get_copy = "pub")]
+    first_schedule_date: Option<NaiveDate>,
+    /// The latest date any schedule runs to.
+    #[getset(get_copy = "pub")]
+    last_schedule_date: Option<NaiveDate>,
+    /// The number of distinct train UIDs with schedule records.
+    #[getset(get_copy = "pub")]
+    distinct_train_uids: usize,
+}
+
+impl CIFRecord {
+    /// The two character code identifying the kind of this record, such as "BS".
+    pub fn record_type(&self) -> &'static str {
+        match self {
+            CIFRecord::Header { .. } => "HD",
+            CIFRecord::TIPLOCInsert { .. } => "TI",
+            CIFRecord::TIPLOCAmend { .. } => "TA",
+            CIFRecord::TIPLOCDelete { .. } => "TD",
+            CIFRecord::Association { .. } => "AA",
+            CIFRecord::BasicSchedule { .. } => "BS",
+            CIFRecord::BasicScheduleExtended { .. } => "BX",
+            CIFRecord::LocationOrigin { .. } => "LO",
+            CIFRecord::LocationIntermediate { .. } => "LI",
+            CIFRecord::ChangeEnRoute { .. } => "CR",
+            CIFRecord::LocationTerminate { .. } => "LT",
+            CIFRecord::TrainNote { .. } => "TN",
+            CIFRecord::LocationNote { .. } => "LN",
+            CIFRecord::Trailer => "ZZ",
+        }
+    }
+}
+
+impl CIFFile {
+    /// Summarise the contents of this file. Schedule dates which can't be parsed, such as
+    /// the blank end date of a deletion, are ignored.
+    pub fn statistics(&self) -> CIFStatistics {
+        let mut stats = CIFStatistics {
+            total_records: self.records().len(),
+            file_mainframe_identity: self.records().first().and_then(|record| match record {
+                CIFRecord::Header {
+                    file_mainframe_identity,
+                    ..
+                } => Some(file_mainframe_identity.trim().to_string()),
+                _ => None,
+            }),
+            extract_date_time: self.extract_date_time(),
+            current_file_reference: self.current_file_reference().map(str::to_string),
+            last_file_reference: self.last_file_reference().map(str::to_string),
+            ..Default::default()
+        };
+        let mut train_uids = HashSet::new();
+        for record in self.records() {
+            *stats.record_counts.entry(record.record_type()).or_default() += 1;
+            if let CIFRecord::BasicSchedule {
+                train_uid,
+                date_runs_from,
+                date_runs_to,
+                ..
+            } = record
+            {
+                train_uids.insert(train_uid.as_str());
+                let date = |date: &str| NaiveDate::parse_from_str(date, "%y%m%d").ok();
+                if let Some(from) = date(date_runs_from) {
+                    stats.first_schedule_date =
+                        Some(stats.first_schedule_date.map_or(from, |d| d.min(from)));
+                }
+                if let Some(to) = date(date_runs_to) {
+                    stats.last_schedule_date =
+                        Some(stats.last_schedule_date.map_or(to, |d| d.max(to)));
+                }
+            }
+        }
+        stats.distinct_train_uids = train_uids.len();
+        stats
+    }
+}
@@ -0,0 +1,342 @@
+//! Helpers for generating synthetic CIF data to test against, without needing a real extract.
+//!
+//! ```
+//! use chrono::NaiveDate;
+//! use nr_cif::{prelude::*, test_util::ScenarioBuilder};
+//!
+//! let from = NaiveDate::from_ymd_opt(2024, 5, 20).unwrap();
+//! let to = NaiveDate::from_ymd_opt(2024, 12, 13).unwrap();
+//! let file = ScenarioBuilder::new(from, to)
+//!     .location("WATRLMN", "WAT")
+//!     .location("WOKING", "WOK")
+//!     .schedules(4)
+//!     .cancellation(0, NaiveDate::from_ymd_opt(2024, 6, 4).unwrap())
+//!     .build();
+//!
+//! let mut db = ScheduleDatabase::new();
+//! assert!(db.apply_file(&file).is_empty());
+//! assert_eq!(db.schedules().len(), 4);
+//! ```
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::types::{CIFFile, CIFRecord};
+
+/// Build a CIF file describing a scenario: a number of permanent schedules running between a
+/// list of locations, optionally with short term overlays and cancellations.
+///
+/// Each schedule calls at every location in order. The first schedule departs at
+/// [`ScenarioBuilder::first_departure`], and each subsequent one follows after
+/// [`ScenarioBuilder::headway`].
+#[derive(Debug, Clone)]
+pub struct ScenarioBuilder {
+    extract: NaiveDateTime,
+    runs_from: NaiveDate,
+    runs_to: NaiveDate,
+    days_run: String,
+    atoc_code: String,
+    locations: Vec<(String, String)>,
+    count: usize,
+    first_departure: NaiveTime,
+    headway: Duration,
+    run_time: Duration,
+    dwell: Duration,
+    overlays: Vec<(usize, NaiveDate)>,
+    cancellations: Vec<(usize, NaiveDate)>,
+}
+
+impl ScenarioBuilder {
+    /// Create a new scenario, where permanent schedules run between the given dates on
+    /// weekdays.
+    pub fn new(runs_from: NaiveDate, runs_to: NaiveDate) -> Self {
+        Self {
+            extract: runs_from.and_time(NaiveTime::MIN),
+            runs_from,
+            runs_to,
+            days_run: "1111100".to_string(),
+            atoc_code: "ZZ".to_string(),
+            locations: vec![],
+            count: 1,
+            first_departure: NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            headway: Duration::minutes(30),
+            run_time: Duration::minutes(10),
+            dwell: Duration::minutes(1),
+            overlays: vec![],
+            cancellations: vec![],
+        }
+    }
+
+    /// The train UID of the schedule at `index`.
+    pub fn uid(index: usize) -> String {
+        format!("Z{:05}", index + 1)
+    }
+
+    /// Set the extract date and time in the header record.
+    pub fn extract_date_time(mut self, extract: NaiveDateTime) -> Self {
+        self.extract = extract;
+        self
+    }
+
+    /// Set the days the permanent schedules run on, as a CIF days run string starting on
+    /// Monday, e.g. `"1111100"`.
+    pub fn days_run<S: Into<String>>(mut self, days_run: S) -> Self {
+        self.days_run = days_run.into();
+        self
+    }
+
+    /// Set the ATOC code of the operator of every schedule.
+    pub fn atoc_code<S: Into<String>>(mut self, atoc_code: S) -> Self {
+        self.atoc_code = atoc_code.into();
+        self
+    }
+
+    /// Add a location which each schedule calls at, in order. The CRS can be empty.
+    pub fn location<S: Into<String>, T: Into<String>>(mut self, tiploc: S, crs: T) -> Self {
+        self.locations.push((tiploc.into(), crs.into()));
+        self
+    }
+
+    /// Set the number of permanent schedules to generate.
+    pub fn schedules(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Set the departure time of the first schedule.
+    pub fn first_departure(mut self, time: NaiveTime) -> Self {
+        self.first_departure = time;
+        self
+    }
+
+    /// Set the time between each schedule departing.
+    pub fn headway(mut self, headway: Duration) -> Self {
+        self.headway = headway;
+        self
+    }
+
+    /// Set the time taken to run between each pair of locations.
+    pub fn run_time(mut self, run_time: Duration) -> Self {
+        self.run_time = run_time;
+        self
+    }
+
+    /// Set the time spent at each intermediate location.
+    pub fn dwell(mut self, dwell: Duration) -> Self {
+        self.dwell = dwell;
+        self
+    }
+
+    /// Add an overlay of the schedule at `index` on a single date, running five minutes later
+    /// than the permanent schedule.
+    pub fn overlay(mut self, index: usize, date: NaiveDate) -> Self {
+        self.overlays.push((index, date));
+        self
+    }
+
+    /// Cancel the schedule at `index` on a single date.
+    pub fn cancellation(mut self, index: usize, date: NaiveDate) -> Self {
+        self.cancellations.push((index, date));
+        self
+    }
+
+    /// Build the scenario into a full extract.
+    pub fn build(self) -> CIFFile {
+        let mut file = CIFFile::new();
+        let records = file.records_mut();
+
+        records.push(CIFRecord::Header {
+            file_mainframe_identity: "TPS.UDFROC1.PD000000".to_string(),
+            date_of_extract: self.extract.format("%d%m%y").to_string(),
+            time_of_extract: self.extract.format("%H%M").to_string(),
+            current_file_reference: "SYNTH0A".to_string(),
+            last_file_reference: "       ".to_string(),
+            update_indicator: 'F',
+            version: 'B',
+            user_start_date: self.runs_from.format("%d%m%y").to_string(),
+            user_end_date: self.runs_to.format("%d%m%y").to_string(),
+        });
+
+        for (idx, (tiploc, crs)) in self.locations.iter().enumerate() {
+            records.push(CIFRecord::TIPLOCInsert {
+                tiploc: format!("{tiploc:<7}"),
+                capitals_identification: 0,
+                nlc: idx as u32 + 1,
+                nlc_check_char: 'A',
+                tps_description: format!("{tiploc:<26}"),
+                stanox: idx as u32 + 1,
+                po_mcp_code: "0000".to_string(),
+                three_alpha_code: format!("{crs:<3}"),
+                nlc_description: format!("{tiploc:<16}"),
+            });
+        }
+
+        for index in 0..self.count {
+            let departure = self.first_departure + self.headway * index as i32;
+            self.push_schedule(
+                records,
+                index,
+                self.runs_from,
+                self.runs_to,
+                &self.days_run,
+                'P',
+                departure,
+            );
+        }
+        for (index, date) in &self.overlays {
+            let departure =
+                self.first_departure + self.headway * *index as i32 + Duration::minutes(5);
+            self.push_schedule(
+                records,
+                *index,
+                *date,
+                *date,
+                &days_run_for(*date),
+                'O',
+                departure,
+            );
+        }
+        for (index, date) in &self.cancellations {
+            records.push(basic_schedule(
+                *index,
+                *date,
+                *date,
+                &days_run_for(*date),
+                'C',
+            ));
+        }
+
+        records.push(CIFRecord::Trailer);
+        file
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push_schedule(
+        &self,
+        records: &mut Vec<CIFRecord>,
+        index: usize,
+        runs_from: NaiveDate,
+        runs_to: NaiveDate,
+        days_run: &str,
+        stp_indicator: char,
+        departure: NaiveTime,
+    ) {
+        records.push(basic_schedule(
+            index,
+            runs_from,
+            runs_to,
+            days_run,
+            stp_indicator,
+        ));
+        records.push(CIFRecord::BasicScheduleExtended {
+            traction_class: "    ".to_string(),
+            uic_code: "     ".to_string(),
+            atoc_code: self.atoc_code.clone(),
+            applicable_timetable_code: 'Y',
+        });
+
+        let last = self.locations.len().saturating_sub(1);
+        let mut time = departure;
+        for (idx, (tiploc, _)) in self.locations.iter().enumerate() {
+            let location = format!("{tiploc:<8}");
+            if idx == 0 {
+                records.push(CIFRecord::LocationOrigin {
+                    location,
+                    scheduled_departure_time: working(time),
+                    public_departure_time: public(time),
+                    platform: "   ".to_string(),
+                    line: "   ".to_string(),
+                    engineering_allowance: "  ".to_string(),
+                    pathing_allowance: "  ".to_string(),
+                    activity: format!("{:<12}", "TB"),
+                    performance_allowance: "  ".to_string(),
+                });
+            } else if idx == last {
+                time += self.run_time;
+                records.push(CIFRecord::LocationTerminate {
+                    location,
+                    scheduled_arrival_time: working(time),
+                    public_arrival_time: public(time),
+                    platform: "   ".to_string(),
+                    path: "   ".to_string(),
+                    activity: format!("{:<12}", "TF"),
+                });
+            } else {
+                time += self.run_time;
+                let arrival = time;
+                time += self.dwell;
+                records.push(CIFRecord::LocationIntermediate {
+                    location,
+                    scheduled_arrival_time: working(arrival),
+                    scheduled_departure_time: working(time),
+                    scheduled_pass: "     ".to_string(),
+                    public_arrival_time: public(arrival),
+                    public_departure_time: public(time),
+                    platform: "   ".to_string(),
+                    line: "   ".to_string(),
+                    path: "   ".to_string(),
+                    activity: format!("{:<12}", "T"),
+                    engineering_allowance: "  ".to_string(),
+                    pathing_allowance: "  ".to_string(),
+                    performance_allowance: "  ".to_string(),
+                });
+            }
+        }
+    }
+}
+
+fn basic_schedule(
+    index: usize,
+    runs_from: NaiveDate,
+    runs_to: NaiveDate,
+    days_run: &str,
+    stp_indicator: char,
+) -> CIFRecord {
+    let cancellation = stp_indicator == 'C';
+    let blank_unless = |s: &str| {
+        if cancellation {
+            " ".repeat(s.len())
+        } else {
+            s.to_string()
+        }
+    };
+    CIFRecord::BasicSchedule {
+        transaction_type: 'N',
+        train_uid: ScenarioBuilder::uid(index),
+        date_runs_from: runs_from.format("%y%m%d").to_string(),
+        date_runs_to: runs_to.format("%y%m%d").to_string(),
+        days_run: days_run.to_string(),
+        bank_holiday_running: ' ',
+        train_status: if cancellation { ' ' } else { 'P' },
+        train_category: blank_unless("OO"),
+        train_identity: blank_unless(&format!("2Z{:02}", (index + 1) % 100)),
+        headcode: "    ".to_string(),
+        course_indicator: '1',
+        train_service_code: blank_unless("99999999"),
+        portion_id: ' ',
+        power_type: blank_unless("EMU"),
+        timing_load: "    ".to_string(),
+        speed: blank_unless("100"),
+        operating_characteristics: "      ".to_string(),
+        seating_class: if cancellation { ' ' } else { 'S' },
+        sleepers: ' ',
+        reservations: ' ',
+        connection_indicator: ' ',
+        catering_code: "    ".to_string(),
+        service_branding: "    ".to_string(),
+        stp_indicator,
+    }
+}
+
+/// A days run string for a single date.
+fn days_run_for(date: NaiveDate) -> String {
+    let day = date.weekday().num_days_from_monday() as usize;
+    (0..7).map(|d| if d == day { '1' } else { '0' }).collect()
+}
+
+fn working(time: NaiveTime) -> String {
+    time.format("%H%M ").to_string()
+}
+
+fn public(time: NaiveTime) -> String {
+    time.format("%H%M").to_string()
+}
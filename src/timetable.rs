@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, fmt};
+
+use chrono::{NaiveDate, NaiveTime};
+use getset::{CopyGetters, Getters};
+
+use crate::{
+    board::{BoardOrder, BoardSortKey},
+    schedule::ScheduleDatabase,
+};
+
+/// A timetable of departures from a station on a date, grouped by destination, as would be
+/// printed on a poster at the station.
+///
+/// The [`fmt::Display`] implementation renders the timetable as plain text.
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct StationTimetable {
+    /// The location the timetable was requested for.
+    #[getset(get = "pub")]
+    location: String,
+    /// The date of the timetable.
+    #[getset(get_copy = "pub")]
+    date: NaiveDate,
+    /// The departures, grouped by destination and ordered by destination name.
+    #[getset(get = "pub")]
+    groups: Vec<StationTimetableGroup>,
+}
+
+/// The departures from a station towards a single destination.
+#[derive(Debug, Clone, Getters)]
+pub struct StationTimetableGroup {
+    /// The TIPLOC of the destination.
+    #[getset(get = "pub")]
+    destination: String,
+    /// The name of the destination.
+    #[getset(get = "pub")]
+    destination_name: String,
+    /// The departures to this destination, in the order requested.
+    #[getset(get = "pub")]
+    departures: Vec<StationTimetableEntry>,
+}
+
+/// A single departure in a [`StationTimetable`].
+#[derive(Debug, Clone, Getters, CopyGetters)]
+pub struct StationTimetableEntry {
+    /// The public departure time.
+    #[getset(get_copy = "pub")]
+    time: NaiveTime,
+    /// The working departure time.
+    #[getset(get_copy = "pub")]
+    working_time: NaiveTime,
+    /// The platform, or an empty string if it isn't known.
+    #[getset(get = "pub")]
+    platform: String,
+    /// The ATOC code of the operator.
+    #[getset(get = "pub")]
+    atoc_code: String,
+    /// The headcode of the train, being its train identity.
+    #[getset(get = "pub")]
+    headcode: String,
+    /// The train UID.
+    #[getset(get = "pub")]
+    train_uid: String,
+}
+
+impl ScheduleDatabase {
+    /// Build a timetable of the public departures from a location (a TIPLOC or CRS code) on a
+    /// date, grouped by the destination of each train. Departures to each destination are
+    /// ordered by public time, then by train UID.
+    pub fn station_timetable<S: AsRef<str>>(
+        &self,
+        location: S,
+        date: NaiveDate,
+    ) -> StationTimetable {
+        self.station_timetable_ordered(location, date, BoardOrder::default())
+    }
+
+    /// Build a timetable of the public departures from a location, as with
+    /// [`ScheduleDatabase::station_timetable`], with the departures to each destination
+    /// listed in the given order.
+    pub fn station_timetable_ordered<S: AsRef<str>>(
+        &self,
+        location: S,
+        date: NaiveDate,
+        order: BoardOrder,
+    ) -> StationTimetable {
+        let tiplocs = self.resolve_location(location.as_ref());
+        let mut groups: BTreeMap<String, Vec<StationTimetableEntry>> = BTreeMap::new();
+
+        for schedule in self.schedules_running_on(date) {
+            let journey = schedule.journey();
+            let Some(destination) = journey.last() else {
+                continue;
+            };
+            for loc in &journey[..journey.len() - 1] {
+                if !tiplocs.contains(loc.tiploc()) || !loc.picks_up() {
+                    continue;
+                }
+                let (Some(time), Some(working_time)) =
+                    (loc.public_departure(), loc.departure_time())
+                else {
+                    continue;
+                };
+                groups
+                    .entry(destination.tiploc().clone())
+                    .or_default()
+                    .push(StationTimetableEntry {
+                        time: time.to_naive_time(),
+                        working_time: working_time.to_naive_time(),
+                        platform: loc.platform().to_string(),
+                        atoc_code: schedule.atoc_code().clone(),
+                        headcode: schedule.train_identity().clone(),
+                        train_uid: schedule.train_uid().clone(),
+                    });
+            }
+        }
+
+        let mut groups: Vec<StationTimetableGroup> = groups
+            .into_iter()
+            .map(|(destination, mut departures)| {
+                departures.sort_by(|a, b| order.compare(&a.sort_key(), &b.sort_key()));
+                StationTimetableGroup {
+                    destination_name: self.location_name(&destination),
+                    destination,
+                    departures,
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| a.destination_name.cmp(&b.destination_name));
+
+        StationTimetable {
+            location: location.as_ref().trim().to_string(),
+            date,
+            groups,
+        }
+    }
+}
+
+impl StationTimetableEntry {
+    fn sort_key(&self) -> BoardSortKey<'_, NaiveTime> {
+        BoardSortKey {
+            public: Some(self.time),
+            working: self.working_time,
+            platform: &self.platform,
+            headcode: &self.headcode,
+            train_uid: &self.train_uid,
+        }
+    }
+}
+
+impl fmt::Display for StationTimetable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Departures from {} on {}", self.location, self.date)?;
+        for group in &self.groups {
+            write!(f, "\n\nTo {}", group.destination_name)?;
+            for departure in &group.departures {
+                write!(
+                    f,
+                    "\n  {}  {:>4}  {:<2}  {}",
+                    departure.time.format("%H:%M"),
+                    departure.platform,
+                    departure.atoc_code,
+                    departure.train_uid
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
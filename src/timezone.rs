@@ -0,0 +1,33 @@
+use chrono::{DateTime, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::{Europe::London, Tz};
+
+use crate::schedule::JourneyTime;
+
+/// Place a date and time from a schedule, which is UK local time, in the UK time zone.
+///
+/// On the night the clocks go back, times between 01:00 and 02:00 happen twice, and are
+/// taken to be the first of these, in British Summer Time. On the night the clocks go
+/// forward, times between 01:00 and 02:00 never happen on the clock, and are taken to be in
+/// Greenwich Mean Time, as they would be read before the change.
+pub fn uk_datetime(local: NaiveDateTime) -> DateTime<Tz> {
+    match London.from_local_datetime(&local) {
+        LocalResult::Single(time) => time,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => London.from_utc_datetime(&local),
+    }
+}
+
+impl JourneyTime {
+    /// This time on a date, in the UK time zone. The date is the day the time falls on,
+    /// which is the running date of the train moved on by the location's
+    /// [`day_offset`](crate::schedule::JourneyLocation::day_offset). See [`uk_datetime`] for how
+    /// the nights the clocks change are handled.
+    pub fn to_uk_datetime(&self, date: NaiveDate) -> DateTime<Tz> {
+        uk_datetime(date.and_time(self.to_naive_time()))
+    }
+
+    /// This time on a date, as with [`JourneyTime::to_uk_datetime`], as an instant in UTC.
+    pub fn to_utc(&self, date: NaiveDate) -> DateTime<Utc> {
+        self.to_uk_datetime(date).with_timezone(&Utc)
+    }
+}
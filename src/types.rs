@@ -1,315 +1,325 @@
+use std::ops::RangeInclusive;
+
+use chrono::{NaiveDate, NaiveDateTime};
 use fixedlength_format_parser::FixedLengthFormatParser;
-use getset::{Getters, MutGetters};
+use getset::{CopyGetters, Getters, MutGetters};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::extract_date_time;
 
 #[derive(Debug, Clone, Getters, MutGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CIFFile {
     #[getset(get = "pub", get_mut = "pub(crate)")]
     records: Vec<CIFRecord>,
+    /// Lines which couldn't be parsed and were skipped, when parsing leniently.
+    #[getset(get = "pub", get_mut = "pub(crate)")]
+    warnings: Vec<CIFParseWarning>,
 }
 
 impl CIFFile {
     pub(crate) fn new() -> Self {
-        Self { records: vec![] }
+        Self {
+            records: vec![],
+            warnings: vec![],
+        }
     }
-}
-
-#[derive(Debug, Clone, FixedLengthFormatParser)]
-pub enum CIFRecord {
-    #[record_type = "HD"]
-    Header {
-        #[field_starts = 2]
-        #[field_length = 20]
-        file_mainframe_identity: String,
-        #[field_length = 6]
-        date_of_extract: String,
-        #[field_length = 4]
-        time_of_extract: String,
-        #[field_length = 7]
-        current_file_reference: String,
-        #[field_length = 7]
-        last_file_reference: String,
-        #[field_length = 1]
-        update_indicator: char,
-        #[field_length = 1]
-        version: char,
-        #[field_length = 6]
-        user_start_date: String,
-        #[field_length = 6]
-        user_end_date: String,
-    },
 
-    #[record_type = "TI"]
-    TIPLOCInsert {
-        #[field_starts = 2]
-        #[field_length = 7]
-        tiploc: String,
-        #[field_length = 2]
-        capitals_identification: u8,
-        #[field_length = 6]
-        nlc: u32,
-        #[field_length = 1]
-        nlc_check_char: char,
-        #[field_length = 26]
-        tps_description: String,
-        #[field_length = 5]
-        stanox: u32,
-        #[field_length = 4]
-        po_mcp_code: String,
-        #[field_length = 3]
-        three_alpha_code: String,
-        #[field_length = 16]
-        nlc_description: String,
-    },
+    /// The header record, if the file starts with one.
+    fn header(&self) -> Option<&CIFRecord> {
+        self.records
+            .first()
+            .filter(|record| matches!(record, CIFRecord::Header { .. }))
+    }
 
-    #[record_type = "TA"]
-    TIPLOCAmend {
-        #[field_starts = 2]
-        #[field_length = 7]
-        tiploc: String,
-        #[field_length = 2]
-        capitals_identification: u8,
-        #[field_length = 6]
-        nlc: u32,
-        #[field_length = 1]
-        nlc_check_char: char,
-        #[field_length = 26]
-        tps_description: String,
-        #[field_length = 5]
-        stanox: u32,
-        #[field_length = 4]
-        po_mcp_code: String,
-        #[field_length = 3]
-        three_alpha_code: String,
-        #[field_length = 16]
-        nlc_description: String,
-        #[field_length = 7]
-        new_tiploc: String,
-    },
+    /// The date and time the file was extracted, from the header.
+    pub fn extract_date_time(&self) -> Option<NaiveDateTime> {
+        match self.header()? {
+            CIFRecord::Header {
+                date_of_extract,
+                time_of_extract,
+                ..
+            } => extract_date_time(date_of_extract, time_of_extract).ok(),
+            _ => None,
+        }
+    }
 
-    #[record_type = "TD"]
-    TIPLOCDelete {
-        #[field_starts = 2]
-        #[field_length = 7]
-        tiploc: String,
-    },
+    /// Whether this is a full extract or an update, from the header.
+    pub fn update_indicator(&self) -> Option<UpdateIndicator> {
+        match self.header()? {
+            CIFRecord::Header {
+                update_indicator: 'F',
+                ..
+            } => Some(UpdateIndicator::Full),
+            CIFRecord::Header {
+                update_indicator: 'U',
+                ..
+            } => Some(UpdateIndicator::Update),
+            _ => None,
+        }
+    }
 
-    #[record_type = "AA"]
-    Association {
-        #[field_starts = 2]
-        #[field_length = 1]
-        transaction_type: char,
-        #[field_length = 6]
-        main_train_uid: String,
-        #[field_length = 6]
-        associated_train_uid: String,
-        #[field_length = 6]
-        association_start_date: String,
-        #[field_length = 6]
-        association_end_date: String,
-        #[field_length = 7]
-        association_days: String,
-        #[field_length = 2]
-        association_category: String,
-        #[field_length = 1]
-        association_date_indicator: char,
-        #[field_length = 7]
-        association_location: String,
-        #[field_length = 1]
-        base_location_suffix: String,
-        #[field_length = 1]
-        association_location_suffix: String,
-        #[field_length = 1]
-        diagram_type: char,
-        #[field_length = 1]
-        association_type: char,
-        #[field_starts = 79]
-        #[field_length = 1]
-        stp_indicator: char,
-    },
+    /// The reference of this file, from the header.
+    pub fn current_file_reference(&self) -> Option<&str> {
+        match self.header()? {
+            CIFRecord::Header {
+                current_file_reference,
+                ..
+            } => Some(current_file_reference.trim()),
+            _ => None,
+        }
+    }
 
-    #[record_type = "BS"]
-    BasicSchedule {
-        #[field_starts = 2]
-        #[field_length = 1]
-        transaction_type: char,
-        #[field_length = 6]
-        train_uid: String,
-        #[field_length = 6]
-        date_runs_from: String,
-        #[field_length = 6]
-        date_runs_to: String,
-        #[field_length = 7]
-        days_run: String,
-        #[field_length = 1]
-        bank_holiday_running: char,
-        #[field_length = 1]
-        train_status: char,
-        #[field_length = 2]
-        train_category: String,
-        #[field_length = 4]
-        train_identity: String,
-        #[field_length = 4]
-        headcode: String,
-        #[field_length = 1]
-        course_indicator: char,
-        #[field_length = 8]
-        train_service_code: String,
-        #[field_length = 1]
-        portion_id: char,
-        #[field_length = 3]
-        power_type: String,
-        #[field_length = 4]
-        timing_load: String,
-        #[field_length = 3]
-        speed: String,
-        #[field_length = 6]
-        operating_characteristics: String,
-        #[field_length = 1]
-        seating_class: char,
-        #[field_length = 1]
-        sleepers: char,
-        #[field_length = 1]
-        reservations: char,
-        #[field_length = 1]
-        connection_indicator: char,
-        #[field_length = 4]
-        catering_code: String,
-        #[field_length = 4]
-        service_branding: String,
-        #[field_starts = 79]
-        #[field_length = 1]
-        stp_indicator: char,
-    },
+    /// The reference of the file this one follows on from, from the header.
+    pub fn last_file_reference(&self) -> Option<&str> {
+        match self.header()? {
+            CIFRecord::Header {
+                last_file_reference,
+                ..
+            } => Some(last_file_reference.trim()),
+            _ => None,
+        }
+    }
 
-    #[record_type = "BX"]
-    BasicScheduleExtended {
-        #[field_starts = 2]
-        #[field_length = 4]
-        traction_class: String,
-        #[field_length = 5]
-        uic_code: String,
-        #[field_length = 2]
-        atoc_code: String,
-        #[field_length = 1]
-        applicable_timetable_code: char,
-    },
+    /// The range of dates the user requested the extract to cover, from the header.
+    pub fn user_date_range(&self) -> Option<RangeInclusive<NaiveDate>> {
+        match self.header()? {
+            CIFRecord::Header {
+                user_start_date,
+                user_end_date,
+                ..
+            } => {
+                let start = NaiveDate::parse_from_str(user_start_date, "%d%m%y").ok()?;
+                let end = NaiveDate::parse_from_str(user_end_date, "%d%m%y").ok()?;
+                Some(start..=end)
+            }
+            _ => None,
+        }
+    }
+}
 
-    #[record_type = "LO"]
-    LocationOrigin {
-        #[field_starts = 2]
-        #[field_length = 8]
-        location: String,
-        #[field_length = 5]
-        scheduled_departure_time: String,
-        #[field_length = 4]
-        public_departure_time: String,
-        #[field_length = 3]
-        platform: String,
-        #[field_length = 3]
-        line: String,
-        #[field_length = 2]
-        engineering_allowance: String,
-        #[field_length = 2]
-        pathing_allowance: String,
-        #[field_length = 12]
-        activity: String,
-        #[field_length = 2]
-        performance_allowance: String,
-    },
+/// Whether a CIF file is a full extract of the timetable or an update to a previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum UpdateIndicator {
+    Full,
+    Update,
+}
 
-    #[record_type = "LI"]
-    LocationIntermediate {
-        #[field_starts = 2]
-        #[field_length = 8]
-        location: String,
-        #[field_length = 5]
-        scheduled_arrival_time: String,
-        #[field_length = 5]
-        scheduled_departure_time: String,
-        #[field_length = 5]
-        scheduled_pass: String,
-        #[field_length = 4]
-        public_arrival_time: String,
-        #[field_length = 4]
-        public_departure_time: String,
-        #[field_length = 3]
-        platform: String,
-        #[field_length = 3]
-        line: String,
-        #[field_length = 3]
-        path: String,
-        #[field_length = 12]
-        activity: String,
-        #[field_length = 2]
-        engineering_allowance: String,
-        #[field_length = 2]
-        pathing_allowance: String,
-        #[field_length = 2]
-        performance_allowance: String,
-    },
+/// A line which couldn't be parsed, and was skipped.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CIFParseWarning {
+    /// The line number in the file.
+    #[getset(get_copy = "pub")]
+    line: usize,
+    /// The content of the line.
+    #[getset(get = "pub")]
+    raw: String,
+    /// Why the line couldn't be parsed.
+    #[getset(get = "pub")]
+    message: String,
+}
 
-    #[record_type = "CR"]
-    ChangeEnRoute {
-        #[field_starts = 2]
-        #[field_length = 8]
-        location: String,
-        #[field_length = 2]
-        train_category: String,
-        #[field_length = 4]
-        train_identity: String,
-        #[field_length = 4]
-        headcode: String,
-        #[field_length = 1]
-        course_indicator: char,
-        #[field_length = 8]
-        profit_centre_code: String,
-        #[field_length = 1]
-        business_sector: char,
-        #[field_length = 3]
-        power_type: String,
-        #[field_length = 4]
-        timing_load: String,
-        #[field_length = 3]
-        speed: String,
-        #[field_length = 6]
-        operating_chars: String,
-        #[field_length = 1]
-        train_class: char,
-        #[field_length = 1]
-        sleepers: char,
-        #[field_length = 1]
-        reservations: char,
-        #[field_length = 1]
-        connect_indicator: char,
-        #[field_length = 4]
-        catering_code: String,
-        #[field_length = 4]
-        service_branding: String,
-        #[field_length = 4]
-        traction_class: String,
-        #[field_length = 5]
-        uic_code: String,
-        #[field_length = 8]
-        retail_train_id: String,
-    },
+impl CIFParseWarning {
+    pub(crate) fn new(line: usize, raw: String, message: String) -> Self {
+        Self { line, raw, message }
+    }
+}
 
-    #[record_type = "LT"]
-    LocationTerminate {
-        #[field_starts = 2]
-        #[field_length = 8]
-        location: String,
-        #[field_length = 5]
-        scheduled_arrival_time: String,
-        #[field_length = 4]
-        public_arrival_time: String,
-        #[field_length = 3]
-        platform: String,
-        #[field_length = 3]
-        path: String,
-        #[field_length = 12]
-        activity: String,
-    },
+/// The layout of each record type: the fields, their types when parsed, and their lengths.
+/// A field starts where the one before it ends, unless its start is given with `@`. Both
+/// [`CIFRecord`] and [`crate::prelude::CIFRecordRef`] are generated from this, by passing
+/// it to the macro named.
+macro_rules! cif_record_layout {
+    ($callback:ident) => {
+        $callback! {
+            Header = "HD" {
+                file_mainframe_identity @ 2: String = 20,
+                date_of_extract: String = 6,
+                time_of_extract: String = 4,
+                current_file_reference: String = 7,
+                last_file_reference: String = 7,
+                update_indicator: char = 1,
+                version: char = 1,
+                user_start_date: String = 6,
+                user_end_date: String = 6,
+            },
+            TIPLOCInsert = "TI" {
+                tiploc @ 2: String = 7,
+                capitals_identification: u8 = 2,
+                nlc: u32 = 6,
+                nlc_check_char: char = 1,
+                tps_description: String = 26,
+                stanox: u32 = 5,
+                po_mcp_code: String = 4,
+                three_alpha_code: String = 3,
+                nlc_description: String = 16,
+            },
+            TIPLOCAmend = "TA" {
+                tiploc @ 2: String = 7,
+                capitals_identification: u8 = 2,
+                nlc: u32 = 6,
+                nlc_check_char: char = 1,
+                tps_description: String = 26,
+                stanox: u32 = 5,
+                po_mcp_code: String = 4,
+                three_alpha_code: String = 3,
+                nlc_description: String = 16,
+                new_tiploc: String = 7,
+            },
+            TIPLOCDelete = "TD" {
+                tiploc @ 2: String = 7,
+            },
+            Association = "AA" {
+                transaction_type @ 2: char = 1,
+                main_train_uid: String = 6,
+                associated_train_uid: String = 6,
+                association_start_date: String = 6,
+                association_end_date: String = 6,
+                association_days: String = 7,
+                association_category: String = 2,
+                association_date_indicator: char = 1,
+                association_location: String = 7,
+                base_location_suffix: String = 1,
+                association_location_suffix: String = 1,
+                diagram_type: char = 1,
+                association_type: char = 1,
+                stp_indicator @ 79: char = 1,
+            },
+            BasicSchedule = "BS" {
+                transaction_type @ 2: char = 1,
+                train_uid: String = 6,
+                date_runs_from: String = 6,
+                date_runs_to: String = 6,
+                days_run: String = 7,
+                bank_holiday_running: char = 1,
+                train_status: char = 1,
+                train_category: String = 2,
+                train_identity: String = 4,
+                headcode: String = 4,
+                course_indicator: char = 1,
+                train_service_code: String = 8,
+                portion_id: char = 1,
+                power_type: String = 3,
+                timing_load: String = 4,
+                speed: String = 3,
+                operating_characteristics: String = 6,
+                seating_class: char = 1,
+                sleepers: char = 1,
+                reservations: char = 1,
+                connection_indicator: char = 1,
+                catering_code: String = 4,
+                service_branding: String = 4,
+                stp_indicator @ 79: char = 1,
+            },
+            BasicScheduleExtended = "BX" {
+                traction_class @ 2: String = 4,
+                uic_code: String = 5,
+                atoc_code: String = 2,
+                applicable_timetable_code: char = 1,
+            },
+            LocationOrigin = "LO" {
+                location @ 2: String = 8,
+                scheduled_departure_time: String = 5,
+                public_departure_time: String = 4,
+                platform: String = 3,
+                line: String = 3,
+                engineering_allowance: String = 2,
+                pathing_allowance: String = 2,
+                activity: String = 12,
+                performance_allowance: String = 2,
+            },
+            LocationIntermediate = "LI" {
+                location @ 2: String = 8,
+                scheduled_arrival_time: String = 5,
+                scheduled_departure_time: String = 5,
+                scheduled_pass: String = 5,
+                public_arrival_time: String = 4,
+                public_departure_time: String = 4,
+                platform: String = 3,
+                line: String = 3,
+                path: String = 3,
+                activity: String = 12,
+                engineering_allowance: String = 2,
+                pathing_allowance: String = 2,
+                performance_allowance: String = 2,
+            },
+            ChangeEnRoute = "CR" {
+                location @ 2: String = 8,
+                train_category: String = 2,
+                train_identity: String = 4,
+                headcode: String = 4,
+                course_indicator: char = 1,
+                profit_centre_code: String = 8,
+                business_sector: char = 1,
+                power_type: String = 3,
+                timing_load: String = 4,
+                speed: String = 3,
+                operating_chars: String = 6,
+                train_class: char = 1,
+                sleepers: char = 1,
+                reservations: char = 1,
+                connect_indicator: char = 1,
+                catering_code: String = 4,
+                service_branding: String = 4,
+                traction_class: String = 4,
+                uic_code: String = 5,
+                retail_train_id: String = 8,
+            },
+            LocationTerminate = "LT" {
+                location @ 2: String = 8,
+                scheduled_arrival_time: String = 5,
+                public_arrival_time: String = 4,
+                platform: String = 3,
+                path: String = 3,
+                activity: String = 12,
+            },
+            TrainNote = "TN" {
+                note_type @ 2: char = 1,
+                note: String = 77,
+            },
+            LocationNote = "LN" {
+                note_type @ 2: char = 1,
+                note: String = 77,
+            },
+            ;
+            Trailer = "ZZ",
+        }
+    };
+}
+pub(crate) use cif_record_layout;
 
-    #[record_type = "ZZ"]
-    Trailer,
+macro_rules! owned_records {
+    (
+        $(
+            $variant:ident = $record_type:tt {
+                $($field:ident $(@ $start:tt)? : $ty:tt = $len:tt,)*
+            },
+        )*
+        ;
+        $($unit:ident = $unit_type:tt,)*
+    ) => {
+        #[derive(Debug, Clone, FixedLengthFormatParser)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub enum CIFRecord {
+            $(
+                #[record_type = $record_type]
+                $variant {
+                    $(
+                        $(#[field_starts = $start])?
+                        #[field_length = $len]
+                        $field: $ty,
+                    )*
+                },
+            )*
+            $(
+                #[record_type = $unit_type]
+                $unit,
+            )*
+        }
+    };
 }
+
+cif_record_layout!(owned_records);
@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+};
+
+use crate::{
+    schedule::{Schedule, ScheduleDatabase},
+    types::{CIFFile, CIFRecord},
+    writer::TransactionType,
+};
+
+impl ScheduleDatabase {
+    /// Build a CIF update file which, applied to this database, brings it up to date with
+    /// `newer`. TIPLOCs are inserted, amended or deleted, then associations and schedules are
+    /// added, revised or deleted, with schedules identified by their train UID, start date and
+    /// STP indicator. The header carries the extract date and file reference of `newer`, and
+    /// follows on from the file reference of this database.
+    pub fn update_to(&self, newer: &ScheduleDatabase) -> CIFFile {
+        let mut file = CIFFile::new();
+        let records = file.records_mut();
+
+        let extract = newer.extract_date_time();
+        let current_reference = newer.file_reference().clone().unwrap_or_default();
+        let last_reference = self.file_reference().clone().unwrap_or_default();
+        let user = current_reference.get(..6).unwrap_or("DFROC1");
+        let user_end_date = newer
+            .schedules()
+            .values()
+            .flatten()
+            .map(|schedule| *schedule.runs_to())
+            .max()
+            .unwrap_or(extract.date());
+        records.push(CIFRecord::Header {
+            file_mainframe_identity: format!("TPS.U{user}.PD{}", extract.format("%y%m%d")),
+            date_of_extract: extract.format("%d%m%y").to_string(),
+            time_of_extract: extract.format("%H%M").to_string(),
+            current_file_reference: current_reference,
+            last_file_reference: last_reference,
+            update_indicator: 'U',
+            version: 'B',
+            user_start_date: extract.format("%d%m%y").to_string(),
+            user_end_date: user_end_date.format("%d%m%y").to_string(),
+        });
+
+        let codes: BTreeSet<&String> = self
+            .tiplocs()
+            .keys()
+            .chain(newer.tiplocs().keys())
+            .collect();
+        for code in codes {
+            match (self.tiplocs().get(code), newer.tiplocs().get(code)) {
+                (None, Some(tiploc)) => records.push(tiploc.to_insert_record()),
+                (Some(old), Some(tiploc)) if old != tiploc => {
+                    records.push(tiploc.to_amend_record())
+                }
+                (Some(_), None) => records.push(CIFRecord::TIPLOCDelete {
+                    tiploc: code.clone(),
+                }),
+                _ => (),
+            }
+        }
+
+        let mains: BTreeSet<&String> = self
+            .associations()
+            .keys()
+            .chain(newer.associations().keys())
+            .collect();
+        for main in mains {
+            let old = self.associations_by_main_uid(main);
+            let new = newer.associations_by_main_uid(main);
+            for association in old {
+                if !new.iter().any(|other| other.same_key(association)) {
+                    records.push(association.to_record(TransactionType::Delete));
+                }
+            }
+            for association in new {
+                match old.iter().find(|other| other.same_key(association)) {
+                    None => records.push(association.to_record(TransactionType::New)),
+                    Some(previous) if previous != association => {
+                        records.push(association.to_record(TransactionType::Revise))
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+
+        let uids: BTreeSet<&String> = self
+            .schedules()
+            .keys()
+            .chain(newer.schedules().keys())
+            .collect();
+        let no_schedules = vec![];
+        for uid in uids {
+            let old = self.schedules().get(uid).unwrap_or(&no_schedules);
+            let new = newer.schedules().get(uid).unwrap_or(&no_schedules);
+            let same_key = |a: &Schedule, b: &Schedule| {
+                a.runs_from() == b.runs_from() && a.stp_indicator() == b.stp_indicator()
+            };
+            for schedule in old {
+                if !new.iter().any(|other| same_key(other, schedule)) {
+                    records.extend(schedule.to_records(TransactionType::Delete));
+                }
+            }
+            for schedule in new {
+                match old.iter().find(|other| same_key(other, schedule)) {
+                    None => records.extend(schedule.to_records(TransactionType::New)),
+                    Some(previous) if previous != schedule => {
+                        records.extend(schedule.to_records(TransactionType::Revise))
+                    }
+                    Some(_) => (),
+                }
+            }
+        }
+
+        records.push(CIFRecord::Trailer);
+        file
+    }
+
+    /// Write a CIF update file bringing this database up to date with `newer`, as built by
+    /// [`ScheduleDatabase::update_to`].
+    pub fn write_update_to<W: Write>(
+        &self,
+        newer: &ScheduleDatabase,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        self.update_to(newer).write_to(writer)
+    }
+}
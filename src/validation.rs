@@ -0,0 +1,191 @@
+use chrono::{Duration, NaiveDate};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{STPIndicator, Schedule, ScheduleDatabase};
+
+/// The longest a train is taken to wait, or run, from one time in its journey to the next.
+/// A time earlier than the one before it is only taken to be after midnight if the gap
+/// across midnight is shorter than this.
+const MAXIMUM_OVERNIGHT_GAP: Duration = Duration::hours(12);
+
+/// A problem with a single schedule, found by [`Schedule::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub enum ScheduleIssue {
+    /// A time at the location with this index in the journey is earlier than the time
+    /// before it, and not just because the train has run past midnight.
+    TimesNotIncreasing { position: usize },
+    /// The train departs its origin at the same time it arrives at its terminus.
+    ZeroLengthJourney,
+    /// The journey doesn't start with an origin, having no locations or no departure time
+    /// from the first.
+    MissingOrigin,
+    /// The journey doesn't end with a terminus, having no locations or no arrival time at the
+    /// last.
+    MissingTerminus,
+    /// The location with this index in the journey has a passing time, but also an arrival
+    /// or departure time as if the train stops there.
+    PassAtStop { position: usize },
+    /// The location with this index in the journey is at a TIPLOC not in the database.
+    UnknownTIPLOC { position: usize },
+}
+
+/// A problem in the schedules of a database, found by [`ScheduleDatabase::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ValidationIssue {
+    /// Two schedules for a train UID with the same STP indicator both run on a date, so
+    /// which applies is unclear. The schedules are given by their index among those for the
+    /// train UID, and the date is the first they both run on.
+    OverlappingSchedules {
+        train_uid: String,
+        stp_indicator: STPIndicator,
+        first_index: usize,
+        second_index: usize,
+        date: NaiveDate,
+    },
+    /// A problem with the schedule at an index among those for a train UID.
+    Schedule {
+        train_uid: String,
+        schedule_index: usize,
+        issue: ScheduleIssue,
+    },
+}
+
+impl Schedule {
+    /// Check this schedule for problems which mean it shouldn't be relied on, such as times
+    /// which go backwards or a journey without an origin. Short term planning cancellations
+    /// have no journey, so have no problems.
+    ///
+    /// Whether the TIPLOCs in the journey are known can only be checked against a database,
+    /// with [`ScheduleDatabase::validate_schedule`].
+    pub fn validate(&self) -> Vec<ScheduleIssue> {
+        if *self.stp_indicator() == STPIndicator::STPCancellationOfPermanentAssociation {
+            return vec![];
+        }
+        let mut issues = self.time_issues();
+        if self
+            .origin()
+            .is_none_or(|origin| origin.departure_time().is_none())
+        {
+            issues.push(ScheduleIssue::MissingOrigin);
+        }
+        if self
+            .destination()
+            .is_none_or(|terminus| terminus.arrival_time().is_none())
+        {
+            issues.push(ScheduleIssue::MissingTerminus);
+        }
+        if self.duration() == Some(Duration::zero()) {
+            issues.push(ScheduleIssue::ZeroLengthJourney);
+        }
+        issues.extend(
+            self.journey()
+                .iter()
+                .enumerate()
+                .filter(|(_, location)| {
+                    location.passing_time().is_some()
+                        && (location.arrival_time().is_some()
+                            || location.departure_time().is_some())
+                })
+                .map(|(position, _)| ScheduleIssue::PassAtStop { position }),
+        );
+        issues
+    }
+
+    /// The problems with the times in this schedule's journey.
+    fn time_issues(&self) -> Vec<ScheduleIssue> {
+        let mut issues = vec![];
+        let mut last = None;
+        for (position, location) in self.journey().iter().enumerate() {
+            let times = [
+                location.arrival_time(),
+                location.passing_time(),
+                location.departure_time(),
+            ];
+            let mut increasing = true;
+            for time in times.into_iter().flatten() {
+                if let Some(last) = last {
+                    if *time < last && last.duration_until(time) >= MAXIMUM_OVERNIGHT_GAP {
+                        increasing = false;
+                    }
+                }
+                last = Some(*time);
+            }
+            if !increasing {
+                issues.push(ScheduleIssue::TimesNotIncreasing { position });
+            }
+        }
+        issues
+    }
+
+    /// The first date on which both this schedule and another run.
+    fn first_common_date(&self, other: &Schedule) -> Option<NaiveDate> {
+        let start = *self.runs_from().max(other.runs_from());
+        let end = *self.runs_to().min(other.runs_to());
+        start
+            .iter_days()
+            .take_while(|date| *date <= end)
+            .take(7)
+            .find(|date| self.runs_on(*date) && other.runs_on(*date))
+    }
+}
+
+impl ScheduleDatabase {
+    /// Check a schedule for problems, as with [`Schedule::validate`], and also for locations
+    /// at TIPLOCs which aren't in this database.
+    pub fn validate_schedule(&self, schedule: &Schedule) -> Vec<ScheduleIssue> {
+        let mut issues = schedule.validate();
+        issues.extend(
+            schedule
+                .journey()
+                .iter()
+                .enumerate()
+                .filter(|(_, location)| !self.tiplocs().contains_key(location.tiploc()))
+                .map(|(position, _)| ScheduleIssue::UnknownTIPLOC { position }),
+        );
+        issues
+    }
+
+    /// Check the schedules for problems in the data. This finds schedules for the same train
+    /// UID and STP indicator which run on the same date, along with the problems with each
+    /// schedule found by [`ScheduleDatabase::validate_schedule`].
+    ///
+    /// The issues are ordered by train UID.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
+        for train_uid in self.uid_index() {
+            let Some(schedules) = self.schedules().get(train_uid) else {
+                continue;
+            };
+            for (first_index, first) in schedules.iter().enumerate() {
+                for (second_index, second) in schedules.iter().enumerate().skip(first_index + 1) {
+                    if first.stp_indicator() != second.stp_indicator() {
+                        continue;
+                    }
+                    if let Some(date) = first.first_common_date(second) {
+                        issues.push(ValidationIssue::OverlappingSchedules {
+                            train_uid: train_uid.clone(),
+                            stp_indicator: *first.stp_indicator(),
+                            first_index,
+                            second_index,
+                            date,
+                        });
+                    }
+                }
+            }
+            for (schedule_index, schedule) in schedules.iter().enumerate() {
+                issues.extend(self.validate_schedule(schedule).into_iter().map(|issue| {
+                    ValidationIssue::Schedule {
+                        train_uid: train_uid.clone(),
+                        schedule_index,
+                        issue,
+                    }
+                }));
+            }
+        }
+        issues
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{self, Read},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use getset::Getters;
+use log::{info, warn};
+use thiserror::Error;
+
+use crate::{
+    changeset::ChangeSet,
+    parser::{parse_cif, CIFParseError},
+    schedule::{ApplyWarning, ScheduleApplyError, ScheduleDatabase},
+    types::CIFRecord,
+};
+
+/// Somewhere CIF update files arrive, such as a directory or an object store bucket.
+pub trait UpdateSource {
+    /// List the names of the files currently available. Files are applied in the order of
+    /// their names.
+    fn list(&self) -> io::Result<Vec<String>>;
+
+    /// Open a file by name.
+    fn open(&self, name: &str) -> io::Result<Box<dyn Read>>;
+}
+
+/// An [`UpdateSource`] reading files from a directory on disk.
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    path: PathBuf,
+}
+
+impl DirectorySource {
+    /// Watch the files in a directory.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl UpdateSource for DirectorySource {
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        Ok(names)
+    }
+
+    fn open(&self, name: &str) -> io::Result<Box<dyn Read>> {
+        Ok(Box::new(File::open(self.path.join(name))?))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum WatchError {
+    #[error("failed to list update files")]
+    List(#[from] io::Error),
+}
+
+/// Something which happened while watching for update files.
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// An update file was applied to the database, making the changes listed.
+    Applied {
+        name: String,
+        extract_date_time: NaiveDateTime,
+        errors: Vec<(usize, ScheduleApplyError)>,
+        warnings: Vec<ApplyWarning>,
+        changes: ChangeSet,
+    },
+    /// An update file wasn't the next in sequence, so was not applied. It is checked again
+    /// on the next poll.
+    OutOfSequence {
+        name: String,
+        expected: String,
+        found: String,
+    },
+    /// An update file couldn't be read, so was not applied. It is read again on the next
+    /// poll, in case it was still being written.
+    Unreadable { name: String, error: CIFParseError },
+    /// An update file didn't start with a header record, so was not applied.
+    MissingHeader { name: String },
+}
+
+/// Watches an [`UpdateSource`] for new CIF update files, and applies them in sequence to a
+/// shared database.
+///
+/// Each file's header must name the previously applied file as its last file reference. A
+/// file which can't be read or is out of sequence is tried again on every poll, until it can
+/// be applied or is skipped with [`Watcher::skip`]. Other files are only considered once.
+#[derive(Getters)]
+pub struct Watcher<S: UpdateSource> {
+    source: S,
+    /// The database updates are applied to.
+    #[getset(get = "pub")]
+    database: Arc<RwLock<ScheduleDatabase>>,
+    /// The names of the files which have already been applied, or won't be.
+    #[getset(get = "pub")]
+    seen: BTreeSet<String>,
+    /// The file reference of the last file applied.
+    #[getset(get = "pub")]
+    last_file_reference: Option<String>,
+}
+
+impl<S: UpdateSource> Watcher<S> {
+    /// Create a new [`Watcher`], applying updates to `database`. If `last_file_reference` is
+    /// given, the first file applied must follow it.
+    pub fn new(
+        source: S,
+        database: Arc<RwLock<ScheduleDatabase>>,
+        last_file_reference: Option<String>,
+    ) -> Self {
+        Self {
+            source,
+            database,
+            seen: BTreeSet::new(),
+            last_file_reference,
+        }
+    }
+
+    /// Mark files as already considered, so they won't be applied, for example because they
+    /// were loaded into the database before the watcher was created.
+    pub fn skip<I: IntoIterator<Item = String>>(&mut self, names: I) {
+        self.seen.extend(names);
+    }
+
+    /// Check the source once for new files, and apply them.
+    pub fn poll(&mut self) -> Result<Vec<WatchEvent>, WatchError> {
+        let mut names = self.source.list()?;
+        names.sort();
+
+        let mut events = vec![];
+        for name in names {
+            if self.seen.contains(&name) {
+                continue;
+            }
+            info!("New update file {name}");
+            let file = match self.source.open(&name) {
+                Ok(reader) => parse_cif(reader),
+                Err(e) => Err(CIFParseError::Read(e)),
+            };
+            let file = match file {
+                Ok(file) => file,
+                Err(error) => {
+                    warn!("Failed to read update file {name}: {error}");
+                    events.push(WatchEvent::Unreadable { name, error });
+                    continue;
+                }
+            };
+
+            let Some(CIFRecord::Header {
+                current_file_reference,
+                last_file_reference,
+                ..
+            }) = file.records().first()
+            else {
+                warn!("Update file {name} doesn't start with a header");
+                self.seen.insert(name.clone());
+                events.push(WatchEvent::MissingHeader { name });
+                continue;
+            };
+            if let Some(expected) = &self.last_file_reference {
+                if expected.trim() != last_file_reference.trim() {
+                    warn!("Update file {name} is out of sequence");
+                    events.push(WatchEvent::OutOfSequence {
+                        name,
+                        expected: expected.trim().to_string(),
+                        found: last_file_reference.trim().to_string(),
+                    });
+                    continue;
+                }
+            }
+
+            let mut database = self.database.write().unwrap_or_else(|e| e.into_inner());
+            let (report, changes) = database.apply_file_with_changes(&file);
+            let (errors, warnings) = report.into_parts();
+            self.last_file_reference = Some(current_file_reference.trim().to_string());
+            self.seen.insert(name.clone());
+            events.push(WatchEvent::Applied {
+                name,
+                extract_date_time: *database.extract_date_time(),
+                errors,
+                warnings,
+                changes,
+            });
+        }
+        Ok(events)
+    }
+
+    /// Poll the source forever, waiting `interval` between each check, and passing every
+    /// event to `on_event`. This only returns if listing the source fails.
+    pub fn run<F: FnMut(WatchEvent)>(
+        &mut self,
+        interval: Duration,
+        mut on_event: F,
+    ) -> Result<(), WatchError> {
+        loop {
+            for event in self.poll()? {
+                on_event(event);
+            }
+            thread::sleep(interval);
+        }
+    }
+}
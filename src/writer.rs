@@ -0,0 +1,914 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use chrono::NaiveDate;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    association::{Association, AssociationCategory, AssociationDateIndicator, AssociationType},
+    schedule::{
+        Allowance, BankHolidayRunning, Catering, ConnectionIndicator, DaysRun, JourneyLocation,
+        JourneyTime, Note, OperatingCharacteristic, PowerType, Reservations, STPIndicator,
+        Schedule, SeatingClass, Sleepers, TimingLoad, TrainCategory, TrainStatus, TIPLOC,
+    },
+    types::{CIFFile, CIFRecord},
+};
+
+/// What a schedule or association record does to the timetable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TransactionType {
+    /// A new schedule or association.
+    New,
+    /// A revision replacing an existing schedule or association.
+    Revise,
+    /// The deletion of an existing schedule or association.
+    Delete,
+}
+
+impl TransactionType {
+    /// The code for this in a basic schedule or association record.
+    pub fn code(&self) -> char {
+        match self {
+            TransactionType::New => 'N',
+            TransactionType::Revise => 'R',
+            TransactionType::Delete => 'D',
+        }
+    }
+}
+
+impl BankHolidayRunning {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            BankHolidayRunning::RunsNormally => ' ',
+            BankHolidayRunning::NotOnSpecificBankHolidayMondays => 'X',
+            BankHolidayRunning::NotOnGlasgowBankHolidays => 'G',
+        }
+    }
+}
+
+impl TrainStatus {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            TrainStatus::NotSpecified => ' ',
+            TrainStatus::Bus => 'B',
+            TrainStatus::Freight => 'F',
+            TrainStatus::PassengerAndParcels => 'P',
+            TrainStatus::Ship => 'S',
+            TrainStatus::Trip => 'T',
+            TrainStatus::STPPassengerAndParcels => '1',
+            TrainStatus::STPFreight => '2',
+            TrainStatus::STPTrip => '3',
+            TrainStatus::STPShip => '4',
+            TrainStatus::STPBus => '5',
+            TrainStatus::Unknown(c) => *c,
+        }
+    }
+}
+
+impl TrainCategory {
+    /// The code for this in a basic schedule record, such as `OO`.
+    pub fn code(&self) -> &str {
+        match self {
+            TrainCategory::NotSpecified => "  ",
+            TrainCategory::LondonUnderground => "OL",
+            TrainCategory::UnadvertisedOrdinaryPassenger => "OU",
+            TrainCategory::OrdinaryPassenger => "OO",
+            TrainCategory::StaffTrain => "OS",
+            TrainCategory::Mixed => "OW",
+            TrainCategory::ChannelTunnel => "XC",
+            TrainCategory::Sleeper => "XD",
+            TrainCategory::International => "XI",
+            TrainCategory::Motorail => "XR",
+            TrainCategory::UnadvertisedExpress => "XU",
+            TrainCategory::ExpressPassenger => "XX",
+            TrainCategory::SleeperDomestic => "XZ",
+            TrainCategory::BusReplacementDueToEngineering => "BR",
+            TrainCategory::BusWTTService => "BS",
+            TrainCategory::Ship => "SS",
+            TrainCategory::EmptyCoachingStock => "EE",
+            TrainCategory::ECSLondonUnderground => "EL",
+            TrainCategory::ECSAndStaff => "ES",
+            TrainCategory::Postal => "JJ",
+            TrainCategory::PostOfficeControlledParcels => "PM",
+            TrainCategory::Parcels => "PP",
+            TrainCategory::EmptyNPCCS => "PV",
+            TrainCategory::Departmental => "DD",
+            TrainCategory::CivilEngineer => "DH",
+            TrainCategory::MechanicalAndElectricalEngineer => "DI",
+            TrainCategory::Stores => "DQ",
+            TrainCategory::Test => "DT",
+            TrainCategory::SignalAndTelecommunicationsEngineer => "DY",
+            TrainCategory::LocomotiveAndBrakeVan => "ZB",
+            TrainCategory::LightLocomotive => "ZZ",
+            TrainCategory::RfDAutomotiveComponents => "J2",
+            TrainCategory::RfDAutomotiveVehicles => "H2",
+            TrainCategory::RfDEdibleProducts => "J3",
+            TrainCategory::RfDIndustrialMinerals => "J4",
+            TrainCategory::RfDChemicals => "J5",
+            TrainCategory::RfDBuildingMaterials => "J6",
+            TrainCategory::RfDGeneralMerchandise => "J8",
+            TrainCategory::RfDEuropean => "H8",
+            TrainCategory::RfDFreightlinerContracts => "J9",
+            TrainCategory::RfDFreightlinerOther => "H9",
+            TrainCategory::CoalDistributive => "A0",
+            TrainCategory::CoalElectricityMGR => "E0",
+            TrainCategory::CoalOtherAndNuclear => "B0",
+            TrainCategory::Metals => "B1",
+            TrainCategory::Aggregates => "B4",
+            TrainCategory::DomesticAndIndustrialWaste => "B5",
+            TrainCategory::BuildingMaterials => "B6",
+            TrainCategory::PetroleumProducts => "B7",
+            TrainCategory::RfDEuropeanChannelTunnelMixed => "H0",
+            TrainCategory::RfDEuropeanChannelTunnelIntermodal => "H1",
+            TrainCategory::RfDEuropeanChannelTunnelAutomotive => "H3",
+            TrainCategory::RfDEuropeanChannelTunnelContractServices => "H4",
+            TrainCategory::RfDEuropeanChannelTunnelHaulmark => "H5",
+            TrainCategory::RfDEuropeanChannelTunnelJointVenture => "H6",
+            TrainCategory::Other(code) => code,
+        }
+    }
+}
+
+impl PowerType {
+    /// The code for this in a basic schedule record, such as `EMU`.
+    pub fn code(&self) -> &str {
+        match self {
+            PowerType::NotSpecified => "",
+            PowerType::Diesel => "D",
+            PowerType::DieselElectricMultipleUnit => "DEM",
+            PowerType::DieselMechanicalMultipleUnit => "DMU",
+            PowerType::Electric => "E",
+            PowerType::ElectroDiesel => "ED",
+            PowerType::EMUPlusLocomotive => "EML",
+            PowerType::ElectricMultipleUnit => "EMU",
+            PowerType::HighSpeedTrain => "HST",
+            PowerType::Other(code) => code,
+        }
+    }
+}
+
+impl TimingLoad {
+    /// The code for this in a basic schedule record, such as `444` or `E`. Some codes mean
+    /// different timing loads for different power types.
+    pub fn code(&self) -> String {
+        match self {
+            TimingLoad::NotSpecified => String::new(),
+            TimingLoad::Class17201721Or1722 => "69".to_string(),
+            TimingLoad::Class141To144 => "A".to_string(),
+            TimingLoad::Class158168170Or175 => "E".to_string(),
+            TimingLoad::Class1650 => "N".to_string(),
+            TimingLoad::Class150153155Or156 => "S".to_string(),
+            TimingLoad::Class1651Or166 => "T".to_string(),
+            TimingLoad::Class220Or221 => "V".to_string(),
+            TimingLoad::Class159 => "X".to_string(),
+            TimingLoad::DMUPowerCarTrailer => "D1".to_string(),
+            TimingLoad::DMU2PowerCarsTrailer => "D2".to_string(),
+            TimingLoad::DMUPowerTwin => "D3".to_string(),
+            TimingLoad::AcceleratedTimings => "AT".to_string(),
+            TimingLoad::Class458 => "E".to_string(),
+            TimingLoad::Class380 => "0".to_string(),
+            TimingLoad::Class3501110MPH => "506".to_string(),
+            TimingLoad::Class325ElectricParcelsUnit => "325".to_string(),
+            TimingLoad::SpecificClass(class) => class.to_string(),
+            TimingLoad::LoadInTonnes(tonnes) => tonnes.to_string(),
+            TimingLoad::Other(code) => code.clone(),
+        }
+    }
+}
+
+impl OperatingCharacteristic {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            OperatingCharacteristic::VacuumBraked => 'B',
+            OperatingCharacteristic::TimedAt100MPH => 'C',
+            OperatingCharacteristic::DOOCoachingStockTrains => 'D',
+            OperatingCharacteristic::ConveysMark4Coaches => 'E',
+            OperatingCharacteristic::GuardRequired => 'G',
+            OperatingCharacteristic::TimedAt110MPH => 'M',
+            OperatingCharacteristic::PushPullTrain => 'P',
+            OperatingCharacteristic::RunsAsRequired => 'Q',
+            OperatingCharacteristic::AirConditionedWithPASystem => 'R',
+            OperatingCharacteristic::SteamHeated => 'S',
+            OperatingCharacteristic::RunsToTerminalsAsRequired => 'Y',
+            OperatingCharacteristic::MayConveyTrafficToSB1CGauge => 'Z',
+            OperatingCharacteristic::Unknown(c) => *c,
+        }
+    }
+}
+
+impl SeatingClass {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            SeatingClass::FirstAndStandard => 'B',
+            SeatingClass::StandardOnly => 'S',
+            SeatingClass::NotSpecified => ' ',
+            SeatingClass::Unknown(c) => *c,
+        }
+    }
+}
+
+impl Sleepers {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            Sleepers::FirstAndStandard => 'B',
+            Sleepers::FirstOnly => 'F',
+            Sleepers::StandardOnly => 'S',
+            Sleepers::NotSpecified => ' ',
+            Sleepers::Unknown(c) => *c,
+        }
+    }
+}
+
+impl Reservations {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            Reservations::Compulsory => 'A',
+            Reservations::CompulsoryForBicycles => 'E',
+            Reservations::Recommended => 'R',
+            Reservations::Possible => 'S',
+            Reservations::NotSpecified => ' ',
+            Reservations::Unknown(c) => *c,
+        }
+    }
+}
+
+impl Catering {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            Catering::NotSpecified => ' ',
+            Catering::BuffetService => 'C',
+            Catering::RestaurantCarForFirstClass => 'F',
+            Catering::HotFood => 'H',
+            Catering::MealForFirstClass => 'M',
+            Catering::WheelchairReservations => 'P',
+            Catering::Restaurant => 'R',
+            Catering::TrolleyService => 'T',
+            Catering::Unknown(c) => *c,
+        }
+    }
+}
+
+impl ConnectionIndicator {
+    /// The code for this in a basic schedule record.
+    pub fn code(&self) -> char {
+        match self {
+            ConnectionIndicator::Indicated(c) => *c,
+            ConnectionIndicator::NotSpecified => ' ',
+        }
+    }
+}
+
+impl STPIndicator {
+    /// The code for this in a basic schedule or association record.
+    pub fn code(&self) -> char {
+        match self {
+            STPIndicator::NewSTPAssociation => 'N',
+            STPIndicator::STPCancellationOfPermanentAssociation => 'C',
+            STPIndicator::STPOverlayOfPermanentAssociation => 'O',
+            STPIndicator::PermanentAssociation => 'P',
+        }
+    }
+}
+
+impl AssociationCategory {
+    /// The code for this in an association record.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AssociationCategory::Join => "JJ",
+            AssociationCategory::Divide => "VV",
+            AssociationCategory::Next => "NP",
+            AssociationCategory::NotSpecified => "  ",
+        }
+    }
+}
+
+impl AssociationDateIndicator {
+    /// The code for this in an association record.
+    pub fn code(&self) -> char {
+        match self {
+            AssociationDateIndicator::Standard => 'S',
+            AssociationDateIndicator::OverNextMidnight => 'N',
+            AssociationDateIndicator::OverPreviousMidnight => 'P',
+        }
+    }
+}
+
+impl AssociationType {
+    /// The code for this in an association record.
+    pub fn code(&self) -> char {
+        match self {
+            AssociationType::Passenger => 'P',
+            AssociationType::Operating => 'O',
+            AssociationType::NotSpecified => ' ',
+        }
+    }
+}
+
+/// A date as written in schedule and association records.
+fn record_date(date: &NaiveDate) -> String {
+    date.format("%y%m%d").to_string()
+}
+
+/// Days run as written in schedule and association records, starting on Monday.
+fn record_days(days: &DaysRun) -> String {
+    format!("{:07b}", days.bits())
+}
+
+/// A working time as written in a location record, such as `0730H`, or blanks.
+fn working_time(time: &Option<JourneyTime>) -> String {
+    match time {
+        Some(time) => format!(
+            "{:02}{:02}{}",
+            time.hour(),
+            time.minute(),
+            if *time.half() { 'H' } else { ' ' }
+        ),
+        None => " ".repeat(5),
+    }
+}
+
+/// A public time as written in a location record, such as `0730`, or blanks.
+fn public_time(time: &Option<JourneyTime>) -> String {
+    match time {
+        Some(time) => format!("{:02}{:02}", time.hour(), time.minute()),
+        None => " ".repeat(4),
+    }
+}
+
+/// An allowance as written in a location record, such as `1H`, or blanks.
+fn allowance(allowance: &Option<Allowance>) -> String {
+    match allowance {
+        Some(allowance) if *allowance.minutes() == 0 && *allowance.half() => "H".to_string(),
+        Some(allowance) => format!(
+            "{}{}",
+            allowance.minutes(),
+            if *allowance.half() { "H" } else { "" }
+        ),
+        None => String::new(),
+    }
+}
+
+/// The location field of a location record: the TIPLOC followed by any suffix.
+fn record_location(location: &JourneyLocation) -> String {
+    let suffix = location.suffix().map(|suffix| suffix.to_string());
+    format!("{:<7}{:1}", location.tiploc(), suffix.unwrap_or_default())
+}
+
+fn note_record(note: &Note, train: bool) -> CIFRecord {
+    if train {
+        CIFRecord::TrainNote {
+            note_type: *note.note_type(),
+            note: note.text().clone(),
+        }
+    } else {
+        CIFRecord::LocationNote {
+            note_type: *note.note_type(),
+            note: note.text().clone(),
+        }
+    }
+}
+
+impl Schedule {
+    /// The records describing this schedule, as they would appear in a CIF file: a basic
+    /// schedule record, then unless this is a cancellation, the extended record, the
+    /// location records and any notes. A deletion is given by a basic schedule record alone,
+    /// identifying the schedule by its train UID, start date and STP indicator.
+    pub fn to_records(&self, transaction_type: TransactionType) -> Vec<CIFRecord> {
+        if transaction_type == TransactionType::Delete {
+            return vec![self.to_delete_record()];
+        }
+        let mut records = vec![CIFRecord::BasicSchedule {
+            transaction_type: transaction_type.code(),
+            train_uid: self.train_uid().clone(),
+            date_runs_from: record_date(self.runs_from()),
+            date_runs_to: record_date(self.runs_to()),
+            days_run: record_days(self.days_run()),
+            bank_holiday_running: self.bank_holiday_running().code(),
+            train_status: self.train_status().code(),
+            train_category: self.train_category().code().to_string(),
+            train_identity: self.train_identity().clone(),
+            headcode: self.nrs_headcode().clone(),
+            course_indicator: *self.course_indicator(),
+            train_service_code: self.train_service_code().clone(),
+            portion_id: *self.portion_id(),
+            power_type: self.power_type().code().to_string(),
+            timing_load: self.timing_load().code(),
+            speed: match self.speed() {
+                0 => String::new(),
+                speed => format!("{speed:03}"),
+            },
+            operating_characteristics: self
+                .operating_characteristics()
+                .iter()
+                .map(OperatingCharacteristic::code)
+                .collect(),
+            seating_class: self.seating_class().code(),
+            sleepers: self.sleepers().code(),
+            reservations: self.reservations().code(),
+            connection_indicator: self.connection_indicator().code(),
+            catering_code: self.catering().iter().map(Catering::code).collect(),
+            service_branding: self.service_branding().clone(),
+            stp_indicator: self.stp_indicator().code(),
+        }];
+        // a cancellation is given by the basic schedule record alone
+        if *self.stp_indicator() == STPIndicator::STPCancellationOfPermanentAssociation {
+            return records;
+        }
+
+        records.push(CIFRecord::BasicScheduleExtended {
+            traction_class: self.traction_class().clone().unwrap_or_default(),
+            uic_code: self.uic_code().clone().unwrap_or_default(),
+            atoc_code: self.atoc_code().clone(),
+            applicable_timetable_code: if *self.subject_to_performance_monitoring() {
+                'Y'
+            } else {
+                'N'
+            },
+        });
+        records.extend(self.notes().iter().map(|note| note_record(note, true)));
+
+        let last = self.journey().len().saturating_sub(1);
+        for (idx, location) in self.journey().iter().enumerate() {
+            records.push(if idx == 0 {
+                CIFRecord::LocationOrigin {
+                    location: record_location(location),
+                    scheduled_departure_time: working_time(location.departure_time()),
+                    public_departure_time: public_time(location.public_departure()),
+                    platform: location.platform().to_string(),
+                    line: location.line().to_string(),
+                    engineering_allowance: allowance(location.engineering_allowance()),
+                    pathing_allowance: allowance(location.pathing_allowance()),
+                    activity: location.activity().clone(),
+                    performance_allowance: allowance(location.performance_allowance()),
+                }
+            } else if idx == last {
+                CIFRecord::LocationTerminate {
+                    location: record_location(location),
+                    scheduled_arrival_time: working_time(location.arrival_time()),
+                    public_arrival_time: public_time(location.public_arrival()),
+                    platform: location.platform().to_string(),
+                    path: location.path().to_string(),
+                    activity: location.activity().clone(),
+                }
+            } else {
+                CIFRecord::LocationIntermediate {
+                    location: record_location(location),
+                    scheduled_arrival_time: working_time(location.arrival_time()),
+                    scheduled_departure_time: working_time(location.departure_time()),
+                    scheduled_pass: working_time(location.passing_time()),
+                    public_arrival_time: public_time(location.public_arrival()),
+                    public_departure_time: public_time(location.public_departure()),
+                    platform: location.platform().to_string(),
+                    line: location.line().to_string(),
+                    path: location.path().to_string(),
+                    activity: location.activity().clone(),
+                    engineering_allowance: allowance(location.engineering_allowance()),
+                    pathing_allowance: allowance(location.pathing_allowance()),
+                    performance_allowance: allowance(location.performance_allowance()),
+                }
+            });
+            records.extend(location.notes().iter().map(|note| note_record(note, false)));
+        }
+        records
+    }
+
+    fn to_delete_record(&self) -> CIFRecord {
+        CIFRecord::BasicSchedule {
+            transaction_type: TransactionType::Delete.code(),
+            train_uid: self.train_uid().clone(),
+            date_runs_from: record_date(self.runs_from()),
+            date_runs_to: String::new(),
+            days_run: String::new(),
+            bank_holiday_running: ' ',
+            train_status: ' ',
+            train_category: String::new(),
+            train_identity: String::new(),
+            headcode: String::new(),
+            course_indicator: ' ',
+            train_service_code: String::new(),
+            portion_id: ' ',
+            power_type: String::new(),
+            timing_load: String::new(),
+            speed: String::new(),
+            operating_characteristics: String::new(),
+            seating_class: ' ',
+            sleepers: ' ',
+            reservations: ' ',
+            connection_indicator: ' ',
+            catering_code: String::new(),
+            service_branding: String::new(),
+            stp_indicator: self.stp_indicator().code(),
+        }
+    }
+}
+
+impl TIPLOC {
+    /// A TIPLOC insert record for this TIPLOC.
+    pub(crate) fn to_insert_record(&self) -> CIFRecord {
+        CIFRecord::TIPLOCInsert {
+            tiploc: self.tiploc().clone(),
+            capitals_identification: 0,
+            nlc: *self.nlc(),
+            nlc_check_char: *self.nlc_check_char(),
+            tps_description: self.description().clone(),
+            stanox: *self.stanox(),
+            po_mcp_code: self.po_mcp_code().clone(),
+            three_alpha_code: self.three_alpha_code().clone(),
+            nlc_description: self.nlc_description().clone(),
+        }
+    }
+
+    /// A TIPLOC amend record replacing the details of this TIPLOC.
+    pub(crate) fn to_amend_record(&self) -> CIFRecord {
+        CIFRecord::TIPLOCAmend {
+            tiploc: self.tiploc().clone(),
+            capitals_identification: 0,
+            nlc: *self.nlc(),
+            nlc_check_char: *self.nlc_check_char(),
+            tps_description: self.description().clone(),
+            stanox: *self.stanox(),
+            po_mcp_code: self.po_mcp_code().clone(),
+            three_alpha_code: self.three_alpha_code().clone(),
+            nlc_description: self.nlc_description().clone(),
+            new_tiploc: String::new(),
+        }
+    }
+}
+
+impl Association {
+    /// An association record for this association, with the given transaction type.
+    pub(crate) fn to_record(&self, transaction_type: TransactionType) -> CIFRecord {
+        let suffix = |suffix: &Option<char>| suffix.map(String::from).unwrap_or_default();
+        CIFRecord::Association {
+            transaction_type: transaction_type.code(),
+            main_train_uid: self.main_train_uid().clone(),
+            associated_train_uid: self.associated_train_uid().clone(),
+            association_start_date: record_date(self.start_date()),
+            association_end_date: record_date(self.end_date()),
+            association_days: record_days(self.days()),
+            association_category: self.category().code().to_string(),
+            association_date_indicator: self.date_indicator().code(),
+            association_location: self.location().clone(),
+            base_location_suffix: suffix(self.base_location_suffix()),
+            association_location_suffix: suffix(self.association_location_suffix()),
+            diagram_type: 'T',
+            association_type: self.association_type().code(),
+            stp_indicator: self.stp_indicator().code(),
+        }
+    }
+}
+
+/// Builds a fixed width line, padding or cutting each field to its width.
+struct LineBuilder(String);
+
+impl LineBuilder {
+    fn new(record_type: &str) -> Self {
+        Self(record_type.to_string())
+    }
+
+    fn text(mut self, value: &str, width: usize) -> Self {
+        let value: String = value.chars().take(width).collect();
+        self.0.push_str(&format!("{value:<width$}"));
+        self
+    }
+
+    fn char(mut self, value: char) -> Self {
+        self.0.push(value);
+        self
+    }
+
+    fn number(mut self, value: u32, width: usize) -> Self {
+        self.0.push_str(&format!("{value:0width$}"));
+        self
+    }
+
+    /// Pad the line with spaces up to a column, counting characters from zero as
+    /// [`LineBuilder::text`] does.
+    fn pad_to(mut self, column: usize) -> Self {
+        let len = self.0.chars().count();
+        if len < column {
+            self.0.push_str(&" ".repeat(column - len));
+        }
+        self
+    }
+
+    fn finish(self) -> String {
+        self.pad_to(80).0
+    }
+}
+
+impl CIFRecord {
+    /// This record as an 80 character line of a CIF file, without a line ending. Each field
+    /// is padded with spaces to its width, and cut short if it is too long.
+    pub fn to_cif_line(&self) -> String {
+        match self {
+            CIFRecord::Header {
+                file_mainframe_identity,
+                date_of_extract,
+                time_of_extract,
+                current_file_reference,
+                last_file_reference,
+                update_indicator,
+                version,
+                user_start_date,
+                user_end_date,
+            } => LineBuilder::new("HD")
+                .text(file_mainframe_identity, 20)
+                .text(date_of_extract, 6)
+                .text(time_of_extract, 4)
+                .text(current_file_reference, 7)
+                .text(last_file_reference, 7)
+                .char(*update_indicator)
+                .char(*version)
+                .text(user_start_date, 6)
+                .text(user_end_date, 6)
+                .finish(),
+            CIFRecord::TIPLOCInsert {
+                tiploc,
+                capitals_identification,
+                nlc,
+                nlc_check_char,
+                tps_description,
+                stanox,
+                po_mcp_code,
+                three_alpha_code,
+                nlc_description,
+            } => LineBuilder::new("TI")
+                .text(tiploc, 7)
+                .number(*capitals_identification as u32, 2)
+                .number(*nlc, 6)
+                .char(*nlc_check_char)
+                .text(tps_description, 26)
+                .number(*stanox, 5)
+                .text(po_mcp_code, 4)
+                .text(three_alpha_code, 3)
+                .text(nlc_description, 16)
+                .finish(),
+            CIFRecord::TIPLOCAmend {
+                tiploc,
+                capitals_identification,
+                nlc,
+                nlc_check_char,
+                tps_description,
+                stanox,
+                po_mcp_code,
+                three_alpha_code,
+                nlc_description,
+                new_tiploc,
+            } => LineBuilder::new("TA")
+                .text(tiploc, 7)
+                .number(*capitals_identification as u32, 2)
+                .number(*nlc, 6)
+                .char(*nlc_check_char)
+                .text(tps_description, 26)
+                .number(*stanox, 5)
+                .text(po_mcp_code, 4)
+                .text(three_alpha_code, 3)
+                .text(nlc_description, 16)
+                .text(new_tiploc, 7)
+                .finish(),
+            CIFRecord::TIPLOCDelete { tiploc } => LineBuilder::new("TD").text(tiploc, 7).finish(),
+            CIFRecord::Association {
+                transaction_type,
+                main_train_uid,
+                associated_train_uid,
+                association_start_date,
+                association_end_date,
+                association_days,
+                association_category,
+                association_date_indicator,
+                association_location,
+                base_location_suffix,
+                association_location_suffix,
+                diagram_type,
+                association_type,
+                stp_indicator,
+            } => LineBuilder::new("AA")
+                .char(*transaction_type)
+                .text(main_train_uid, 6)
+                .text(associated_train_uid, 6)
+                .text(association_start_date, 6)
+                .text(association_end_date, 6)
+                .text(association_days, 7)
+                .text(association_category, 2)
+                .char(*association_date_indicator)
+                .text(association_location, 7)
+                .text(base_location_suffix, 1)
+                .text(association_location_suffix, 1)
+                .char(*diagram_type)
+                .char(*association_type)
+                .pad_to(79)
+                .char(*stp_indicator)
+                .finish(),
+            CIFRecord::BasicSchedule {
+                transaction_type,
+                train_uid,
+                date_runs_from,
+                date_runs_to,
+                days_run,
+                bank_holiday_running,
+                train_status,
+                train_category,
+                train_identity,
+                headcode,
+                course_indicator,
+                train_service_code,
+                portion_id,
+                power_type,
+                timing_load,
+                speed,
+                operating_characteristics,
+                seating_class,
+                sleepers,
+                reservations,
+                connection_indicator,
+                catering_code,
+                service_branding,
+                stp_indicator,
+            } => LineBuilder::new("BS")
+                .char(*transaction_type)
+                .text(train_uid, 6)
+                .text(date_runs_from, 6)
+                .text(date_runs_to, 6)
+                .text(days_run, 7)
+                .char(*bank_holiday_running)
+                .char(*train_status)
+                .text(train_category, 2)
+                .text(train_identity, 4)
+                .text(headcode, 4)
+                .char(*course_indicator)
+                .text(train_service_code, 8)
+                .char(*portion_id)
+                .text(power_type, 3)
+                .text(timing_load, 4)
+                .text(speed, 3)
+                .text(operating_characteristics, 6)
+                .char(*seating_class)
+                .char(*sleepers)
+                .char(*reservations)
+                .char(*connection_indicator)
+                .text(catering_code, 4)
+                .text(service_branding, 4)
+                .pad_to(79)
+                .char(*stp_indicator)
+                .finish(),
+            CIFRecord::BasicScheduleExtended {
+                traction_class,
+                uic_code,
+                atoc_code,
+                applicable_timetable_code,
+            } => LineBuilder::new("BX")
+                .text(traction_class, 4)
+                .text(uic_code, 5)
+                .text(atoc_code, 2)
+                .char(*applicable_timetable_code)
+                .finish(),
+            CIFRecord::LocationOrigin {
+                location,
+                scheduled_departure_time,
+                public_departure_time,
+                platform,
+                line,
+                engineering_allowance,
+                pathing_allowance,
+                activity,
+                performance_allowance,
+            } => LineBuilder::new("LO")
+                .text(location, 8)
+                .text(scheduled_departure_time, 5)
+                .text(public_departure_time, 4)
+                .text(platform, 3)
+                .text(line, 3)
+                .text(engineering_allowance, 2)
+                .text(pathing_allowance, 2)
+                .text(activity, 12)
+                .text(performance_allowance, 2)
+                .finish(),
+            CIFRecord::LocationIntermediate {
+                location,
+                scheduled_arrival_time,
+                scheduled_departure_time,
+                scheduled_pass,
+                public_arrival_time,
+                public_departure_time,
+                platform,
+                line,
+                path,
+                activity,
+                engineering_allowance,
+                pathing_allowance,
+                performance_allowance,
+            } => LineBuilder::new("LI")
+                .text(location, 8)
+                .text(scheduled_arrival_time, 5)
+                .text(scheduled_departure_time, 5)
+                .text(scheduled_pass, 5)
+                .text(public_arrival_time, 4)
+                .text(public_departure_time, 4)
+                .text(platform, 3)
+                .text(line, 3)
+                .text(path, 3)
+                .text(activity, 12)
+                .text(engineering_allowance, 2)
+                .text(pathing_allowance, 2)
+                .text(performance_allowance, 2)
+                .finish(),
+            CIFRecord::ChangeEnRoute {
+                location,
+                train_category,
+                train_identity,
+                headcode,
+                course_indicator,
+                profit_centre_code,
+                business_sector,
+                power_type,
+                timing_load,
+                speed,
+                operating_chars,
+                train_class,
+                sleepers,
+                reservations,
+                connect_indicator,
+                catering_code,
+                service_branding,
+                traction_class,
+                uic_code,
+                retail_train_id,
+            } => LineBuilder::new("CR")
+                .text(location, 8)
+                .text(train_category, 2)
+                .text(train_identity, 4)
+                .text(headcode, 4)
+                .char(*course_indicator)
+                .text(profit_centre_code, 8)
+                .char(*business_sector)
+                .text(power_type, 3)
+                .text(timing_load, 4)
+                .text(speed, 3)
+                .text(operating_chars, 6)
+                .char(*train_class)
+                .char(*sleepers)
+                .char(*reservations)
+                .char(*connect_indicator)
+                .text(catering_code, 4)
+                .text(service_branding, 4)
+                .text(traction_class, 4)
+                .text(uic_code, 5)
+                .text(retail_train_id, 8)
+                .finish(),
+            CIFRecord::LocationTerminate {
+                location,
+                scheduled_arrival_time,
+                public_arrival_time,
+                platform,
+                path,
+                activity,
+            } => LineBuilder::new("LT")
+                .text(location, 8)
+                .text(scheduled_arrival_time, 5)
+                .text(public_arrival_time, 4)
+                .text(platform, 3)
+                .text(path, 3)
+                .text(activity, 12)
+                .finish(),
+            CIFRecord::TrainNote { note_type, note } => LineBuilder::new("TN")
+                .char(*note_type)
+                .text(note, 77)
+                .finish(),
+            CIFRecord::LocationNote { note_type, note } => LineBuilder::new("LN")
+                .char(*note_type)
+                .text(note, 77)
+                .finish(),
+            CIFRecord::Trailer => LineBuilder::new("ZZ").finish(),
+        }
+    }
+}
+
+impl fmt::Display for CIFRecord {
+    /// Writes the record as a line of a CIF file, as with [`CIFRecord::to_cif_line`].
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_cif_line())
+    }
+}
+
+impl CIFFile {
+    /// Write the records of this file as a CIF file, one 80 character line per record, each
+    /// ending with a line feed.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for record in self.records() {
+            writeln!(writer, "{record}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1444 @@
+use nr_cif::prelude::*;
+
+use chrono::NaiveDate;
+use std::fs::File;
+
+fn sample_file() -> CIFFile {
+    let f = File::open("./tests/sample.cif").expect("cannot read file");
+    parse_cif(f).expect("sample should parse")
+}
+
+#[test]
+fn test_placeholder_public_times() {
+    let file = sample_file();
+
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&file).is_empty());
+    let ecs = &db.schedules()["C10004"][0];
+    assert_eq!(*ecs.journey()[0].public_departure(), None);
+    assert_eq!(*ecs.journey()[1].public_arrival(), None);
+    // passing points have no public times either
+    let fast = &db.schedules()["C10002"][0];
+    assert_eq!(*fast.journey()[2].public_arrival(), None);
+
+    let mut db = ScheduleDatabase::new();
+    db.set_ignore_placeholder_public_times(false);
+    assert!(db.apply_file(&file).is_empty());
+    let ecs = &db.schedules()["C10004"][0];
+    assert!(ecs.journey()[0].public_departure().is_some());
+}
+
+#[test]
+fn test_reference_only() {
+    let f = File::open("./tests/sample.cif").expect("cannot read file");
+    let file = parse_cif_reference(f, true).expect("sample should parse");
+    assert!(file
+        .records()
+        .iter()
+        .all(|r| !matches!(r, CIFRecord::BasicSchedule { .. })));
+
+    let mut db = ReferenceDatabase::new();
+    assert!(db.apply_file(&file).is_empty());
+    assert_eq!(db.tiplocs().len(), 9);
+    assert_eq!(db.associations().len(), 1);
+
+    // an older full extract is rejected without clearing or changing the database
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let header = lines[0].replacen("PD2406010106242130", "PD2405310305242130", 1);
+    let older = parse_cif_reference(
+        format!(
+            "{header}\n{}\n{}\n",
+            lines[1].replacen("WATRLMN", "WATRLMX", 1),
+            lines[56]
+        )
+        .as_bytes(),
+        true,
+    )
+    .expect("older file should parse");
+    assert_eq!(db.apply_file(&older).len(), 1);
+    assert_eq!(db.tiplocs().len(), 9);
+    assert_eq!(db.associations().len(), 1);
+}
+
+#[test]
+fn test_reference_skips_schedules() {
+    let mut db = ReferenceDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    assert_eq!(db.tiplocs().len(), 9);
+    assert_eq!(db.associations().len(), 1);
+
+    // applying the same extract again is a full update, rather than adding to the data
+    assert!(db.apply_file(&sample_file()).is_empty());
+    assert_eq!(db.tiplocs().len(), 9);
+    assert_eq!(db.associations().len(), 1);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_write_schedules_parallel() {
+    use std::io::Write;
+
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+
+    let mut out = vec![];
+    db.write_schedules_parallel(&mut out, |schedule, buffer| {
+        writeln!(buffer, "{}", schedule.train_uid())
+    })
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "C10001\nC10001\nC10002\nC10002\nC10003\nC10004\nC10006\nL10005\n"
+    );
+}
+
+#[test]
+fn test_apply_reader() {
+    let mut from_file = ScheduleDatabase::new();
+    assert!(from_file.apply_file(&sample_file()).is_empty());
+
+    let f = File::open("./tests/sample.cif").expect("cannot read file");
+    let mut streamed = ScheduleDatabase::new();
+    let report = streamed.apply_reader(f).expect("sample should parse");
+    assert!(report.errors().is_empty());
+    assert_eq!(streamed.schedules().len(), from_file.schedules().len());
+    assert_eq!(streamed.tiplocs().len(), from_file.tiplocs().len());
+}
+
+#[test]
+fn test_train_identity_and_headcode() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let sample = sample.replacen("1B01    124671005", "1B014321124671005", 1);
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader(sample.as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+
+    let schedule = &db.schedules()["C10001"][0];
+    assert_eq!(schedule.train_identity(), "1B01");
+    assert_eq!(schedule.nrs_headcode(), "4321");
+    assert_eq!(db.schedules()["C10002"][0].nrs_headcode(), "");
+    #[allow(deprecated)]
+    let headcode = schedule.headcode();
+    assert_eq!(headcode, "1B01");
+}
+
+#[test]
+fn test_schedule_branding() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines[11].replace_range(69..78, "CT   E   ");
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader((lines.join("\n") + "\n").as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+
+    let schedule = &db.schedules()["C10001"][0];
+    assert_eq!(*schedule.course_indicator(), '1');
+    assert_eq!(
+        *schedule.connection_indicator(),
+        ConnectionIndicator::Indicated('C')
+    );
+    assert_eq!(schedule.service_branding(), "E");
+    let other = &db.schedules()["C10002"][0];
+    assert_eq!(
+        *other.connection_indicator(),
+        ConnectionIndicator::NotSpecified
+    );
+    assert_eq!(other.service_branding(), "");
+}
+
+#[test]
+fn test_extended_schedule_details() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines[12].replace_range(2..11, "444 31512");
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader((lines.join("\n") + "\n").as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+
+    let schedule = &db.schedules()["C10001"][0];
+    assert_eq!(schedule.traction_class().as_deref(), Some("444"));
+    assert_eq!(schedule.uic_code().as_deref(), Some("31512"));
+    assert_eq!(*db.schedules()["C10002"][0].traction_class(), None);
+}
+
+#[test]
+fn test_allowances() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines[14].replace_range(54..60, "1H H 2");
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader((lines.join("\n") + "\n").as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+
+    let location = &db.schedules()["C10001"][0].journey()[1];
+    let engineering = location.engineering_allowance().unwrap();
+    assert_eq!((*engineering.minutes(), *engineering.half()), (1, true));
+    assert_eq!(location.pathing_allowance().unwrap().seconds(), 30);
+    assert_eq!(location.performance_allowance().unwrap().seconds(), 120);
+    assert_eq!(
+        *db.schedules()["C10001"][0].journey()[2].pathing_allowance(),
+        None
+    );
+}
+
+#[test]
+fn test_activities() {
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+
+    let journey = db.schedules()["C10001"][0].journey();
+    let origin = &journey[0];
+    assert_eq!(origin.activity(), "TB");
+    assert_eq!(*origin.activities(), vec![Activity::TrainBegins]);
+    assert!(origin.picks_up() && !origin.sets_down());
+    let destination = journey.last().unwrap();
+    assert_eq!(*destination.activities(), vec![Activity::TrainFinishes]);
+    assert!(destination.sets_down() && !destination.picks_up());
+
+    assert_eq!(
+        Activity::from_code("-U"),
+        Some(Activity::StopsToAttachVehicles)
+    );
+    assert_eq!(Activity::StopNotAdvertised.code(), "N");
+}
+
+#[test]
+fn test_platforms() {
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+
+    let origin = &db.schedules()["C10001"][0].journey()[0];
+    assert_eq!(*origin.platform(), "12");
+    assert_eq!(origin.platform().to_string(), "12");
+    assert!(origin.path().is_empty());
+
+    assert_eq!(Platform::new(" 10a "), Platform::from("10A"));
+    assert_eq!(Line::new("F  L").as_str(), "F L");
+    assert_ne!(Platform::new("1"), Platform::new("2"));
+}
+
+#[test]
+fn test_location_suffix() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines[14].replace_range(9..10, "2");
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader((lines.join("\n") + "\n").as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+
+    let journey = db.schedules()["C10001"][0].journey();
+    assert_eq!(journey[1].tiploc(), "CLPHMJC");
+    assert_eq!(*journey[1].suffix(), Some(2));
+    assert_eq!(*journey[0].suffix(), None);
+}
+
+#[test]
+fn test_notes() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines.insert(20, format!("{:<80}", "LNGTerminates on platform 1"));
+    lines.insert(15, format!("{:<80}", "LNGCalls to set down only"));
+    lines.insert(13, format!("{:<80}", "TNGRuns as required"));
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader((lines.join("\n") + "\n").as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+
+    let schedule = &db.schedules()["C10001"][0];
+    assert_eq!(schedule.notes().len(), 1);
+    assert_eq!(*schedule.notes()[0].note_type(), 'G');
+    assert_eq!(schedule.notes()[0].text(), "Runs as required");
+    let journey = schedule.journey();
+    assert_eq!(journey[1].notes()[0].text(), "Calls to set down only");
+    assert_eq!(
+        journey.last().unwrap().notes()[0].text(),
+        "Terminates on platform 1"
+    );
+    assert!(journey[0].notes().is_empty());
+
+    let mut plain = ScheduleDatabase::new();
+    assert!(plain.apply_file(&sample_file()).is_empty());
+    assert_eq!(db.schedules().len(), plain.schedules().len());
+}
+
+#[test]
+fn test_unknown_codes() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines[11].replace_range(30..32, "Q9");
+    let sample = lines.join("\n") + "\n";
+
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader(sample.as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+    assert_eq!(
+        *db.schedules()["C10001"][0].train_category(),
+        TrainCategory::Other("Q9".to_string())
+    );
+}
+
+#[test]
+fn test_unknown_code_policy() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines[11].replace_range(30..32, "Q9");
+    let sample = lines.join("\n") + "\n";
+
+    let mut db = ScheduleDatabaseBuilder::new()
+        .on_unknown_code(UnknownCodePolicy::Error)
+        .panic_on_first_error(false)
+        .build();
+    let report = db
+        .apply_reader(sample.as_bytes())
+        .expect("sample should parse");
+    assert!(matches!(
+        report.errors()[..],
+        [(_, ScheduleApplyError::InvalidTrainCategory(_))]
+    ));
+
+    let mut db = ScheduleDatabaseBuilder::new()
+        .on_unknown_code(UnknownCodePolicy::Warn)
+        .build();
+    let report = db
+        .apply_reader(sample.as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+    let warning = &report.warnings()[0];
+    assert_eq!(warning.category(), ApplyWarningCategory::UnknownCode);
+    assert_eq!(warning.identifier(), "C10001");
+}
+
+#[test]
+fn test_skip_record_kinds() {
+    let mut db = ScheduleDatabaseBuilder::new()
+        .skip_record_kinds([RecordKind::Association, RecordKind::Tiploc])
+        .build();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    assert!(db.associations().is_empty());
+    assert!(db.tiplocs().is_empty());
+    assert!(!db.schedules().is_empty());
+
+    // a skipped note after the terminating location still ends the schedule
+    let sample = std::fs::read_to_string("./tests/sample.cif").expect("cannot read file");
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines.insert(20, format!("{:<80}", "LNGTerminates on platform 1"));
+    let mut db = ScheduleDatabaseBuilder::new()
+        .skip_record_kinds([RecordKind::Note])
+        .build();
+    let report = db
+        .apply_reader((lines.join("\n") + "\n").as_bytes())
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+    let schedule = &db.schedules()["C10001"][0];
+    assert_eq!(schedule.journey().last().unwrap().tiploc(), "SOTON");
+    assert!(schedule.journey().last().unwrap().notes().is_empty());
+    assert_eq!(db.schedules().len(), 6);
+}
+
+#[test]
+fn test_schedule_filter() {
+    let file = sample_file();
+
+    let mut db = ScheduleDatabaseBuilder::new()
+        .only_calling_at_crs(["SUR"])
+        .build();
+    assert!(db.apply_file(&file).is_empty());
+    assert_eq!(db.schedules().keys().collect::<Vec<_>>(), ["C10001"]);
+    // the overlay of a kept train is kept with it
+    assert_eq!(db.schedules()["C10001"].len(), 2);
+
+    let mut db = ScheduleDatabaseBuilder::new()
+        .only_operators(["ZZ"])
+        .build();
+    assert!(db.apply_file(&file).is_empty());
+    assert_eq!(db.schedules().keys().collect::<Vec<_>>(), ["L10005"]);
+
+    let mut db = ScheduleDatabaseBuilder::new().passenger_only().build();
+    assert!(db.apply_file(&file).is_empty());
+    assert!(!db.schedules().contains_key("L10005"));
+    assert!(db.schedules().contains_key("C10006"));
+}
+
+#[test]
+fn test_purge_before() {
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+
+    let summary = db.purge_before(NaiveDate::from_ymd_opt(2024, 6, 5).unwrap());
+    // the overlay of C10001 and the cancellation of C10002
+    assert_eq!(summary.schedules_removed(), 2);
+    assert_eq!(summary.associations_removed(), 0);
+    assert_eq!(db.schedules()["C10001"].len(), 1);
+    assert_eq!(db.schedules_by_headcode("1B01").len(), 1);
+    assert!(db.schedules().contains_key("C10006"));
+
+    let summary = db.purge_before(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+    assert_eq!(summary.associations_removed(), 1);
+    assert!(db.schedules().is_empty());
+    assert!(db.associations().is_empty());
+    assert!(db.schedules_by_operator("SW").is_empty());
+}
+
+#[test]
+fn test_retain_window() {
+    let date = |d| NaiveDate::from_ymd_opt(2024, 6, d).unwrap();
+    let mut db = ScheduleDatabaseBuilder::new()
+        .retain_window(date(5), date(30))
+        .build();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    // the overlay of C10001 and the cancellation of C10002 end before the window
+    assert_eq!(db.schedules()["C10001"].len(), 1);
+    assert_eq!(db.schedules()["C10002"].len(), 1);
+    assert!(db.schedules().contains_key("C10006"));
+    assert!(!db.associations().is_empty());
+
+    let summary = db.retain_window(
+        NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2025, 1, 31).unwrap(),
+    );
+    assert_eq!(summary.associations_removed(), 1);
+    assert!(db.schedules().is_empty());
+    assert!(db.associations().is_empty());
+}
+
+#[test]
+fn test_day_offsets() {
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+
+    let overnight = &db.schedules()["C10003"][0];
+    let offsets: Vec<_> = overnight
+        .journey()
+        .iter()
+        .map(|l| *l.day_offset())
+        .collect();
+    // Woking is reached before midnight, and left after it
+    assert_eq!(offsets, vec![0, 0, 1, 1]);
+    assert!(db.schedules()["C10001"][0]
+        .journey()
+        .iter()
+        .all(|l| *l.day_offset() == 0));
+}
+
+#[test]
+fn test_calls_at() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let permanent = lines[11].replacen("BSN", "BSD", 1);
+
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    let calls = db.calls_at("SURBITN");
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[1].schedule_index(), 1);
+    let (schedule, location) = db.resolve_call(calls[1]).unwrap();
+    assert_eq!(schedule.train_uid(), "C10001");
+    assert_eq!(location.tiploc(), "SURBITN");
+
+    // the overlay moves down when the permanent schedule is deleted
+    let report = db.apply_records_with_progress(&[parse_record(&permanent).unwrap()], |_| ());
+    assert!(report.errors().is_empty());
+    let calls = db.calls_at("SURBITN");
+    assert_eq!(calls.len(), 1);
+    let (schedule, location) = db.resolve_call(calls[0]).unwrap();
+    assert_eq!(
+        *schedule.stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+    assert_eq!(location.tiploc(), "SURBITN");
+}
+
+#[test]
+fn test_schedule_call() {
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    let calls: Vec<ScheduleCall> = db.calls_at("WOKING").into_iter().cloned().collect();
+    assert!(!calls.is_empty());
+    for call in &calls {
+        let (schedule, location) = db.resolve_call(call).unwrap();
+        assert_eq!(schedule.train_uid(), call.train_uid());
+        assert_eq!(location.tiploc(), "WOKING");
+        assert_eq!(
+            schedule.journey()[call.position()].tiploc(),
+            location.tiploc()
+        );
+    }
+}
+
+#[test]
+fn test_write_cif() {
+    // every record in the sample is written as the line it was read from
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    // with the kinds of record the sample doesn't have
+    lines.extend(
+        [
+            "TAWOKING 00556500AWOKING STATION           865200000WOKWOKING          WOKINGS",
+            "TDEASTLGH",
+            "CRWOKING  OO2B02    124671006 EMU450 100      S                EMU4    ",
+            "TNGRuns as required",
+            "LNACalls to set down only",
+        ]
+        .map(|line| format!("{line:<80}")),
+    );
+    for line in &lines {
+        let record = parse_record(line).unwrap();
+        assert_eq!(&record.to_cif_line(), line);
+        assert_eq!(&record.to_string(), line);
+        let reread = parse_record(&record.to_cif_line()).unwrap();
+        assert_eq!(format!("{reread:?}"), format!("{record:?}"));
+    }
+    let mut written = vec![];
+    sample_file().write_to(&mut written).unwrap();
+    assert_eq!(String::from_utf8(written).unwrap(), sample);
+
+    // fields too long for the record are cut short
+    let note = CIFRecord::TrainNote {
+        note_type: 'G',
+        note: "Runs as required ".repeat(5),
+    };
+    assert_eq!(note.to_cif_line().len(), 80);
+    assert!(note.to_cif_line().starts_with("TNGRuns as required"));
+}
+
+#[test]
+fn test_older_full_extract() {
+    let mut db = ScheduleDatabase::new();
+    db.set_panic_on_first_error(false);
+    assert!(db.apply_file(&sample_file()).is_empty());
+
+    // a full extract from the day before is rejected, without clearing the database
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let header = lines[0].replacen("PD2406010106242130", "PD2405310305242130", 1);
+    let older = parse_cif(
+        format!(
+            "{header}\n{}\n{}\n{}\n",
+            lines[1].replacen("LONDON WATERLOO  ", "LONDON WATERLOO X", 1),
+            lines[11..20].join("\n").replace("1B01", "1X99"),
+            lines[56]
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    let errors = db.apply_file(&older);
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        errors[0].1,
+        ScheduleApplyError::AttemptingToApplyOlderData
+    ));
+    assert_eq!(db.schedules().len(), 6);
+    assert_eq!(db.tiplocs().len(), 9);
+    assert_eq!(db.associations_by_main_uid("C10001").len(), 1);
+    assert_eq!(db.calls_at("SURBITN").len(), 2);
+    // nothing else in the rejected file is applied
+    assert_eq!(db.tiplocs()["WATRLMN"].description(), "LONDON WATERLOO");
+    assert_eq!(db.schedules()["C10001"].len(), 2);
+    assert!(db.schedules_by_headcode("1X99").is_empty());
+}
+
+#[test]
+fn test_repeated_full_extract() {
+    let count = |db: &ScheduleDatabase| db.schedules().values().map(Vec::len).sum::<usize>();
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    let schedules = count(&db);
+
+    // a second full extract replaces the schedules, rather than adding to them
+    assert!(db.apply_file(&sample_file()).is_empty());
+    assert_eq!(count(&db), schedules);
+    assert_eq!(db.schedules().len(), 6);
+    assert_eq!(db.schedules()["C10001"].len(), 2);
+    assert_eq!(db.schedules_by_headcode("1B01").len(), 2);
+    assert_eq!(db.calls_at("SURBITN").len(), 2);
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn test_scenario_builder() {
+    use nr_cif::test_util::ScenarioBuilder;
+
+    let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+    let scenario = || {
+        ScenarioBuilder::new(date("2024-05-20"), date("2024-12-13"))
+            .location("WATRLMN", "WAT")
+            .location("SURBITN", "SUR")
+            .location("WOKING", "WOK")
+            .schedules(30)
+            .overlay(1, date("2024-06-03"))
+            .cancellation(2, date("2024-06-04"))
+            .build()
+    };
+    let written = |file: &CIFFile| {
+        let mut bytes = vec![];
+        file.write_to(&mut bytes).unwrap();
+        bytes
+    };
+
+    // the same scenario always generates the same file, which reads back unchanged
+    let first = written(&scenario());
+    assert_eq!(first, written(&scenario()));
+    assert_eq!(first, written(&parse_cif(first.as_slice()).unwrap()));
+
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&scenario()).is_empty());
+    assert!(db.validate().is_empty());
+    assert_eq!(db.schedules().len(), 30);
+    assert_eq!(db.schedules().values().flatten().count(), 32);
+    // each train follows the last after the headway, and calls at every location
+    let last = &db.schedules()[&ScenarioBuilder::uid(29)][0];
+    let times: Vec<_> = last
+        .journey()
+        .iter()
+        .map(|location| (*location.arrival_time(), *location.departure_time()))
+        .collect();
+    let time = |s: &str| Some(s.parse::<JourneyTime>().unwrap());
+    assert_eq!(
+        times,
+        [
+            (None, time("2130 ")),
+            (time("2140 "), time("2141 ")),
+            (time("2151 "), None),
+        ]
+    );
+
+    let overlay = db
+        .effective_schedule(ScenarioBuilder::uid(1), date("2024-06-03"))
+        .unwrap();
+    assert_eq!(
+        *overlay.stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+    assert!(db
+        .effective_schedule(ScenarioBuilder::uid(2), date("2024-06-04"))
+        .is_none());
+    assert!(db
+        .effective_schedule(ScenarioBuilder::uid(2), date("2024-06-05"))
+        .is_some());
+}
+
+#[test]
+fn test_apply_progress() {
+    let file = sample_file();
+
+    let mut db = ScheduleDatabase::new();
+    let mut reports = vec![];
+    let report = db.apply_records_with_progress(file.records(), |progress| reports.push(progress));
+    assert!(report.errors().is_empty());
+    assert!(report.warnings().is_empty());
+
+    let last = reports.last().unwrap();
+    assert_eq!(last.phase(), ApplyPhase::Trailer);
+    assert_eq!(Some(last.record_index() + 1), last.total_records());
+    assert_eq!(last.bundles_applied(), reports.len());
+    assert!(reports
+        .iter()
+        .any(|progress| progress.phase() == ApplyPhase::Schedules));
+}
+
+#[test]
+fn test_apply_warnings() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let bs = lines[51];
+    let records: Vec<CIFRecord> = [
+        lines[0].to_string(),
+        // revise a schedule which was never inserted
+        bs.replacen("BSNC10006", "BSRX00001", 1),
+        lines[52].to_string(),
+        lines[53].to_string(),
+        lines[54].to_string(),
+        lines[55].to_string(),
+        // delete and cancel schedules which don't exist
+        bs.replacen("BSNC10006", "BSDX00002", 1),
+        bs.replacen("BSNC10006", "BSNX00003", 1)
+            .replacen(" N", " C", 1),
+        lines.last().unwrap().to_string(),
+    ]
+    .iter()
+    .map(|line| format!("{line:<80}").parse().unwrap())
+    .collect();
+
+    let mut db = ScheduleDatabase::new();
+    let (errors, warnings) = db
+        .apply_records_with_progress(&records, |_| ())
+        .into_parts();
+    assert!(errors.is_empty(), "{errors:?}");
+    let warnings: Vec<_> = warnings
+        .iter()
+        .map(|w| (w.record_index(), w.category(), w.identifier().as_str()))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (5, ApplyWarningCategory::RevisedUnknownSchedule, "X00001"),
+            (6, ApplyWarningCategory::DeletedUnknownSchedule, "X00002"),
+            (7, ApplyWarningCategory::CancelledUnknownSchedule, "X00003"),
+        ]
+    );
+    assert!(db.schedules().contains_key("X00001"));
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn test_parse_cif_zip() {
+    let f = File::open("./tests/sample.zip").expect("cannot read file");
+    let files = parse_cif_zip(f, "*.MCA").expect("archive should parse");
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].0, "RJTTF001.MCA");
+    assert_eq!(files[0].1.records().len(), sample_file().records().len());
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn test_parse_cif_tar() {
+    let f = File::open("./tests/sample.tar.gz").expect("cannot read file");
+    let files = parse_cif_tar(f, "updates/*.cif").expect("archive should parse");
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].1.records().len(), sample_file().records().len());
+
+    let f = File::open("./tests/sample.tar.gz").expect("cannot read file");
+    assert!(matches!(
+        parse_cif_tar(f, "*.MCA"),
+        Err(ArchiveError::NoMatchingMember(_))
+    ));
+}
+
+#[cfg(feature = "snapshot")]
+#[test]
+fn test_snapshot_round_trip() {
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    db.set_bank_holidays(BankHolidayCalendar::great_britain(2024..=2024));
+
+    let mut snapshot = vec![];
+    db.write_snapshot(&mut snapshot).unwrap();
+    assert_eq!(snapshot[8..12], SNAPSHOT_VERSION.to_le_bytes());
+    let loaded = ScheduleDatabase::read_snapshot(&snapshot[..]).unwrap();
+    assert_eq!(loaded.schedules().len(), db.schedules().len());
+    assert_eq!(loaded.tiplocs().len(), db.tiplocs().len());
+    assert_eq!(loaded.extract_date_time(), db.extract_date_time());
+    assert_eq!(loaded.file_reference(), db.file_reference());
+    // the indexes are rebuilt, but settings aren't saved
+    assert_eq!(loaded.schedules_matching_uid("C10001").len(), 2);
+    assert_eq!(loaded.calls_at("SURBITN"), db.calls_at("SURBITN"));
+    assert_eq!(loaded.schedules_by_headcode("1B01").len(), 2);
+    assert_eq!(loaded.associations_by_associated_uid("C10002").len(), 1);
+    assert_eq!(loaded.get_crs_from_tiploc("WATRLMN"), ["WAT"]);
+    assert_eq!(loaded.bank_holidays(), &BankHolidayCalendar::new());
+
+    snapshot[8] = 99;
+    assert!(matches!(
+        ScheduleDatabase::read_snapshot(&snapshot[..]),
+        Err(SnapshotError::UnsupportedVersion(99))
+    ));
+    assert!(matches!(
+        ScheduleDatabase::read_snapshot(&b"not a snapshot"[..]),
+        Err(SnapshotError::NotASnapshot)
+    ));
+}
+
+#[test]
+#[cfg(feature = "rkyv")]
+fn test_archive_round_trip() {
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+
+    let path = "./target/test_archive.rkyv";
+    db.write_archive(File::create(path).unwrap()).unwrap();
+    // SAFETY: the archive isn't modified while it is mapped
+    let mapped = unsafe { MappedScheduleDatabase::open(path) }.unwrap();
+    let archived = mapped.database();
+    assert_eq!(archived.schedules().len(), db.schedules().len());
+    let schedule = &archived.schedules().get("C10001").unwrap()[0];
+    assert_eq!(
+        schedule.journey().len(),
+        db.schedules()["C10001"][0].journey().len()
+    );
+    assert_eq!(
+        *schedule.stp_indicator(),
+        STPIndicator::PermanentAssociation
+    );
+    assert_eq!(
+        archived
+            .tiplocs()
+            .get("WATRLMN")
+            .unwrap()
+            .three_alpha_code(),
+        "WAT"
+    );
+
+    let loaded = mapped.to_database();
+    assert_eq!(loaded.schedules_matching_uid("C10001").len(), 2);
+
+    let mut bytes = std::fs::read(path).unwrap();
+    bytes[8] = 99;
+    assert!(matches!(
+        access_archive(&bytes),
+        Err(ArchiveSnapshotError::UnsupportedVersion(99))
+    ));
+}
+
+#[test]
+fn test_lenient_parsing() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines.insert(3, format!("{:<80}", "QQ this is not a record"));
+    let corrupted = lines.join("\n") + "\n";
+
+    assert!(parse_cif(corrupted.as_bytes()).is_err());
+
+    let file = parse_cif_with_options(corrupted.as_bytes(), ParseOptions::lenient())
+        .expect("bad lines should be skipped");
+    assert_eq!(file.records().len(), sample_file().records().len());
+    assert_eq!(file.warnings().len(), 1);
+    assert_eq!(file.warnings()[0].line(), 4);
+}
+
+#[test]
+fn test_line_endings() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let expected = sample_file().records().len();
+
+    // CRLF endings, trimmed trailing spaces and no newline after the trailer
+    let crlf = sample
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    let file = parse_cif(crlf.as_bytes()).expect("CRLF file should parse");
+    assert_eq!(file.records().len(), expected);
+    assert!(matches!(file.records().last(), Some(CIFRecord::Trailer)));
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_parse_cif_async() {
+    let sample = std::fs::read("./tests/sample.cif").unwrap();
+    let file = parse_cif_async(&sample[..])
+        .await
+        .expect("sample should parse");
+    assert_eq!(file.records().len(), sample_file().records().len());
+
+    let mut reader = AsyncCIFRecordReader::new(&sample[..]);
+    let mut count = 0;
+    while let Some(record) = reader.next_record().await {
+        record.expect("sample should parse");
+        count += 1;
+    }
+    assert_eq!(count, file.records().len());
+}
+
+#[test]
+fn test_progress_observer() {
+    #[derive(Default)]
+    struct Counts {
+        bytes: u64,
+        parsed: usize,
+        applied: usize,
+    }
+
+    impl ProgressObserver for Counts {
+        fn bytes_read(&mut self, total: u64) {
+            self.bytes = total;
+        }
+
+        fn records_parsed(&mut self, total: usize) {
+            self.parsed = total;
+        }
+
+        fn records_applied(&mut self, progress: &ApplyProgress) {
+            self.applied = progress.record_index() + 1;
+        }
+    }
+
+    let sample = std::fs::read("./tests/sample.cif").unwrap();
+    let mut counts = Counts::default();
+    let file = parse_cif_with_observer(&sample[..], ParseOptions::strict(), &mut counts)
+        .expect("sample should parse");
+    assert_eq!(counts.bytes, sample.len() as u64);
+    assert_eq!(counts.parsed, file.records().len());
+
+    let mut counts = Counts::default();
+    let mut db = ScheduleDatabase::new();
+    let report = db
+        .apply_reader_with_observer(&sample[..], &mut counts)
+        .expect("sample should parse");
+    assert!(report.errors().is_empty());
+    assert_eq!(counts.parsed, file.records().len());
+    assert_eq!(counts.applied, file.records().len());
+}
+
+#[test]
+fn test_parse_cif_bytes() {
+    let sample = std::fs::read("./tests/sample.cif").unwrap();
+    let records = parse_cif_bytes(&sample).expect("sample should parse");
+    let owned = sample_file();
+    assert_eq!(records.len(), owned.records().len());
+
+    for (borrowed, owned) in records.iter().zip(owned.records()) {
+        if let (
+            CIFRecordRef::BasicSchedule {
+                train_uid,
+                stp_indicator,
+                ..
+            },
+            CIFRecord::BasicSchedule {
+                train_uid: owned_uid,
+                stp_indicator: owned_stp,
+                ..
+            },
+        ) = (borrowed, owned)
+        {
+            assert_eq!(train_uid, owned_uid);
+            assert_eq!(stp_indicator.chars().next(), Some(*owned_stp));
+        }
+    }
+    assert!(matches!(
+        parse_cif_bytes(b"QQ not a record\n"),
+        Err(CIFParseError::UnknownRecordType(1, _))
+    ));
+
+    // a multi-byte character across the end of the TPS description is rejected, not split
+    let sample = String::from_utf8(sample).unwrap();
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines[1].replace_range(43..44, "é");
+    assert!(CIFRecordRef::from_line(&lines[1]).is_none());
+    assert!(matches!(
+        parse_cif_bytes((lines.join("\n") + "\n").as_bytes()),
+        Err(CIFParseError::InvalidText(2))
+    ));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_parse_cif_mmap() {
+    let expected = sample_file().records().len();
+    // SAFETY: the sample isn't modified while it is mapped
+    let file = unsafe { parse_cif_mmap("./tests/sample.cif") }.expect("sample should parse");
+    assert_eq!(file.records().len(), expected);
+    assert_eq!(
+        format!("{:?}", file.records()),
+        format!("{:?}", sample_file().records())
+    );
+
+    // SAFETY: as above
+    let mapped = unsafe { MappedCIFFile::open("./tests/sample.cif") }.unwrap();
+    assert_eq!(mapped.records().unwrap().len(), expected);
+}
+
+#[test]
+fn test_parse_record() {
+    let record = parse_record("TDCLPHMJW\r\n").expect("line should parse");
+    assert!(matches!(record, CIFRecord::TIPLOCDelete { tiploc } if tiploc == "CLPHMJW"));
+    assert!(parse_record("QQ not a record").is_err());
+}
+
+#[test]
+fn test_truncated_file() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+
+    // ends cleanly, but before the trailer
+    let without_trailer = lines[..lines.len() - 1].join("\n") + "\n";
+    assert!(matches!(
+        parse_cif(without_trailer.as_bytes()),
+        Err(CIFParseError::MissingTrailer { lines_read }) if lines_read == lines.len() - 1
+    ));
+    assert!(matches!(
+        parse_cif_bytes(without_trailer.as_bytes()),
+        Err(CIFParseError::MissingTrailer { .. })
+    ));
+
+    // cut off part way through a line
+    let truncated = &without_trailer[..without_trailer.len() - 30];
+    assert!(matches!(
+        parse_cif(truncated.as_bytes()),
+        Err(CIFParseError::UnexpectedEof { line, bytes_read })
+            if line == lines.len() - 1 && bytes_read == truncated.len() as u64
+    ));
+}
+
+#[test]
+fn test_parse_cif_multi() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let concatenated = sample.repeat(2);
+    let files = parse_cif_multi(concatenated.as_bytes()).expect("extracts should parse");
+    assert_eq!(files.len(), 2);
+    for file in &files {
+        assert!(matches!(
+            file.records().first(),
+            Some(CIFRecord::Header { .. })
+        ));
+        assert_eq!(file.records().len(), sample_file().records().len());
+    }
+}
+
+#[test]
+fn test_statistics() {
+    let stats = sample_file().statistics();
+    assert_eq!(stats.total_records(), sample_file().records().len());
+    assert_eq!(stats.record_counts()["BS"], 8);
+    assert_eq!(stats.record_counts()["TI"], 9);
+    assert_eq!(stats.record_counts()["ZZ"], 1);
+    assert_eq!(stats.distinct_train_uids(), 6);
+    assert_eq!(
+        stats.first_schedule_date(),
+        NaiveDate::from_ymd_opt(2024, 5, 20)
+    );
+    assert_eq!(
+        stats.last_schedule_date(),
+        NaiveDate::from_ymd_opt(2024, 12, 13)
+    );
+    assert!(stats.extract_date_time().is_some());
+}
+
+#[test]
+fn test_header_accessors() {
+    let file = sample_file();
+    assert_eq!(
+        file.extract_date_time(),
+        NaiveDate::from_ymd_opt(2024, 6, 1).and_then(|d| d.and_hms_opt(21, 30, 0))
+    );
+    assert_eq!(file.update_indicator(), Some(UpdateIndicator::Full));
+    assert_eq!(file.current_file_reference(), Some("DFROC1A"));
+    assert_eq!(file.last_file_reference(), Some("DFROC1Z"));
+    assert_eq!(
+        file.user_date_range(),
+        Some(
+            NaiveDate::from_ymd_opt(2024, 6, 1).unwrap()
+                ..=NaiveDate::from_ymd_opt(2025, 5, 31).unwrap()
+        )
+    );
+}
+
+#[test]
+fn test_apply_file_checked() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let update = |current: &str, last: &str| {
+        let header = sample.lines().next().unwrap();
+        let replaced = header.replacen("DFROC1ADFROC1ZF", &format!("{current}{last}U"), 1);
+        parse_cif(sample.replacen(header, &replaced, 1).as_bytes()).unwrap()
+    };
+
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file_checked(&sample_file()).unwrap().is_empty());
+    assert_eq!(db.file_reference().as_deref(), Some("DFROC1A"));
+
+    assert!(matches!(
+        db.apply_file_checked(&update("DFROC1C", "DFROC1B")),
+        Err(SequenceError::OutOfSequence { expected, found })
+            if expected == "DFROC1A" && found == "DFROC1B"
+    ));
+    assert!(db.apply_file_checked(&update("DFROC1B", "DFROC1A")).is_ok());
+    assert_eq!(
+        db.apply_file_checked(&update("DFROC1B", "DFROC1A"))
+            .unwrap_err(),
+        SequenceError::AlreadyApplied("DFROC1B".to_string())
+    );
+}
+
+#[test]
+fn test_apply_files() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let header = sample.lines().next().unwrap();
+    let with_header = |date: &str, current: &str, last: &str, indicator: &str| {
+        let replaced = header
+            .replacen("0106242130", &format!("{date}2130"), 1)
+            .replacen("DFROC1ADFROC1ZF", &format!("{current}{last}{indicator}"), 1);
+        parse_cif(sample.replacen(header, &replaced, 1).as_bytes()).unwrap()
+    };
+
+    let files = vec![
+        with_header("030624", "DFROC1C", "DFROC1B", "U"),
+        with_header("010624", "DFROC1A", "DFROC1Z", "U"),
+        with_header("020624", "DFROC1B", "DFROC1A", "F"),
+    ];
+    let count = |db: &ScheduleDatabase| db.schedules().values().map(Vec::len).sum::<usize>();
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    let schedules = count(&db);
+    let errors = db.apply_files(files);
+    assert!(errors.iter().all(Vec::is_empty), "{errors:?}");
+    assert_eq!(db.file_reference().as_deref(), Some("DFROC1C"));
+    assert_eq!(
+        db.extract_date_time().date(),
+        NaiveDate::from_ymd_opt(2024, 6, 3).unwrap()
+    );
+    // the full extract replaces the schedules already there, and the update after it adds
+    // its new schedules to those
+    assert_eq!(db.schedules().len(), 6);
+    assert_eq!(count(&db), schedules * 2);
+    assert_eq!(db.schedules()["C10003"].len(), 2);
+}
+
+#[test]
+fn test_revise_schedule() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let records: Vec<CIFRecord> = lines[11..20]
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| match idx {
+            0 => line.replacen("BSNC10001240520241213", "BSRC10001240520241206", 1),
+            _ => line.to_string(),
+        })
+        .map(|line| parse_record(&line).unwrap())
+        .collect();
+
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    let report = db.apply_records_with_progress(&records, |_| ());
+    assert!(report.errors().is_empty());
+    assert!(report.warnings().is_empty());
+
+    let schedules = &db.schedules()["C10001"];
+    assert_eq!(schedules.len(), 2);
+    assert_eq!(
+        *schedules[0].runs_to(),
+        NaiveDate::from_ymd_opt(2024, 12, 6).unwrap()
+    );
+    assert_eq!(
+        *schedules[1].stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+}
+
+#[test]
+fn test_delete_schedule() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    // the overlay of C10001, and then its permanent schedule
+    let overlay = lines[26].replacen("BSN", "BSD", 1);
+    let permanent = lines[11].replacen("BSN", "BSD", 1);
+    assert!(overlay.starts_with("BSDC10001240603"));
+
+    let mut db = ScheduleDatabase::new();
+    assert!(db.apply_file(&sample_file()).is_empty());
+    assert_eq!(db.schedules()["C10001"].len(), 2);
+
+    let report = db.apply_records_with_progress(&[parse_record(&overlay).unwrap()], |_| ());
+    assert!(report.errors().is_empty() && report.warnings().is_empty());
+    let schedules = &db.schedules()["C10001"];
+    assert_eq!(schedules.len(), 1);
+    assert_eq!(
+        *schedules[0].stp_indicator(),
+        STPIndicator::PermanentAssociation
+    );
+
+    // deleting the overlay again does nothing
+    let report = db.apply_records_with_progress(&[parse_record(&overlay).unwrap()], |_| ());
+    assert_eq!(report.warnings().len(), 1);
+
+    let report = db.apply_records_with_progress(&[parse_record(&permanent).unwrap()], |_| ());
+    assert!(report.warnings().is_empty());
+    assert!(!db.schedules().contains_key("C10001"));
+    assert!(db.schedules_matching_uid("C10001").is_empty());
+}
+
+#[test]
+fn test_reference_warnings() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let association = sample.lines().nth(10).unwrap().replacen("AAN", "AAD", 1);
+    let records: Vec<CIFRecord> = ["TDNOWHERE", &association]
+        .iter()
+        .map(|line| parse_record(line).unwrap())
+        .collect();
+
+    let mut db = ScheduleDatabase::new();
+    let report = db.apply_file_with_report(&sample_file());
+    assert!(report.errors().is_empty() && report.warnings().is_empty());
+    db.apply_records_with_progress(&records, |_| ());
+    let report = db.apply_records_with_progress(&records, |_| ());
+    let warnings: Vec<_> = report
+        .warnings()
+        .iter()
+        .map(|w| (w.category(), w.identifier().as_str()))
+        .collect();
+    assert_eq!(
+        warnings,
+        vec![
+            (ApplyWarningCategory::DeletedUnknownTIPLOC, "NOWHERE"),
+            (ApplyWarningCategory::DeletedUnknownAssociation, "C10001"),
+        ]
+    );
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    full_updates: usize,
+    inserted: Vec<String>,
+    revised: Vec<String>,
+    deleted: Vec<String>,
+    tiplocs: Vec<String>,
+    associations: usize,
+}
+
+impl ScheduleObserver for RecordingObserver {
+    fn on_full_update(&mut self) {
+        self.full_updates += 1;
+    }
+
+    fn on_schedule_inserted(&mut self, schedule: &Schedule) {
+        self.inserted.push(schedule.train_uid().clone());
+    }
+
+    fn on_schedule_revised(&mut self, previous: &Schedule, schedule: &Schedule) {
+        assert_eq!(previous.train_uid(), schedule.train_uid());
+        self.revised.push(schedule.train_uid().clone());
+    }
+
+    fn on_schedule_deleted(&mut self, schedule: &Schedule) {
+        self.deleted.push(schedule.train_uid().clone());
+    }
+
+    fn on_tiploc_changed(
+        &mut self,
+        tiploc: &str,
+        _previous: Option<&TIPLOC>,
+        _current: Option<&TIPLOC>,
+    ) {
+        self.tiplocs.push(tiploc.to_string());
+    }
+
+    fn on_association_changed(
+        &mut self,
+        _previous: Option<&Association>,
+        _current: Option<&Association>,
+    ) {
+        self.associations += 1;
+    }
+}
+
+#[test]
+fn test_schedule_observer() {
+    let mut db = ScheduleDatabase::new();
+    let mut observer = RecordingObserver::default();
+    let report = db.apply_records_observed(sample_file().records(), &mut observer);
+    assert!(report.errors().is_empty());
+    assert_eq!(observer.full_updates, 1);
+    assert_eq!(observer.inserted.len(), 8);
+    assert_eq!(observer.tiplocs.len(), 9);
+    assert_eq!(observer.associations, 1);
+
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let mut revision: Vec<String> = lines[11..20].iter().map(|l| l.to_string()).collect();
+    revision[0] = revision[0].replacen("BSN", "BSR", 1);
+    let overlay = lines[26].replacen("BSN", "BSD", 1);
+    let records: Vec<CIFRecord> = revision
+        .iter()
+        .map(String::as_str)
+        .chain([overlay.as_str(), "TDNOWHERE"])
+        .map(|line| parse_record(line).unwrap())
+        .collect();
+
+    let mut observer = RecordingObserver::default();
+    db.apply_records_observed(&records, &mut observer);
+    assert_eq!(observer.full_updates, 0);
+    assert!(observer.inserted.is_empty());
+    assert_eq!(observer.revised, vec!["C10001"]);
+    assert_eq!(observer.deleted, vec!["C10001"]);
+    // deleting an unknown TIPLOC changes nothing
+    assert!(observer.tiplocs.is_empty());
+}
+
+#[test]
+fn test_change_set() {
+    let mut db = ScheduleDatabase::new();
+    let (report, changes) = db.apply_file_with_changes(&sample_file());
+    assert!(report.errors().is_empty());
+    assert!(changes.full_update());
+    assert_eq!(changes.schedules().len(), 8);
+    assert!(changes
+        .schedules()
+        .iter()
+        .all(|c| c.kind() == ChangeKind::Inserted));
+    assert_eq!(changes.tiplocs().len(), 9);
+    assert_eq!(changes.associations()[0].main_train_uid(), "C10001");
+
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let overlay = sample.lines().nth(26).unwrap().replacen("BSN", "BSD", 1);
+    let file = parse_cif(format!("{overlay}\n{}\n", "ZZ".repeat(40)).as_bytes()).unwrap();
+    let (_, changes) = db.apply_file_with_changes(&file);
+    assert!(!changes.full_update());
+    assert_eq!(changes.schedules().len(), 1);
+    let change = &changes.schedules()[0];
+    assert_eq!(change.kind(), ChangeKind::Deleted);
+    assert_eq!(change.train_uid(), "C10001");
+    assert_eq!(
+        change.runs_from(),
+        NaiveDate::from_ymd_opt(2024, 6, 3).unwrap()
+    );
+    assert_eq!(
+        change.stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+
+    let (_, changes) = db.apply_file_with_changes(&file);
+    assert!(changes.is_empty());
+}
+
+#[test]
+#[cfg(not(feature = "panic-on-first-error"))]
+fn test_apply_file_transactional() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let association = sample
+        .lines()
+        .nth(10)
+        .unwrap()
+        .replacen("240520241213", "999999241213", 1);
+    let update = format!("{:80}\n{association}\n{}\n", "TDWATRLMN", "ZZ".repeat(40));
+    let update = parse_cif(update.as_bytes()).unwrap();
+
+    let mut db = ScheduleDatabase::new();
+    db.apply_file(&sample_file());
+    let report = db.apply_file_transactional(&update, 0).unwrap_err();
+    assert_eq!(report.errors().len(), 1);
+    // the TIPLOC deletion before the error was rolled back
+    assert!(db.tiplocs().contains_key("WATRLMN"));
+    assert_eq!(db.associations_by_main_uid("C10001").len(), 1);
+
+    let report = db.apply_file_transactional(&update, 1).unwrap();
+    assert_eq!(report.errors().len(), 1);
+    assert!(!db.tiplocs().contains_key("WATRLMN"));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_serde_round_trip() {
+    let text = ron::to_string(&sample_file()).unwrap();
+    let file: CIFFile = ron::from_str(&text).unwrap();
+    assert_eq!(file.records().len(), sample_file().records().len());
+
+    let mut db = ScheduleDatabase::new();
+    let report = db.apply_file_with_report(&file);
+    let text = ron::to_string(&db).unwrap();
+    let loaded: ScheduleDatabase = ron::from_str(&text).unwrap();
+    assert_eq!(loaded.schedules().len(), db.schedules().len());
+    assert_eq!(loaded.tiplocs(), db.tiplocs());
+    assert_eq!(
+        format!("{:?}", loaded.schedules()["C10001"]),
+        format!("{:?}", db.schedules()["C10001"])
+    );
+    assert_eq!(
+        loaded.schedules_matching_uid("C1000").len(),
+        db.schedules_matching_uid("C1000").len()
+    );
+
+    let loaded: ApplyReport = ron::from_str(&ron::to_string(&report).unwrap()).unwrap();
+    assert_eq!(loaded.warnings(), report.warnings());
+
+    // errors are kept when a report is round tripped
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let header =
+        sample
+            .lines()
+            .next()
+            .unwrap()
+            .replacen("PD2406010106242130", "PD2405310305242130", 1);
+    let trailer = sample.lines().last().unwrap();
+    let older = parse_cif(format!("{header}\n{trailer}\n").as_bytes()).unwrap();
+    db.set_panic_on_first_error(false);
+    let report = db.apply_file_with_report(&older);
+    let loaded: ApplyReport = ron::from_str(&ron::to_string(&report).unwrap()).unwrap();
+    assert!(matches!(
+        loaded.errors()[..],
+        [(0, ScheduleApplyError::AttemptingToApplyOlderData)]
+    ));
+}
+
+/// A store which keeps only the train UIDs and TIPLOC codes it is given.
+#[derive(Default)]
+struct CodeSink {
+    extract: Option<chrono::NaiveDateTime>,
+    train_uids: Vec<String>,
+    tiplocs: Vec<String>,
+    associations: usize,
+}
+
+impl ScheduleSink for CodeSink {
+    fn full_update(&mut self) {
+        *self = Self::default();
+    }
+
+    fn last_extract_date_time(&self) -> chrono::NaiveDateTime {
+        self.extract.unwrap_or_default()
+    }
+
+    fn set_extract(&mut self, extract_date_time: chrono::NaiveDateTime, _file_reference: String) {
+        self.extract = Some(extract_date_time);
+    }
+
+    fn upsert_tiploc(&mut self, tiploc: TIPLOC) -> Option<TIPLOC> {
+        self.tiplocs.push(tiploc.tiploc().clone());
+        None
+    }
+
+    fn delete_tiploc(&mut self, _tiploc: &str) -> Option<TIPLOC> {
+        None
+    }
+
+    fn upsert_association(&mut self, _association: Association) -> Option<Association> {
+        self.associations += 1;
+        None
+    }
+
+    fn delete_association(
+        &mut self,
+        _main_train_uid: &str,
+        _associated_train_uid: &str,
+        _start_date: NaiveDate,
+        _location: &str,
+        _stp_indicator: STPIndicator,
+    ) -> Option<Association> {
+        None
+    }
+
+    fn insert_schedule(&mut self, schedule: Schedule) {
+        self.train_uids.push(schedule.train_uid().clone());
+    }
+
+    fn revise_schedule(&mut self, _schedule: Schedule) -> Option<Schedule> {
+        None
+    }
+
+    fn delete_schedule(
+        &mut self,
+        _train_uid: &str,
+        _runs_from: NaiveDate,
+        _stp_indicator: STPIndicator,
+    ) -> Option<Schedule> {
+        None
+    }
+
+    fn has_schedules(&self, train_uid: &str) -> bool {
+        self.train_uids.iter().any(|uid| uid == train_uid)
+    }
+}
+
+#[test]
+fn test_schedule_sink() {
+    let mut sink = CodeSink::default();
+    let report = apply_records_to_sink(&mut sink, sample_file().records());
+    assert!(report.errors().is_empty() && report.warnings().is_empty());
+    assert_eq!(sink.tiplocs.len(), 9);
+    assert_eq!(sink.associations, 1);
+    assert_eq!(sink.train_uids.len(), 8);
+    assert_eq!(sink.train_uids[0], "C10001");
+    assert!(sink.extract.is_some());
+}
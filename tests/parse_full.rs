@@ -41,7 +41,7 @@ fn test_parse_full() {
             }
 
             // Test TIPLOCs don't have trailing number (issue #9)
-            for (_, sched_stack) in schedule.schedules() {
+            for sched_stack in schedule.schedules().values() {
                 for sched in sched_stack {
                     for waypoint in sched.journey() {
                         assert!(waypoint.tiploc().len() <= 7, "TIPLOC too long");
@@ -84,8 +84,7 @@ fn test_parse_full() {
             log::info!("Complete.\nErrors: {errors:?}");
             if env::var("SAVE_PARSED_OUTPUT")
                 .unwrap_or("no".to_string())
-                .to_ascii_lowercase()
-                == "yes"
+                .eq_ignore_ascii_case("yes")
             {
                 let path = "./target/test_parsed_schedule.ron";
                 log::info!("Saving output to {path}.");
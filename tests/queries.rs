@@ -0,0 +1,2097 @@
+use chrono::{Duration, NaiveDate};
+use nr_cif::{pagination::Paginate, prelude::*};
+
+use std::fs::File;
+
+fn load_sample() -> ScheduleDatabase {
+    let f = File::open("./tests/sample.cif").expect("cannot read file");
+    let file = parse_cif(f).expect("sample should parse");
+    let mut db = ScheduleDatabase::new();
+    let errors = db.apply_file(&file);
+    assert!(errors.is_empty(), "{errors:?}");
+    db
+}
+
+fn date(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+#[test]
+fn test_schedules_by_class() {
+    let db = load_sample();
+
+    let uids: Vec<_> = db
+        .schedules_by_class(444, Some("SW"), Some(date("2024-06-03")))
+        .iter()
+        .map(|s| (s.train_uid().as_str(), *s.stp_indicator()))
+        .collect();
+    assert_eq!(
+        uids,
+        vec![
+            ("C10001", STPIndicator::PermanentAssociation),
+            ("C10001", STPIndicator::STPOverlayOfPermanentAssociation),
+            ("C10003", STPIndicator::PermanentAssociation),
+            ("C10004", STPIndicator::PermanentAssociation),
+        ]
+    );
+
+    let uids: Vec<_> = db
+        .schedules_by_class(450, None, None)
+        .iter()
+        .map(|s| s.train_uid().as_str())
+        .collect();
+    assert_eq!(uids, vec!["C10002"]);
+
+    assert!(db.schedules_by_class(444, Some("GW"), None).is_empty());
+}
+
+#[test]
+fn test_fastest_service() {
+    let db = load_sample();
+
+    let fastest = db
+        .fastest_service("WAT", "BSK", date("2024-06-05"), None)
+        .expect("a service to exist");
+    assert_eq!(fastest.schedule().train_uid(), "C10002");
+    assert_eq!(fastest.duration(), chrono::Duration::minutes(40));
+
+    // C10002 is cancelled on this date, leaving the late service which runs past midnight
+    let fastest = db
+        .fastest_service("WATRLMN", "BSNGSTK", date("2024-06-04"), None)
+        .expect("a service to exist");
+    assert_eq!(fastest.schedule().train_uid(), "C10003");
+    assert_eq!(fastest.duration(), chrono::Duration::minutes(50));
+
+    let window = chrono::NaiveTime::from_hms_opt(6, 0, 0).unwrap()
+        ..=chrono::NaiveTime::from_hms_opt(7, 15, 0).unwrap();
+    let fastest = db
+        .fastest_service("WAT", "BSK", date("2024-06-03"), Some(window))
+        .expect("a service to exist");
+    assert_eq!(
+        *fastest.schedule().stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+
+    assert!(db
+        .fastest_service("BSK", "WAT", date("2024-06-05"), None)
+        .is_none());
+}
+
+#[test]
+fn test_schedule_segment() {
+    let db = load_sample();
+    let schedule = &db.schedules()["C10001"][0];
+
+    let segment: Vec<_> = schedule
+        .segment("CLPHMJC", "BSNGSTK")
+        .expect("segment should exist")
+        .iter()
+        .map(|loc| loc.tiploc().as_str())
+        .collect();
+    assert_eq!(segment, vec!["CLPHMJC", "SURBITN", "WOKING", "BSNGSTK"]);
+
+    assert!(schedule.segment("BSNGSTK", "CLPHMJC").is_none());
+    assert!(schedule.segment("WATRLMN", "EASTLGH").is_none());
+
+    // C10002 passes Woking without calling
+    let schedule = &db.schedules()["C10002"][0];
+    assert!(schedule.segment("WOKING", "BSNGSTK").is_none());
+}
+
+#[test]
+fn test_reachable_from() {
+    let db = load_sample();
+
+    let depart_after = date("2024-06-05").and_hms_opt(7, 0, 0).unwrap();
+    let reachable: Vec<_> = db
+        .reachable_from("CLJ", depart_after, chrono::Duration::minutes(60), true)
+        .iter()
+        .map(|r| (r.tiploc().clone(), r.arrival().time(), r.changes()))
+        .collect();
+    let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+    assert_eq!(
+        reachable,
+        vec![
+            ("SURBITN".to_string(), time(7, 18), 0),
+            ("WOKING".to_string(), time(7, 30), 0),
+            ("BSNGSTK".to_string(), time(7, 52), 0),
+        ]
+    );
+}
+
+#[test]
+fn test_dated_trips() {
+    let db = load_sample();
+
+    let mut calendar = BankHolidayCalendar::new();
+    calendar.add_bank_holiday_monday(date("2024-06-03"));
+
+    let trips = db.dated_trips_with_calendar(date("2024-06-03")..=date("2024-06-04"), &calendar);
+    let uids: Vec<_> = trips
+        .iter()
+        .map(|t| (t.date(), t.schedule().train_uid().as_str()))
+        .collect();
+    assert_eq!(
+        uids,
+        vec![
+            (date("2024-06-03"), "L10005"),
+            (date("2024-06-03"), "C10004"),
+            (date("2024-06-03"), "C10001"),
+            (date("2024-06-03"), "C10002"),
+            (date("2024-06-04"), "L10005"),
+            (date("2024-06-04"), "C10004"),
+            (date("2024-06-04"), "C10001"),
+            (date("2024-06-04"), "C10003"),
+        ]
+    );
+
+    let overnight = trips.last().unwrap();
+    assert_eq!(
+        overnight.calls().last().unwrap().public_arrival(),
+        date("2024-06-05").and_hms_opt(1, 0, 0)
+    );
+}
+
+#[test]
+fn test_station_timetable() {
+    let db = load_sample();
+
+    let timetable = db.station_timetable("WAT", date("2024-06-05"));
+    assert_eq!(
+        timetable.to_string(),
+        "Departures from WAT on 2024-06-05
+
+To BASINGSTOKE
+  07:30    10  SW  C10002
+
+To SOUTHAMPTON CENTRAL
+  07:00    12  SW  C10001
+  23:30     5  SW  C10003"
+    );
+}
+
+#[test]
+fn test_cancellations_at() {
+    let db = load_sample();
+
+    let cancelled: Vec<_> = db
+        .cancellations_at("CLJ", date("2024-06-04"))
+        .iter()
+        .map(|s| s.train_uid().as_str())
+        .collect();
+    assert_eq!(cancelled, vec!["C10002"]);
+
+    // C10002 only passes through Woking
+    assert!(db.cancellations_at("WOK", date("2024-06-04")).is_empty());
+    assert!(db.cancellations_at("CLJ", date("2024-06-05")).is_empty());
+}
+
+#[test]
+fn test_materialise() {
+    let db = load_sample();
+
+    let calls = db.schedules()["C10002"][0].materialise(date("2024-06-05"));
+    assert_eq!(
+        calls[1].arrival(),
+        date("2024-06-05").and_hms_opt(7, 37, 30)
+    );
+    assert_eq!(calls[2].pass(), date("2024-06-05").and_hms_opt(7, 52, 0));
+
+    let calls = db.schedules()["C10003"][0].materialise(date("2024-06-05"));
+    assert_eq!(
+        calls[1].arrival(),
+        date("2024-06-05").and_hms_opt(23, 59, 0)
+    );
+    assert_eq!(
+        calls[1].departure(),
+        date("2024-06-06").and_hms_opt(0, 1, 0)
+    );
+    assert_eq!(
+        calls[1].public_departure(),
+        date("2024-06-06").and_hms_opt(0, 1, 0)
+    );
+}
+
+#[test]
+fn test_positioning_moves() {
+    let db = load_sample();
+
+    let uids: Vec<_> = db
+        .positioning_moves("CLJ", "WAT", date("2024-06-05"))
+        .iter()
+        .map(|s| s.train_uid().as_str())
+        .collect();
+    assert_eq!(uids, vec!["C10004"]);
+    assert!(db
+        .positioning_moves("WAT", "CLJ", date("2024-06-05"))
+        .is_empty());
+}
+
+#[test]
+fn test_timing_anomalies() {
+    let db = load_sample();
+    let schedule = &db.schedules()["C10001"][0];
+
+    assert!(schedule.timing_anomalies(|_, _| None).is_empty());
+
+    let anomalies = schedule.timing_anomalies(|from, to| match (from, to) {
+        ("WATRLMN", "CLPHMJC") => Some(20.0),
+        ("CLPHMJC", "SURBITN") => Some(8.0),
+        _ => None,
+    });
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!(anomalies[0].index(), 0);
+    assert_eq!(anomalies[0].to(), "CLPHMJC");
+    assert!(matches!(
+        anomalies[0].kind(),
+        TimingAnomalyKind::ExcessiveSpeed(speed) if speed > 100.0
+    ));
+}
+
+#[test]
+fn test_schedules_matching_uid() {
+    let db = load_sample();
+
+    let uids = |pattern: &str| -> Vec<_> {
+        db.schedules_matching_uid(pattern)
+            .iter()
+            .map(|s| s.train_uid().as_str())
+            .collect()
+    };
+    assert_eq!(
+        uids("C1000*"),
+        vec!["C10001", "C10001", "C10002", "C10002", "C10003", "C10004", "C10006"]
+    );
+    assert_eq!(uids("?10005"), vec!["L10005"]);
+    assert_eq!(uids("C1000?"), uids("C1000*"));
+    assert!(uids("C1000").is_empty());
+}
+
+#[test]
+fn test_paginate() {
+    let db = load_sample();
+
+    let page = db.schedules_matching_uid("C*").into_iter().paginate(5, 5);
+    assert_eq!(page.total(), 7);
+    assert_eq!(page.items().len(), 2);
+    assert!(!page.has_more());
+
+    let page = db.schedules_matching_uid("C*").into_iter().paginate(0, 5);
+    assert_eq!(page.next_offset(), Some(5));
+}
+
+#[test]
+fn test_paged_queries() {
+    let db = load_sample();
+
+    let page = db.schedules_matching_uid_page("C*", 5, 5);
+    assert_eq!(page.total(), 7);
+    assert_eq!(
+        page.into_items(),
+        db.schedules_matching_uid("C*")[5..].to_vec()
+    );
+
+    let all: Vec<_> = db.query().execute().collect();
+    let page = db.query().execute_page(1, 2);
+    assert_eq!(page.total(), all.len());
+    assert_eq!(page.items()[..], all[1..3]);
+
+    let from = date("2024-06-03").and_hms_opt(6, 55, 0).unwrap();
+    let page = db.departures_page("WAT", from, Duration::hours(1), BoardOrder::default(), 1, 1);
+    assert_eq!(page.total(), 2);
+    assert!(!page.has_more());
+    let departure = &page.items()[0];
+    assert_eq!(departure.train_uid(), "C10002");
+    assert_eq!(
+        departure.calling_points().len(),
+        db.departures("WAT", from, Duration::hours(1))[1]
+            .calling_points()
+            .len()
+    );
+}
+
+#[test]
+fn test_next_departures_to() {
+    let db = load_sample();
+
+    let departures: Vec<_> = db
+        .next_departures_to(
+            "WAT",
+            "SOU",
+            date("2024-06-07").and_hms_opt(8, 0, 0).unwrap(),
+            3,
+        )
+        .iter()
+        .map(|d| (d.service().schedule().train_uid().as_str(), d.departure()))
+        .collect();
+    assert_eq!(
+        departures,
+        vec![
+            ("C10003", date("2024-06-07").and_hms_opt(23, 30, 0).unwrap()),
+            ("C10003", date("2024-06-08").and_hms_opt(23, 30, 0).unwrap()),
+            ("C10003", date("2024-06-09").and_hms_opt(23, 30, 0).unwrap()),
+        ]
+    );
+
+    assert!(db
+        .next_departures_to(
+            "WAT",
+            "SOU",
+            date("2024-06-07").and_hms_opt(8, 0, 0).unwrap(),
+            0,
+        )
+        .is_empty());
+}
+
+#[test]
+fn test_connection_feasible() {
+    let db = load_sample();
+    let c10004 = &db.schedules()["C10004"][0];
+    let c10002 = &db.schedules()["C10002"][0];
+    let c10001 = &db.schedules()["C10001"][0];
+
+    // C10004 is empty stock and sets down nobody at Waterloo
+    assert!(db
+        .connection_feasible(
+            c10004,
+            c10002,
+            "WAT",
+            date("2024-06-05"),
+            Duration::minutes(5)
+        )
+        .is_none());
+
+    let connection = db
+        .connection_feasible(
+            c10001,
+            c10002,
+            "CLJ",
+            date("2024-06-05"),
+            Duration::minutes(5),
+        )
+        .expect("both trains call at Clapham Junction");
+    assert_eq!(connection.margin(), Duration::minutes(31));
+    assert!(connection.feasible());
+
+    let connection = db
+        .connection_feasible(
+            c10002,
+            c10001,
+            "CLJ",
+            date("2024-06-05"),
+            Duration::minutes(5),
+        )
+        .expect("both trains call at Clapham Junction");
+    assert_eq!(connection.margin(), Duration::minutes(-29));
+    assert!(!connection.feasible());
+}
+
+#[test]
+fn test_operator_routes() {
+    let db = load_sample();
+
+    let routes = db.operator_routes(date("2024-06-05"));
+    assert_eq!(routes.keys().collect::<Vec<_>>(), vec!["SW", "ZZ"]);
+
+    let sw = &routes["SW"];
+    let pairs: Vec<_> = sw
+        .origin_destinations()
+        .iter()
+        .map(|(o, d)| (o.as_str(), d.as_str()))
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("CLPHMJW", "WATRLMN"),
+            ("WATRLMN", "BSNGSTK"),
+            ("WATRLMN", "SOTON"),
+        ]
+    );
+    // C10001 and C10003 share an origin and destination, but not a calling pattern
+    assert_eq!(sw.calling_patterns().len(), 4);
+    assert_eq!(
+        sw.calling_patterns()[1].calls(),
+        &vec!["WATRLMN", "CLPHMJC", "BSNGSTK"]
+    );
+}
+
+#[test]
+fn test_suggest_locations() {
+    let db = load_sample();
+
+    let suggestions = db.suggest_locations("clk", 2);
+    assert_eq!(suggestions[0].tiploc(), "CLPHMJC");
+    assert_eq!(suggestions[0].crs().as_deref(), Some("CLJ"));
+    assert_eq!(suggestions[0].distance(), 1);
+
+    let suggestions = db.suggest_locations("Southampton Centrl", 1);
+    assert_eq!(suggestions[0].tiploc(), "SOTON");
+}
+
+#[cfg(feature = "lru")]
+#[test]
+fn test_board_cache() {
+    let cache = BoardCache::new(load_sample(), std::num::NonZeroUsize::new(8).unwrap());
+
+    let first = cache.station_timetable("WAT", date("2024-06-05"));
+    let second = cache.station_timetable("WAT", date("2024-06-05"));
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+
+    cache.clear();
+    let third = cache.station_timetable("WAT", date("2024-06-05"));
+    assert!(!std::sync::Arc::ptr_eq(&first, &third));
+    assert_eq!(first.to_string(), third.to_string());
+
+    // requests in the same minute share a board
+    let at = |h, m, s| date("2024-06-05").and_hms_opt(h, m, s).unwrap();
+    let first = cache.departures("WAT", at(7, 0, 10), Duration::hours(1));
+    let second = cache.departures("WAT", at(7, 0, 50), Duration::hours(1));
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+    let later = cache.departures("WAT", at(7, 1, 0), Duration::hours(1));
+    assert!(!std::sync::Arc::ptr_eq(&first, &later));
+
+    let mut cache = cache;
+    cache.set_bucket(Duration::minutes(15));
+    let first = cache.arrivals("SOU", at(8, 16, 0), Duration::hours(1));
+    let second = cache.arrivals("SOU", at(8, 29, 30), Duration::hours(1));
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+    assert_eq!(*cache.bucket(), Duration::minutes(15));
+}
+
+#[test]
+fn test_associations() {
+    let db = load_sample();
+
+    let associations = db.associations_by_main_uid("C10001");
+    assert_eq!(associations.len(), 1);
+    let association = &associations[0];
+    assert_eq!(association.associated_train_uid(), "C10002");
+    assert_eq!(*association.category(), AssociationCategory::Divide);
+    assert_eq!(association.location(), "WOKING");
+    assert_eq!(*association.association_type(), AssociationType::Passenger);
+    assert!(association.runs_on(date("2024-06-03")));
+    assert!(!association.runs_on(date("2024-06-08")));
+
+    assert_eq!(db.associations_by_associated_uid("C10002").len(), 1);
+    assert!(db.associations_by_associated_uid("C10001").is_empty());
+}
+
+#[test]
+fn test_effective_schedule() {
+    let db = load_sample();
+
+    let permanent = db.effective_schedule("C10001", date("2024-06-04")).unwrap();
+    assert_eq!(
+        *permanent.stp_indicator(),
+        STPIndicator::PermanentAssociation
+    );
+    let overlay = db.effective_schedule("C10001", date("2024-06-03")).unwrap();
+    assert_eq!(
+        *overlay.stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+    // cancelled
+    assert!(db
+        .effective_schedule("C10002", date("2024-06-04"))
+        .is_none());
+    // doesn't run on Saturdays
+    assert!(db
+        .effective_schedule("C10001", date("2024-06-08"))
+        .is_none());
+    assert!(db
+        .effective_schedule("X99999", date("2024-06-03"))
+        .is_none());
+}
+
+#[test]
+fn test_bank_holiday_calendar() {
+    let calendar = BankHolidayCalendar::great_britain(2024..=2025);
+    let mondays: Vec<_> = calendar
+        .bank_holiday_mondays()
+        .iter()
+        .filter(|d| d.format("%Y").to_string() == "2024")
+        .copied()
+        .collect();
+    assert_eq!(
+        mondays,
+        vec![
+            date("2024-01-01"),
+            date("2024-04-01"),
+            date("2024-05-06"),
+            date("2024-05-27"),
+            date("2024-08-26"),
+        ]
+    );
+    assert_eq!(calendar.bank_holiday_mondays().len(), 9);
+    assert!(calendar
+        .glasgow_bank_holidays()
+        .contains(&date("2024-07-15")));
+    assert!(calendar
+        .glasgow_bank_holidays()
+        .contains(&date("2025-09-29")));
+
+    let mut db = load_sample();
+    assert!(db
+        .effective_schedule("C10003", date("2024-05-27"))
+        .is_some());
+    db.set_bank_holidays(calendar);
+    assert!(db
+        .effective_schedule("C10003", date("2024-05-27"))
+        .is_none());
+    assert!(db
+        .effective_schedule("C10003", date("2024-05-28"))
+        .is_some());
+    // runs normally on bank holidays
+    assert!(db
+        .effective_schedule("C10001", date("2024-05-27"))
+        .is_some());
+}
+
+#[test]
+fn test_bank_holiday_overlay() {
+    let mut calendar = BankHolidayCalendar::new();
+    calendar.add_bank_holiday_monday(date("2024-06-03"));
+
+    // the overlay of C10001 on the Monday doesn't run on bank holiday Mondays
+    let sample = std::fs::read_to_string("./tests/sample.cif")
+        .unwrap()
+        .replacen(
+            "BSNC100012406032406031000000 P",
+            "BSNC100012406032406031000000XP",
+            1,
+        );
+    let mut db = ScheduleDatabase::new();
+    assert!(db
+        .apply_file(&parse_cif(sample.as_bytes()).unwrap())
+        .is_empty());
+    assert!(db
+        .effective_schedule("C10001", date("2024-06-03"))
+        .is_some());
+    db.set_bank_holidays(calendar.clone());
+    // the permanent schedule it replaces doesn't run in its place
+    assert!(db
+        .effective_schedule("C10001", date("2024-06-03"))
+        .is_none());
+    assert_eq!(
+        *db.effective_schedule("C10001", date("2024-06-04"))
+            .unwrap()
+            .stp_indicator(),
+        STPIndicator::PermanentAssociation
+    );
+
+    // dated trips use the database's calendar, unless another is given
+    let uids = |trips: Vec<DatedTrip>| {
+        trips
+            .iter()
+            .map(|trip| trip.schedule().train_uid().clone())
+            .collect::<Vec<_>>()
+    };
+    let monday = date("2024-06-03")..=date("2024-06-03");
+    assert_eq!(
+        uids(db.dated_trips(monday.clone())),
+        ["L10005", "C10004", "C10002"]
+    );
+    assert_eq!(
+        uids(db.dated_trips_with_calendar(monday.clone(), &BankHolidayCalendar::new())).len(),
+        5
+    );
+    assert_eq!(
+        uids(db.dated_trips_with_calendar(monday, &calendar)),
+        ["L10005", "C10004", "C10002"]
+    );
+}
+
+#[test]
+fn test_tiplocs_for_stanox() {
+    let mut db = load_sample();
+
+    let waterloo = &db.tiplocs()["WATRLMN"];
+    assert_eq!(*waterloo.nlc(), 559800);
+    assert_eq!(*waterloo.nlc_check_char(), 'A');
+    assert_eq!(waterloo.nlc_description(), "WATERLOO");
+
+    let clapham: Vec<_> = db
+        .tiplocs_for_stanox(87215)
+        .iter()
+        .map(|t| t.tiploc().as_str())
+        .collect();
+    assert_eq!(clapham, vec!["CLPHMJC", "CLPHMJW"]);
+    assert!(db.tiplocs_for_stanox(0).is_empty());
+
+    let records = [parse_record("TDCLPHMJW").unwrap()];
+    db.apply_records(&records);
+    assert_eq!(db.tiplocs_for_stanox(87215).len(), 1);
+}
+
+#[test]
+fn test_crs_lookups() {
+    let db = load_sample();
+
+    let clapham: Vec<_> = db
+        .get_tiplocs_for_crs("CLJ")
+        .iter()
+        .map(|t| t.tiploc().as_str())
+        .collect();
+    assert_eq!(clapham, vec!["CLPHMJC", "CLPHMJW"]);
+    assert!(db.get_tiplocs_for_crs("XXX").is_empty());
+
+    let waterloo = db.schedules_calling_at_crs("WAT");
+    assert!(!waterloo.is_empty());
+    assert!(waterloo
+        .iter()
+        .all(|s| s.journey().iter().any(|l| l.tiploc() == "WATRLMN")));
+}
+
+#[test]
+fn test_schedules_at_stanox() {
+    let db = load_sample();
+
+    let clapham = db.schedules_at_stanox(87215);
+    assert!(!clapham.is_empty());
+    assert!(clapham
+        .iter()
+        .all(|s| s.journey().iter().any(|l| l.tiploc().starts_with("CLPHMJ"))));
+    assert!(db.schedules_at_stanox(0).is_empty());
+}
+
+#[test]
+fn test_find_by_headcode() {
+    let db = load_sample();
+
+    assert_eq!(db.schedules_by_headcode("1B01").len(), 2);
+
+    let date = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+    let found = db.find_by_headcode("1B01", date);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].train_uid(), "C10001");
+    assert_eq!(
+        *found[0].stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+
+    assert_eq!(db.find_by_headcode_in_area("1B01", date, 87).len(), 1);
+    assert!(db.find_by_headcode_in_area("1B01", date, 12).is_empty());
+    assert!(db.find_by_headcode("9Z99", date).is_empty());
+}
+
+#[test]
+fn test_schedules_by_operator() {
+    let db = load_sample();
+
+    let swr = db.schedules_by_operator(TrainOperator::SouthWesternRailway.atoc_code());
+    assert!(!swr.is_empty());
+    assert!(swr
+        .iter()
+        .all(|s| s.operator() == Some(TrainOperator::SouthWesternRailway)));
+    assert_eq!(db.schedules_by_operator("ZZ").len(), 1);
+    assert!(db.schedules_by_operator("XX").is_empty());
+
+    assert_eq!(
+        TrainOperator::from_atoc_code("SW").unwrap().name(),
+        "South Western Railway"
+    );
+    assert_eq!(TrainOperator::from_atoc_code("QQ"), None);
+}
+
+#[test]
+fn test_schedules_by_service_code() {
+    let db = load_sample();
+
+    let schedules = db.schedules_by_service_code("24671006");
+    assert!(!schedules.is_empty());
+    assert!(schedules
+        .iter()
+        .all(|s| s.train_service_code() == "24671006"));
+    assert!(db.schedules_by_service_code("99999999").is_empty());
+}
+
+#[test]
+fn test_departures() {
+    let db = load_sample();
+
+    let from = date("2024-06-03").and_hms_opt(6, 55, 0).unwrap();
+    let departures = db.departures("WAT", from, Duration::hours(1));
+    let uids: Vec<_> = departures.iter().map(|d| d.train_uid().as_str()).collect();
+    // C10001 runs to a short term plan on Mondays
+    assert_eq!(uids, vec!["C10001", "C10002"]);
+
+    let first = &departures[0];
+    assert_eq!(first.time(), from + Duration::minutes(10));
+    assert_eq!(first.platform(), "13");
+    assert_eq!(first.destination_name(), "SOUTHAMPTON CENTRAL");
+    assert_eq!(first.headcode(), "1B01");
+    let calls: Vec<_> = first
+        .calling_points()
+        .iter()
+        .map(|c| c.tiploc().as_str())
+        .collect();
+    assert_eq!(
+        calls,
+        vec!["CLPHMJC", "SURBITN", "WOKING", "BSNGSTK", "WINCHSR", "SOTON"]
+    );
+
+    // the overnight train from the day before still calls at Woking after midnight
+    let late = date("2024-06-03").and_hms_opt(0, 0, 0).unwrap();
+    let departures = db.departures("WOK", late, Duration::minutes(30));
+    assert_eq!(departures.len(), 1);
+    assert_eq!(departures[0].running_date(), date("2024-06-02"));
+}
+
+#[test]
+fn test_arrivals() {
+    let db = load_sample();
+
+    let from = date("2024-06-05").and_hms_opt(7, 45, 0).unwrap();
+    let arrivals = db.arrivals("BSK", from, Duration::minutes(30));
+    let uids: Vec<_> = arrivals.iter().map(|a| a.train_uid().as_str()).collect();
+    assert_eq!(uids, vec!["C10001", "C10002"]);
+
+    let first = &arrivals[0];
+    assert_eq!(first.time(), from + Duration::minutes(7));
+    assert_eq!(first.origin_name(), "LONDON WATERLOO");
+    let calls: Vec<_> = first
+        .calling_points()
+        .iter()
+        .map(|c| c.tiploc().as_str())
+        .collect();
+    assert_eq!(calls, vec!["WATRLMN", "CLPHMJC", "SURBITN", "WOKING"]);
+    assert_eq!(
+        *first.formation().power_type(),
+        PowerType::ElectricMultipleUnit
+    );
+
+    // trains aren't shown arriving where they start
+    assert!(db
+        .arrivals("WAT", from - Duration::hours(1), Duration::hours(1))
+        .is_empty());
+}
+
+#[test]
+fn test_direct_services() {
+    let db = load_sample();
+
+    let services = db.direct_services("WAT", "BSK", date("2024-06-05"));
+    let uids: Vec<_> = services
+        .iter()
+        .map(|s| s.schedule().train_uid().as_str())
+        .collect();
+    assert_eq!(uids, vec!["C10001", "C10002", "C10003"]);
+    assert_eq!(
+        (
+            *services[1].departure().hour(),
+            *services[1].departure().minute()
+        ),
+        (7, 30)
+    );
+    assert_eq!(
+        (
+            *services[1].arrival().hour(),
+            *services[1].arrival().minute()
+        ),
+        (8, 10)
+    );
+
+    // no services run the other way
+    assert!(db
+        .direct_services("BSK", "WAT", date("2024-06-05"))
+        .is_empty());
+}
+
+#[test]
+fn test_instances() {
+    let db = load_sample();
+    let dates = date("2024-06-01")..=date("2024-06-09");
+
+    let permanent = &db.schedules()["C10001"][0];
+    let runs: Vec<_> = permanent
+        .instances(dates.clone())
+        .iter()
+        .map(|r| r.date())
+        .collect();
+    assert_eq!(runs.len(), 5);
+    assert_eq!(runs[0], date("2024-06-03"));
+
+    // the overlay is in force on the Monday
+    let runs = db.instances("C10001", dates.clone());
+    assert_eq!(runs.len(), 5);
+    assert_eq!(
+        *runs[0].schedule().stp_indicator(),
+        STPIndicator::STPOverlayOfPermanentAssociation
+    );
+    assert_eq!(
+        *runs[1].schedule().stp_indicator(),
+        STPIndicator::PermanentAssociation
+    );
+
+    // and the cancellation removes the Tuesday
+    let runs = db.instances("C10002", dates);
+    assert_eq!(runs.len(), 4);
+    assert!(runs.iter().all(|r| r.date() != date("2024-06-04")));
+}
+
+#[test]
+fn test_journey_time_arithmetic() {
+    let time = |s: &str| s.parse::<JourneyTime>().unwrap();
+
+    let late = time("2359H");
+    assert_eq!(late.to_seconds(), 86370);
+    assert_eq!(
+        late.to_naive_time(),
+        chrono::NaiveTime::from_hms_opt(23, 59, 30).unwrap()
+    );
+    assert_eq!(late.duration_until(&time("0001 ")), Duration::seconds(90));
+    assert_eq!(
+        time("0700 ").duration_until(&time("0730H")),
+        Duration::seconds(1830)
+    );
+
+    assert_eq!(late.add_minutes(2), time("0001H"));
+    assert_eq!(time("0001 ").add_minutes(-2), time("2359 "));
+}
+
+#[cfg(feature = "chrono-tz")]
+#[test]
+fn test_uk_datetimes() {
+    use chrono::{TimeZone, Utc};
+
+    let time = |s: &str| s.parse::<JourneyTime>().unwrap();
+    let utc = |h, m| Utc.with_ymd_and_hms(2024, 3, 31, h, m, 0).unwrap();
+
+    // British Summer Time
+    assert_eq!(
+        time("0700 ").to_utc(date("2024-06-03")),
+        Utc.with_ymd_and_hms(2024, 6, 3, 6, 0, 0).unwrap()
+    );
+    // the clocks go forward, and 01:30 is read as GMT
+    assert_eq!(time("0130 ").to_utc(date("2024-03-31")), utc(1, 30));
+    assert_eq!(time("0230 ").to_utc(date("2024-03-31")), utc(1, 30));
+    // the clocks go back, and the first 01:30 is taken
+    assert_eq!(
+        time("0130 ").to_utc(date("2024-10-27")),
+        Utc.with_ymd_and_hms(2024, 10, 27, 0, 30, 0).unwrap()
+    );
+    assert_eq!(time("0000 ").to_utc(date("2024-04-01")), utc(23, 0));
+}
+
+#[test]
+fn test_schedule_locations() {
+    let db = load_sample();
+
+    let schedule = &db.schedules()["C10002"][0];
+    assert_eq!(schedule.origin().unwrap().tiploc(), "WATRLMN");
+    assert_eq!(schedule.destination().unwrap().tiploc(), "BSNGSTK");
+    let calls: Vec<_> = schedule
+        .public_calling_points()
+        .iter()
+        .map(|l| l.tiploc().as_str())
+        .collect();
+    assert_eq!(calls, vec!["WATRLMN", "CLPHMJC", "BSNGSTK"]);
+    let passes: Vec<_> = schedule
+        .passes()
+        .iter()
+        .map(|l| l.tiploc().as_str())
+        .collect();
+    assert_eq!(passes, vec!["WOKING"]);
+
+    // empty coaching stock has no public calls
+    assert!(db.schedules()["C10004"][0]
+        .public_calling_points()
+        .is_empty());
+}
+
+#[test]
+fn test_durations() {
+    let db = load_sample();
+
+    let schedule = &db.schedules()["C10001"][0];
+    assert_eq!(schedule.duration(), Some(Duration::minutes(85)));
+    assert_eq!(schedule.public_duration(), Some(Duration::minutes(85)));
+
+    let overnight = &db.schedules()["C10003"][0];
+    assert_eq!(overnight.duration(), Some(Duration::minutes(90)));
+    assert_eq!(
+        overnight.journey()[1].dwell_time(),
+        Some(Duration::minutes(2))
+    );
+
+    let half = &db.schedules()["C10002"][0].journey()[1];
+    assert_eq!(half.dwell_time(), Some(Duration::minutes(1)));
+    assert_eq!(half.public_dwell_time(), Some(Duration::minutes(1)));
+    // nothing to wait between at the origin
+    assert_eq!(schedule.journey()[0].dwell_time(), None);
+}
+
+#[test]
+fn test_sectional_running_times() {
+    let db = load_sample();
+
+    let times = db.sectional_running_times("WOKING", "BSNGSTK");
+    assert_eq!(times.len(), 2);
+    // C10001 and its overlay
+    assert_eq!(*times[0].train_category(), TrainCategory::ExpressPassenger);
+    assert_eq!(times[0].samples(), 2);
+    assert_eq!(times[0].median(), Duration::minutes(21));
+    // C10002 passes Woking, and C10003 leaves it after midnight
+    assert_eq!(times[1].minimum(), Duration::minutes(18));
+    assert_eq!(times[1].median(), Duration::seconds(18 * 60 + 30));
+    assert_eq!(times[1].maximum(), Duration::minutes(19));
+
+    assert!(db.sectional_running_times("BSNGSTK", "WOKING").is_empty());
+}
+
+#[test]
+fn test_schedule_query() {
+    let db = load_sample();
+
+    assert_eq!(db.query().execute().count(), 8);
+
+    fn uids<'a>(query: ScheduleQuery<'a>) -> Vec<(&'a str, STPIndicator)> {
+        query
+            .execute()
+            .map(|s| (s.train_uid().as_str(), *s.stp_indicator()))
+            .collect()
+    }
+    assert_eq!(
+        uids(db.query().operator("SW").calling_at("SOTON")),
+        vec![
+            ("C10001", STPIndicator::PermanentAssociation),
+            ("C10001", STPIndicator::STPOverlayOfPermanentAssociation),
+            ("C10003", STPIndicator::PermanentAssociation),
+            ("C10006", STPIndicator::NewSTPAssociation),
+        ]
+    );
+    assert_eq!(
+        uids(
+            db.query()
+                .calling_at("SOTON")
+                .stp_indicator(STPIndicator::PermanentAssociation)
+        ),
+        vec![
+            ("C10001", STPIndicator::PermanentAssociation),
+            ("C10003", STPIndicator::PermanentAssociation),
+        ]
+    );
+    // C10002 only passes Woking
+    assert_eq!(
+        db.query()
+            .calling_at("WOKING")
+            .calling_at("BSNGSTK")
+            .execute()
+            .filter(|s| s.train_uid() == "C10002")
+            .count(),
+        0
+    );
+    assert_eq!(
+        uids(db.query().days_run(DaysRun::WEEKENDS)),
+        vec![
+            ("C10003", STPIndicator::PermanentAssociation),
+            ("C10006", STPIndicator::NewSTPAssociation),
+        ]
+    );
+    assert_eq!(
+        uids(
+            db.query()
+                .category(TrainCategory::OrdinaryPassenger)
+                .running_between(date("2024-06-08"), date("2024-06-09"))
+        ),
+        vec![
+            ("C10003", STPIndicator::PermanentAssociation),
+            ("C10006", STPIndicator::NewSTPAssociation),
+        ]
+    );
+    assert_eq!(
+        uids(db.query().power_type(PowerType::Diesel)),
+        vec![("L10005", STPIndicator::PermanentAssociation)]
+    );
+}
+
+#[test]
+fn test_first_and_last_departure() {
+    let db = load_sample();
+    let summary = |departure: Option<NextDeparture>| {
+        departure.map(|d| (d.service().schedule().train_uid().clone(), d.departure()))
+    };
+
+    // the overlay moves the first train to 07:05 on the Monday
+    assert_eq!(
+        summary(db.first_departure("WAT", "BSNGSTK", date("2024-06-03"))),
+        Some((
+            "C10001".to_string(),
+            date("2024-06-03").and_hms_opt(7, 5, 0).unwrap()
+        ))
+    );
+    assert_eq!(
+        summary(db.last_departure("WAT", "BSNGSTK", date("2024-06-03"))),
+        Some((
+            "C10003".to_string(),
+            date("2024-06-03").and_hms_opt(23, 30, 0).unwrap()
+        ))
+    );
+    // the last train of the day leaves Woking after midnight
+    assert_eq!(
+        summary(db.last_departure("WOKING", "SOU", date("2024-06-03"))),
+        Some((
+            "C10003".to_string(),
+            date("2024-06-04").and_hms_opt(0, 1, 0).unwrap()
+        ))
+    );
+    assert!(db
+        .first_departure("SOU", "WAT", date("2024-06-03"))
+        .is_none());
+}
+
+#[test]
+fn test_service_frequency() {
+    let db = load_sample();
+
+    // C10001 at 07:00, C10002 at 07:30 and C10003 at 23:30
+    let frequency = db.service_frequency("WAT", "BSNGSTK", date("2024-06-05"));
+    assert_eq!(frequency.services(), 3);
+    let at = |h, m| date("2024-06-05").and_hms_opt(h, m, 0).unwrap();
+    assert_eq!(
+        frequency.per_hour().iter().collect::<Vec<_>>(),
+        vec![(&at(7, 0), &2), (&at(23, 0), &1)]
+    );
+    assert_eq!(frequency.minimum_headway(), Some(Duration::minutes(30)));
+    assert_eq!(frequency.maximum_headway(), Some(Duration::minutes(960)));
+    assert_eq!(frequency.mean_headway(), Some(Duration::minutes(495)));
+
+    let frequency = db.service_frequency("SOU", "WAT", date("2024-06-08"));
+    assert_eq!(frequency.services(), 1);
+    assert_eq!(frequency.mean_headway(), None);
+}
+
+#[test]
+fn test_platform_occupancy() {
+    let mut db = load_sample();
+    // a short term planning train on the Tuesday, booked into platform 2 at Woking as C10001
+    // is there
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let records: Vec<CIFRecord> = sample.lines().collect::<Vec<_>>()[51..56]
+        .iter()
+        .map(|line| {
+            line.replacen(
+                "BSNC100062406082406080000010",
+                "BSNC100072406042406040100000",
+                1,
+            )
+            .replacen("1000 10003", "0700 07003", 1)
+            .replacen("1045 1046      104510463", "0730 0732      073007322", 1)
+        })
+        .map(|line| parse_record(&line).unwrap())
+        .collect();
+    assert!(db.apply_records(&records).is_empty());
+
+    let at = |d, h, m| date(d).and_hms_opt(h, m, 0).unwrap();
+    let occupancy = db.platform_occupancy("WOK", date("2024-06-04"));
+    let summary: Vec<_> = occupancy
+        .iter()
+        .map(|platform| {
+            (
+                platform.platform().as_str(),
+                platform
+                    .occupations()
+                    .iter()
+                    .map(|o| {
+                        (
+                            o.train_uid().as_str(),
+                            o.from(),
+                            o.until(),
+                            o.conflicts().len(),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "1",
+                vec![
+                    // C10003 started its journey the evening before
+                    (
+                        "C10003",
+                        at("2024-06-03", 23, 59),
+                        at("2024-06-04", 0, 1),
+                        0
+                    ),
+                    (
+                        "C10003",
+                        at("2024-06-04", 23, 59),
+                        at("2024-06-05", 0, 1),
+                        0
+                    ),
+                ]
+            ),
+            (
+                "2",
+                vec![
+                    (
+                        "C10001",
+                        at("2024-06-04", 7, 30),
+                        at("2024-06-04", 7, 31),
+                        1
+                    ),
+                    (
+                        "C10007",
+                        at("2024-06-04", 7, 30),
+                        at("2024-06-04", 7, 32),
+                        1
+                    ),
+                ]
+            ),
+            // C10002 passes without a platform, and is cancelled on the Tuesday anyway
+        ]
+    );
+    assert!(occupancy[1].has_conflicts());
+    assert_eq!(occupancy[1].occupations()[0].conflicts(), &["C10007"]);
+    assert!(!db.platform_occupancy("WOK", date("2024-06-05"))[1].has_conflicts());
+}
+
+#[test]
+fn test_validate() {
+    let mut db = load_sample();
+    assert_eq!(db.validate(), vec![]);
+
+    // a second overlay of C10001 on the same day, with Woking timed an hour early
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let records: Vec<CIFRecord> = sample.lines().collect::<Vec<_>>()[26..35]
+        .iter()
+        .map(|line| line.replacen("0735 0736      073507362", "0635 0636      063506362", 1))
+        .map(|line| parse_record(&line).unwrap())
+        .collect();
+    assert!(db.apply_records(&records).is_empty());
+
+    assert_eq!(
+        db.validate(),
+        vec![
+            ValidationIssue::OverlappingSchedules {
+                train_uid: "C10001".to_string(),
+                stp_indicator: STPIndicator::STPOverlayOfPermanentAssociation,
+                first_index: 1,
+                second_index: 2,
+                date: date("2024-06-03"),
+            },
+            ValidationIssue::Schedule {
+                train_uid: "C10001".to_string(),
+                schedule_index: 2,
+                issue: ScheduleIssue::TimesNotIncreasing { position: 3 },
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_validate_schedule() {
+    let mut db = load_sample();
+    assert!(db
+        .schedules()
+        .values()
+        .flatten()
+        .all(|schedule| schedule.validate().is_empty()));
+
+    // C10002, but stopping at Woking as it passes, and finishing somewhere unknown
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let records: Vec<CIFRecord> = sample.lines().collect::<Vec<_>>()[20..26]
+        .iter()
+        .map(|line| {
+            line.replacen("BSNC10002", "BSNC10008", 1)
+                .replacen("LIWOKING            0752", "LIWOKING  0751      0752", 1)
+                .replacen("LTBSNGSTK", "LTNOWHERE", 1)
+        })
+        .map(|line| parse_record(&line).unwrap())
+        .collect();
+    assert!(db.apply_records(&records).is_empty());
+
+    let schedule = &db.schedules()["C10008"][0];
+    assert_eq!(
+        schedule.validate(),
+        vec![ScheduleIssue::PassAtStop { position: 2 }]
+    );
+    assert_eq!(
+        db.validate_schedule(schedule),
+        vec![
+            ScheduleIssue::PassAtStop { position: 2 },
+            ScheduleIssue::UnknownTIPLOC { position: 3 },
+        ]
+    );
+}
+
+#[test]
+fn test_schedule_diff() {
+    let db = load_sample();
+    let permanent = &db.schedules()["C10001"][0];
+    let overlay = &db.schedules()["C10001"][1];
+    assert!(permanent.diff(permanent).is_empty());
+
+    let changes = permanent.diff(overlay);
+    let fields: Vec<_> = changes
+        .iter()
+        .filter_map(|change| match change {
+            ScheduleFieldChange::Attribute { field, .. } => Some(field.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        fields,
+        ["runs_from", "runs_to", "days_run", "stp_indicator"]
+    );
+    // every time is five minutes later on the day of the overlay
+    let times: Vec<_> = changes
+        .iter()
+        .filter(|change| matches!(change, ScheduleFieldChange::TimeChanged { .. }))
+        .collect();
+    assert_eq!(times.len(), 24);
+    assert!(times
+        .iter()
+        .all(|change| change.time_difference() == Some(Duration::minutes(5))));
+    assert!(changes.contains(&ScheduleFieldChange::PlatformChanged {
+        position: 0,
+        tiploc: "WATRLMN".to_string(),
+        before: Platform::new("12"),
+        after: Platform::new("13"),
+    }));
+
+    // C10001 calls at Surbiton, and runs on beyond Basingstoke
+    let added: Vec<_> = db.schedules()["C10002"][0]
+        .diff(permanent)
+        .into_iter()
+        .filter_map(|change| match change {
+            ScheduleFieldChange::LocationAdded { position, tiploc } => Some((position, tiploc)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        added,
+        vec![
+            (2, "SURBITN".to_string()),
+            (5, "WINCHSR".to_string()),
+            (6, "SOTON".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_update_to() {
+    let db = load_sample();
+    // Eastleigh is removed, Woking is renamed and Wimbledon added, C10001 arrives at
+    // Southampton a minute later, and the association and C10006 are removed
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let mut changed: Vec<String> = lines[..51]
+        .iter()
+        .filter(|line| !line.starts_with("TIEASTLGH") && !line.starts_with("AA"))
+        .map(|line| {
+            line.replacen("AWOKING      ", "AWOKING STN  ", 1).replacen(
+                "LTSOTON   0825 08254",
+                "LTSOTON   0826 08264",
+                1,
+            )
+        })
+        .collect();
+    changed.insert(
+        1,
+        format!(
+            "{:<80}",
+            "TIWIMBLDN00557700AWIMBLEDON                 871830000WIMWIMBLEDON"
+        ),
+    );
+    changed.push(lines[56].to_string());
+    let mut newer = ScheduleDatabase::new();
+    let errors = newer.apply_file(&parse_cif(changed.join("\n").as_bytes()).unwrap());
+    assert!(errors.is_empty(), "{errors:?}");
+
+    let update = db.update_to(&newer);
+    assert_eq!(update.update_indicator(), Some(UpdateIndicator::Update));
+    assert_eq!(update.last_file_reference(), Some("DFROC1A"));
+    let summary: Vec<(&str, String)> = update
+        .records()
+        .iter()
+        .filter_map(|record| match record {
+            CIFRecord::TIPLOCInsert { tiploc, .. } => Some(("TI", tiploc.clone())),
+            CIFRecord::TIPLOCAmend { tiploc, .. } => Some(("TA", tiploc.clone())),
+            CIFRecord::TIPLOCDelete { tiploc } => Some(("TD", tiploc.clone())),
+            CIFRecord::Association {
+                transaction_type,
+                main_train_uid,
+                ..
+            } => Some(("AA", format!("{transaction_type}{main_train_uid}"))),
+            CIFRecord::BasicSchedule {
+                transaction_type,
+                train_uid,
+                ..
+            } => Some(("BS", format!("{transaction_type}{train_uid}"))),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("TD", "EASTLGH".to_string()),
+            ("TI", "WIMBLDN".to_string()),
+            ("TA", "WOKING".to_string()),
+            ("AA", "DC10001".to_string()),
+            ("BS", "RC10001".to_string()),
+            ("BS", "DC10006".to_string()),
+        ]
+    );
+
+    // applying the update brings the sample up to date
+    let mut updated = load_sample();
+    let errors = updated.apply_file(&update);
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(updated.update_to(&newer).records().len(), 2);
+    assert_eq!(updated.schedules()["C10001"], newer.schedules()["C10001"]);
+
+    // the update is written as a CIF file which reads back the same
+    let mut written = vec![];
+    db.write_update_to(&newer, &mut written).unwrap();
+    let text = String::from_utf8(written).unwrap();
+    assert_eq!(text.lines().count(), update.records().len());
+    assert!(text.lines().all(|line| line.len() == 80));
+    let mut reread = load_sample();
+    let errors = reread.apply_file(&parse_cif(text.as_bytes()).unwrap());
+    assert!(errors.is_empty(), "{errors:?}");
+    assert_eq!(reread.update_to(&newer).records().len(), 2);
+}
+
+#[test]
+fn test_schedule_builder() {
+    let db = load_sample();
+    let time = |s: &str| s.parse::<JourneyTime>().unwrap();
+    let schedule = ScheduleBuilder::new(
+        "C10002",
+        date("2024-05-20"),
+        date("2024-12-13"),
+        DaysRun::WEEKDAYS,
+    )
+    .atoc_code("SW")
+    .performance_monitoring(true)
+    .train_category(TrainCategory::OrdinaryPassenger)
+    .train_identity("2B02")
+    .train_service_code("24671006")
+    .power_type(PowerType::ElectricMultipleUnit)
+    .timing_load(TimingLoad::SpecificClass(450))
+    .speed(100)
+    .seating_class(SeatingClass::StandardOnly)
+    .origin("WATRLMN", time("0730"))
+    .platform("10")
+    .call("CLPHMJC", time("0737H"), time("0738H"))
+    .platform("7")
+    .pass("WOKING", time("0752"))
+    .terminus("BSNGSTK", time("0810"))
+    .platform("1")
+    .build();
+    assert!(schedule.validate().is_empty());
+
+    // the schedule is written as the records it was read from
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let written: Vec<String> = schedule
+        .to_records(TransactionType::New)
+        .iter()
+        .map(CIFRecord::to_cif_line)
+        .collect();
+    assert_eq!(written.len(), 6);
+    for (idx, line) in written.iter().enumerate() {
+        // the sample gives placeholder public times when the train passes Woking
+        if idx != 4 {
+            assert_eq!(line, lines[20 + idx]);
+        }
+    }
+    assert!(schedule.diff(&db.schedules()["C10002"][0]).is_empty());
+    // a deletion only identifies the schedule
+    let deletion = schedule.to_records(TransactionType::Delete);
+    assert_eq!(deletion.len(), 1);
+    assert!(deletion[0]
+        .to_cif_line()
+        .starts_with("BSDC10002240520       "));
+
+    let overnight = ScheduleBuilder::new(
+        "C20001",
+        date("2024-06-01"),
+        date("2024-06-01"),
+        DaysRun::SATURDAY,
+    )
+    .origin("WATRLMN", JourneyTime::new(23, 50, false))
+    .terminus("WOKING", JourneyTime::new(0, 20, false))
+    .public_times(None, None)
+    .build();
+    assert_eq!(*overnight.journey()[1].day_offset(), 1);
+    assert_eq!(*overnight.journey()[1].public_arrival(), None);
+}
+
+#[test]
+fn test_write_allowances() {
+    let time = |s: &str| s.parse::<JourneyTime>().unwrap();
+    let allowance = |s: &str| s.parse::<Allowance>().unwrap();
+    let schedule = ScheduleBuilder::new(
+        "C20001",
+        date("2024-06-03"),
+        date("2024-06-03"),
+        DaysRun::MONDAY,
+    )
+    .atoc_code("SW")
+    .origin("WATRLMN", time("0730"))
+    .engineering_allowance(allowance("0"))
+    .pathing_allowance(allowance("H"))
+    .performance_allowance(allowance("1H"))
+    .terminus("WOKING", time("0800"))
+    .build();
+
+    let lines: Vec<String> = schedule
+        .to_records(TransactionType::New)
+        .iter()
+        .map(CIFRecord::to_cif_line)
+        .collect();
+    let origin = lines.iter().find(|line| line.starts_with("LO")).unwrap();
+    assert_eq!(&origin[25..29], "0 H ");
+    assert_eq!(&origin[41..43], "1H");
+
+    // the allowances are read back as they were written
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let sample: Vec<&str> = sample.lines().collect();
+    let cif = [
+        vec![sample[0].to_string()],
+        lines,
+        vec![sample[56].to_string()],
+    ]
+    .concat();
+    let mut db = ScheduleDatabase::new();
+    let errors = db.apply_file(&parse_cif(cif.join("\n").as_bytes()).unwrap());
+    assert!(errors.is_empty(), "{errors:?}");
+    let read = &db.schedules()["C20001"][0].journey()[0];
+    let origin = &schedule.journey()[0];
+    assert_eq!(read.engineering_allowance(), origin.engineering_allowance());
+    assert_eq!(read.pathing_allowance(), origin.pathing_allowance());
+    assert_eq!(read.performance_allowance(), origin.performance_allowance());
+
+    // fields are padded by characters, so a line is always 80 characters long
+    let note = CIFRecord::TrainNote {
+        note_type: 'G',
+        note: "Café stop".to_string(),
+    };
+    assert_eq!(note.to_cif_line().chars().count(), 80);
+}
+
+#[cfg(feature = "petgraph")]
+#[test]
+fn test_event_graph() {
+    let db = load_sample();
+    // both C10001 and C10002 run on a Wednesday
+    let wednesday = date("2024-06-05");
+    let graph = EventGraphBuilder::new(&db, wednesday).build();
+
+    let edges = |kind| {
+        graph
+            .raw_edges()
+            .iter()
+            .filter(move |edge| edge.weight.kind() == kind)
+            .map(|edge| (&graph[edge.source()], &graph[edge.target()], edge.weight))
+    };
+
+    for (from, to, edge) in edges(TimetableEdgeKind::Run) {
+        assert_eq!(from.kind(), TimetableEventKind::Departure);
+        assert_eq!(to.kind(), TimetableEventKind::Arrival);
+        assert_eq!(from.train_uid(), to.train_uid());
+        assert_ne!(from.tiploc(), to.tiploc());
+        assert_eq!(edge.duration(), to.time() - from.time());
+    }
+    for (from, to, edge) in edges(TimetableEdgeKind::Dwell) {
+        assert_eq!(from.kind(), TimetableEventKind::Arrival);
+        assert_eq!(to.kind(), TimetableEventKind::Departure);
+        assert_eq!(from.train_uid(), to.train_uid());
+        assert_eq!(from.tiploc(), to.tiploc());
+        assert_eq!(edge.duration(), to.time() - from.time());
+    }
+
+    // C10001 runs between its seven calls and dwells at the five in between
+    let runs = |uid: &str| {
+        edges(TimetableEdgeKind::Run)
+            .filter(|(from, _, _)| from.train_uid() == uid)
+            .map(|(from, to, _)| (from.tiploc().as_str(), to.tiploc().as_str()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        runs("C10001"),
+        [
+            ("WATRLMN", "CLPHMJC"),
+            ("CLPHMJC", "SURBITN"),
+            ("SURBITN", "WOKING"),
+            ("WOKING", "BSNGSTK"),
+            ("BSNGSTK", "WINCHSR"),
+            ("WINCHSR", "SOTON"),
+        ]
+    );
+    // C10002 passes Woking, so runs straight from Clapham Junction to Basingstoke
+    assert_eq!(
+        runs("C10002"),
+        [("WATRLMN", "CLPHMJC"), ("CLPHMJC", "BSNGSTK")]
+    );
+    assert_eq!(
+        edges(TimetableEdgeKind::Dwell)
+            .filter(|(from, _, _)| from.train_uid() == "C10001")
+            .count(),
+        5
+    );
+
+    // the only change is at Clapham Junction, from C10001 at 07:07 to C10002 at 07:38H
+    let transfers: Vec<_> = edges(TimetableEdgeKind::Transfer).collect();
+    assert_eq!(transfers.len(), 1);
+    let (from, to, edge) = transfers[0];
+    assert_eq!(from.kind(), TimetableEventKind::Arrival);
+    assert_eq!(to.kind(), TimetableEventKind::Departure);
+    assert_eq!(
+        (from.train_uid().as_str(), to.train_uid().as_str()),
+        ("C10001", "C10002")
+    );
+    assert_eq!(
+        (from.tiploc().as_str(), to.tiploc().as_str()),
+        ("CLPHMJC", "CLPHMJC")
+    );
+    let wait = Duration::seconds(31 * 60 + 30);
+    assert_eq!(edge.duration(), wait);
+
+    // the bounds on the transfer time are inclusive
+    let transfers = |minimum, maximum| {
+        let graph = EventGraphBuilder::new(&db, wednesday)
+            .minimum_transfer(minimum)
+            .maximum_transfer_wait(maximum)
+            .build();
+        graph
+            .raw_edges()
+            .iter()
+            .filter(|edge| edge.weight.kind() == TimetableEdgeKind::Transfer)
+            .count()
+    };
+    let second = Duration::seconds(1);
+    assert_eq!(transfers(wait, wait), 1);
+    assert_eq!(transfers(wait + second, Duration::hours(1)), 0);
+    assert_eq!(transfers(Duration::minutes(5), wait - second), 0);
+    assert_eq!(transfers(Duration::zero(), Duration::hours(2)), 1);
+}
+
+#[test]
+fn test_timetable_change_dates() {
+    // the freight train stops running at the end of September
+    let sample = std::fs::read_to_string("./tests/sample.cif")
+        .unwrap()
+        .replacen(
+            "BSNL100052405202412131111100",
+            "BSNL100052405202409301111100",
+            1,
+        );
+    let mut db = ScheduleDatabase::new();
+    assert!(db
+        .apply_file(&parse_cif(sample.as_bytes()).unwrap())
+        .is_empty());
+
+    let changes = |min_proportion| {
+        db.timetable_change_dates(min_proportion)
+            .iter()
+            .map(|change| (change.date(), change.starting(), change.ending()))
+            .collect::<Vec<_>>()
+    };
+    // the five permanent schedules start together, and the short term plan isn't counted.
+    // Each change is dated the day after a schedule last runs.
+    assert_eq!(
+        changes(0.5),
+        [(date("2024-05-20"), 5, 0), (date("2024-12-14"), 0, 4)]
+    );
+    // a single schedule is a fifth of them, which just meets the threshold
+    assert_eq!(
+        changes(0.2),
+        [
+            (date("2024-05-20"), 5, 0),
+            (date("2024-10-01"), 0, 1),
+            (date("2024-12-14"), 0, 4),
+        ]
+    );
+    assert_eq!(changes(0.0), changes(0.2));
+    assert_eq!(changes(0.21), changes(0.5));
+    assert_eq!(changes(0.9), [(date("2024-05-20"), 5, 0)]);
+}
+
+/// Check the indexes built by a database match the schedules it holds.
+fn assert_indexes_match(db: &ScheduleDatabase) {
+    let sorted = |schedules: Vec<&Schedule>| {
+        let mut schedules: Vec<*const Schedule> = schedules.into_iter().map(|s| s as _).collect();
+        schedules.sort();
+        schedules
+    };
+    let all: Vec<&Schedule> = db.schedules().values().flatten().collect();
+
+    // train UIDs
+    assert_eq!(sorted(db.schedules_matching_uid("*")), sorted(all.clone()));
+    for uid in db.schedules().keys() {
+        assert_eq!(
+            db.schedules_matching_uid(uid).len(),
+            db.schedules()[uid].len()
+        );
+    }
+
+    // headcodes
+    for schedule in all.iter().filter(|s| !s.train_identity().is_empty()) {
+        let expected: Vec<&Schedule> = all
+            .iter()
+            .filter(|other| other.train_identity() == schedule.train_identity())
+            .copied()
+            .collect();
+        assert_eq!(
+            sorted(db.schedules_by_headcode(schedule.train_identity())),
+            sorted(expected)
+        );
+    }
+
+    // locations
+    for tiploc in db.tiplocs().values() {
+        let expected: Vec<&Schedule> = all
+            .iter()
+            .filter(|schedule| {
+                schedule.journey().iter().any(|location| {
+                    db.tiplocs()
+                        .get(location.tiploc())
+                        .is_some_and(|t| t.stanox() == tiploc.stanox())
+                })
+            })
+            .copied()
+            .collect();
+        assert_eq!(
+            sorted(db.schedules_at_stanox(*tiploc.stanox())),
+            sorted(expected)
+        );
+    }
+}
+
+#[test]
+fn test_merge() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let tiplocs = [
+        // Woking has been renamed and given a new STANOX
+        format!(
+            "{:<80}",
+            format!("TIWOKING 00556500A{:<26}865210000WOKWOKING", "WOKING NEW")
+        ),
+        format!(
+            "{:<80}",
+            format!("TIGUILDFD00556600A{:<26}865300000GLDGUILDFORD", "GUILDFORD")
+        ),
+    ];
+    let supplement = |with_schedules: bool| {
+        let mut cif = vec![lines[0].to_string()];
+        cif.extend(tiplocs.iter().cloned());
+        if with_schedules {
+            // a new permanent schedule for C10002, and a new train
+            cif.extend(lines[20..26].iter().map(|l| l.replacen("2B02", "2Z02", 1)));
+            cif.extend(lines[51..56].iter().map(|l| {
+                l.replacen("C10006", "C20006", 1)
+                    .replacen("2B50", "2Z50", 1)
+            }));
+        }
+        cif.push(lines[56].to_string());
+        let mut db = ScheduleDatabase::new();
+        assert!(db
+            .apply_file(&parse_cif(cif.join("\n").as_bytes()).unwrap())
+            .is_empty());
+        db
+    };
+    let merged = |policy| {
+        let mut db = load_sample();
+        db.merge(supplement(true), policy).unwrap();
+        assert_indexes_match(&db);
+        db
+    };
+    let identities = |db: &ScheduleDatabase| {
+        db.schedules()["C10002"]
+            .iter()
+            .map(|s| (s.train_identity().clone(), *s.stp_indicator()))
+            .collect::<Vec<_>>()
+    };
+    let original = identities(&load_sample());
+
+    let db = merged(MergeConflictPolicy::KeepExisting);
+    assert_eq!(db.tiplocs()["WOKING"].description(), "WOKING");
+    assert_eq!(*db.tiplocs()["WOKING"].stanox(), 86520);
+    assert!(db.tiplocs().contains_key("GUILDFD"));
+    assert_eq!(identities(&db), original);
+    assert!(db.schedules_by_headcode("2Z02").is_empty());
+    assert_eq!(db.schedules_by_headcode("2Z50").len(), 1);
+    assert_eq!(db.schedules().len(), 7);
+
+    let db = merged(MergeConflictPolicy::PreferOther);
+    assert_eq!(db.tiplocs()["WOKING"].description(), "WOKING NEW");
+    assert_eq!(*db.tiplocs()["WOKING"].stanox(), 86521);
+    assert!(db.schedules_at_stanox(86520).is_empty());
+    assert_eq!(
+        identities(&db),
+        [("2Z02".to_string(), STPIndicator::PermanentAssociation)]
+    );
+    assert!(db.schedules_by_headcode("2B02").is_empty());
+    assert_eq!(db.schedules_by_headcode("2Z50").len(), 1);
+    assert_eq!(db.schedules().len(), 7);
+
+    let db = merged(MergeConflictPolicy::Combine);
+    assert_eq!(db.tiplocs()["WOKING"].description(), "WOKING NEW");
+    let mut combined = original.clone();
+    combined.push(("2Z02".to_string(), STPIndicator::PermanentAssociation));
+    assert_eq!(identities(&db), combined);
+    assert_eq!(db.schedules_by_headcode("2B02").len(), 1);
+    assert_eq!(db.schedules_by_headcode("2Z02").len(), 1);
+    assert_eq!(db.schedules().len(), 7);
+
+    // nothing is merged when there is a conflict
+    let mut db = load_sample();
+    assert!(matches!(
+        db.merge(supplement(true), MergeConflictPolicy::Error),
+        Err(MergeError::ScheduleConflict(uid)) if uid == "C10002"
+    ));
+    assert!(matches!(
+        db.merge(supplement(false), MergeConflictPolicy::Error),
+        Err(MergeError::TIPLOCConflict(tiploc)) if tiploc == "WOKING"
+    ));
+    assert_eq!(identities(&db), original);
+    assert!(!db.tiplocs().contains_key("GUILDFD"));
+    assert_eq!(db.schedules().len(), 6);
+    assert_indexes_match(&db);
+}
+
+#[test]
+fn test_merge_combines_associations() {
+    let count = |db: &ScheduleDatabase| db.associations().values().map(Vec::len).sum::<usize>();
+    let mut db = load_sample();
+    let before = count(&db);
+    assert!(before > 0);
+
+    // the same associations in both databases aren't kept twice
+    db.merge(load_sample(), MergeConflictPolicy::Combine)
+        .unwrap();
+    assert_eq!(count(&db), before);
+    assert_indexes_match(&db);
+}
+
+#[test]
+fn test_freight_services() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let freight = &lines[46..51];
+    let mut cif: Vec<String> = lines[..56].iter().map(|l| l.to_string()).collect();
+    // another train with the same service code, which always runs
+    cif.extend(freight.iter().map(|l| {
+        l.replacen("L10005", "L10007", 1)
+            .replacen("6B01", "7B02", 1)
+            .replacen("060Q", "060 ", 1)
+    }));
+    // and one with a different service code
+    cif.extend(freight.iter().map(|l| {
+        l.replacen("L10005", "L10008", 1)
+            .replacen("155443001", "155443002", 1)
+    }));
+    // the first freight train is cancelled on Wednesday
+    cif.push(format!(
+        "{:<79}C",
+        "BSNL100052406052406050010000            1"
+    ));
+    cif.push(lines[56].to_string());
+    let mut db = ScheduleDatabase::new();
+    assert!(db
+        .apply_file(&parse_cif(cif.join("\n").as_bytes()).unwrap())
+        .is_empty());
+
+    let freight = &db.schedules()["L10005"][0];
+    assert!(freight.is_freight());
+    assert!(freight.runs_as_required());
+    assert_eq!(freight.trailing_load(), Some(1600));
+    assert_eq!(freight.train_class(), Some('6'));
+    let passenger = &db.schedules()["C10001"][0];
+    assert!(!passenger.is_freight());
+    assert!(!passenger.runs_as_required());
+    assert_eq!(passenger.trailing_load(), None);
+    assert_eq!(passenger.train_class(), Some('1'));
+    assert!(TrainStatus::STPFreight.is_freight());
+    assert!(!TrainStatus::STPPassengerAndParcels.is_freight());
+    assert!(!db.schedules()["L10007"][0].runs_as_required());
+    assert_eq!(db.schedules()["L10007"][0].train_class(), Some('7'));
+
+    let uids = |date| {
+        let mut uids: Vec<_> = db
+            .freight_services(date)
+            .map(|s| s.train_uid().as_str())
+            .collect();
+        uids.sort();
+        uids
+    };
+    assert_eq!(uids(date("2024-06-04")), ["L10005", "L10007", "L10008"]);
+    assert_eq!(uids(date("2024-06-05")), ["L10007", "L10008"]);
+    // they don't run at weekends
+    assert!(uids(date("2024-06-08")).is_empty());
+
+    let groups = |date| {
+        db.freight_services_by_service_code(date)
+            .into_iter()
+            .map(|(code, schedules)| {
+                let uids: Vec<_> = schedules.iter().map(|s| s.train_uid().as_str()).collect();
+                (code, uids)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        groups(date("2024-06-04")),
+        [
+            ("55443001", vec!["L10005", "L10007"]),
+            ("55443002", vec!["L10008"]),
+        ]
+    );
+    assert_eq!(
+        groups(date("2024-06-05")),
+        [("55443001", vec!["L10007"]), ("55443002", vec!["L10008"])]
+    );
+}
+
+#[test]
+fn test_compare_public_times() {
+    // Surbiton isn't advertised, and the public times at Woking are two minutes later
+    let sample = std::fs::read_to_string("./tests/sample.cif")
+        .unwrap()
+        .replacen("0718 0719      07180719", "0718 0719              ", 1)
+        .replacen("0730 0731      07300731", "0730 0731      07320733", 1);
+    let mut db = ScheduleDatabase::new();
+    assert!(db
+        .apply_file(&parse_cif(sample.as_bytes()).unwrap())
+        .is_empty());
+
+    let compared = db.schedules()["C10001"][0].compare_public_times();
+    assert_eq!(compared.len(), 7);
+    let origin = &compared[0];
+    assert_eq!(origin.tiploc(), "WATRLMN");
+    assert_eq!(origin.arrival_difference(), None);
+    assert_eq!(origin.departure_difference(), Some(Duration::zero()));
+    assert!(!origin.missing_public_arrival());
+    assert!(!origin.missing_public_departure());
+
+    let surbiton = &compared[2];
+    assert_eq!(surbiton.tiploc(), "SURBITN");
+    assert_eq!(surbiton.arrival_difference(), None);
+    assert_eq!(surbiton.departure_difference(), None);
+    assert!(surbiton.missing_public_arrival());
+    assert!(surbiton.missing_public_departure());
+
+    let woking = &compared[3];
+    assert_eq!(woking.tiploc(), "WOKING");
+    assert_eq!(woking.arrival_difference(), Some(Duration::minutes(2)));
+    assert_eq!(woking.departure_difference(), Some(Duration::minutes(2)));
+    assert!(!woking.missing_public_arrival());
+    assert!(!woking.missing_public_departure());
+
+    let destination = &compared[6];
+    assert_eq!(destination.tiploc(), "SOTON");
+    assert_eq!(destination.arrival_difference(), Some(Duration::zero()));
+    assert!(!destination.missing_public_departure());
+
+    // public times are rounded down from the half minute, and passes aren't compared
+    let compared = db.schedules()["C10002"][0].compare_public_times();
+    let tiplocs: Vec<_> = compared.iter().map(|c| c.tiploc().as_str()).collect();
+    assert_eq!(tiplocs, ["WATRLMN", "CLPHMJC", "BSNGSTK"]);
+    assert_eq!(
+        compared[1].arrival_difference(),
+        Some(Duration::seconds(-30))
+    );
+    assert_eq!(
+        compared[1].departure_difference(),
+        Some(Duration::seconds(-30))
+    );
+}
+
+#[test]
+fn test_station_groups() {
+    // a junction with no STANOX isn't part of any station
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    lines.insert(
+        10,
+        format!(
+            "{:<80}",
+            format!("TIWOKJN  00000000A{:<26}000000000", "WOKING JN")
+        ),
+    );
+    let mut db = ScheduleDatabase::new();
+    assert!(db
+        .apply_file(&parse_cif(lines.join("\n").as_bytes()).unwrap())
+        .is_empty());
+    assert!(db.tiplocs().contains_key("WOKJN"));
+
+    let groups: Vec<_> = db
+        .station_groups()
+        .into_iter()
+        .map(|group| {
+            (
+                group.stanox(),
+                group.tiplocs().clone(),
+                group.crs().clone(),
+                group.description().clone(),
+            )
+        })
+        .collect();
+    let group = |stanox, tiplocs: &[&str], crs: &str, description: &str| {
+        (
+            stanox,
+            tiplocs.iter().map(|t| t.to_string()).collect::<Vec<_>>(),
+            Some(crs.to_string()),
+            description.to_string(),
+        )
+    };
+    assert_eq!(
+        groups,
+        [
+            group(85221, &["EASTLGH"], "ESL", "EASTLEIGH"),
+            group(85311, &["WINCHSR"], "WIN", "WINCHESTER"),
+            group(85507, &["SOTON"], "SOU", "SOUTHAMPTON CENTRAL"),
+            group(86011, &["BSNGSTK"], "BSK", "BASINGSTOKE"),
+            group(86520, &["WOKING"], "WOK", "WOKING"),
+            group(87031, &["SURBITN"], "SUR", "SURBITON"),
+            // Clapham Junction West has no CRS, so the station is named after the other
+            group(87215, &["CLPHMJC", "CLPHMJW"], "CLJ", "CLAPHAM JUNCTION"),
+            group(87219, &["WATRLMN"], "WAT", "LONDON WATERLOO"),
+        ]
+    );
+}
+
+#[test]
+fn test_international_services() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let mut lines: Vec<String> = sample.lines().map(str::to_string).collect();
+    // C10001 is given a UIC code, except when it runs to its overlay, and C10002 runs
+    // through the Channel Tunnel
+    lines[12] = lines[12].replacen("BX         ", "BX    12345", 1);
+    lines[20] = lines[20].replacen("POO2B02", "PXC2B02", 1);
+    let mut db = ScheduleDatabase::new();
+    assert!(db
+        .apply_file(&parse_cif(lines.join("\n").as_bytes()).unwrap())
+        .is_empty());
+
+    let permanent = &db.schedules()["C10001"][0];
+    assert_eq!(permanent.uic_code().as_deref(), Some("12345"));
+    assert!(permanent.is_international());
+    assert!(!permanent.is_channel_tunnel());
+    let tunnel = &db.schedules()["C10002"][0];
+    assert!(tunnel.is_international());
+    assert!(tunnel.is_channel_tunnel());
+    let domestic = &db.schedules()["C10003"][0];
+    assert!(!domestic.is_international());
+    assert!(!domestic.is_channel_tunnel());
+    assert!(TrainCategory::International.is_international());
+    assert!(!TrainCategory::International.is_channel_tunnel());
+    assert!(TrainCategory::RfDEuropeanChannelTunnelIntermodal.is_international());
+
+    let uids = |schedules: Vec<&Schedule>| {
+        schedules
+            .iter()
+            .map(|s| s.train_uid().clone())
+            .collect::<Vec<_>>()
+    };
+    let wednesday = date("2024-06-05");
+    assert_eq!(
+        uids(db.international_services(wednesday)),
+        ["C10001", "C10002"]
+    );
+    assert_eq!(uids(db.channel_tunnel_services(wednesday)), ["C10002"]);
+    // the overlay has no UIC code
+    let monday = date("2024-06-03");
+    assert_eq!(uids(db.international_services(monday)), ["C10002"]);
+    // C10002 is cancelled
+    let tuesday = date("2024-06-04");
+    assert_eq!(uids(db.international_services(tuesday)), ["C10001"]);
+    assert!(db.channel_tunnel_services(tuesday).is_empty());
+    // nothing international runs at weekends
+    assert!(db.international_services(date("2024-06-08")).is_empty());
+}
+
+#[test]
+fn test_board_order() {
+    let sample = std::fs::read_to_string("./tests/sample.cif").unwrap();
+    let lines: Vec<&str> = sample.lines().collect();
+    let overnight = &lines[36..42];
+    let mut cif: Vec<String> = lines[..56].iter().map(|l| l.to_string()).collect();
+    // copies of C10003 leaving Woking at the same public time from other platforms, one of
+    // which leaves half a minute later in the working timetable, and one before midnight
+    let copy = |uid: &str, identity: &str, woking: (&str, &str)| {
+        overnight
+            .iter()
+            .map(|l| {
+                l.replacen("C10003", uid, 1)
+                    .replacen("2B99", identity, 1)
+                    .replacen(woking.0, woking.1, 1)
+            })
+            .collect::<Vec<_>>()
+    };
+    cif.extend(copy("C20001", "2A01", ("235900011  ", "2359000110 ")));
+    cif.extend(copy(
+        "C20002",
+        "2C01",
+        ("2359 0001      235900011", "2359 0001H     235900012"),
+    ));
+    cif.extend(copy(
+        "C20003",
+        "2Z03",
+        ("2359 0001      235900011", "2354 2355      235423553"),
+    ));
+    cif.push(lines[56].to_string());
+    let mut db = ScheduleDatabase::new();
+    assert!(db
+        .apply_file(&parse_cif(cif.join("\n").as_bytes()).unwrap())
+        .is_empty());
+
+    let from = date("2024-06-04").and_hms_opt(23, 50, 0).unwrap();
+    let board = |time, tie_break| {
+        db.departures_ordered(
+            "WOK",
+            from,
+            Duration::minutes(30),
+            BoardOrder::new(time, tie_break),
+        )
+        .iter()
+        .map(|d| d.train_uid().clone())
+        .collect::<Vec<_>>()
+    };
+
+    // the train leaving after midnight is listed after the one leaving before
+    let departures = db.departures("WOK", from, Duration::minutes(30));
+    let times: Vec<_> = departures.iter().map(|d| d.time().to_string()).collect();
+    assert_eq!(
+        times,
+        [
+            "2024-06-04 23:55:00",
+            "2024-06-05 00:01:00",
+            "2024-06-05 00:01:00",
+            "2024-06-05 00:01:00",
+        ]
+    );
+    assert_eq!(
+        board(BoardTime::Public, BoardTieBreak::TrainUid),
+        ["C20003", "C10003", "C20001", "C20002"]
+    );
+    // platform 10 comes after platform 2
+    assert_eq!(
+        board(BoardTime::Public, BoardTieBreak::Platform),
+        ["C20003", "C10003", "C20002", "C20001"]
+    );
+    assert_eq!(
+        board(BoardTime::Public, BoardTieBreak::Headcode),
+        ["C20003", "C20001", "C10003", "C20002"]
+    );
+    // the working times break the tie, and trains still equal are ordered by UID
+    assert_eq!(
+        board(BoardTime::Working, BoardTieBreak::Headcode),
+        ["C20003", "C20001", "C10003", "C20002"]
+    );
+    assert_eq!(
+        board(BoardTime::Working, BoardTieBreak::TrainUid),
+        ["C20003", "C10003", "C20001", "C20002"]
+    );
+    assert_eq!(
+        board(BoardTime::Working, BoardTieBreak::Platform),
+        ["C20003", "C10003", "C20001", "C20002"]
+    );
+}
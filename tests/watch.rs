@@ -0,0 +1,81 @@
+#![cfg(feature = "watch")]
+
+use nr_cif::prelude::*;
+
+use std::{
+    fs,
+    sync::{Arc, RwLock},
+};
+
+#[test]
+fn test_watch_directory() {
+    let dir = std::env::temp_dir().join(format!("nr-cif-watch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let sample = fs::read_to_string("./tests/sample.cif").unwrap();
+    fs::write(dir.join("a.cif"), &sample).unwrap();
+
+    let database = Arc::new(RwLock::new(ScheduleDatabase::new()));
+    let mut watcher = Watcher::new(DirectorySource::new(&dir), database.clone(), None);
+    let events = watcher.poll().unwrap();
+    assert!(
+        matches!(&events[..], [WatchEvent::Applied { errors, .. }] if errors.is_empty()),
+        "{events:?}"
+    );
+    assert_eq!(database.read().unwrap().schedules().len(), 6);
+
+    // nothing new, then a file which doesn't follow the one applied
+    assert!(watcher.poll().unwrap().is_empty());
+    fs::write(dir.join("b.cif"), &sample).unwrap();
+    let events = watcher.poll().unwrap();
+    assert!(matches!(&events[..], [WatchEvent::OutOfSequence { .. }]));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_watch_retries_out_of_sequence() {
+    let dir = std::env::temp_dir().join(format!("nr-cif-watch-retry-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let sample = fs::read_to_string("./tests/sample.cif").unwrap();
+    let before = sample.replacen("DFROC1ADFROC1Z", "DFROC1ZDFROC1Y", 1);
+
+    let database = Arc::new(RwLock::new(ScheduleDatabase::new()));
+    let mut watcher = Watcher::new(
+        DirectorySource::new(&dir),
+        database.clone(),
+        Some("DFROC1Y".to_string()),
+    );
+
+    // the file arrives before the one it follows, so is tried again on every poll
+    fs::write(dir.join("b.cif"), &sample).unwrap();
+    for _ in 0..2 {
+        let events = watcher.poll().unwrap();
+        assert!(matches!(&events[..], [WatchEvent::OutOfSequence { .. }]));
+    }
+    assert!(watcher.seen().is_empty());
+
+    fs::write(dir.join("a.cif"), &before).unwrap();
+    let events = watcher.poll().unwrap();
+    let names: Vec<_> = events
+        .iter()
+        .map(|event| match event {
+            WatchEvent::Applied {
+                name,
+                errors,
+                changes,
+                ..
+            } => {
+                assert!(errors.is_empty(), "{errors:?}");
+                assert!(changes.full_update());
+                assert!(!changes.schedules().is_empty());
+                name.as_str()
+            }
+            _ => panic!("{event:?}"),
+        })
+        .collect();
+    assert_eq!(names, ["a.cif", "b.cif"]);
+    assert_eq!(watcher.last_file_reference().as_deref(), Some("DFROC1A"));
+    assert!(watcher.poll().unwrap().is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}